@@ -0,0 +1,62 @@
+-- Tables created until now by schema_reset/schema.sql. IF NOT EXISTS lets databases created with that
+-- script adopt the migrations without being wiped
+
+CREATE TABLE IF NOT EXISTS `wallets` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`balance` DECIMAL(12,2) NOT NULL DEFAULT 0,
+	`max_single_amount` DECIMAL(12,2) NULL DEFAULT NULL,
+	`max_daily_amount` DECIMAL(12,2) NULL DEFAULT NULL,
+	`max_monthly_amount` DECIMAL(12,2) NULL DEFAULT NULL,
+	`max_daily_count` INT NULL DEFAULT NULL
+);
+
+CREATE TABLE IF NOT EXISTS `transactions` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`wallets_ID` INT NULL DEFAULT NULL,
+	`amount` DECIMAL(12, 2),
+	`status` ENUM('Initialized', 'PendingReview', 'Confirmed', 'Declined', 'Cancelled', 'Refunded', 'Expired', 'InternalError', 'Log') NOT NULL DEFAULT 'Initialized',
+	`token` VARCHAR(32) DEFAULT NULL,
+	`errors` VARCHAR(128) DEFAULT NULL,
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT `transactions_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE,
+	INDEX `transactions_token` (`token`),
+	INDEX `transactions_status` (`status`),
+	INDEX `transactions_wallets_ID_created_at` (`wallets_ID`, `created_at`)
+);
+
+CREATE TABLE IF NOT EXISTS `webhook_subscriptions` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`wallets_ID` INT NULL DEFAULT NULL,
+	`url` VARCHAR(512) NOT NULL,
+	`secret` VARCHAR(64) NOT NULL,
+	`active` BOOLEAN NOT NULL DEFAULT TRUE,
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT `webhook_subscriptions_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS `webhook_deliveries` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`webhook_subscriptions_ID` INT NOT NULL,
+	`event_type` VARCHAR(64) NOT NULL,
+	`payload` TEXT NOT NULL,
+	`status` ENUM('Pending', 'Delivered', 'DeadLetter') NOT NULL DEFAULT 'Pending',
+	`attempts` INT NOT NULL DEFAULT 0,
+	`next_attempt_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`last_response_status` INT NULL DEFAULT NULL,
+	`last_error` VARCHAR(256) NULL DEFAULT NULL,
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`delivered_at` DATETIME NULL DEFAULT NULL,
+	CONSTRAINT `webhook_deliveries_webhook_subscriptions_ID` FOREIGN KEY (`webhook_subscriptions_ID`) REFERENCES `webhook_subscriptions` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE,
+	INDEX `webhook_deliveries_status_next_attempt_at` (`status`, `next_attempt_at`)
+);
+
+CREATE TABLE IF NOT EXISTS `outbox` (
+	`ID` BIGINT PRIMARY KEY AUTO_INCREMENT,
+	`aggregate_type` ENUM('Transaction', 'Wallet') NOT NULL,
+	`aggregate_ID` INT NOT NULL,
+	`event_type` VARCHAR(64) NOT NULL,
+	`payload` TEXT NOT NULL,
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`dispatched_at` DATETIME NULL DEFAULT NULL,
+	INDEX `outbox_dispatched_at` (`dispatched_at`, `ID`)
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS `balance_adjustments`;
+ALTER TABLE `wallets` DROP COLUMN `frozen`;
@@ -0,0 +1,19 @@
+ALTER TABLE `wallets` ADD COLUMN `frozen` BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE `balance_adjustments` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`wallets_ID` INT NOT NULL,
+	`amount` DECIMAL(12,2) NOT NULL,
+	`reason` VARCHAR(256) NOT NULL,
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT `balance_adjustments_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE,
+	INDEX `balance_adjustments_wallets_ID` (`wallets_ID`)
+);
+
+-- Balances had no recorded origin until now, so whatever their transactions don't explain is recorded as
+-- the opening balance, for the reconciliation report to start from a clean state
+INSERT INTO `balance_adjustments` (`wallets_ID`, `amount`, `reason`)
+SELECT `wallets`.`ID`, `wallets`.`balance` - COALESCE(SUM(`transactions`.`amount`), 0), 'Opening balance'
+FROM `wallets`
+LEFT JOIN `transactions` ON `transactions`.`wallets_ID` = `wallets`.`ID` AND `transactions`.`status` IN ('Initialized', 'Confirmed')
+GROUP BY `wallets`.`ID`, `wallets`.`balance`;
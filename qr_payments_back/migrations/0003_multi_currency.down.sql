@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS `wallet_balances`;
+ALTER TABLE `balance_adjustments` DROP COLUMN `currency`;
+ALTER TABLE `transactions` DROP COLUMN `currency`;
+ALTER TABLE `wallets` DROP COLUMN `currency`;
@@ -0,0 +1,13 @@
+-- Amounts had no currency until now, every existing wallet, transaction and adjustment is in USD
+ALTER TABLE `wallets` ADD COLUMN `currency` CHAR(3) NOT NULL DEFAULT 'USD' AFTER `ID`;
+ALTER TABLE `transactions` ADD COLUMN `currency` CHAR(3) NOT NULL DEFAULT 'USD' AFTER `amount`;
+ALTER TABLE `balance_adjustments` ADD COLUMN `currency` CHAR(3) NOT NULL DEFAULT 'USD' AFTER `amount`;
+
+-- Balances of a wallet in currencies other than its own, which stays in `wallets`.`balance`
+CREATE TABLE `wallet_balances` (
+	`wallets_ID` INT NOT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`balance` DECIMAL(12,2) NOT NULL DEFAULT 0,
+	PRIMARY KEY (`wallets_ID`, `currency`),
+	CONSTRAINT `wallet_balances_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+ALTER TABLE `transactions` DROP COLUMN `converted_currency`;
+ALTER TABLE `transactions` DROP COLUMN `converted_amount`;
+ALTER TABLE `transactions` DROP COLUMN `fx_rate`;
+ALTER TABLE `transactions` DROP COLUMN `fx_quote`;
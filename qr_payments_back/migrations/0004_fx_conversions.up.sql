@@ -0,0 +1,5 @@
+-- Payments converted from the wallet currency record the quote they locked, its rate and the amount paid
+ALTER TABLE `transactions` ADD COLUMN `fx_quote` VARCHAR(32) NULL DEFAULT NULL AFTER `currency`;
+ALTER TABLE `transactions` ADD COLUMN `fx_rate` DECIMAL(20,10) NULL DEFAULT NULL AFTER `fx_quote`;
+ALTER TABLE `transactions` ADD COLUMN `converted_amount` DECIMAL(12,2) NULL DEFAULT NULL AFTER `fx_rate`;
+ALTER TABLE `transactions` ADD COLUMN `converted_currency` CHAR(3) NULL DEFAULT NULL AFTER `converted_amount`;
@@ -0,0 +1,11 @@
+-- Ledger of the fees charged on every transaction, and of their refunds as negative lines
+CREATE TABLE `transaction_fees` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`transactions_ID` INT NOT NULL,
+	`party` ENUM('Payer', 'Merchant') NOT NULL,
+	`amount` DECIMAL(12,2) NOT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT `transaction_fees_transactions_ID` FOREIGN KEY (`transactions_ID`) REFERENCES `transactions` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE,
+	INDEX `transaction_fees_transactions_ID` (`transactions_ID`)
+);
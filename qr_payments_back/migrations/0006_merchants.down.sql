@@ -0,0 +1,4 @@
+ALTER TABLE `transactions` DROP FOREIGN KEY `transactions_merchants_ID`;
+ALTER TABLE `transactions` DROP INDEX `transactions_merchants_ID_created_at`;
+ALTER TABLE `transactions` DROP COLUMN `merchants_ID`;
+DROP TABLE IF EXISTS `merchants`;
@@ -0,0 +1,19 @@
+-- Merchants paid through QR codes, each settled into a wallet of its own. Name, city, country and MCC are
+-- the merchant fields of the EMV QR payload, sized to its limits
+CREATE TABLE `merchants` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`wallets_ID` INT NOT NULL,
+	`name` VARCHAR(25) NOT NULL,
+	`city` VARCHAR(15) NOT NULL,
+	`country` CHAR(2) NOT NULL,
+	`mcc` CHAR(4) NOT NULL,
+	`status` ENUM('Active', 'Suspended') NOT NULL DEFAULT 'Active',
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT `merchants_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT,
+	UNIQUE INDEX `merchants_wallets_ID` (`wallets_ID`)
+);
+
+-- Payments made to a merchant. Existing transactions were made to none
+ALTER TABLE `transactions` ADD COLUMN `merchants_ID` INT NULL DEFAULT NULL AFTER `wallets_ID`;
+ALTER TABLE `transactions` ADD CONSTRAINT `transactions_merchants_ID` FOREIGN KEY (`merchants_ID`) REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT;
+ALTER TABLE `transactions` ADD INDEX `transactions_merchants_ID_created_at` (`merchants_ID`, `created_at`);
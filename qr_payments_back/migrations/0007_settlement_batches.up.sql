@@ -0,0 +1,34 @@
+-- Batches paying merchants what they collected, net of refunds and fees, into their settlement wallet. Each
+-- batch closes the days since the previous one of its merchant, so no two batches close the same day
+CREATE TABLE `settlement_batches` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`merchants_ID` INT NOT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`period_start` DATE NOT NULL,
+	`period_end` DATE NOT NULL,
+	`payments` DECIMAL(12,2) NOT NULL,
+	`refunds` DECIMAL(12,2) NOT NULL,
+	`fees` DECIMAL(12,2) NOT NULL,
+	`net` DECIMAL(12,2) NOT NULL,
+	`status` ENUM('Pending', 'Held', 'Paid') NOT NULL DEFAULT 'Pending',
+	`created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`paid_at` DATETIME NULL DEFAULT NULL,
+	CONSTRAINT `settlement_batches_merchants_ID` FOREIGN KEY (`merchants_ID`) REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT,
+	UNIQUE INDEX `settlement_batches_merchants_ID_period_end` (`merchants_ID`, `period_end`),
+	INDEX `settlement_batches_status` (`status`)
+);
+
+-- Transactions settled by every batch. The unique index keeps a payment from being settled twice, and its
+-- refund from being deducted twice
+CREATE TABLE `settlement_lines` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`batches_ID` INT NOT NULL,
+	`transactions_ID` INT NOT NULL,
+	`kind` ENUM('Payment', 'Refund') NOT NULL,
+	`amount` DECIMAL(12,2) NOT NULL,
+	`fee` DECIMAL(12,2) NOT NULL,
+	CONSTRAINT `settlement_lines_batches_ID` FOREIGN KEY (`batches_ID`) REFERENCES `settlement_batches` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT `settlement_lines_transactions_ID` FOREIGN KEY (`transactions_ID`) REFERENCES `transactions` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT,
+	UNIQUE INDEX `settlement_lines_transactions_ID_kind` (`transactions_ID`, `kind`),
+	INDEX `settlement_lines_batches_ID` (`batches_ID`)
+);
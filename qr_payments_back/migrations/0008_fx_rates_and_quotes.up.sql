@@ -0,0 +1,22 @@
+-- Rates quoted for converting between currencies, replaced as a whole by every update
+CREATE TABLE `fx_rates` (
+	`from_currency` CHAR(3) NOT NULL,
+	`to_currency` CHAR(3) NOT NULL,
+	`rate` DECIMAL(20,10) NOT NULL,
+	`updated_at` DATETIME NOT NULL,
+	PRIMARY KEY (`from_currency`, `to_currency`)
+);
+
+-- Open quotes, shared by every instance of the API. A payment locks a quote by deleting it, and expired
+-- ones are dropped as new quotes are created
+CREATE TABLE `fx_quotes` (
+	`ID` VARCHAR(32) PRIMARY KEY,
+	`from_currency` CHAR(3) NOT NULL,
+	`to_currency` CHAR(3) NOT NULL,
+	`rate` DECIMAL(20,10) NOT NULL,
+	`amount` DECIMAL(12,2) NOT NULL,
+	`source_amount` DECIMAL(12,2) NOT NULL,
+	`created_at` DATETIME NOT NULL,
+	`expires_at` DATETIME NOT NULL,
+	INDEX `fx_quotes_expires_at` (`expires_at`)
+);
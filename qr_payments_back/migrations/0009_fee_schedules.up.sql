@@ -0,0 +1,11 @@
+-- Fee schedules applied to new payments, replaced as a whole by every update. Rules are stored as the JSON
+-- sent to `PUT /v1/fees/schedules`, and the ID keeps the order they were listed in
+CREATE TABLE `fee_schedules` (
+	`ID` INT PRIMARY KEY AUTO_INCREMENT,
+	`party` ENUM('Payer', 'Merchant') NOT NULL,
+	`kind` ENUM('Payment', 'Conversion') NULL DEFAULT NULL,
+	`merchants_ID` INT NULL DEFAULT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`rule` JSON NOT NULL,
+	CONSTRAINT `fee_schedules_merchants_ID` FOREIGN KEY (`merchants_ID`) REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Cancellations, refunds and expiries record when they returned the amount to the wallet, so statements
+-- date refunds to the day they happened
+ALTER TABLE `transactions` ADD COLUMN `reversed_at` DATETIME NULL DEFAULT NULL AFTER `created_at`;
+-- Transactions reversed before then are dated by the fee lines refunded with them, when they had any
+UPDATE `transactions` SET `reversed_at` = (
+	SELECT MIN(`created_at`) FROM `transaction_fees` WHERE `transactions_ID` = `transactions`.`ID` AND `amount` < 0
+) WHERE `status` IN ('Cancelled', 'Refunded', 'Expired');
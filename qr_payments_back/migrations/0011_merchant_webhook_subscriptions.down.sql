@@ -0,0 +1,7 @@
+ALTER TABLE `webhook_subscriptions` ADD COLUMN `wallets_ID` INT NULL DEFAULT NULL AFTER `ID`;
+ALTER TABLE `webhook_subscriptions` ADD CONSTRAINT `webhook_subscriptions_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE;
+UPDATE `webhook_subscriptions`
+JOIN `merchants` ON `merchants`.`ID` = `webhook_subscriptions`.`merchants_ID`
+SET `webhook_subscriptions`.`wallets_ID` = `merchants`.`wallets_ID`;
+ALTER TABLE `webhook_subscriptions` DROP FOREIGN KEY `webhook_subscriptions_merchants_ID`;
+ALTER TABLE `webhook_subscriptions` DROP COLUMN `merchants_ID`;
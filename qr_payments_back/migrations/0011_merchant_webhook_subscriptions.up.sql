@@ -0,0 +1,11 @@
+-- Webhook subscriptions are scoped to a merchant, notified of the transactions made to it
+ALTER TABLE `webhook_subscriptions` ADD COLUMN `merchants_ID` INT NULL DEFAULT NULL AFTER `ID`;
+ALTER TABLE `webhook_subscriptions` ADD CONSTRAINT `webhook_subscriptions_merchants_ID` FOREIGN KEY (`merchants_ID`) REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE;
+-- Subscriptions to a wallet move to the merchant settled into it. The ones to every wallet, or to a wallet no
+-- merchant is settled into, match no merchant and are deactivated
+UPDATE `webhook_subscriptions`
+JOIN `merchants` ON `merchants`.`wallets_ID` = `webhook_subscriptions`.`wallets_ID`
+SET `webhook_subscriptions`.`merchants_ID` = `merchants`.`ID`;
+UPDATE `webhook_subscriptions` SET `active` = FALSE WHERE `merchants_ID` IS NULL;
+ALTER TABLE `webhook_subscriptions` DROP FOREIGN KEY `webhook_subscriptions_wallets_ID`;
+ALTER TABLE `webhook_subscriptions` DROP COLUMN `wallets_ID`;
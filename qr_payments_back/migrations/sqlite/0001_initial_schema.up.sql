@@ -0,0 +1,147 @@
+-- SQLite translation of migrations 0001 to 0007 in migrations/, kept in sync with them by hand. Later
+-- schema changes get their own numbered script in this directory, applied by SqliteStore::open.
+-- Decimals are stored as TEXT to keep their exact value, and datetimes as TEXT in local time, as MySQL
+-- stores them
+
+CREATE TABLE `wallets` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`currency` CHAR(3) NOT NULL DEFAULT 'USD',
+	`balance` TEXT NOT NULL DEFAULT '0',
+	`max_single_amount` TEXT NULL DEFAULT NULL,
+	`max_daily_amount` TEXT NULL DEFAULT NULL,
+	`max_monthly_amount` TEXT NULL DEFAULT NULL,
+	`max_daily_count` INTEGER NULL DEFAULT NULL,
+	`frozen` BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE `wallet_balances` (
+	`wallets_ID` INTEGER NOT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`balance` TEXT NOT NULL DEFAULT '0',
+	PRIMARY KEY (`wallets_ID`, `currency`),
+	CONSTRAINT `wallet_balances_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE `merchants` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`wallets_ID` INTEGER NOT NULL UNIQUE,
+	`name` VARCHAR(25) NOT NULL,
+	`city` VARCHAR(15) NOT NULL,
+	`country` CHAR(2) NOT NULL,
+	`mcc` CHAR(4) NOT NULL,
+	`status` TEXT NOT NULL DEFAULT 'Active' CHECK (`status` IN ('Active', 'Suspended')),
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	CONSTRAINT `merchants_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT
+);
+
+CREATE TABLE `transactions` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`wallets_ID` INTEGER NULL DEFAULT NULL,
+	`merchants_ID` INTEGER NULL DEFAULT NULL,
+	`amount` TEXT,
+	`currency` CHAR(3) NOT NULL DEFAULT 'USD',
+	`fx_quote` VARCHAR(32) NULL DEFAULT NULL,
+	`fx_rate` TEXT NULL DEFAULT NULL,
+	`converted_amount` TEXT NULL DEFAULT NULL,
+	`converted_currency` CHAR(3) NULL DEFAULT NULL,
+	`status` TEXT NOT NULL DEFAULT 'Initialized' CHECK (`status` IN ('Initialized', 'PendingReview', 'Confirmed', 'Declined', 'Cancelled', 'Refunded', 'Expired', 'InternalError', 'Log')),
+	`token` VARCHAR(32) DEFAULT NULL,
+	`errors` VARCHAR(128) DEFAULT NULL,
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	CONSTRAINT `transactions_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT `transactions_merchants_ID` FOREIGN KEY (`merchants_ID`) REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT
+);
+CREATE INDEX `transactions_token` ON `transactions` (`token`);
+CREATE INDEX `transactions_status` ON `transactions` (`status`);
+CREATE INDEX `transactions_wallets_ID_created_at` ON `transactions` (`wallets_ID`, `created_at`);
+CREATE INDEX `transactions_merchants_ID_created_at` ON `transactions` (`merchants_ID`, `created_at`);
+
+CREATE TABLE `transaction_fees` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`transactions_ID` INTEGER NOT NULL,
+	`party` TEXT NOT NULL CHECK (`party` IN ('Payer', 'Merchant')),
+	`amount` TEXT NOT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	CONSTRAINT `transaction_fees_transactions_ID` FOREIGN KEY (`transactions_ID`) REFERENCES `transactions` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE INDEX `transaction_fees_transactions_ID` ON `transaction_fees` (`transactions_ID`);
+
+CREATE TABLE `settlement_batches` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`merchants_ID` INTEGER NOT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`period_start` TEXT NOT NULL,
+	`period_end` TEXT NOT NULL,
+	`payments` TEXT NOT NULL,
+	`refunds` TEXT NOT NULL,
+	`fees` TEXT NOT NULL,
+	`net` TEXT NOT NULL,
+	`status` TEXT NOT NULL DEFAULT 'Pending' CHECK (`status` IN ('Pending', 'Held', 'Paid')),
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	`paid_at` TEXT NULL DEFAULT NULL,
+	CONSTRAINT `settlement_batches_merchants_ID` FOREIGN KEY (`merchants_ID`) REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT
+);
+CREATE UNIQUE INDEX `settlement_batches_merchants_ID_period_end` ON `settlement_batches` (`merchants_ID`, `period_end`);
+CREATE INDEX `settlement_batches_status` ON `settlement_batches` (`status`);
+
+CREATE TABLE `settlement_lines` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`batches_ID` INTEGER NOT NULL,
+	`transactions_ID` INTEGER NOT NULL,
+	`kind` TEXT NOT NULL CHECK (`kind` IN ('Payment', 'Refund')),
+	`amount` TEXT NOT NULL,
+	`fee` TEXT NOT NULL,
+	CONSTRAINT `settlement_lines_batches_ID` FOREIGN KEY (`batches_ID`) REFERENCES `settlement_batches` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT `settlement_lines_transactions_ID` FOREIGN KEY (`transactions_ID`) REFERENCES `transactions` (`ID`) ON UPDATE CASCADE ON DELETE RESTRICT
+);
+CREATE UNIQUE INDEX `settlement_lines_transactions_ID_kind` ON `settlement_lines` (`transactions_ID`, `kind`);
+CREATE INDEX `settlement_lines_batches_ID` ON `settlement_lines` (`batches_ID`);
+
+CREATE TABLE `webhook_subscriptions` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`wallets_ID` INTEGER NULL DEFAULT NULL,
+	`url` VARCHAR(512) NOT NULL,
+	`secret` VARCHAR(64) NOT NULL,
+	`active` BOOLEAN NOT NULL DEFAULT TRUE,
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	CONSTRAINT `webhook_subscriptions_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE `webhook_deliveries` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`webhook_subscriptions_ID` INTEGER NOT NULL,
+	`event_type` VARCHAR(64) NOT NULL,
+	`payload` TEXT NOT NULL,
+	`status` TEXT NOT NULL DEFAULT 'Pending' CHECK (`status` IN ('Pending', 'Delivered', 'DeadLetter')),
+	`attempts` INTEGER NOT NULL DEFAULT 0,
+	`next_attempt_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	`last_response_status` INTEGER NULL DEFAULT NULL,
+	`last_error` VARCHAR(256) NULL DEFAULT NULL,
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	`delivered_at` TEXT NULL DEFAULT NULL,
+	CONSTRAINT `webhook_deliveries_webhook_subscriptions_ID` FOREIGN KEY (`webhook_subscriptions_ID`) REFERENCES `webhook_subscriptions` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE INDEX `webhook_deliveries_status_next_attempt_at` ON `webhook_deliveries` (`status`, `next_attempt_at`);
+
+CREATE TABLE `outbox` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`aggregate_type` TEXT NOT NULL CHECK (`aggregate_type` IN ('Transaction', 'Wallet')),
+	`aggregate_ID` INTEGER NOT NULL,
+	`event_type` VARCHAR(64) NOT NULL,
+	`payload` TEXT NOT NULL,
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	`dispatched_at` TEXT NULL DEFAULT NULL
+);
+CREATE INDEX `outbox_dispatched_at` ON `outbox` (`dispatched_at`, `ID`);
+
+CREATE TABLE `balance_adjustments` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`wallets_ID` INTEGER NOT NULL,
+	`amount` TEXT NOT NULL,
+	`currency` CHAR(3) NOT NULL DEFAULT 'USD',
+	`reason` VARCHAR(256) NOT NULL,
+	`created_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+	CONSTRAINT `balance_adjustments_wallets_ID` FOREIGN KEY (`wallets_ID`) REFERENCES `wallets` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+CREATE INDEX `balance_adjustments_wallets_ID` ON `balance_adjustments` (`wallets_ID`);
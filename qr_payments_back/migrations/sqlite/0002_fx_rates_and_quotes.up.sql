@@ -0,0 +1,24 @@
+-- SQLite translation of migration 0008 in migrations/
+
+-- Rates quoted for converting between currencies, replaced as a whole by every update
+CREATE TABLE `fx_rates` (
+	`from_currency` CHAR(3) NOT NULL,
+	`to_currency` CHAR(3) NOT NULL,
+	`rate` TEXT NOT NULL,
+	`updated_at` TEXT NOT NULL,
+	PRIMARY KEY (`from_currency`, `to_currency`)
+);
+
+-- Open quotes. A payment locks a quote by deleting it, and expired ones are dropped as new quotes are
+-- created
+CREATE TABLE `fx_quotes` (
+	`ID` VARCHAR(32) PRIMARY KEY,
+	`from_currency` CHAR(3) NOT NULL,
+	`to_currency` CHAR(3) NOT NULL,
+	`rate` TEXT NOT NULL,
+	`amount` TEXT NOT NULL,
+	`source_amount` TEXT NOT NULL,
+	`created_at` TEXT NOT NULL,
+	`expires_at` TEXT NOT NULL
+);
+CREATE INDEX `fx_quotes_expires_at` ON `fx_quotes` (`expires_at`);
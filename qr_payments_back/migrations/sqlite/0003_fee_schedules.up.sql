@@ -0,0 +1,13 @@
+-- SQLite translation of migration 0009 in migrations/
+
+-- Fee schedules applied to new payments, replaced as a whole by every update. Rules are stored as the JSON
+-- sent to `PUT /v1/fees/schedules`, and the ID keeps the order they were listed in
+CREATE TABLE `fee_schedules` (
+	`ID` INTEGER PRIMARY KEY AUTOINCREMENT,
+	`party` TEXT NOT NULL CHECK (`party` IN ('Payer', 'Merchant')),
+	`kind` TEXT NULL DEFAULT NULL CHECK (`kind` IN ('Payment', 'Conversion')),
+	`merchants_ID` INTEGER NULL DEFAULT NULL,
+	`currency` CHAR(3) NOT NULL,
+	`rule` TEXT NOT NULL,
+	CONSTRAINT `fee_schedules_merchants_ID` FOREIGN KEY (`merchants_ID`) REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE
+);
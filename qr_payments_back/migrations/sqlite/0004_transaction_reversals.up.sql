@@ -0,0 +1,9 @@
+-- SQLite translation of migration 0010 in migrations/
+
+-- Cancellations, refunds and expiries record when they returned the amount to the wallet, so statements
+-- date refunds to the day they happened
+ALTER TABLE `transactions` ADD COLUMN `reversed_at` TEXT NULL DEFAULT NULL;
+-- Transactions reversed before then are dated by the fee lines refunded with them, when they had any
+UPDATE `transactions` SET `reversed_at` = (
+	SELECT MIN(`created_at`) FROM `transaction_fees` WHERE `transactions_ID` = `transactions`.`ID` AND CAST(`amount` AS REAL) < 0
+) WHERE `status` IN ('Cancelled', 'Refunded', 'Expired');
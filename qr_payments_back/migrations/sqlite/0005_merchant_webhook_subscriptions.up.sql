@@ -0,0 +1,12 @@
+-- SQLite translation of migration 0011 in migrations/. The `wallets_ID` column is kept unused, as SQLite
+-- cannot drop a column with a foreign key without rebuilding the table, which would cascade to the
+-- deliveries
+
+-- Webhook subscriptions are scoped to a merchant, notified of the transactions made to it
+ALTER TABLE `webhook_subscriptions` ADD COLUMN `merchants_ID` INTEGER NULL DEFAULT NULL REFERENCES `merchants` (`ID`) ON UPDATE CASCADE ON DELETE CASCADE;
+-- Subscriptions to a wallet move to the merchant settled into it. The ones to every wallet, or to a wallet no
+-- merchant is settled into, match no merchant and are deactivated
+UPDATE `webhook_subscriptions` SET `merchants_ID` = (
+	SELECT `ID` FROM `merchants` WHERE `merchants`.`wallets_ID` = `webhook_subscriptions`.`wallets_ID`
+);
+UPDATE `webhook_subscriptions` SET `active` = FALSE WHERE `merchants_ID` IS NULL;
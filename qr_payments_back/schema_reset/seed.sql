@@ -0,0 +1,4 @@
+INSERT INTO `wallets` (`balance`) VALUES (10000);
+INSERT INTO `wallets` (`balance`) VALUES (5000);
+INSERT INTO `balance_adjustments` (`wallets_ID`, `amount`, `reason`) VALUES (1, 10000, 'Opening balance');
+INSERT INTO `balance_adjustments` (`wallets_ID`, `amount`, `reason`) VALUES (2, 5000, 'Opening balance');
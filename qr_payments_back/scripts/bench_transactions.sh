@@ -0,0 +1,90 @@
+#!/usr/bin/env bash
+# Throughput of POST /v1/transactions against MySQL, for two revisions of the API built in release mode.
+# Each revision is checked out in a git worktree and served on a fresh database with the same seed, so the
+# runs only differ in the code under test.
+#
+# Usage: scripts/bench_transactions.sh <before-rev> [after-rev]
+#   e.g. scripts/bench_transactions.sh <commit before the blocking pool change> HEAD
+# Requires docker, the mysql client, curl and oha (cargo install oha)
+#
+# Tunables: REQUESTS (default 20000), CONCURRENCY (default 256), WORKERS (default 16)
+#
+# No before/after numbers have been measured with it yet, so it makes no claim about the MySQL throughput
+set -euo pipefail
+
+BEFORE=${1:?"usage: $0 <before-rev> [after-rev]"}
+AFTER=${2:-HEAD}
+REQUESTS=${REQUESTS:-20000}
+CONCURRENCY=${CONCURRENCY:-256}
+WORKERS=${WORKERS:-16}
+
+MYSQL_PORT=3307
+MYSQL_PASS=bench
+API_PORT=18090
+CONTAINER=qrpay-bench-mysql
+REPO=$(git rev-parse --show-toplevel)
+WORKDIR=$(mktemp -d)
+
+cleanup() {
+    [[ -n "${SERVER_PID:-}" ]] && kill "$SERVER_PID" 2>/dev/null || true
+    docker rm -f "$CONTAINER" >/dev/null 2>&1 || true
+    for tree in "$WORKDIR"/*/; do
+        git -C "$REPO" worktree remove --force "$tree" 2>/dev/null || true
+    done
+    rm -rf "$WORKDIR"
+}
+trap cleanup EXIT
+
+mysql_cli() {
+    mysql --protocol=TCP -h 127.0.0.1 -P "$MYSQL_PORT" -uroot -p"$MYSQL_PASS" "$@"
+}
+
+docker run -d --rm --name "$CONTAINER" -e MYSQL_ROOT_PASSWORD="$MYSQL_PASS" \
+    -p "$MYSQL_PORT:3306" mysql:8.0 >/dev/null
+until mysql_cli -e "SELECT 1;" >/dev/null 2>&1; do sleep 1; done
+
+# Recreates the schema as the revision defines it: schema.sql, then its migrations and seed when it has them
+reset_schema() {
+    local crate=$1
+    mysql_cli < "$crate/schema_reset/schema.sql"
+    for migration in $(ls "$crate"/migrations/*.up.sql 2>/dev/null | sort); do
+        mysql_cli qr_payments < "$migration"
+    done
+    if [[ -f "$crate/schema_reset/seed.sql" ]]; then
+        mysql_cli qr_payments < "$crate/schema_reset/seed.sql"
+    fi
+    mysql_cli qr_payments -e "UPDATE wallets SET balance = 1000000000 WHERE ID = 1;"
+}
+
+bench() {
+    local rev=$1
+    local tree="$WORKDIR/$(git -C "$REPO" rev-parse --short "$rev")"
+    git -C "$REPO" worktree add --detach "$tree" "$rev" >/dev/null 2>&1
+    local crate="$tree/qr_payments_back"
+
+    # Only the keys every revision understands, the rest keep their defaults
+    cat > "$crate/config/config.json" <<JSON
+{
+    "api": { "addr": "127.0.0.1", "port": $API_PORT, "workers": $WORKERS },
+    "db": { "user": "root", "pass": "$MYSQL_PASS", "addr": "127.0.0.1:$MYSQL_PORT", "db_name": "qr_payments", "migrate_on_startup": false }
+}
+JSON
+    reset_schema "$crate"
+    (cd "$crate" && cargo build --release --quiet)
+
+    (cd "$crate" && exec ./target/release/qr_payments_backend >/dev/null 2>&1) &
+    SERVER_PID=$!
+    until curl -s -o /dev/null "http://127.0.0.1:$API_PORT/v1/wallets"; do sleep 1; done
+
+    echo "== $rev"
+    oha --no-tui -n "$REQUESTS" -c "$CONCURRENCY" -m POST -H "Content-Type: application/json" \
+        -d '{"wallets_id": 1, "amount": -1}' "http://127.0.0.1:$API_PORT/v1/transactions" \
+        | grep -E "Requests/sec|Slowest|Average|99.00%|\[[0-9]{3}\]"
+
+    kill "$SERVER_PID"
+    wait "$SERVER_PID" 2>/dev/null || true
+    SERVER_PID=
+}
+
+bench "$BEFORE"
+bench "$AFTER"
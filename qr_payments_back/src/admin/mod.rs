@@ -0,0 +1,473 @@
+use chrono::TimeDelta;
+use clap::{Parser, Subcommand};
+use error_mapper::{create_new_error, TheResult};
+use mysql::prelude::Queryable;
+use rust_decimal::Decimal;
+
+use crate::{
+    config::{Config, DbConfig, DbDriver},
+    database::{
+        in_transaction,
+        migrations::{self, MigrateCommand},
+        DbConn, Repositories,
+    },
+    datatypes::{TransactionsIdType, WalletsIdType},
+    modules::{
+        currencies::Currency,
+        transactions::{Transaction, TransactionStatus},
+        wallets::{reconciliation, Wallet},
+    },
+    DATETIME_FORMAT,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Script recreating the schema, and the dev data loaded once the migrations ran. Anchored to the crate
+/// the binary was built from, like the default migrations directory, so they don't depend on the working
+/// directory
+const SCHEMA_SCRIPT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schema_reset/schema.sql");
+const SEED_SCRIPT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schema_reset/seed.sql");
+/// Max length of the `reason` column in the `balance_adjustments` table
+const REASON_MAX_LENGTH: usize = 256;
+
+/// Administrative tasks over the qr_payments database, reading the same configuration as the API
+#[derive(Parser)]
+#[command(name = "qrpay-admin", version, about)]
+pub struct AdminCli {
+    /// Configuration file. Defaults to `QRPAY_CONFIG`, then to `config/config.json`
+    #[arg(long, global = true)]
+    config: Option<String>,
+    #[command(subcommand)]
+    command: AdminCommand,
+}
+
+#[derive(Subcommand)]
+enum AdminCommand {
+    /// Creates, freezes and adjusts wallets
+    #[command(subcommand)]
+    Wallets(WalletsCommand),
+    /// Lists, inspects and cancels transactions
+    #[command(subcommand)]
+    Transactions(TransactionsCommand),
+    /// Manages the schema migrations. Applies the pending ones by default
+    Migrate {
+        #[command(subcommand)]
+        command: Option<MigrateCommand>,
+    },
+    /// Drops and recreates the schema, then applies every migration and loads the dev seed. Dev only
+    ResetSchema {
+        /// Confirms every table is wiped
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Compares every wallet balance against its adjustments and transactions, failing on differences
+    Reconcile,
+}
+
+#[derive(Subcommand)]
+enum WalletsCommand {
+    /// Lists every wallet
+    List,
+    /// Creates a wallet, recording its balance as the opening adjustment
+    Create {
+        #[arg(long, default_value_t = Decimal::ZERO)]
+        balance: Decimal,
+        #[arg(long, value_parser = parse_currency, default_value = "USD")]
+        currency: Currency,
+    },
+    /// Stops the wallet from starting new transactions
+    Freeze { wallets_id: WalletsIdType },
+    /// Lets a frozen wallet start transactions again
+    Unfreeze { wallets_id: WalletsIdType },
+    /// Applies a manual amount to the wallet balance, negative to debit it. Adjusting another currency than
+    /// the wallet's own opens a balance in it
+    Adjust {
+        wallets_id: WalletsIdType,
+        #[arg(long, allow_hyphen_values = true)]
+        amount: Decimal,
+        /// Defaults to the wallet currency
+        #[arg(long, value_parser = parse_currency)]
+        currency: Option<Currency>,
+        #[arg(long)]
+        reason: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TransactionsCommand {
+    /// Lists the latest transactions as JSON lines, newest first
+    List {
+        #[arg(long)]
+        wallets_id: Option<WalletsIdType>,
+        #[arg(long, value_parser = parse_status)]
+        status: Option<TransactionStatus>,
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+    },
+    /// Prints a transaction as JSON
+    Show { transactions_id: TransactionsIdType },
+    /// Cancels the Initialized transactions older than the received seconds, returning their amount to the
+    /// wallet. Defaults to `transactions.ttl_secs`
+    CancelStale {
+        #[arg(long)]
+        older_than_secs: Option<u64>,
+    },
+}
+
+/// ## Description
+/// Runs the received command against the configured database. Only the MySQL driver is supported, as the
+/// migrations and the reports are written for it
+pub async fn run(cli: AdminCli) -> TheResult<()> {
+    Config::initialize_config(cli.config.as_deref())?;
+    let db_config = Config::get_db_config().await?;
+    if db_config.driver != DbDriver::MySql {
+        return Err(create_new_error!(
+            "qrpay-admin only supports the mysql driver"
+        ));
+    }
+    let repositories = Repositories::init(&db_config).await?;
+
+    match cli.command {
+        AdminCommand::Wallets(command) => run_wallets(command, &repositories).await,
+        AdminCommand::Transactions(command) => run_transactions(command, &repositories).await,
+        AdminCommand::Migrate { command } => {
+            let command = command.unwrap_or(MigrateCommand::Up);
+            let outcome = migrations::run_command(command, db_config.migrations_dir).await?;
+            println!("{}", outcome);
+            Ok(())
+        }
+        AdminCommand::ResetSchema { yes } => reset_schema(yes, &db_config).await,
+        AdminCommand::Reconcile => reconcile().await,
+    }
+}
+
+async fn run_wallets(command: WalletsCommand, repositories: &Repositories) -> TheResult<()> {
+    match command {
+        WalletsCommand::List => {
+            println!(
+                "{:>8} {:>8} {:>14} {:>7} {:>14} {:>14} {:>14} {:>6}",
+                "ID",
+                "currency",
+                "balance",
+                "frozen",
+                "max_single",
+                "max_daily",
+                "max_monthly",
+                "count"
+            );
+            for wallet in repositories.wallets.select_all()? {
+                let limit = |amount: Option<Decimal>| {
+                    amount.map_or_else(|| String::from("-"), |amount| amount.to_string())
+                };
+                println!(
+                    "{:>8} {:>8} {:>14} {:>7} {:>14} {:>14} {:>14} {:>6}",
+                    wallet.id,
+                    wallet.currency,
+                    wallet.balance,
+                    wallet.frozen,
+                    limit(wallet.limits.max_single_amount),
+                    limit(wallet.limits.max_daily_amount),
+                    limit(wallet.limits.max_monthly_amount),
+                    wallet
+                        .limits
+                        .max_daily_count
+                        .map_or_else(|| String::from("-"), |count| count.to_string())
+                );
+            }
+        }
+        WalletsCommand::Create { balance, currency } => {
+            if balance < Decimal::ZERO {
+                return Err(create_new_error!("Opening balance cannot be negative"));
+            }
+
+            let wallet = DbConn::run(move |conn| {
+                in_transaction(conn, |db_transaction| {
+                    let mut wallet = Wallet::insert(db_transaction, currency)?;
+                    if balance != Decimal::ZERO
+                        && wallet
+                            .adjust_balance(db_transaction, balance, currency, "Opening balance")?
+                            .is_none()
+                    {
+                        return Err(create_new_error!("Could not record the opening balance"));
+                    }
+                    Ok(wallet)
+                })
+            })
+            .await?;
+            println!(
+                "Created wallet with ID: {} and balance {} {}",
+                wallet.id, wallet.balance, wallet.currency
+            );
+        }
+        WalletsCommand::Freeze { wallets_id } => {
+            set_frozen(repositories, wallets_id, true).await?;
+            println!("Wallet with ID: {} frozen", wallets_id);
+        }
+        WalletsCommand::Unfreeze { wallets_id } => {
+            set_frozen(repositories, wallets_id, false).await?;
+            println!("Wallet with ID: {} unfrozen", wallets_id);
+        }
+        WalletsCommand::Adjust {
+            wallets_id,
+            amount,
+            currency,
+            reason,
+        } => {
+            if amount == Decimal::ZERO {
+                return Err(create_new_error!("Adjustment amount cannot be zero"));
+            }
+            let reason = reason.trim().to_string();
+            if reason.is_empty() || reason.chars().count() > REASON_MAX_LENGTH {
+                return Err(create_new_error!(format!(
+                    "Reason must have between 1 and {} characters",
+                    REASON_MAX_LENGTH
+                )));
+            }
+
+            let (currency, balance, adjustment) = DbConn::run(move |conn| {
+                in_transaction(conn, |db_transaction| {
+                    let Some(mut wallet) = Wallet::select_by_id(db_transaction, wallets_id)? else {
+                        return Err(create_new_error!(format!(
+                            "Wallet with ID: {} was not found",
+                            wallets_id
+                        )));
+                    };
+                    let currency = currency.unwrap_or(wallet.currency);
+                    if !currency.is_exact(amount) {
+                        return Err(create_new_error!(format!(
+                            "Amount cannot have more than {} decimal places in {}",
+                            currency.minor_units(),
+                            currency
+                        )));
+                    }
+
+                    //  The balance is only checked by the guarded update, so concurrent debits cannot both pass
+                    let Some(adjustment) =
+                        wallet.adjust_balance(db_transaction, amount, currency, &reason)?
+                    else {
+                        return Err(create_new_error!(format!(
+                            "Wallet with ID: {} has insufficient {} balance for the adjustment",
+                            wallets_id, currency
+                        )));
+                    };
+                    let balance = if currency == wallet.currency {
+                        wallet.balance
+                    } else {
+                        wallet
+                            .select_held_balances(db_transaction)?
+                            .into_iter()
+                            .find(|balance| balance.currency == currency)
+                            .map_or(Decimal::ZERO, |balance| balance.balance)
+                    };
+                    Ok((currency, balance, adjustment))
+                })
+            })
+            .await?;
+            println!(
+                "Adjustment with ID: {} applied {} {} to wallet with ID: {}, new balance {} {}",
+                adjustment.id, adjustment.amount, currency, wallets_id, balance, currency
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_frozen(
+    repositories: &Repositories,
+    wallets_id: WalletsIdType,
+    frozen: bool,
+) -> TheResult<()> {
+    let Some(mut wallet) = repositories.wallets.select_by_id(wallets_id)? else {
+        return Err(create_new_error!(format!(
+            "Wallet with ID: {} was not found",
+            wallets_id
+        )));
+    };
+
+    DbConn::run(move |conn| wallet.update_frozen(conn, frozen)).await
+}
+
+async fn run_transactions(
+    command: TransactionsCommand,
+    repositories: &Repositories,
+) -> TheResult<()> {
+    match command {
+        TransactionsCommand::List {
+            wallets_id,
+            status,
+            limit,
+        } => {
+            let transactions = DbConn::run(move |conn| {
+                Transaction::select_filtered(conn, wallets_id, status, limit)
+            })
+            .await?;
+            for transaction in transactions {
+                println!("{}", to_json(&transaction)?);
+            }
+        }
+        TransactionsCommand::Show { transactions_id } => {
+            let Some(transaction) = repositories.transactions.select_by_id(transactions_id)? else {
+                return Err(create_new_error!(format!(
+                    "Transaction with ID: {} was not found",
+                    transactions_id
+                )));
+            };
+            let transaction = serde_json::to_string_pretty(&transaction)
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            println!("{}", transaction);
+        }
+        TransactionsCommand::CancelStale { older_than_secs } => {
+            let older_than_secs = match older_than_secs {
+                Some(older_than_secs) => older_than_secs,
+                None => Config::get_transactions_config().await?.ttl_secs,
+            };
+            let cutoff = i64::try_from(older_than_secs)
+                .ok()
+                .and_then(TimeDelta::try_seconds)
+                .and_then(|older_than| {
+                    chrono::Local::now()
+                        .naive_local()
+                        .checked_sub_signed(older_than)
+                })
+                .ok_or_else(|| {
+                    create_new_error!(format!(
+                        "--older-than-secs {} is out of range",
+                        older_than_secs
+                    ))
+                })?;
+
+            let mut cancelled = 0;
+            for mut transaction in repositories
+                .transactions
+                .select_initialized_before(cutoff)?
+            {
+                //  The ones confirmed or expired meanwhile are skipped by the status guard
+                if repositories
+                    .transactions
+                    .reverse(&mut transaction, TransactionStatus::Cancelled)?
+                {
+                    println!("Cancelled {}", to_json(&transaction)?);
+                    cancelled += 1;
+                }
+            }
+            println!(
+                "Cancelled {} transaction(s) created before {}",
+                cancelled,
+                cutoff.format(DATETIME_FORMAT)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// ## Description
+/// Recreates the schema with `schema.sql`, then applies the migrations and loads `seed.sql`, all on the
+/// same connection
+async fn reset_schema(confirmed: bool, db_config: &DbConfig) -> TheResult<()> {
+    let db_name = db_config.db_name.as_str();
+    if !confirmed {
+        return Err(create_new_error!(
+            "Resetting the schema wipes every table, run again with --yes to confirm"
+        ));
+    }
+
+    let schema = read_script(SCHEMA_SCRIPT)?;
+    //  The script names the schema it drops, which must be the configured one
+    if !schema.contains(&format!("USE {};", db_name)) {
+        return Err(create_new_error!(format!(
+            "{} does not recreate the configured schema {}",
+            SCHEMA_SCRIPT, db_name
+        )));
+    }
+    let seed = read_script(SEED_SCRIPT)?;
+
+    let migrations_dir = db_config.migrations_dir.clone();
+    let versions = DbConn::run(move |conn| {
+        conn.query_drop(schema)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let versions = migrations::migrate_up(conn, &migrations_dir)?;
+        conn.query_drop(seed)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        Ok(versions)
+    })
+    .await?;
+    println!(
+        "Schema {} recreated with {} migration(s): {:?}, and seeded",
+        db_name,
+        versions.len(),
+        versions
+    );
+
+    Ok(())
+}
+
+async fn reconcile() -> TheResult<()> {
+    let entries = DbConn::run(reconciliation::select_report).await?;
+
+    println!(
+        "{:>8} {:>8} {:>14} {:>14} {:>14} {:>14} {:>14} {:>14} {:>14} {:>11} {:>14}",
+        "ID",
+        "currency",
+        "balance",
+        "adjustments",
+        "transactions",
+        "fees",
+        "payouts",
+        "expected",
+        "difference",
+        "initialized",
+        "pending_review"
+    );
+    for entry in &entries {
+        println!(
+            "{:>8} {:>8} {:>14} {:>14} {:>14} {:>14} {:>14} {:>14} {:>14} {:>11} {:>14}",
+            entry.wallets_id,
+            entry.currency,
+            entry.balance,
+            entry.adjustments,
+            entry.transactions,
+            entry.fees,
+            entry.payouts,
+            entry.expected_balance(),
+            entry.difference(),
+            entry.initialized_count,
+            entry.pending_review_count
+        );
+    }
+
+    let mismatches = entries
+        .iter()
+        .filter(|entry| entry.difference() != Decimal::ZERO)
+        .count();
+    if mismatches > 0 {
+        return Err(create_new_error!(format!(
+            "{} of {} wallet balance(s) do not reconcile",
+            mismatches,
+            entries.len()
+        )));
+    }
+    println!("All {} wallet balance(s) reconcile", entries.len());
+
+    Ok(())
+}
+
+fn parse_currency(input: &str) -> Result<Currency, String> {
+    Currency::from_code(input).ok_or_else(|| format!("Unsupported currency: {}", input))
+}
+
+fn parse_status(input: &str) -> Result<TransactionStatus, String> {
+    TransactionStatus::from_string(input.to_string())
+        .ok_or_else(|| format!("Unknown transaction status: {}", input))
+}
+
+fn read_script(path: &str) -> TheResult<String> {
+    std::fs::read_to_string(path)
+        .map_err(|error| create_new_error!(format!("Could not read {}: {}", path, error)))
+}
+
+fn to_json(transaction: &Transaction) -> TheResult<String> {
+    serde_json::to_string(transaction).map_err(|error| create_new_error!(error.to_string()))
+}
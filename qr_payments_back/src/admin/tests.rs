@@ -0,0 +1,49 @@
+use clap::{CommandFactory, Parser};
+use rust_decimal::Decimal;
+
+use super::{AdminCli, AdminCommand, WalletsCommand};
+use crate::modules::currencies::Currency;
+
+#[test]
+fn admin_cli_definition_is_valid() {
+    AdminCli::command().debug_assert();
+}
+
+#[test]
+fn adjustments_accept_negative_amounts_and_an_optional_currency() {
+    let cli = AdminCli::try_parse_from([
+        "qrpay-admin",
+        "wallets",
+        "adjust",
+        "7",
+        "--amount",
+        "-12.50",
+        "--currency",
+        "EUR",
+        "--reason",
+        "Chargeback",
+    ])
+    .unwrap();
+
+    let AdminCommand::Wallets(WalletsCommand::Adjust {
+        wallets_id,
+        amount,
+        currency,
+        reason,
+    }) = cli.command
+    else {
+        panic!("expected a wallets adjust command");
+    };
+    assert_eq!(wallets_id, 7);
+    assert_eq!(amount, "-12.50".parse::<Decimal>().unwrap());
+    assert_eq!(currency, Currency::from_code("EUR"));
+    assert_eq!(reason, "Chargeback");
+}
+
+#[test]
+fn unsupported_currencies_are_rejected_while_parsing() {
+    assert!(
+        AdminCli::try_parse_from(["qrpay-admin", "wallets", "create", "--currency", "XXX",])
+            .is_err()
+    );
+}
@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{test, web, App};
+use futures_util::future::join_all;
+use rust_decimal::Decimal;
+use serde_json::json;
+
+use crate::{
+    config::Config,
+    database::{memory::InMemoryStore, Repositories},
+    modules::wallets::Wallet,
+};
+
+use super::v1_services;
+
+/// Blocking delay of every repository operation, standing in for a MySQL round trip
+const DB_LATENCY: Duration = Duration::from_millis(2);
+const REQUESTS: usize = 256;
+
+/// ## Description
+/// Throughput of `POST /v1/transactions` with every request in flight at once, on the single threaded
+/// runtime of the test service. Run with `cargo test --release bench -- --ignored --nocapture`
+///
+/// Were the DB round trips to stall the worker, the requests would run one after the other and take at least
+/// `REQUESTS` times the latency of their round trips. Offloaded to the blocking thread pool they overlap, so
+/// the elapsed time stays well below that
+///
+/// The latency is simulated, so this only shows the requests overlap, not the throughput against MySQL.
+/// `scripts/bench_transactions.sh` compares two revisions of the server against a real MySQL, but no results
+/// of it have been recorded
+#[actix_web::test]
+#[ignore]
+async fn new_transactions_throughput() {
+    Config::initialize_config(None).unwrap();
+    let wallets = vec![Wallet {
+        id: 1,
+        balance: Decimal::from(1_000_000_000),
+        ..Default::default()
+    }];
+    let repositories =
+        Repositories::in_memory(InMemoryStore::new(wallets).with_latency(DB_LATENCY));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::from(repositories.wallets.clone()))
+            .app_data(web::Data::from(repositories.transactions.clone()))
+            .app_data(web::Data::from(repositories.merchants.clone()))
+            .app_data(web::Data::from(repositories.settlements.clone()))
+            .app_data(web::Data::from(repositories.fx.clone()))
+            .app_data(web::Data::from(repositories.fees.clone()))
+            .configure(v1_services),
+    )
+    .await;
+
+    let started = Instant::now();
+    let responses = join_all((0..REQUESTS).map(|_| {
+        test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/v1/transactions")
+                .set_json(json!({ "wallets_id": 1, "amount": -1 }))
+                .to_request(),
+        )
+    }))
+    .await;
+    let elapsed = started.elapsed();
+
+    assert!(responses
+        .iter()
+        .all(|response| response.status().is_success()));
+    println!(
+        "{} requests in {:.2?}: {:.0} requests/s",
+        REQUESTS,
+        elapsed,
+        REQUESTS as f64 / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,292 @@
+use actix_web::{http::StatusCode, HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+use crate::{
+    logging,
+    modules::{
+        currencies::Currency, fx::QuoteError, transactions::TransactionStatus,
+        wallets::limits::LimitBreach,
+    },
+    validation::{FieldError, AMOUNT_MAX},
+};
+
+/// Stable, machine readable error codes. Clients match on these, so existing ones must not be renamed
+#[derive(Debug, Clone, Copy, PartialEq, strum::Display)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InvalidRequest,
+    ValidationFailed,
+    WalletNotFound,
+    WalletFrozen,
+    MerchantNotFound,
+    MerchantSuspended,
+    SettlementNotFound,
+    WebhookSubscriptionNotFound,
+    InsufficientFunds,
+    CurrencyMismatch,
+    ConversionUnavailable,
+    QuoteNotFound,
+    QuoteExpired,
+    QuoteMismatch,
+    /// Uses the code of the limit breached, e.g. `DAILY_COUNT_LIMIT_EXCEEDED`
+    #[strum(to_string = "{0}")]
+    LimitExceeded(LimitBreach),
+    TransactionNotFound,
+    TransactionDeclined,
+    InvalidTransition,
+    ConcurrentModification,
+    InvalidConfiguration,
+    InternalError,
+}
+
+/// ## Description
+/// Error returned by the API handlers. Responds with the envelope `{code, message, details, request_id}`
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: ErrorCode,
+    message: String,
+    details: Option<Value>,
+}
+
+/// Body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(test, derive(Default))]
+#[schema(as = ApiError)]
+pub struct ErrorBody {
+    #[schema(example = "WALLET_NOT_FOUND")]
+    pub code: String,
+    pub message: String,
+    /// Depends on the code, e.g. the invalid fields of VALIDATION_FAILED
+    pub details: Option<Value>,
+    /// Also echoed in the X-Request-Id header
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// The cause is expected to be logged by the caller, it is never sent to the client
+    pub fn internal() -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::InternalError,
+            "Internal server error",
+        )
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::ValidationFailed,
+            message,
+        )
+    }
+
+    pub fn invalid_fields(errors: Vec<FieldError>) -> Self {
+        Self::validation("Request has invalid fields").with_details(json!({ "fields": errors }))
+    }
+
+    pub fn wallet_not_found(wallets_id: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::WalletNotFound,
+            format!("Wallet with ID: {} was not found", wallets_id),
+        )
+    }
+
+    pub fn wallet_frozen(wallets_id: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::WalletFrozen,
+            format!("Wallet with ID: {} is frozen", wallets_id),
+        )
+    }
+
+    pub fn merchant_not_found(merchants_id: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::MerchantNotFound,
+            format!("Merchant with ID: {} was not found", merchants_id),
+        )
+    }
+
+    pub fn merchant_suspended(merchants_id: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::MerchantSuspended,
+            format!("Merchant with ID: {} is suspended", merchants_id),
+        )
+    }
+
+    pub fn settlement_not_found(batches_id: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::SettlementNotFound,
+            format!("Settlement batch with ID: {} was not found", batches_id),
+        )
+    }
+
+    pub fn webhook_subscription_not_found(subscriptions_id: impl std::fmt::Display) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::WebhookSubscriptionNotFound,
+            format!(
+                "Webhook subscription with ID: {} was not found",
+                subscriptions_id
+            ),
+        )
+    }
+
+    pub fn insufficient_funds() -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::InsufficientFunds,
+            "Wallet has insufficient balance for transaction",
+        )
+    }
+
+    pub fn currency_mismatch(wallets_id: impl std::fmt::Display, currency: Currency) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::CurrencyMismatch,
+            format!(
+                "Wallet with ID: {} holds no {} balance, a conversion must be requested to pay in it",
+                wallets_id, currency
+            ),
+        )
+        .with_details(json!({ "currency": currency }))
+    }
+
+    pub fn conversion_unavailable(from: Currency, to: Currency) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::ConversionUnavailable,
+            format!(
+                "No exchange rate is available to convert {} to {}",
+                from, to
+            ),
+        )
+        .with_details(json!({ "from": from, "to": to }))
+    }
+
+    pub fn quote(error: QuoteError) -> Self {
+        match error {
+            QuoteError::Unavailable { from, to } => Self::conversion_unavailable(from, to),
+            QuoteError::OutOfRange { from } => Self::validation(format!(
+                "Amount converted to {} cannot exceed {} in absolute value",
+                from, AMOUNT_MAX
+            )),
+            QuoteError::NotFound(id) => Self::new(
+                StatusCode::NOT_FOUND,
+                ErrorCode::QuoteNotFound,
+                format!("Quote with ID: {} was not found or was already used", id),
+            ),
+            QuoteError::Expired(id) => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::QuoteExpired,
+                format!("Quote with ID: {} expired, a new one must be requested", id),
+            ),
+            QuoteError::Mismatch(id) => Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::QuoteMismatch,
+                format!(
+                    "Quote with ID: {} was created for other currencies or another amount",
+                    id
+                ),
+            ),
+        }
+    }
+
+    pub fn limit_exceeded(breach: LimitBreach) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::LimitExceeded(breach),
+            breach.message(),
+        )
+    }
+
+    pub fn transaction_not_found() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::TransactionNotFound,
+            "No transaction found for the required criteria",
+        )
+    }
+
+    pub fn invalid_transition(from: TransactionStatus, to: TransactionStatus) -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            ErrorCode::InvalidTransition,
+            format!("Transaction cannot move from status {} to {}", from, to),
+        )
+        .with_details(json!({ "from": from.to_string(), "to": to.to_string() }))
+    }
+
+    /// The current configuration is kept, the message lists every invalid field
+    pub fn invalid_configuration(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::InvalidConfiguration,
+            message,
+        )
+    }
+
+    pub fn concurrent_modification() -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            ErrorCode::ConcurrentModification,
+            "Transaction status changed while processing the request",
+        )
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(ErrorBody {
+            code: self.code.to_string(),
+            message: self.message.clone(),
+            details: self.details.clone(),
+            request_id: logging::request_id(),
+        })
+    }
+}
+
+/// ## Description
+/// Turns JSON bodies and paths that can't be parsed into the error envelope, instead of actix' plain text
+/// responses
+pub(super) fn invalid_request<E: std::fmt::Display>(
+    error: E,
+    _request: &HttpRequest,
+) -> actix_web::Error {
+    ApiError::new(
+        StatusCode::BAD_REQUEST,
+        ErrorCode::InvalidRequest,
+        error.to_string(),
+    )
+    .into()
+}
@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    config::{Config, DbDriver},
+    database::{DbConn, PoolStats},
+    tasks::{self, TaskStatus},
+    ALIVE_SINCE, APP_VERSION,
+};
+
+/// Set once the graceful shutdown starts, so load balancers stop routing before the server stops
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn health_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(healthz).service(readyz);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(healthz, readyz), tags((name = "ops")))]
+pub(super) struct HealthApi;
+
+pub(super) fn start_draining() {
+    DRAINING.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct Liveness {
+    status: &'static str,
+    version: &'static str,
+    uptime_secs: i64,
+    tasks: Vec<TaskStatus>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct Readiness {
+    status: &'static str,
+    version: &'static str,
+    uptime_secs: i64,
+    draining: bool,
+    database: DatabaseCheck,
+    tasks: Vec<TaskStatus>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DatabaseCheck {
+    driver: DbDriver,
+    /// ok, failing, or unchecked for the drivers without a pool to ping
+    status: &'static str,
+    latency_ms: Option<f64>,
+    error: Option<String>,
+    pool: Option<PoolStats>,
+}
+
+/// healthz
+///
+/// Liveness: the process is up and serving, regardless of its dependencies
+#[utoipa::path(
+    tag = "ops",
+    summary = "Liveness, regardless of the dependencies",
+    responses((status = 200, description = "The process is up", body = Liveness))
+)]
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(Liveness {
+        status: "ok",
+        version: APP_VERSION,
+        uptime_secs: uptime_secs(),
+        tasks: tasks::statuses(),
+    })
+}
+
+/// readyz
+///
+/// Readiness: the database answers, no background task stalled or died and the server is not shutting
+/// down. Responds 503 with the same body otherwise
+#[utoipa::path(
+    tag = "ops",
+    summary = "Readiness: database, background tasks and draining",
+    responses(
+        (status = 200, description = "Ready to serve", body = Readiness),
+        (status = 503, description = "Not ready, same body", body = Readiness),
+    )
+)]
+#[get("/readyz")]
+async fn readyz() -> HttpResponse {
+    let database = check_database().await;
+    let tasks = tasks::statuses();
+    let draining = DRAINING.load(Ordering::Relaxed);
+
+    let ready = !draining && database.status != "failing" && tasks.iter().all(|task| task.healthy);
+    let readiness = Readiness {
+        status: if ready { "ready" } else { "not_ready" },
+        version: APP_VERSION,
+        uptime_secs: uptime_secs(),
+        draining,
+        database,
+        tasks,
+    };
+
+    match ready {
+        true => HttpResponse::Ok().json(readiness),
+        false => HttpResponse::ServiceUnavailable().json(readiness),
+    }
+}
+
+async fn check_database() -> DatabaseCheck {
+    let driver = Config::get_db_config()
+        .await
+        .map(|db_config| db_config.driver)
+        .unwrap_or_default();
+    let mut check = DatabaseCheck {
+        driver,
+        status: "unchecked",
+        latency_ms: None,
+        error: None,
+        pool: DbConn::pool_stats(),
+    };
+    if !DbConn::is_initialized() {
+        return check;
+    }
+
+    match DbConn::ping().await {
+        Ok(latency) => {
+            check.status = "ok";
+            check.latency_ms = Some(latency.as_secs_f64() * 1000.0);
+        }
+        Err(error) => {
+            check.status = "failing";
+            check.error = Some(error.error.error_content);
+        }
+    }
+    //  Read after the ping so it counts in the statistics
+    check.pool = DbConn::pool_stats();
+
+    check
+}
+
+fn uptime_secs() -> i64 {
+    ALIVE_SINCE
+        .get()
+        .map(|alive_since| (chrono::Local::now().naive_local() - *alive_since).num_seconds())
+        .unwrap_or_default()
+}
@@ -0,0 +1,65 @@
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+
+use utoipa::OpenApi;
+
+use crate::metrics;
+
+pub(super) fn metrics_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(export);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(export), tags((name = "ops")))]
+pub(super) struct MetricsApi;
+
+/// metrics
+///
+/// Every metric in the Prometheus text format
+#[utoipa::path(
+    tag = "ops",
+    summary = "Metrics in the Prometheus text format",
+    responses((status = 200, description = "Every metric", content_type = "text/plain", body = String))
+)]
+#[get("/metrics")]
+async fn export() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(metrics::render())
+}
+
+/// ## Description
+/// Middleware counting every request and its latency, labelled by the route pattern rather than the path,
+/// so IDs and tokens don't create a series each
+pub(super) async fn track_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| String::from("unmatched"));
+
+    let response = next.call(request).await;
+    let status = match &response {
+        Ok(response) => response.status(),
+        Err(error) => error.as_response_error().status_code(),
+    };
+
+    metrics::HTTP_REQUESTS
+        .with_label_values(&[&method, &route, status.as_str()])
+        .inc();
+    metrics::HTTP_REQUEST_DURATION
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+
+    response
+}
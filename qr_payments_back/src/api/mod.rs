@@ -1,60 +1,193 @@
-use actix_web::{dev::ServerHandle, web, App, HttpServer};
+use std::time::Duration;
+
+use actix_web::{dev::ServerHandle, middleware::from_fn, web, App, HttpServer};
 use error_mapper::{create_new_error, TheResult};
-use the_logger::{log_info, TheLogger};
+use the_logger::TheLogger;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc::{self, Receiver, Sender};
 
-use crate::{config::Config, TIME_FORMAT};
+use crate::{
+    config::Config,
+    database,
+    database::Repositories,
+    log_error, log_info,
+    modules::{fees, fx},
+    TIME_FORMAT,
+};
 
+#[cfg(test)]
+mod bench;
+pub mod error;
+mod health;
+mod metrics;
+mod openapi;
+pub mod request_id;
 mod services;
+#[cfg(test)]
+mod tests;
+pub mod validation;
 
 pub struct ApiData {
     stop_signal: mpsc::Sender<()>,
 }
 
-pub async fn start_api(stop_channels: (Sender<()>, Receiver<()>)) -> TheResult<()> {
+pub async fn start_api(
+    stop_channels: (Sender<()>, Receiver<()>),
+    repositories: Repositories,
+) -> TheResult<()> {
     let api_config = Config::get_api_config().await?;
-
-    let server =
-        HttpServer::new(move || {
-            let stop_sender = stop_channels.0.clone();
-            App::new()
-                .app_data(web::Data::new(ApiData {
-                    stop_signal: stop_sender,
-                }))
-                .service(
-                    web::scope("/v1")
-                        .service(web::scope("/configurations").configure(services::api_services))
-                        .service(
-                            web::scope("/wallets")
-                                .configure(crate::modules::wallets::services::wallets_services),
-                        )
-                        .service(web::scope("/transactions").configure(
-                            crate::modules::transactions::services::transactions_services,
-                        )),
-                )
+    let fx_config = Config::get_fx_config().await?;
+    if let Some(rates_file) = fx_config.rates_file {
+        let rates = fx::load_rates_file(&rates_file)?;
+        let now = chrono::Local::now().naive_local();
+        let fx = web::Data::from(repositories.fx.clone());
+        if database::blocking(&fx, move |repository| {
+            fx::seed_rates(repository, rates, now)
+        })
+        .await?
+        {
+            log_info!(TheLogger::instance(), "Stored the rates of {}", rates_file);
+        }
+    }
+    let fees_config = Config::get_fees_config().await?;
+    if let Some(schedules_file) = fees_config.schedules_file {
+        let schedules = fees::load_schedules_file(&schedules_file)?;
+        let fees = web::Data::from(repositories.fees.clone());
+        if database::blocking(&fees, move |repository| {
+            fees::seed_schedules(repository, schedules)
         })
-        .bind((api_config.addr.as_str(), api_config.port))
-        .map_err(|error| create_new_error!(format!("Failed to initialize HTTP server: {}", error)))?
-        .workers(api_config.workers)
-        .run();
+        .await?
+        {
+            log_info!(
+                TheLogger::instance(),
+                "Stored the fee schedules of {}",
+                schedules_file
+            );
+        }
+    }
+    let (stop_sender, stop_receiver) = stop_channels;
+    tokio::spawn(stop_on_signal(stop_sender.clone()));
+
+    let server = HttpServer::new(move || {
+        let stop_sender = stop_sender.clone();
+        App::new()
+            .wrap(from_fn(metrics::track_requests))
+            .wrap(from_fn(request_id::propagate_request_id))
+            .app_data(web::Data::new(ApiData {
+                stop_signal: stop_sender,
+            }))
+            .app_data(web::Data::from(repositories.wallets.clone()))
+            .app_data(web::Data::from(repositories.transactions.clone()))
+            .app_data(web::Data::from(repositories.merchants.clone()))
+            .app_data(web::Data::from(repositories.settlements.clone()))
+            .app_data(web::Data::from(repositories.webhooks.clone()))
+            .app_data(web::Data::from(repositories.fx.clone()))
+            .app_data(web::Data::from(repositories.fees.clone()))
+            .configure(health::health_services)
+            .configure(metrics::metrics_services)
+            .configure(openapi::openapi_services)
+            .configure(v1_services)
+    })
+    .bind((api_config.addr.as_str(), api_config.port))
+    .map_err(|error| create_new_error!(format!("Failed to initialize HTTP server: {}", error)))?
+    .workers(api_config.workers)
+    //  SIGINT and SIGTERM go through the stop channel, so readiness fails before the server stops
+    .disable_signals()
+    .run();
 
     let server_handler = server.handle();
-    tokio::spawn(api_stop_handler(server_handler, stop_channels.1));
+    tokio::spawn(api_stop_handler(
+        server_handler,
+        stop_receiver,
+        Duration::from_secs(api_config.drain_secs),
+    ));
 
     server
         .await
         .map_err(|error| create_new_error!(error.to_string()))
 }
 
-async fn api_stop_handler(server_handler: ServerHandle, mut stop_receiver: Receiver<()>) {
+/// ## Description
+/// Registers every v1 route. The repositories, the exchange and the fee schedules must be registered as app
+/// data beforehand
+pub fn v1_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/v1")
+            .app_data(web::JsonConfig::default().error_handler(error::invalid_request))
+            .app_data(web::PathConfig::default().error_handler(error::invalid_request))
+            .app_data(web::QueryConfig::default().error_handler(error::invalid_request))
+            .service(web::scope("/configurations").configure(services::api_services))
+            .service(
+                web::scope("/wallets")
+                    .configure(crate::modules::wallets::services::wallets_services),
+            )
+            .service(
+                web::scope("/transactions")
+                    .configure(crate::modules::transactions::services::transactions_services),
+            )
+            .service(
+                web::scope("/merchants")
+                    .configure(crate::modules::merchants::services::merchants_services)
+                    .configure(crate::modules::settlements::services::settlements_services),
+            )
+            .service(web::scope("/fx").configure(crate::modules::fx::services::fx_services))
+            .service(web::scope("/fees").configure(crate::modules::fees::services::fees_services))
+            .service(
+                web::scope("/webhooks")
+                    .configure(crate::modules::webhooks::services::webhooks_services),
+            ),
+    );
+}
+
+/// ## Description
+/// Turns SIGINT, and SIGTERM on Unix, into a stop signal
+async fn stop_on_signal(stop_sender: Sender<()>) {
+    let logger = TheLogger::instance();
+    #[cfg(unix)]
+    {
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(error) => {
+                log_error!(logger, "Could not listen for SIGTERM: {}", error);
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    if let Err(error) = tokio::signal::ctrl_c().await {
+        log_error!(logger, "Could not listen for Ctrl-C: {}", error);
+        return;
+    }
+
+    if let Err(error) = stop_sender.send(()).await {
+        log_error!(logger, "Failed to send stop signal: {}", error);
+    }
+}
+
+/// ## Description
+/// Waits for the stop signal, then fails readiness for `drain_secs` before stopping the server, so load
+/// balancers stop routing to it while it still serves
+async fn api_stop_handler(
+    server_handler: ServerHandle,
+    mut stop_receiver: Receiver<()>,
+    drain: Duration,
+) {
     stop_receiver.recv().await;
 
     let logger = TheLogger::instance();
     log_info!(
         logger,
-        "Initializing shutdown at: {}",
-        chrono::Local::now().naive_local().format(TIME_FORMAT)
+        "Initializing shutdown at: {}, draining for {:?}",
+        chrono::Local::now().naive_local().format(TIME_FORMAT),
+        drain
     );
+    health::start_draining();
+    tokio::time::sleep(drain).await;
 
     server_handler.stop(true).await;
 
@@ -0,0 +1,67 @@
+use actix_web::web;
+use utoipa::{
+    openapi::{InfoBuilder, OpenApi as Document},
+    OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::{
+    modules::{
+        fees::services::FeesApi, fx::services::FxApi, merchants::services::MerchantsApi,
+        settlements::services::SettlementsApi, transactions::services::TransactionsApi,
+        wallets::services::WalletsApi, webhooks::services::WebhooksApi,
+    },
+    APP_NAME, APP_VERSION,
+};
+
+use super::{health::HealthApi, metrics::MetricsApi, services::ConfigurationsApi};
+
+#[cfg(test)]
+mod tests;
+
+/// Route of the document, and of the Swagger UI rendering it
+pub(super) const DOCUMENT_PATH: &str = "/v1/openapi.json";
+pub(super) const DOCS_PATH: &str = "/v1/docs";
+
+/// ## Description
+/// Operations of every module, each documented by the `#[utoipa::path]` of its handlers and mounted on the
+/// scope the module is served under
+#[derive(OpenApi)]
+#[openapi(nest(
+    (path = "/v1/configurations", api = ConfigurationsApi),
+    (path = "/v1/wallets", api = WalletsApi),
+    (path = "/v1/transactions", api = TransactionsApi),
+    (path = "/v1/merchants", api = MerchantsApi),
+    (path = "/v1/merchants", api = SettlementsApi),
+    (path = "/v1/fx", api = FxApi),
+    (path = "/v1/fees", api = FeesApi),
+    (path = "/v1/webhooks", api = WebhooksApi),
+))]
+struct ApiDoc;
+
+/// ## Description
+/// Serves the OpenAPI document and the Swagger UI, whose assets are embedded in the binary. Registered on
+/// the app rather than the `/v1` scope, and before it, as the UI serves its assets under its own path
+pub(super) fn openapi_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(SwaggerUi::new(format!("{}/{{_:.*}}", DOCS_PATH)).url(DOCUMENT_PATH, document()));
+}
+
+/// ## Description
+/// Builds the OpenAPI document out of the annotated handlers. Every route registered by the services must
+/// be documented, the route-sync test fails otherwise. The docs routes themselves are left out
+pub fn document() -> Document {
+    let mut document = ApiDoc::openapi()
+        .merge_from(HealthApi::openapi())
+        .merge_from(MetricsApi::openapi());
+    document.info = InfoBuilder::new()
+        .title(APP_NAME)
+        .version(APP_VERSION)
+        .description(Some(
+            "QR payments backend. Amounts are decimal strings with two decimal places, datetimes are local \
+            and without offset. Errors use the ApiError envelope, every error response carries the \
+            request_id echoed in the X-Request-Id header",
+        ))
+        .build();
+
+    document
+}
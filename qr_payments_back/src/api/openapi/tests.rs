@@ -0,0 +1,301 @@
+use std::collections::BTreeSet;
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_and_read_body, call_and_read_body_json, call_service, init_service, TestRequest},
+    web, App, HttpRequest, HttpResponse,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    api::error::ErrorBody,
+    config::ReloadReport,
+    modules::{
+        fees::{Fee, FeeBreakdown, FeeTier, NewSchedules},
+        fx::{services::NewQuoteRequest, Conversion, NewRates, Quote, Rate, RateTable},
+        merchants::{
+            qr::QrCode,
+            services::NewQrCodeRequest,
+            statements::{Statement, StatementMovement},
+            Merchant, MerchantDetails, MerchantStatusRequest, NewMerchant,
+        },
+        settlements::{SettlementBatch, SettlementBatchDetail, SettlementLine, SettlementReport},
+        transactions::{
+            services::{NewTransactionRequest, PostTransactionRequest, ReviewTransactionRequest},
+            NewTransactionResponse, Transaction,
+        },
+        wallets::{limits::WalletLimits, Wallet, WalletBalance},
+        webhooks::{
+            services::NewSubscriptionRequest, NewSubscriptionResponse, WebhookDelivery,
+            WebhookSubscription,
+        },
+    },
+};
+
+use super::{
+    super::{health::health_services, metrics::metrics_services, v1_services},
+    document, openapi_services, DOCS_PATH, DOCUMENT_PATH,
+};
+
+/// Path no route serves, answered by the default service with the resource map
+const UNROUTED_PATH: &str = "/unrouted";
+
+/// ## Description
+/// Every resource registered on the app, as its handler name and full path. Actix exposes its routing tree
+/// only through the Debug output of the resource map, which nests every scope's resources in its `nodes`.
+/// Resources registered without a name, such as `web::resource` or `.route()`, come out as `<unnamed>`
+async fn registered_routes() -> BTreeSet<(String, String)> {
+    let app = init_service(
+        App::new()
+            .configure(health_services)
+            .configure(metrics_services)
+            .configure(openapi_services)
+            .configure(v1_services)
+            .default_service(web::to(|request: HttpRequest| async move {
+                HttpResponse::Ok().body(format!("{:#?}", request.resource_map()))
+            })),
+    )
+    .await;
+    let resource_map =
+        call_and_read_body(&app, TestRequest::get().uri(UNROUTED_PATH).to_request()).await;
+    let resource_map = String::from_utf8(resource_map.to_vec()).unwrap();
+
+    let lines = resource_map.lines().collect::<Vec<_>>();
+    //  Enclosing scopes, as the indentation of their map and their full path
+    let mut scopes: Vec<(usize, String)> = Vec::new();
+    let mut routes = BTreeSet::new();
+    for (index, line) in lines.iter().enumerate() {
+        //  Named resources are listed again under `"name": ResourceMap {`, only the tree nodes are bare
+        if line.trim() != "ResourceMap {" {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        while scopes.last().is_some_and(|(scope, _)| *scope >= indent) {
+            scopes.pop();
+        }
+        let node = &lines[index..];
+        let field = |name: &str| node.iter().position(|line| line.trim().starts_with(name));
+        let quoted = |line: &str| {
+            line.trim()
+                .trim_end_matches(',')
+                .trim_matches('"')
+                .to_string()
+        };
+
+        let pattern = quoted(node[field("patterns: Single(").unwrap() + 1]);
+        let path = format!(
+            "{}{}",
+            scopes.last().map_or("", |(_, path)| path.as_str()),
+            pattern
+        );
+        if node[field("is_prefix:").unwrap()].contains("true") {
+            scopes.push((indent, path));
+            continue;
+        }
+        let name = field("name:").unwrap();
+        let name = match node[name].contains("None") {
+            true => String::from("<unnamed>"),
+            false => quoted(node[name + 1]),
+        };
+        routes.insert((name, path));
+    }
+
+    routes
+}
+
+/// Every documented operation, as its operation ID, which utoipa takes from the handler name, and path
+fn documented_routes() -> BTreeSet<(String, String)> {
+    let document = serde_json::to_value(document()).unwrap();
+    document["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, item)| {
+            item.as_object().unwrap().values().map(move |operation| {
+                (
+                    operation["operationId"].as_str().unwrap().to_string(),
+                    path.clone(),
+                )
+            })
+        })
+        .collect()
+}
+
+#[actix_web::test]
+async fn every_route_is_documented_and_nothing_else() {
+    let registered = registered_routes()
+        .await
+        .into_iter()
+        .filter(|(_, path)| path != DOCUMENT_PATH && !path.starts_with(DOCS_PATH))
+        .collect::<BTreeSet<_>>();
+    let documented = documented_routes();
+    assert!(registered.contains(&(
+        String::from("get_settlement"),
+        String::from("/v1/merchants/{merchants_id}/settlements/{batches_id}")
+    )));
+
+    let undocumented = registered.difference(&documented).collect::<Vec<_>>();
+    let unknown = documented.difference(&registered).collect::<Vec<_>>();
+    assert!(
+        undocumented.is_empty(),
+        "Routes missing in the OpenAPI document: {:?}",
+        undocumented
+    );
+    assert!(
+        unknown.is_empty(),
+        "Documented routes that are not registered: {:?}",
+        unknown
+    );
+}
+
+#[test]
+fn every_reference_points_to_a_schema() {
+    let document = serde_json::to_value(document()).unwrap();
+    let schemas = document["components"]["schemas"].as_object().unwrap();
+
+    let mut references = Vec::new();
+    collect_references(&document, &mut references);
+    assert!(!references.is_empty());
+    for reference in references {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        assert!(schemas.contains_key(name), "Unknown schema {}", reference);
+    }
+}
+
+fn collect_references(value: &Value, references: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(reference)) = object.get("$ref") {
+                references.push(reference.clone());
+            }
+            object
+                .values()
+                .for_each(|value| collect_references(value, references));
+        }
+        Value::Array(array) => array
+            .iter()
+            .for_each(|value| collect_references(value, references)),
+        _ => {}
+    }
+}
+
+/// ## Description
+/// Properties of a schema, following references and the `allOf` flattened structs are documented with
+fn properties(schemas: &Value, schema: &Value) -> BTreeSet<String> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        return properties(schemas, &schemas[name]);
+    }
+    let mut properties = schema["properties"]
+        .as_object()
+        .map(|properties| properties.keys().cloned().collect::<BTreeSet<_>>())
+        .unwrap_or_default();
+    if let Some(parts) = schema["allOf"].as_array() {
+        for part in parts {
+            properties.extend(self::properties(schemas, part));
+        }
+    }
+    properties
+}
+
+/// ## Description
+/// Asserts the schema has exactly the fields the type serializes
+fn assert_schema_matches<T: Serialize + Default>(schema: &str) {
+    let document = serde_json::to_value(document()).unwrap();
+    let schemas = &document["components"]["schemas"];
+    assert!(
+        schemas.get(schema).is_some(),
+        "Schema {} should be documented",
+        schema
+    );
+    let fields = serde_json::to_value(T::default())
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    assert_eq!(
+        properties(schemas, &schemas[schema]),
+        fields,
+        "Schema {} is out of sync",
+        schema
+    );
+}
+
+#[test]
+fn response_schemas_match_their_types() {
+    assert_schema_matches::<ErrorBody>("ApiError");
+    assert_schema_matches::<ReloadReport>("ReloadReport");
+    assert_schema_matches::<Wallet>("Wallet");
+    assert_schema_matches::<WalletBalance>("WalletBalance");
+    assert_schema_matches::<Transaction>("Transaction");
+    assert_schema_matches::<Conversion>("Conversion");
+    assert_schema_matches::<Merchant>("Merchant");
+    assert_schema_matches::<QrCode>("QrCode");
+    assert_schema_matches::<Statement>("Statement");
+    assert_schema_matches::<StatementMovement>("StatementMovement");
+    assert_schema_matches::<SettlementBatch>("SettlementBatch");
+    assert_schema_matches::<SettlementBatchDetail>("SettlementBatchDetail");
+    assert_schema_matches::<SettlementLine>("SettlementLine");
+    assert_schema_matches::<SettlementReport>("SettlementReport");
+    assert_schema_matches::<NewTransactionResponse>("NewTransactionResponse");
+    assert_schema_matches::<FeeBreakdown>("FeeBreakdown");
+    assert_schema_matches::<Fee>("Fee");
+    assert_schema_matches::<FeeTier>("FeeTier");
+    assert_schema_matches::<Rate>("Rate");
+    assert_schema_matches::<RateTable>("RateTable");
+    assert_schema_matches::<Quote>("Quote");
+    assert_schema_matches::<WebhookSubscription>("WebhookSubscription");
+    assert_schema_matches::<NewSubscriptionResponse>("NewSubscriptionResponse");
+    assert_schema_matches::<WebhookDelivery>("WebhookDelivery");
+}
+
+#[test]
+fn request_schemas_match_their_types() {
+    assert_schema_matches::<NewTransactionRequest>("NewTransactionRequest");
+    assert_schema_matches::<PostTransactionRequest>("PostTransactionRequest");
+    assert_schema_matches::<ReviewTransactionRequest>("ReviewTransactionRequest");
+    assert_schema_matches::<WalletLimits>("WalletLimits");
+    assert_schema_matches::<NewMerchant>("NewMerchant");
+    assert_schema_matches::<MerchantDetails>("MerchantDetails");
+    assert_schema_matches::<MerchantStatusRequest>("MerchantStatusRequest");
+    assert_schema_matches::<NewQrCodeRequest>("NewQrCodeRequest");
+    assert_schema_matches::<NewRates>("NewRates");
+    assert_schema_matches::<NewQuoteRequest>("NewQuoteRequest");
+    assert_schema_matches::<NewSchedules>("NewSchedules");
+    assert_schema_matches::<NewSubscriptionRequest>("NewSubscriptionRequest");
+}
+
+#[actix_web::test]
+async fn serves_the_document_and_the_embedded_ui() {
+    let app = init_service(App::new().configure(openapi_services)).await;
+
+    let document: Value =
+        call_and_read_body_json(&app, TestRequest::get().uri(DOCUMENT_PATH).to_request()).await;
+    assert_eq!(document["openapi"], "3.1.0");
+    assert!(document["paths"]["/v1/transactions"]["post"].is_object());
+
+    let page = call_and_read_body(
+        &app,
+        TestRequest::get()
+            .uri(&format!("{}/", DOCS_PATH))
+            .to_request(),
+    )
+    .await;
+    let page = String::from_utf8(page.to_vec()).unwrap();
+    assert!(page.contains("swagger-ui"));
+    assert!(!page.contains("unpkg.com"));
+
+    //  The assets are served from the binary
+    let response = call_service(
+        &app,
+        TestRequest::get()
+            .uri(&format!("{}/swagger-ui-bundle.js", DOCS_PATH))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
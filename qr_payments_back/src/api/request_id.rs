@@ -0,0 +1,47 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpMessage,
+};
+
+use crate::logging;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+/// Longest ID accepted from a client, longer or non printable ones are replaced
+const REQUEST_ID_MAX_LENGTH: usize = 128;
+
+/// ID of the request being handled, also available from the request extensions
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// ## Description
+/// Middleware that takes the `X-Request-Id` received, or generates one, attaches it to every record
+/// logged while handling the request and echoes it in the response headers. Error responses carry it in
+/// their body through `ApiError`, which reads it from the same logging context
+pub(super) async fn propagate_request_id(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| {
+            !value.is_empty()
+                && value.len() <= REQUEST_ID_MAX_LENGTH
+                && value.chars().all(|char| char.is_ascii_graphic())
+        })
+        .map(String::from)
+        .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+
+    let mut response = logging::scope(request_id.clone(), next.call(request)).await?;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(response)
+}
@@ -1,37 +1,67 @@
 use actix_web::{get, put, web, HttpResponse};
-use the_logger::{log_error, log_info, TheLogger};
+use error_mapper::TheResult;
+use the_logger::TheLogger;
+use utoipa::OpenApi;
 
-use crate::{api::ApiData, ALIVE_SINCE, APP_NAME, APP_VERSION, DATETIME_FORMAT, TIME_FORMAT};
+use crate::{
+    api::{
+        error::{ApiError, ErrorBody},
+        ApiData,
+    },
+    config::{Config, ReloadReport},
+    log_error, log_info, ALIVE_SINCE, APP_NAME, APP_VERSION, DATETIME_FORMAT, TIME_FORMAT,
+};
 
 pub(super) fn api_services(cfg: &mut web::ServiceConfig) {
-    cfg.service(alive).service(stop);
+    cfg.service(alive).service(stop).service(reload);
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(alive, stop, reload), tags((name = "configurations")))]
+pub(super) struct ConfigurationsApi;
+
 /// v1/configurations/alive
+#[utoipa::path(
+    tag = "configurations",
+    summary = "Name, version and start datetime",
+    responses(
+        (status = 200, description = "Alive message", content_type = "text/plain", body = String),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
 #[get("/alive")]
-pub(super) async fn alive() -> HttpResponse {
+pub(super) async fn alive() -> Result<HttpResponse, ApiError> {
     let Some(alive_since) = ALIVE_SINCE
         .get()
         .map(|alive| alive.format(DATETIME_FORMAT).to_string())
     else {
-        return HttpResponse::InternalServerError().finish();
+        log_error!(TheLogger::instance(), "Start datetime was not recorded");
+        return Err(ApiError::internal());
     };
     let alive_msg = format!(
         "{}, version: {} | Alive since: {}",
         APP_NAME, APP_VERSION, alive_since
     );
 
-    HttpResponse::Ok().body(alive_msg)
+    Ok(HttpResponse::Ok().body(alive_msg))
 }
 
 /// v1/configurations/stop
+#[utoipa::path(
+    tag = "configurations",
+    summary = "Gracefully stops the server",
+    responses(
+        (status = 200, description = "Stop signal sent"),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
 #[put("/stop")]
-async fn stop(api_data: web::Data<ApiData>) -> HttpResponse {
+async fn stop(api_data: web::Data<ApiData>) -> Result<HttpResponse, ApiError> {
     let logger = TheLogger::instance();
 
     if let Err(error) = api_data.stop_signal.send(()).await {
         log_error!(logger, "Failed to send stop signal: {}", error);
-        return HttpResponse::InternalServerError().finish();
+        return Err(ApiError::internal());
     };
 
     log_info!(
@@ -40,5 +70,45 @@ async fn stop(api_data: web::Data<ApiData>) -> HttpResponse {
         chrono::Local::now().naive_local().format(TIME_FORMAT)
     );
 
-    HttpResponse::Ok().finish()
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// v1/configurations/reload
+///
+/// Same as sending SIGHUP. Responds with the applied changes and the ones waiting for a restart
+#[utoipa::path(
+    tag = "configurations",
+    summary = "Reloads the configuration, same as SIGHUP",
+    responses(
+        (status = 200, description = "Applied changes and the ones waiting for a restart", body = ReloadReport),
+        (status = 422, description = "Invalid configuration", body = ErrorBody),
+    )
+)]
+#[put("/reload")]
+async fn reload() -> Result<HttpResponse, ApiError> {
+    reload_response(Config::reload().await).await
+}
+
+/// ## Description
+/// Responds with the reload report, or with the validation errors as 422 when the configuration was kept
+pub(super) async fn reload_response(
+    outcome: TheResult<ReloadReport>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+
+    match outcome {
+        Ok(report) => {
+            log_info!(
+                logger,
+                "Configuration reloaded, applied: {:?}, restart required: {:?}",
+                report.applied,
+                report.restart_required
+            );
+            Ok(HttpResponse::Ok().json(report))
+        }
+        Err(error) => {
+            log_error!(logger, "Could not reload configuration: {}", error);
+            Err(ApiError::invalid_configuration(error.error.error_content))
+        }
+    }
 }
@@ -0,0 +1,2037 @@
+use actix_web::{http::StatusCode, test, web, App};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+
+#[cfg(feature = "sqlite")]
+use crate::database::sqlite::SqliteStore;
+
+use crate::{
+    config::Config,
+    database::{memory::InMemoryStore, Repositories},
+    modules::{
+        currencies::Currency,
+        fees::{Fee, FeeParty},
+        settlements::scheduler::settle,
+        transactions::{
+            export::{TransactionFilter, TransactionOwner},
+            DebitOutcome, Transaction, TransactionStatus,
+        },
+        wallets::{
+            limits::{LimitBreach, LimitValuation, WalletLimits},
+            Wallet, WalletBalance,
+        },
+    },
+};
+
+use super::{
+    health::{health_services, start_draining},
+    metrics::{metrics_services, track_requests},
+    request_id::{propagate_request_id, REQUEST_ID_HEADER},
+    services::reload_response,
+    v1_services,
+};
+
+/// Runs every behavioral test against each repository backend
+macro_rules! backend_tests {
+    ($($test: ident),* $(,)?) => {
+        mod in_memory {
+            $(
+                #[actix_web::test]
+                async fn $test() {
+                    super::$test(super::Backend::InMemory).await
+                }
+            )*
+        }
+
+        #[cfg(feature = "sqlite")]
+        mod sqlite {
+            $(
+                #[actix_web::test]
+                async fn $test() {
+                    super::$test(super::Backend::Sqlite).await
+                }
+            )*
+        }
+    };
+}
+
+backend_tests!(
+    get_wallets_returns_stored_wallets,
+    put_wallet_limits_validates_and_stores_limits,
+    new_transaction_debits_wallet_and_confirms_once,
+    new_transaction_rejects_invalid_requests,
+    requests_are_validated_field_by_field,
+    new_transaction_enforces_wallet_limits,
+    wallet_limits_are_enforced_while_debiting,
+    debits_apply_to_the_stored_balance,
+    cancel_and_refund_return_the_amount_to_the_wallet,
+    flagged_transaction_is_debited_only_when_approved,
+    first_payments_are_flagged_per_merchant_over_the_whole_history,
+    rejected_review_declines_without_debiting,
+    concurrent_reviews_debit_the_wallet_once,
+    approved_review_is_checked_against_wallet_limits,
+    payments_are_debited_from_the_balance_of_their_currency,
+    held_currency_payments_count_against_wallet_limits,
+    held_balances_are_not_overdrawn_while_debiting,
+    converted_payments_lock_a_quote_and_record_both_amounts,
+    quotes_stay_open_for_payments_rejected_by_their_checks,
+    fees_are_debited_with_the_payment_and_refunded_with_it,
+    merchants_are_managed_and_only_paid_while_active,
+    merchants_are_settled_once_per_period_and_held_while_suspended,
+    batches_netting_zero_or_uncovered_do_not_fail_the_payouts,
+    transactions_are_exported_and_summed_into_statements,
+    statements_open_with_what_was_booked_in_earlier_months,
+    webhook_subscriptions_are_created_listed_and_deactivated,
+);
+
+macro_rules! init_app {
+    ($repositories: expr) => {{
+        Config::initialize_config(None).unwrap();
+        test::init_service(
+            App::new()
+                .wrap(actix_web::middleware::from_fn(propagate_request_id))
+                .app_data(web::Data::from($repositories.wallets.clone()))
+                .app_data(web::Data::from($repositories.transactions.clone()))
+                .app_data(web::Data::from($repositories.merchants.clone()))
+                .app_data(web::Data::from($repositories.settlements.clone()))
+                .app_data(web::Data::from($repositories.webhooks.clone()))
+                .app_data(web::Data::from($repositories.fx.clone()))
+                .app_data(web::Data::from($repositories.fees.clone()))
+                .configure(v1_services),
+        )
+        .await
+    }};
+}
+
+#[derive(Clone, Copy)]
+enum Backend {
+    InMemory,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+fn repositories(backend: Backend, balance: i64) -> Repositories {
+    repositories_holding(backend, balance, &[])
+}
+
+/// Repositories with a USD wallet that also holds balances in the received currencies
+fn repositories_holding(backend: Backend, balance: i64, held: &[(&str, i64)]) -> Repositories {
+    let wallets = vec![Wallet {
+        id: 1,
+        balance: Decimal::from(balance),
+        ..Default::default()
+    }];
+    let held = held
+        .iter()
+        .map(|(currency, balance)| {
+            let balance = WalletBalance {
+                currency: Currency::from_code(currency).expect("currency should be supported"),
+                balance: Decimal::from(*balance),
+            };
+            (1, balance)
+        })
+        .collect::<Vec<_>>();
+
+    match backend {
+        Backend::InMemory => {
+            Repositories::in_memory(InMemoryStore::new(wallets).with_held_balances(held))
+        }
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => {
+            let store = SqliteStore::open(":memory:").expect("in-memory database should open");
+            store
+                .replace_wallets(&wallets)
+                .expect("wallets should be seeded");
+            store
+                .insert_held_balances(&held)
+                .expect("held balances should be seeded");
+            Repositories::sqlite(store)
+        }
+    }
+}
+
+fn balance(repositories: &Repositories) -> Decimal {
+    repositories
+        .wallets
+        .select_by_id(1)
+        .unwrap()
+        .expect("wallet should exist")
+        .balance
+}
+
+fn new_transaction(amount: i64) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/v1/transactions")
+        .set_json(json!({ "wallets_id": 1, "amount": amount }))
+}
+
+/// Token of a new or approved transaction, out of its response
+fn token_of(response: Value) -> String {
+    response["token"]
+        .as_str()
+        .expect("response should have a token")
+        .to_string()
+}
+
+fn transaction_action(action: &str, token: &str) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri(&format!("/v1/transactions/{}", action))
+        .set_json(json!({ "wallets_id": 1, "transaction_token": token }))
+}
+
+async fn get_wallets_returns_stored_wallets(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    let wallets: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get().uri("/v1/wallets").to_request(),
+    )
+    .await;
+    assert_eq!(wallets.as_array().map(Vec::len), Some(1));
+
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/v1/wallets/2").to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+async fn put_wallet_limits_validates_and_stores_limits(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    let request = test::TestRequest::put()
+        .uri("/v1/wallets/1/limits")
+        .set_json(json!({ "max_single_amount": -5 }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+
+    let request = test::TestRequest::put()
+        .uri("/v1/wallets/1/limits")
+        .set_json(json!({ "max_single_amount": 50 }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::OK
+    );
+
+    let wallet = repositories.wallets.select_by_id(1).unwrap().unwrap();
+    assert_eq!(wallet.limits.max_single_amount, Some(Decimal::from(50)));
+}
+
+async fn new_transaction_debits_wallet_and_confirms_once(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    let token =
+        token_of(test::call_and_read_body_json(&app, new_transaction(-100).to_request()).await);
+    assert_eq!(balance(&repositories), Decimal::from(900));
+
+    let response =
+        test::call_service(&app, transaction_action("confirm", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response =
+        test::call_service(&app, transaction_action("confirm", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "INVALID_TRANSITION");
+    assert_eq!(balance(&repositories), Decimal::from(900));
+}
+
+async fn new_transaction_rejects_invalid_requests(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    //  Unknown wallets are logged and rejected
+    let request = test::TestRequest::post()
+        .uri("/v1/transactions")
+        .set_json(json!({ "wallets_id": 2, "amount": -100 }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "WALLET_NOT_FOUND");
+
+    let response = test::call_service(&app, new_transaction(0).to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = test::call_service(&app, new_transaction(-1001).to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "INSUFFICIENT_FUNDS");
+
+    //  Malformed bodies get the same envelope
+    let request = test::TestRequest::post()
+        .uri("/v1/transactions")
+        .set_json(json!({ "wallets_id": 1 }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "INVALID_REQUEST");
+
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+}
+
+async fn requests_are_validated_field_by_field(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    let invalid_fields = |body: Value| {
+        body["details"]["fields"]
+            .as_array()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|field| format!("{}:{}", field["field"], field["code"]))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    let request = test::TestRequest::post()
+        .uri("/v1/transactions")
+        .set_json(json!({ "wallets_id": 1, "amount": "-10.005" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+    assert_eq!(invalid_fields(body), ["\"amount\":\"INVALID_SCALE\""]);
+
+    let request = test::TestRequest::post()
+        .uri("/v1/transactions")
+        .set_json(json!({ "wallets_id": 0, "amount": "10000000000" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(
+        invalid_fields(body),
+        [
+            "\"wallets_id\":\"OUT_OF_RANGE\"",
+            "\"amount\":\"OUT_OF_RANGE\"",
+            "\"amount\":\"INVALID_SIGN\"",
+        ]
+    );
+
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        transaction_action("confirm", "not-a-token").to_request(),
+    )
+    .await;
+    assert_eq!(
+        invalid_fields(body),
+        ["\"transaction_token\":\"INVALID_FORMAT\""]
+    );
+
+    let request = test::TestRequest::put()
+        .uri("/v1/wallets/1/limits")
+        .set_json(json!({ "max_daily_amount": "10.123" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(
+        invalid_fields(body),
+        ["\"max_daily_amount\":\"INVALID_SCALE\""]
+    );
+
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+}
+
+async fn new_transaction_enforces_wallet_limits(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    let request = test::TestRequest::put()
+        .uri("/v1/wallets/1/limits")
+        .set_json(json!({ "max_daily_count": 1 }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::OK
+    );
+
+    let response = test::call_service(&app, new_transaction(-100).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body: Value = test::call_and_read_body_json(&app, new_transaction(-100).to_request()).await;
+    assert_eq!(body["code"], "DAILY_COUNT_LIMIT_EXCEEDED");
+    assert_eq!(balance(&repositories), Decimal::from(900));
+}
+
+async fn wallet_limits_are_enforced_while_debiting(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let mut wallet = repositories.wallets.select_by_id(1).unwrap().unwrap();
+    let limits = WalletLimits {
+        max_daily_count: Some(1),
+        ..Default::default()
+    };
+    repositories
+        .wallets
+        .update_limits(&mut wallet, limits)
+        .unwrap();
+
+    //  Both payments passed the early check with the same outflow, as concurrent requests would
+    let mut first = wallet;
+    let outcome = repositories
+        .transactions
+        .insert_affecting_wallet(
+            &mut Transaction::payment(1, Decimal::from(-100)),
+            &[],
+            &mut first,
+            &LimitValuation::default(),
+        )
+        .unwrap();
+    assert_eq!(outcome, DebitOutcome::Debited);
+
+    let mut second = wallet;
+    let outcome = repositories
+        .transactions
+        .insert_affecting_wallet(
+            &mut Transaction::payment(1, Decimal::from(-100)),
+            &[],
+            &mut second,
+            &LimitValuation::default(),
+        )
+        .unwrap();
+    assert_eq!(
+        outcome,
+        DebitOutcome::LimitExceeded(LimitBreach::DailyCount)
+    );
+    assert_eq!(balance(&repositories), Decimal::from(900));
+}
+
+async fn debits_apply_to_the_stored_balance(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let wallet = repositories.wallets.select_by_id(1).unwrap().unwrap();
+
+    //  Both payments read the balance before either was debited, as concurrent requests would
+    for _ in 0..2 {
+        let mut stale = wallet;
+        let outcome = repositories
+            .transactions
+            .insert_affecting_wallet(
+                &mut Transaction::payment(1, Decimal::from(-300)),
+                &[],
+                &mut stale,
+                &LimitValuation::default(),
+            )
+            .unwrap();
+        assert_eq!(outcome, DebitOutcome::Debited);
+    }
+    assert_eq!(balance(&repositories), Decimal::from(400));
+
+    let mut stale = wallet;
+    let mut overdrawing = Transaction::payment(1, Decimal::from(-500));
+    let outcome = repositories
+        .transactions
+        .insert_affecting_wallet(
+            &mut overdrawing,
+            &[],
+            &mut stale,
+            &LimitValuation::default(),
+        )
+        .unwrap();
+    assert_eq!(outcome, DebitOutcome::InsufficientFunds);
+    let initialized = repositories
+        .transactions
+        .select_by_status(TransactionStatus::Initialized)
+        .unwrap();
+    assert_eq!(initialized.len(), 2);
+    assert_eq!(balance(&repositories), Decimal::from(400));
+}
+
+async fn cancel_and_refund_return_the_amount_to_the_wallet(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    let token =
+        token_of(test::call_and_read_body_json(&app, new_transaction(-100).to_request()).await);
+    let response =
+        test::call_service(&app, transaction_action("refund", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let stale = repositories
+        .transactions
+        .select_by_status(TransactionStatus::Initialized)
+        .unwrap()
+        .remove(0);
+    let response =
+        test::call_service(&app, transaction_action("cancel", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+
+    //  A confirmation that read the transaction before it was cancelled does not overwrite it
+    assert!(!repositories
+        .transactions
+        .update_status_from(
+            &stale.with_status(TransactionStatus::Confirmed),
+            TransactionStatus::Initialized
+        )
+        .unwrap());
+    let cancelled = repositories
+        .transactions
+        .select_by_status(TransactionStatus::Cancelled)
+        .unwrap();
+    assert_eq!(cancelled.len(), 1);
+
+    let token =
+        token_of(test::call_and_read_body_json(&app, new_transaction(-200).to_request()).await);
+    test::call_service(&app, transaction_action("confirm", &token).to_request()).await;
+    let response =
+        test::call_service(&app, transaction_action("refund", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+
+    let response =
+        test::call_service(&app, transaction_action("refund", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+}
+
+async fn flagged_transaction_is_debited_only_when_approved(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+
+    //  A large first payment is flagged for review by the configured rules
+    let response = test::call_service(&app, new_transaction(-1500).to_request()).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(balance(&repositories), Decimal::from(5000));
+
+    let reviews: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/transactions/reviews")
+            .to_request(),
+    )
+    .await;
+    let transactions_id = reviews[0]["id"].as_u64().expect("review should be listed");
+
+    let request = test::TestRequest::put()
+        .uri(&format!("/v1/transactions/reviews/{}", transactions_id))
+        .set_json(json!({ "approved": true }))
+        .to_request();
+    let token = token_of(test::call_and_read_body_json(&app, request).await);
+    assert_eq!(balance(&repositories), Decimal::from(3500));
+
+    let response =
+        test::call_service(&app, transaction_action("confirm", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn first_payments_are_flagged_per_merchant_over_the_whole_history(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+
+    for name in ["Cafe Central", "Bakery"] {
+        let request = test::TestRequest::post()
+            .uri("/v1/merchants")
+            .set_json(json!({
+                "currency": "USD",
+                "name": name,
+                "city": "Springfield",
+                "country": "US",
+                "mcc": "5812",
+            }))
+            .to_request();
+        test::call_service(&app, request).await;
+    }
+
+    //  A payment to the first merchant confirmed long before the history the other rules look at
+    let mut payment = Transaction::payment(1, Decimal::from(-1500))
+        .paid_to(1)
+        .with_status(TransactionStatus::Confirmed)
+        .made_at(chrono::Local::now().naive_local() - chrono::TimeDelta::days(400));
+    repositories.transactions.insert(&mut payment, &[]).unwrap();
+
+    let pay = |merchants_id: u64| {
+        test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(json!({ "wallets_id": 1, "merchants_id": merchants_id, "amount": -1500 }))
+            .to_request()
+    };
+    let response = test::call_service(&app, pay(1)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, pay(2)).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+}
+
+async fn rejected_review_declines_without_debiting(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+
+    test::call_service(&app, new_transaction(-1500).to_request()).await;
+    let reviews: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/transactions/reviews")
+            .to_request(),
+    )
+    .await;
+    let transactions_id = reviews[0]["id"].as_u64().expect("review should be listed");
+
+    let request = test::TestRequest::put()
+        .uri(&format!("/v1/transactions/reviews/{}", transactions_id))
+        .set_json(json!({ "approved": false }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::OK
+    );
+
+    let request = test::TestRequest::put()
+        .uri(&format!("/v1/transactions/reviews/{}", transactions_id))
+        .set_json(json!({ "approved": true }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(balance(&repositories), Decimal::from(5000));
+}
+
+async fn concurrent_reviews_debit_the_wallet_once(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+
+    test::call_service(&app, new_transaction(-1500).to_request()).await;
+    let pending = repositories
+        .transactions
+        .select_by_status(TransactionStatus::PendingReview)
+        .unwrap()
+        .remove(0);
+    let wallet = repositories.wallets.select_by_id(1).unwrap().unwrap();
+
+    //  Every review read the transaction while it was pending, as concurrent requests would
+    let approved = pending.clone().with_status(TransactionStatus::Initialized);
+    for expected in [Some(DebitOutcome::Debited), None] {
+        let mut stale = wallet;
+        let outcome = repositories
+            .transactions
+            .update_status_affecting_wallet(
+                &approved,
+                TransactionStatus::PendingReview,
+                &mut stale,
+                &LimitValuation::default(),
+            )
+            .unwrap();
+        assert_eq!(outcome, expected);
+    }
+    let rejected = pending.with_status(TransactionStatus::Declined);
+    assert!(!repositories
+        .transactions
+        .update_status_from(&rejected, TransactionStatus::PendingReview)
+        .unwrap());
+
+    assert_eq!(balance(&repositories), Decimal::from(3500));
+    let initialized = repositories
+        .transactions
+        .select_by_status(TransactionStatus::Initialized)
+        .unwrap();
+    assert_eq!(initialized.len(), 1);
+}
+
+async fn approved_review_is_checked_against_wallet_limits(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+
+    test::call_service(&app, new_transaction(-1500).to_request()).await;
+    let reviews: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/transactions/reviews")
+            .to_request(),
+    )
+    .await;
+    let transactions_id = reviews[0]["id"].as_u64().expect("review should be listed");
+
+    //  The limit is lowered while the payment waits for its review
+    let request = test::TestRequest::put()
+        .uri("/v1/wallets/1/limits")
+        .set_json(json!({ "max_single_amount": 1000 }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::OK
+    );
+
+    let request = test::TestRequest::put()
+        .uri(&format!("/v1/transactions/reviews/{}", transactions_id))
+        .set_json(json!({ "approved": true }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(body["code"], "SINGLE_AMOUNT_LIMIT_EXCEEDED");
+    assert_eq!(balance(&repositories), Decimal::from(5000));
+
+    let reviews: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/transactions/reviews")
+            .to_request(),
+    )
+    .await;
+    assert_eq!(reviews.as_array().map(Vec::len), Some(1));
+}
+
+async fn payments_are_debited_from_the_balance_of_their_currency(backend: Backend) {
+    let repositories = repositories_holding(backend, 1000, &[("EUR", 500)]);
+    let app = init_app!(repositories);
+    let payment = |amount: &str, currency: &str, convert: bool| {
+        test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(json!({
+                "wallets_id": 1,
+                "amount": amount,
+                "currency": currency,
+                "convert": convert,
+            }))
+    };
+
+    let token = token_of(
+        test::call_and_read_body_json(&app, payment("-100", "EUR", false).to_request()).await,
+    );
+    let balances: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/wallets/1/balances")
+            .to_request(),
+    )
+    .await;
+    assert_eq!(
+        balances,
+        json!([
+            { "currency": "USD", "balance": "1000" },
+            { "currency": "EUR", "balance": "400" },
+        ])
+    );
+
+    //  Currencies the wallet does not hold need a conversion, which has no rates to work with yet
+    let body: Value =
+        test::call_and_read_body_json(&app, payment("-100", "GBP", false).to_request()).await;
+    assert_eq!(body["code"], "CURRENCY_MISMATCH");
+    let body: Value =
+        test::call_and_read_body_json(&app, payment("-100", "GBP", true).to_request()).await;
+    assert_eq!(body["code"], "CONVERSION_UNAVAILABLE");
+
+    //  Amounts must be whole minor units of their currency, and the currency a supported ISO 4217 one
+    for (amount, currency, field, code) in [
+        ("-1.5", "JPY", "amount", "INVALID_SCALE"),
+        ("-1", "usd", "currency", "INVALID_FORMAT"),
+        ("-1", "XAU", "currency", "UNSUPPORTED_CURRENCY"),
+    ] {
+        let response =
+            test::call_service(&app, payment(amount, currency, false).to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["details"]["fields"][0]["field"], field);
+        assert_eq!(body["details"]["fields"][0]["code"], code);
+    }
+
+    let response =
+        test::call_service(&app, transaction_action("cancel", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let wallet = repositories.wallets.select_by_id(1).unwrap().unwrap();
+    let balances = repositories.wallets.select_balances(&wallet).unwrap();
+    assert_eq!(balances[1].balance, Decimal::from(500));
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+}
+
+async fn held_currency_payments_count_against_wallet_limits(backend: Backend) {
+    let repositories = repositories_holding(backend, 1000, &[("EUR", 500)]);
+    let app = init_app!(repositories);
+    let payment = |amount: i64, currency: &str| {
+        test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(json!({ "wallets_id": 1, "amount": amount, "currency": currency }))
+            .to_request()
+    };
+    let request = test::TestRequest::put()
+        .uri("/v1/wallets/1/limits")
+        .set_json(json!({ "max_daily_amount": "300", "max_daily_count": 3 }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::OK
+    );
+
+    //  Without a rate the amount cannot be valued in the wallet currency of its limits
+    let body: Value = test::call_and_read_body_json(&app, payment(-10, "EUR")).await;
+    assert_eq!(body["code"], "LIMIT_RATE_UNAVAILABLE");
+
+    let request = test::TestRequest::put()
+        .uri("/v1/fx/rates")
+        .set_json(json!({ "rates": [{ "from": "EUR", "to": "USD", "rate": "2" }] }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::OK
+    );
+
+    //  100 EUR are worth 200 USD, leaving 100 USD of the daily limit
+    let response = test::call_service(&app, payment(-100, "EUR")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = test::call_and_read_body_json(&app, payment(-101, "USD")).await;
+    assert_eq!(body["code"], "DAILY_AMOUNT_LIMIT_EXCEEDED");
+    let body: Value = test::call_and_read_body_json(&app, payment(-51, "EUR")).await;
+    assert_eq!(body["code"], "DAILY_AMOUNT_LIMIT_EXCEEDED");
+
+    //  Payments in every currency count towards the daily count
+    let response = test::call_service(&app, payment(-50, "USD")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, payment(-1, "EUR")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = test::call_and_read_body_json(&app, payment(-1, "USD")).await;
+    assert_eq!(body["code"], "DAILY_COUNT_LIMIT_EXCEEDED");
+    assert_eq!(balance(&repositories), Decimal::from(950));
+}
+
+async fn held_balances_are_not_overdrawn_while_debiting(backend: Backend) {
+    let repositories = repositories_holding(backend, 1000, &[("EUR", 500)]);
+    let wallet = repositories.wallets.select_by_id(1).unwrap().unwrap();
+    let euro = Currency::from_code("EUR").expect("currency should be supported");
+
+    //  Both payments passed the early balance check, as concurrent requests would
+    let debits = [300, 300].map(|amount| {
+        let mut stale = wallet;
+        repositories
+            .transactions
+            .insert_affecting_wallet(
+                &mut Transaction::payment(1, Decimal::from(-amount)).in_currency(euro),
+                &[],
+                &mut stale,
+                &LimitValuation::default(),
+            )
+            .unwrap()
+    });
+    assert_eq!(
+        debits,
+        [DebitOutcome::Debited, DebitOutcome::InsufficientFunds]
+    );
+
+    let balances = repositories.wallets.select_balances(&wallet).unwrap();
+    assert_eq!(balances[1].balance, Decimal::from(200));
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+}
+
+async fn converted_payments_lock_a_quote_and_record_both_amounts(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+    let put_rates = |rate: &str| {
+        test::TestRequest::put()
+            .uri("/v1/fx/rates")
+            .set_json(json!({ "rates": [{ "from": "USD", "to": "EUR", "rate": rate }] }))
+            .to_request()
+    };
+    let new_quote = |amount: &str| {
+        test::TestRequest::post()
+            .uri("/v1/fx/quotes")
+            .set_json(json!({ "from": "USD", "to": "EUR", "amount": amount }))
+            .to_request()
+    };
+    let payment = |body: Value| {
+        test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(body)
+            .to_request()
+    };
+
+    let rates: Value = test::call_and_read_body_json(&app, put_rates("0.92")).await;
+    assert_eq!(rates["rates"][0]["rate"], "0.92");
+    let quote: Value = test::call_and_read_body_json(&app, new_quote("100")).await;
+    assert_eq!(quote["source_amount"], "108.70");
+
+    //  The quote keeps its rate after the rates change, and can only be locked once
+    test::call_service(&app, put_rates("0.5")).await;
+    let quoted_payment = json!({
+        "wallets_id": 1,
+        "amount": "-100",
+        "currency": "EUR",
+        "quote_id": quote["id"],
+    });
+    let token =
+        token_of(test::call_and_read_body_json(&app, payment(quoted_payment.clone())).await);
+    assert_eq!(balance(&repositories), Decimal::new(89130, 2));
+    let response = test::call_service(&app, payment(quoted_payment)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "QUOTE_NOT_FOUND");
+
+    let transaction = repositories
+        .transactions
+        .select_by_token_and_wallets_id(1, token.clone())
+        .unwrap()
+        .expect("transaction should exist");
+    let transaction = serde_json::to_value(transaction).unwrap();
+    assert_eq!(transaction["amount"], "-108.70");
+    assert_eq!(transaction["currency"], "USD");
+    assert_eq!(
+        transaction["conversion"],
+        json!({ "quotes_id": quote["id"], "rate": "0.92", "amount": "-100", "currency": "EUR" })
+    );
+
+    //  Quotes only pay the amount they were created for
+    let quote: Value = test::call_and_read_body_json(&app, new_quote("50")).await;
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        payment(json!({ "wallets_id": 1, "amount": "-100", "currency": "EUR", "quote_id": quote["id"] })),
+    )
+    .await;
+    assert_eq!(body["code"], "QUOTE_MISMATCH");
+
+    //  Without a quote, the payment is converted at the current rate
+    let body = json!({ "wallets_id": 1, "amount": "-10", "currency": "EUR", "convert": true });
+    let response = test::call_service(&app, payment(body)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(balance(&repositories), Decimal::new(87130, 2));
+
+    let body = json!({ "wallets_id": 1, "amount": "-10", "currency": "USD", "convert": true });
+    let body: Value = test::call_and_read_body_json(&app, payment(body)).await;
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+
+    //  Reversals return the amount debited, whatever the rate is now
+    let response =
+        test::call_service(&app, transaction_action("cancel", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(balance(&repositories), Decimal::new(98000, 2));
+}
+
+async fn quotes_stay_open_for_payments_rejected_by_their_checks(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+    let put_limits = |limits: Value| {
+        test::TestRequest::put()
+            .uri("/v1/wallets/1/limits")
+            .set_json(limits)
+            .to_request()
+    };
+
+    let request = test::TestRequest::put()
+        .uri("/v1/fx/rates")
+        .set_json(json!({ "rates": [{ "from": "USD", "to": "EUR", "rate": "0.92" }] }))
+        .to_request();
+    test::call_service(&app, request).await;
+    let request = test::TestRequest::post()
+        .uri("/v1/fx/quotes")
+        .set_json(json!({ "from": "USD", "to": "EUR", "amount": "100" }))
+        .to_request();
+    let quote: Value = test::call_and_read_body_json(&app, request).await;
+    let payment = || {
+        test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(json!({
+                "wallets_id": 1,
+                "amount": "-100",
+                "currency": "EUR",
+                "quote_id": quote["id"],
+            }))
+            .to_request()
+    };
+
+    let response = test::call_service(&app, put_limits(json!({ "max_single_amount": 100 }))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = test::call_and_read_body_json(&app, payment()).await;
+    assert_eq!(body["code"], "SINGLE_AMOUNT_LIMIT_EXCEEDED");
+    assert_eq!(balance(&repositories), Decimal::from(1000));
+
+    //  The rejected payment left the quote open, so it still pays once the limit allows it
+    let response = test::call_service(&app, put_limits(json!({}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, payment()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(balance(&repositories), Decimal::new(89130, 2));
+}
+
+async fn fees_are_debited_with_the_payment_and_refunded_with_it(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+    let put_schedules = |schedules: Value| {
+        test::TestRequest::put()
+            .uri("/v1/fees/schedules")
+            .set_json(json!({ "schedules": schedules }))
+            .to_request()
+    };
+
+    let response = test::call_service(
+        &app,
+        put_schedules(json!([
+            { "party": "Payer", "currency": "USD", "rule": { "type": "percentage", "percent": "101" } },
+            { "party": "Payer", "currency": "USD", "rule": { "type": "fixed", "amount": "0.001" } },
+        ])),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(response).await;
+    let fields = body["details"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        vec![
+            "schedules.rule.percent",
+            "schedules.rule.amount",
+            "schedules.rule.amount",
+            "schedules"
+        ]
+    );
+
+    let response = test::call_service(
+        &app,
+        put_schedules(json!([
+            { "party": "Payer", "merchants_id": 99, "currency": "USD", "rule": { "type": "fixed", "amount": "1" } },
+        ])),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    //  1.5% between 0.50 and 5.00 for the payer, and a flat 0.30 for the merchant
+    let response = test::call_service(
+        &app,
+        put_schedules(json!([
+            {
+                "party": "Payer",
+                "kind": "Payment",
+                "currency": "USD",
+                "rule": {
+                    "type": "capped",
+                    "rule": { "type": "percentage", "percent": "1.5" },
+                    "min": "0.50",
+                    "max": "5",
+                },
+            },
+            { "party": "Merchant", "currency": "USD", "rule": { "type": "fixed", "amount": "0.30" } },
+        ])),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let schedules: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/fees/schedules")
+            .to_request(),
+    )
+    .await;
+    assert_eq!(schedules[0]["rule"]["rule"]["percent"], "1.5");
+    assert_eq!(schedules[1]["merchants_id"], Value::Null);
+
+    let body: Value = test::call_and_read_body_json(&app, new_transaction(-100).to_request()).await;
+    assert_eq!(
+        body["breakdown"],
+        json!({
+            "amount": "-100",
+            "currency": "USD",
+            "fees": [
+                { "party": "Payer", "amount": "1.5", "currency": "USD" },
+                { "party": "Merchant", "amount": "0.30", "currency": "USD" },
+            ],
+            "total": "-101.5",
+        })
+    );
+    assert_eq!(balance(&repositories), Decimal::new(489850, 2));
+    let token = token_of(body);
+
+    let body: Value = test::call_and_read_body_json(&app, new_transaction(-10).to_request()).await;
+    assert_eq!(body["breakdown"]["total"], "-10.50");
+    assert_eq!(balance(&repositories), Decimal::new(488800, 2));
+
+    //  Cancelling returns the amount with the payer fee, and refunds every fee in the ledger
+    let response =
+        test::call_service(&app, transaction_action("cancel", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(balance(&repositories), Decimal::new(498950, 2));
+
+    let transaction = repositories
+        .transactions
+        .select_by_token_and_wallets_id(1, token)
+        .unwrap()
+        .expect("transaction should exist");
+    let transactions_id = serde_json::to_value(transaction).unwrap()["id"].clone();
+    let fees: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!("/v1/transactions/{}/fees", transactions_id))
+            .to_request(),
+    )
+    .await;
+    let amounts = fees
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|fee| fee["amount"].as_str().unwrap().parse::<Decimal>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        amounts,
+        vec![
+            Decimal::new(150, 2),
+            Decimal::new(30, 2),
+            Decimal::new(-150, 2),
+            Decimal::new(-30, 2)
+        ]
+    );
+
+    //  Payments pending review are debited with their fees once approved
+    let body: Value =
+        test::call_and_read_body_json(&app, new_transaction(-1500).to_request()).await;
+    assert_eq!(body["breakdown"]["total"], "-1505");
+    assert_eq!(balance(&repositories), Decimal::new(498950, 2));
+
+    let reviews: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/transactions/reviews")
+            .to_request(),
+    )
+    .await;
+    let request = test::TestRequest::put()
+        .uri(&format!("/v1/transactions/reviews/{}", reviews[0]["id"]))
+        .set_json(json!({ "approved": true }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(body["breakdown"]["total"], "-1505");
+    assert_eq!(balance(&repositories), Decimal::new(348450, 2));
+}
+
+async fn merchants_are_managed_and_only_paid_while_active(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+    let pay_merchant = |wallets_id: u64, merchants_id: u64, currency: Option<&str>| {
+        test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(json!({
+                "wallets_id": wallets_id,
+                "merchants_id": merchants_id,
+                "amount": -25,
+                "currency": currency,
+            }))
+            .to_request()
+    };
+    let error_code = |body: Value| body["code"].as_str().unwrap().to_string();
+
+    let request = test::TestRequest::post()
+        .uri("/v1/merchants")
+        .set_json(json!({
+            "currency": "XAU",
+            "name": "",
+            "city": "Buenos Aires Capital",
+            "country": "ARG",
+            "mcc": "58",
+        }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(response).await;
+    let fields = body["details"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(fields, vec!["currency", "name", "city", "country", "mcc"]);
+
+    //  Merchants are created with a settlement wallet of their own
+    let request = test::TestRequest::post()
+        .uri("/v1/merchants")
+        .set_json(json!({
+            "currency": "USD",
+            "name": "Cafe Central",
+            "city": "Springfield",
+            "country": "US",
+            "mcc": "5812",
+        }))
+        .to_request();
+    let merchant: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(merchant["id"], 1);
+    let settlement_id = merchant["wallets_id"].as_u64().unwrap();
+    assert_ne!(settlement_id, 1);
+    assert_eq!(merchant["currency"], "USD");
+    assert_eq!(merchant["status"], "Active");
+    let settlement = repositories
+        .wallets
+        .select_by_id(settlement_id)
+        .unwrap()
+        .expect("settlement wallet should exist");
+    assert_eq!(settlement.balance, Decimal::ZERO);
+
+    let request = test::TestRequest::put()
+        .uri("/v1/merchants/1")
+        .set_json(json!({
+            "name": "Cafe Central II",
+            "city": "Shelbyville",
+            "country": "US",
+            "mcc": "5814",
+        }))
+        .to_request();
+    let merchant: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(merchant["name"], "Cafe Central II");
+    let merchants: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get().uri("/v1/merchants").to_request(),
+    )
+    .await;
+    assert_eq!(merchants.as_array().unwrap().len(), 1);
+    assert_eq!(merchants[0]["city"], "Shelbyville");
+
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/v1/merchants/9").to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        error_code(test::read_body_json(response).await),
+        "MERCHANT_NOT_FOUND"
+    );
+
+    let qr_code = |amount: &str| {
+        test::TestRequest::post()
+            .uri("/v1/merchants/1/qr")
+            .set_json(json!({ "amount": amount }))
+            .to_request()
+    };
+    let body: Value = test::call_and_read_body_json(&app, qr_code("12.5")).await;
+    let payload = body["payload"].as_str().unwrap();
+    assert!(payload.starts_with("000201010212"));
+    assert!(payload.contains("540512.505802US"));
+    let response = test::call_service(&app, qr_code("-12.50")).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    //  Payments record the merchant paid, in the currency it settles in
+    let body: Value = test::call_and_read_body_json(&app, pay_merchant(1, 1, None)).await;
+    let transaction = repositories
+        .transactions
+        .select_by_token_and_wallets_id(1, token_of(body))
+        .unwrap()
+        .expect("transaction should exist");
+    let transaction = serde_json::to_value(transaction).unwrap();
+    assert_eq!(transaction["merchants_id"], 1);
+    assert_eq!(transaction["currency"], "USD");
+    assert_eq!(balance(&repositories), Decimal::from(4975));
+
+    for (request, status, code) in [
+        (
+            pay_merchant(1, 1, Some("EUR")),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+        (
+            pay_merchant(settlement_id, 1, None),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+        (
+            pay_merchant(1, 9, None),
+            StatusCode::NOT_FOUND,
+            "MERCHANT_NOT_FOUND",
+        ),
+    ] {
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), status);
+        assert_eq!(error_code(test::read_body_json(response).await), code);
+    }
+
+    //  Suspended merchants can't be paid nor show QR codes until they are active again
+    let set_status = |status: &str| {
+        test::TestRequest::put()
+            .uri("/v1/merchants/1/status")
+            .set_json(json!({ "status": status }))
+            .to_request()
+    };
+    let merchant: Value = test::call_and_read_body_json(&app, set_status("Suspended")).await;
+    assert_eq!(merchant["status"], "Suspended");
+
+    let response = test::call_service(&app, pay_merchant(1, 1, None)).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        error_code(test::read_body_json(response).await),
+        "MERCHANT_SUSPENDED"
+    );
+    let response = test::call_service(&app, qr_code("12.50")).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(balance(&repositories), Decimal::from(4975));
+
+    test::call_service(&app, set_status("Active")).await;
+    let response = test::call_service(&app, pay_merchant(1, 1, Some("USD"))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(balance(&repositories), Decimal::from(4950));
+}
+
+async fn merchants_are_settled_once_per_period_and_held_while_suspended(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+    let settlements = web::Data::from(repositories.settlements.clone());
+    let merchants = web::Data::from(repositories.merchants.clone());
+    let today = chrono::Local::now().date_naive();
+    let day = |days: i64| today + chrono::TimeDelta::days(days);
+    let decimal = |value: &Value| value.as_str().unwrap().parse::<Decimal>().unwrap();
+
+    //  2% charged to the merchant, deducted from its payouts
+    let request = test::TestRequest::put()
+        .uri("/v1/fees/schedules")
+        .set_json(json!({ "schedules": [
+            { "party": "Merchant", "currency": "USD", "rule": { "type": "percentage", "percent": "2" } },
+        ] }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, request).await.status(),
+        StatusCode::OK
+    );
+
+    let request = test::TestRequest::post()
+        .uri("/v1/merchants")
+        .set_json(json!({
+            "currency": "USD",
+            "name": "Cafe Central",
+            "city": "Springfield",
+            "country": "US",
+            "mcc": "5812",
+        }))
+        .to_request();
+    let merchant: Value = test::call_and_read_body_json(&app, request).await;
+    let settlement_id = merchant["wallets_id"].as_u64().unwrap();
+    let settlement_balance = || {
+        repositories
+            .wallets
+            .select_by_id(settlement_id)
+            .unwrap()
+            .expect("settlement wallet should exist")
+            .balance
+    };
+
+    let mut tokens = Vec::new();
+    for amount in [-100, -50, -20] {
+        let request = test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(json!({ "wallets_id": 1, "merchants_id": 1, "amount": amount }))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, request).await;
+        tokens.push(token_of(body));
+    }
+    for token in &tokens[..2] {
+        test::call_service(&app, transaction_action("confirm", token).to_request()).await;
+    }
+
+    //  Only confirmed payments are settled, and the same days are never closed twice
+    assert_eq!(settle(&settlements, &merchants, today).await.unwrap(), 0);
+    assert_eq!(settlement_balance(), Decimal::new(14700, 2));
+    assert_eq!(settle(&settlements, &merchants, today).await.unwrap(), 0);
+    assert_eq!(settlement_balance(), Decimal::new(14700, 2));
+
+    //  Refunds of settled payments are deducted by the next batch, their fees given back
+    test::call_service(&app, transaction_action("refund", &tokens[1]).to_request()).await;
+    test::call_service(&app, transaction_action("confirm", &tokens[2]).to_request()).await;
+    assert_eq!(settle(&settlements, &merchants, day(1)).await.unwrap(), 0);
+    assert_eq!(settlement_balance(), Decimal::new(11760, 2));
+
+    //  Batches of suspended merchants are held until they are active again
+    let set_status = |status: &str| {
+        test::TestRequest::put()
+            .uri("/v1/merchants/1/status")
+            .set_json(json!({ "status": status }))
+            .to_request()
+    };
+    test::call_service(&app, set_status("Suspended")).await;
+    test::call_service(&app, transaction_action("refund", &tokens[2]).to_request()).await;
+    assert_eq!(settle(&settlements, &merchants, day(2)).await.unwrap(), 0);
+    assert_eq!(settlement_balance(), Decimal::new(11760, 2));
+
+    let get_report = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/v1/merchants/1/settlements{}", query))
+            .to_request()
+    };
+    let query = format!("?from={}&to={}", today, day(2));
+    let report: Value = test::call_and_read_body_json(&app, get_report(&query)).await;
+    let batches = report["batches"].as_array().unwrap();
+    assert_eq!(batches.len(), 3);
+    assert_eq!(batches[0]["status"], "Held");
+    assert_eq!(batches[0]["period_start"], day(2).to_string());
+    assert_eq!(decimal(&batches[0]["net"]), Decimal::new(-1960, 2));
+    assert_eq!(decimal(&report["net"]), Decimal::new(9800, 2));
+    assert_eq!(decimal(&report["paid"]), Decimal::new(11760, 2));
+
+    test::call_service(&app, set_status("Active")).await;
+    assert_eq!(settle(&settlements, &merchants, day(2)).await.unwrap(), 0);
+    assert_eq!(settlement_balance(), Decimal::new(9800, 2));
+
+    let report: Value = test::call_and_read_body_json(&app, get_report(&query)).await;
+    assert_eq!(report["batches"][0]["status"], "Paid");
+    assert_eq!(decimal(&report["paid"]), Decimal::new(9800, 2));
+    assert_eq!(decimal(&report["payments"]), Decimal::new(17000, 2));
+    assert_eq!(decimal(&report["refunds"]), Decimal::new(-7000, 2));
+    assert_eq!(decimal(&report["fees"]), Decimal::new(200, 2));
+
+    //  Once every batch is paid out the statement balance is back to zero, the negative batches counting
+    //  in favour of the merchant
+    let statement: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!(
+                "/v1/merchants/1/statements/{}",
+                today.format("%Y-%m")
+            ))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(decimal(&statement["credits"]), Decimal::new(22040, 2));
+    assert_eq!(decimal(&statement["debits"]), Decimal::new(-22040, 2));
+    assert_eq!(decimal(&statement["closing_balance"]), Decimal::ZERO);
+    let kinds = statement["movements"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|movement| movement["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    for (kind, count) in [
+        ("Payment", 3),
+        ("Fee", 3),
+        ("Refund", 2),
+        ("FeeRefund", 2),
+        ("Payout", 3),
+    ] {
+        assert_eq!(kinds.iter().filter(|&&other| other == kind).count(), count);
+    }
+
+    let first_id = report["batches"][2]["id"].clone();
+    let detail: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!("/v1/merchants/1/settlements/{}", first_id))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(detail["period_start"], today.to_string());
+    let lines = detail["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["kind"], "Payment");
+    assert_eq!(decimal(&lines[0]["fee"]), Decimal::new(200, 2));
+
+    for (request, status, code) in [
+        (
+            test::TestRequest::get()
+                .uri("/v1/merchants/1/settlements/99")
+                .to_request(),
+            StatusCode::NOT_FOUND,
+            "SETTLEMENT_NOT_FOUND",
+        ),
+        (
+            get_report("?from=2025-03-06&to=2025-03-05"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+        (
+            get_report("?from=yesterday"),
+            StatusCode::BAD_REQUEST,
+            "INVALID_REQUEST",
+        ),
+    ] {
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), status);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["code"], code);
+    }
+}
+
+async fn batches_netting_zero_or_uncovered_do_not_fail_the_payouts(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+    let settlements = web::Data::from(repositories.settlements.clone());
+    let merchants = web::Data::from(repositories.merchants.clone());
+    let today = chrono::Local::now().date_naive();
+    let day = |days: i64| today + chrono::TimeDelta::days(days);
+
+    let request = test::TestRequest::post()
+        .uri("/v1/merchants")
+        .set_json(json!({
+            "currency": "USD",
+            "name": "Cafe Central",
+            "city": "Springfield",
+            "country": "US",
+            "mcc": "5812",
+        }))
+        .to_request();
+    let merchant: Value = test::call_and_read_body_json(&app, request).await;
+    let settlement_id = merchant["wallets_id"].as_u64().unwrap();
+    let settlement_balance = || {
+        repositories
+            .wallets
+            .select_by_id(settlement_id)
+            .unwrap()
+            .expect("settlement wallet should exist")
+            .balance
+    };
+    let pay = |wallets_id: u64, merchants_id: Option<u64>| {
+        test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(
+                json!({ "wallets_id": wallets_id, "merchants_id": merchants_id, "amount": -100 }),
+            )
+            .to_request()
+    };
+
+    let first = token_of(test::call_and_read_body_json(&app, pay(1, Some(1))).await);
+    test::call_service(&app, transaction_action("confirm", &first).to_request()).await;
+    assert_eq!(settle(&settlements, &merchants, today).await.unwrap(), 0);
+    assert_eq!(settlement_balance(), Decimal::from(100));
+
+    //  The refund of the first payment cancels the second one out, crediting nothing
+    test::call_service(&app, transaction_action("refund", &first).to_request()).await;
+    let second = token_of(test::call_and_read_body_json(&app, pay(1, Some(1))).await);
+    test::call_service(&app, transaction_action("confirm", &second).to_request()).await;
+    assert_eq!(settle(&settlements, &merchants, day(1)).await.unwrap(), 0);
+    assert_eq!(settlement_balance(), Decimal::from(100));
+
+    //  Once the merchant spent its payouts, the refund of the second payment cannot be deducted yet
+    test::call_and_read_body_json::<_, _, Value>(&app, pay(settlement_id, None)).await;
+    test::call_service(&app, transaction_action("refund", &second).to_request()).await;
+    for _ in 0..2 {
+        assert_eq!(settle(&settlements, &merchants, day(2)).await.unwrap(), 0);
+        assert_eq!(settlement_balance(), Decimal::ZERO);
+    }
+
+    let report: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!(
+                "/v1/merchants/1/settlements?from={}&to={}",
+                today,
+                day(2)
+            ))
+            .to_request(),
+    )
+    .await;
+    let statuses = report["batches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|batch| {
+            (
+                batch["status"].as_str().unwrap(),
+                batch["net"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        vec![("Held", "-100"), ("Paid", "0"), ("Paid", "100")]
+    );
+}
+
+async fn statements_open_with_what_was_booked_in_earlier_months(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+
+    let request = test::TestRequest::post()
+        .uri("/v1/merchants")
+        .set_json(json!({
+            "currency": "USD",
+            "name": "Cafe Central",
+            "city": "Springfield",
+            "country": "US",
+            "mcc": "5812",
+        }))
+        .to_request();
+    test::call_service(&app, request).await;
+
+    //  Two payments made in an earlier month, the second one refunded in this one
+    let earlier = chrono::Local::now().naive_local() - chrono::TimeDelta::days(40);
+    let merchant_fee = |amount: i64| Fee {
+        party: FeeParty::Merchant,
+        amount: Decimal::from(amount),
+        currency: Currency::USD,
+    };
+    let mut payments = [(100, 2), (50, 1)].map(|(amount, fee)| {
+        let mut payment = Transaction::payment(1, Decimal::from(-amount))
+            .paid_to(1)
+            .with_status(TransactionStatus::Confirmed)
+            .made_at(earlier);
+        repositories
+            .transactions
+            .insert(&mut payment, &[merchant_fee(fee)])
+            .unwrap();
+        payment
+    });
+    assert!(repositories
+        .transactions
+        .reverse(&mut payments[1], TransactionStatus::Refunded)
+        .unwrap());
+
+    let month = chrono::Local::now().format("%Y-%m").to_string();
+    let statement: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!("/v1/merchants/1/statements/{}", month))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(statement["opening_balance"], "147");
+    assert_eq!(statement["credits"], "1");
+    assert_eq!(statement["debits"], "-50");
+    assert_eq!(statement["closing_balance"], "98");
+    let kinds = statement["movements"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|movement| movement["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds, vec!["Refund", "FeeRefund"]);
+}
+
+async fn transactions_are_exported_and_summed_into_statements(backend: Backend) {
+    let repositories = repositories(backend, 5000);
+    let app = init_app!(repositories);
+    let today = chrono::Local::now().date_naive();
+
+    let request = test::TestRequest::post()
+        .uri("/v1/merchants")
+        .set_json(json!({
+            "currency": "USD",
+            "name": "Cafe Central",
+            "city": "Springfield",
+            "country": "US",
+            "mcc": "5812",
+        }))
+        .to_request();
+    test::call_service(&app, request).await;
+
+    //  Paid and confirmed, paid and refunded, paid and left unconfirmed, and a payment to no merchant
+    let mut tokens = Vec::new();
+    for (merchants_id, amount) in [(Some(1), -25), (Some(1), -40), (Some(1), -10), (None, -5)] {
+        let request = test::TestRequest::post()
+            .uri("/v1/transactions")
+            .set_json(json!({ "wallets_id": 1, "merchants_id": merchants_id, "amount": amount }))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, request).await;
+        tokens.push(token_of(body));
+    }
+    for token in [&tokens[0], &tokens[1], &tokens[3]] {
+        test::call_service(&app, transaction_action("confirm", token).to_request()).await;
+    }
+    test::call_service(&app, transaction_action("refund", &tokens[1]).to_request()).await;
+
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/wallets/1/transactions/export")
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    let disposition = response.headers().get("content-disposition").unwrap();
+    assert!(disposition
+        .to_str()
+        .unwrap()
+        .contains("wallet-1-transactions-"));
+    let csv = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        "id,wallets_id,merchants_id,amount,currency,paid_amount,paid_currency,rate,status,created_at"
+    );
+    assert_eq!(lines.len(), 5);
+    let columns = lines[1].split(',').collect::<Vec<_>>();
+    assert_eq!(
+        columns[1..9],
+        ["1", "1", "-25", "USD", "-25", "USD", "", "Confirmed"]
+    );
+    assert_eq!(lines[4].split(',').nth(2), Some(""));
+
+    let query = format!("?from={}&to={}&format=ndjson", today, today);
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!("/v1/merchants/1/transactions/export{}", query))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/x-ndjson"
+    );
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+    let statuses = body
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .map(|row| row["status"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(statuses, vec!["Confirmed", "Refunded", "Initialized"]);
+
+    //  Exports read the transactions a page at a time, continuing after the last ID sent
+    let filter = TransactionFilter {
+        owner: TransactionOwner::Wallet(1),
+        from: today.and_time(chrono::NaiveTime::MIN),
+        to: (today + chrono::TimeDelta::days(1)).and_time(chrono::NaiveTime::MIN),
+    };
+    let first_page = repositories
+        .transactions
+        .select_page(&filter, 0, 3)
+        .unwrap();
+    assert_eq!(first_page.len(), 3);
+    let last_id = serde_json::to_value(&first_page[2]).unwrap()["id"]
+        .as_u64()
+        .unwrap();
+    let second_page = repositories
+        .transactions
+        .select_page(&filter, last_id, 3)
+        .unwrap();
+    assert_eq!(second_page.len(), 1);
+
+    //  The refunded payment is booked and given back within the month, the unconfirmed one not at all
+    let month = today.format("%Y-%m").to_string();
+    let statement: Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!("/v1/merchants/1/statements/{}", month))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(statement["month"], month);
+    assert_eq!(statement["opening_balance"], "0");
+    assert_eq!(statement["credits"], "65");
+    assert_eq!(statement["debits"], "-40");
+    assert_eq!(statement["closing_balance"], "25");
+    let movements = statement["movements"].as_array().unwrap();
+    assert_eq!(movements.len(), 3);
+    assert_eq!(movements[2]["kind"], "Refund");
+    assert_eq!(movements[2]["transactions_id"], 2);
+
+    for (uri, status, code) in [
+        (
+            String::from("/v1/wallets/9/transactions/export"),
+            StatusCode::NOT_FOUND,
+            "WALLET_NOT_FOUND",
+        ),
+        (
+            String::from("/v1/merchants/9/statements/2025-01"),
+            StatusCode::NOT_FOUND,
+            "MERCHANT_NOT_FOUND",
+        ),
+        (
+            String::from("/v1/merchants/1/transactions/export?from=2025-03-06&to=2025-03-05"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+        (
+            String::from("/v1/wallets/1/transactions/export?to=-262143-01-01"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+        (
+            String::from("/v1/merchants/1/settlements?to=-262143-01-01"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+        (
+            String::from("/v1/wallets/1/transactions/export?format=xml"),
+            StatusCode::BAD_REQUEST,
+            "INVALID_REQUEST",
+        ),
+        (
+            String::from("/v1/merchants/1/statements/2025-13"),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+        (
+            format!(
+                "/v1/merchants/1/statements/{}",
+                (today + chrono::Months::new(1)).format("%Y-%m")
+            ),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "VALIDATION_FAILED",
+        ),
+    ] {
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), status);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["code"], code);
+    }
+}
+
+async fn webhook_subscriptions_are_created_listed_and_deactivated(backend: Backend) {
+    let repositories = repositories(backend, 1000);
+    let app = init_app!(repositories);
+
+    let request = test::TestRequest::post()
+        .uri("/v1/webhooks/subscriptions")
+        .set_json(json!({ "url": "https://merchant.example/hook", "merchants_id": 7 }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "MERCHANT_NOT_FOUND");
+
+    let request = test::TestRequest::post()
+        .uri("/v1/webhooks/subscriptions")
+        .set_json(json!({ "url": "ftp://merchant.example/hook", "merchants_id": 0 }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+    let fields = body["details"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| (error["field"].clone(), error["code"].clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            (json!("url"), json!("INVALID_FORMAT")),
+            (json!("merchants_id"), json!("OUT_OF_RANGE")),
+        ]
+    );
+
+    let request = test::TestRequest::delete()
+        .uri("/v1/webhooks/subscriptions/99")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "WEBHOOK_SUBSCRIPTION_NOT_FOUND");
+
+    let request = test::TestRequest::post()
+        .uri("/v1/merchants")
+        .set_json(json!({
+            "currency": "USD",
+            "name": "Cafe Central",
+            "city": "Springfield",
+            "country": "US",
+            "mcc": "5812",
+        }))
+        .to_request();
+    let merchant: Value = test::call_and_read_body_json(&app, request).await;
+
+    let request = test::TestRequest::post()
+        .uri("/v1/webhooks/subscriptions")
+        .set_json(json!({ "url": "https://merchant.example/hook", "merchants_id": merchant["id"] }))
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(created["active"], true);
+    assert_eq!(created["merchants_id"], merchant["id"]);
+    assert_eq!(created["secret"].as_str().unwrap().len(), 32);
+    let subscriptions_id = created["id"].as_u64().unwrap();
+
+    let request = test::TestRequest::get()
+        .uri("/v1/webhooks/subscriptions")
+        .to_request();
+    let listed: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(listed[0]["id"], subscriptions_id);
+    assert!(listed[0].get("secret").is_none());
+
+    let request = test::TestRequest::delete()
+        .uri(&format!("/v1/webhooks/subscriptions/{}", subscriptions_id))
+        .to_request();
+    let deactivated: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(deactivated["active"], false);
+
+    let request = test::TestRequest::get()
+        .uri("/v1/webhooks/deliveries?status=Pending")
+        .to_request();
+    let deliveries: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(deliveries, json!([]));
+}
+
+#[actix_web::test]
+async fn reloading_an_unchanged_configuration_applies_nothing() {
+    let app = init_app!(repositories(Backend::InMemory, 0));
+    let response = test::call_service(
+        &app,
+        test::TestRequest::put()
+            .uri("/v1/configurations/reload")
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: Value = test::read_body_json(response).await;
+    assert_eq!(report, json!({"applied": [], "restart_required": []}));
+}
+
+#[actix_web::test]
+async fn invalid_reloads_respond_with_the_validation_errors() {
+    let error = reload_response(Err(error_mapper::create_new_error!(
+        "Invalid configuration:\n  - api.port must be greater than 0"
+    )))
+    .await
+    .unwrap_err();
+
+    let response = actix_web::ResponseError::error_response(&error);
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = actix_web::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], json!("INVALID_CONFIGURATION"));
+    assert!(body["message"]
+        .as_str()
+        .is_some_and(|message| message.contains("api.port must be greater than 0")));
+}
+
+#[actix_web::test]
+async fn readiness_ignores_failed_iterations_and_fails_while_draining() {
+    Config::initialize_config(None).unwrap();
+    let app = test::init_service(App::new().configure(health_services)).await;
+    crate::tasks::report(
+        "failing_task",
+        std::time::Duration::from_secs(60),
+        &Err(error_mapper::create_new_error!(
+            "Merchant could not be settled"
+        )),
+    );
+
+    let response =
+        test::call_service(&app, test::TestRequest::get().uri("/healthz").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    //  A failed iteration is retried on the next one, only a stalled task fails readiness
+    let response =
+        test::call_service(&app, test::TestRequest::get().uri("/readyz").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let readiness: Value = test::read_body_json(response).await;
+    let failing_task = readiness["tasks"]
+        .as_array()
+        .and_then(|tasks| tasks.iter().find(|task| task["name"] == "failing_task"))
+        .cloned()
+        .unwrap();
+    assert_eq!(failing_task["healthy"], json!(true));
+    assert_eq!(
+        failing_task["last_error"],
+        json!("Merchant could not be settled")
+    );
+
+    start_draining();
+    let response =
+        test::call_service(&app, test::TestRequest::get().uri("/readyz").to_request()).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let readiness: Value = test::read_body_json(response).await;
+    assert_eq!(readiness["status"], json!("not_ready"));
+    assert_eq!(readiness["draining"], json!(true));
+
+    let response =
+        test::call_service(&app, test::TestRequest::get().uri("/healthz").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn requests_are_counted_by_route_pattern_and_exported() {
+    Config::initialize_config(None).unwrap();
+    let repositories = repositories(Backend::InMemory, 100);
+    let app = test::init_service(
+        App::new()
+            .wrap(actix_web::middleware::from_fn(track_requests))
+            .app_data(web::Data::from(repositories.wallets.clone()))
+            .app_data(web::Data::from(repositories.transactions.clone()))
+            .app_data(web::Data::from(repositories.merchants.clone()))
+            .app_data(web::Data::from(repositories.settlements.clone()))
+            .app_data(web::Data::from(repositories.webhooks.clone()))
+            .app_data(web::Data::from(repositories.fx.clone()))
+            .app_data(web::Data::from(repositories.fees.clone()))
+            .configure(metrics_services)
+            .configure(v1_services),
+    )
+    .await;
+    let requests = |route: &str, status: &str| {
+        crate::metrics::HTTP_REQUESTS
+            .with_label_values(&["GET", route, status])
+            .get()
+    };
+    let found = requests("/v1/wallets/{wallets_id}", "200");
+    let not_found = requests("/v1/wallets/{wallets_id}", "404");
+    let unmatched = requests("unmatched", "404");
+
+    for uri in [
+        "/v1/wallets/1",
+        "/v1/wallets/1",
+        "/v1/wallets/99",
+        "/nowhere",
+    ] {
+        test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    }
+
+    //  Every ID shares the series of its route
+    assert_eq!(requests("/v1/wallets/{wallets_id}", "200"), found + 2);
+    assert_eq!(requests("/v1/wallets/{wallets_id}", "404"), not_found + 1);
+    assert_eq!(requests("unmatched", "404"), unmatched + 1);
+
+    let response =
+        test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        prometheus::TEXT_FORMAT
+    );
+    let body = test::read_body(response).await;
+    let body = std::str::from_utf8(&body).unwrap();
+    assert!(body.contains(
+        r#"qrpay_http_requests_total{method="GET",route="/v1/wallets/{wallets_id}",status="200"}"#
+    ));
+    assert!(body.contains(
+        r#"qrpay_http_request_duration_seconds_count{method="GET",route="/v1/wallets/{wallets_id}"}"#
+    ));
+    //  Metrics never touched are exported as well
+    assert!(body.contains("qrpay_db_rollback_failures_total 0"));
+}
+
+#[actix_web::test]
+async fn request_ids_are_echoed_and_reported_in_error_bodies() {
+    let app = init_app!(repositories(Backend::InMemory, 0));
+
+    let response = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/v1/wallets/99")
+            .insert_header((REQUEST_ID_HEADER, "client-id-1"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "client-id-1"
+    );
+    let error: Value = test::read_body_json(response).await;
+    assert_eq!(error["code"], json!("WALLET_NOT_FOUND"));
+    assert_eq!(error["request_id"], json!("client-id-1"));
+
+    //  IDs that are too long or not printable are replaced by a generated one
+    for received in ["x".repeat(129), String::from("with space")] {
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/v1/wallets/1")
+                .insert_header((REQUEST_ID_HEADER, received.as_str()))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let generated = response.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert_ne!(generated, received.as_str());
+        assert_eq!(generated.len(), 32);
+    }
+}
+
+#[actix_web::test]
+async fn blocking_operations_log_with_the_request_context() {
+    let (propagated, lost) = crate::logging::scope(String::from("blocking-1"), async {
+        let propagated = web::block(crate::logging::propagate(crate::logging::request_id))
+            .await
+            .unwrap();
+        let lost = web::block(crate::logging::request_id).await.unwrap();
+        (propagated, lost)
+    })
+    .await;
+
+    assert_eq!(propagated.as_deref(), Some("blocking-1"));
+    assert_eq!(lost, None);
+}
@@ -0,0 +1,38 @@
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use the_logger::TheLogger;
+
+use crate::{api::error::ApiError, log_info, validation::Validate};
+
+/// ## Description
+/// JSON body extractor that also validates the body, so handlers only ever see valid requests and no DB
+/// access happens for invalid ones. Invalid bodies are rejected with 422 and the list of field errors
+pub struct ValidJson<T>(pub T);
+
+impl<T> ValidJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidJson<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let body = web::Json::<T>::from_request(request, payload);
+
+        Box::pin(async move {
+            let body = body.await?.into_inner();
+            let errors = body.validate();
+            if errors.is_empty() {
+                return Ok(ValidJson(body));
+            }
+
+            let error = ApiError::invalid_fields(errors);
+            log_info!(TheLogger::instance(), "Invalid request: {}", error);
+            Err(error.into())
+        })
+    }
+}
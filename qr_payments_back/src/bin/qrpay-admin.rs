@@ -0,0 +1,10 @@
+use clap::Parser;
+use qr_payments_backend::admin::{self, AdminCli};
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = admin::run(AdminCli::parse()).await {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
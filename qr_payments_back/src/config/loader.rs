@@ -0,0 +1,462 @@
+use std::{env, fs};
+
+use error_mapper::{create_new_error, TheError, TheResult};
+use rust_decimal::Decimal;
+use serde::Serializer;
+use serde_json::{Map, Value};
+
+use super::{ConfigInner, DbDriver};
+
+/// File read when neither `--config` nor `QRPAY_CONFIG` point to one
+pub const DEFAULT_CONFIG_PATH: &str = "config/config.json";
+/// Environment variable holding the configuration file path
+pub const CONFIG_PATH_ENV: &str = "QRPAY_CONFIG";
+/// Prefix of the environment overrides, as `QRPAY_{SECTION}__{FIELD}`, e.g. `QRPAY_API__PORT`
+const ENV_PREFIX: &str = "QRPAY_";
+const ENV_SEPARATOR: &str = "__";
+const REDACTED: &str = "********";
+/// Upper bounds of the durations, far above any sensible value while keeping the date arithmetic with them
+/// from overflowing
+const HISTORY_DAYS_MAX: u32 = 3650;
+const WINDOW_SECS_MAX: i64 = 30 * 86_400;
+const TTL_SECS_MAX: u64 = 7 * 86_400;
+const QUOTE_TTL_SECS_MAX: u64 = 86_400;
+
+impl ConfigInner {
+    /// ## Description
+    /// Builds the configuration from its layers, each one overriding the previous: the defaults, the
+    /// configuration file and the `QRPAY_*` environment variables. Secrets are then read from their files
+    /// and every field is validated
+    ///
+    /// ### Returns
+    /// The configuration, or a single error listing every problem found
+    pub(super) fn load(path: Option<&str>) -> TheResult<Self> {
+        let mut layered = serde_json::to_value(Self::default())
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        if let Some(file) = read_file(path)? {
+            merge(&mut layered, file);
+        }
+
+        let mut errors = apply_env_overrides(&mut layered, env::vars());
+        let mut config = match serde_json::from_value::<Self>(layered) {
+            Ok(config) => config,
+            Err(error) => {
+                errors.push(error.to_string());
+                return Err(invalid(errors));
+            }
+        };
+
+        errors.extend(config.resolve_secrets());
+        errors.extend(config.validate());
+        if !errors.is_empty() {
+            return Err(invalid(errors));
+        }
+
+        Ok(config)
+    }
+
+    /// ## Description
+    /// Compares every field against the received configuration. Secrets are compared redacted, so their
+    /// changes are not detected here
+    ///
+    /// ### Returns
+    /// The dotted paths of the fields with a different value, such as `api.port`
+    pub(super) fn changed_fields(&self, other: &Self) -> TheResult<Vec<String>> {
+        let current =
+            serde_json::to_value(self).map_err(|error| create_new_error!(error.to_string()))?;
+        let other =
+            serde_json::to_value(other).map_err(|error| create_new_error!(error.to_string()))?;
+
+        let mut fields = Vec::new();
+        collect_changes(String::new(), &current, &other, &mut fields);
+        Ok(fields)
+    }
+
+    /// ## Description
+    /// Reads the secrets configured as files, so they can be mounted instead of written in the config file
+    fn resolve_secrets(&mut self) -> Vec<String> {
+        let Some(pass_file) = &self.db.pass_file else {
+            return Vec::new();
+        };
+        if !self.db.pass.is_empty() {
+            return vec![String::from(
+                "db.pass and db.pass_file are mutually exclusive",
+            )];
+        }
+
+        match fs::read_to_string(pass_file) {
+            Ok(pass) => {
+                self.db.pass = pass.trim_end_matches(['\r', '\n']).to_string();
+                Vec::new()
+            }
+            Err(error) => vec![format!(
+                "db.pass_file {} could not be read: {}",
+                pass_file, error
+            )],
+        }
+    }
+
+    /// ## Description
+    /// Checks every field, collecting all the problems instead of stopping at the first one
+    pub(super) fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, message: &str| {
+            if !valid {
+                errors.push(message.to_string());
+            }
+        };
+
+        let api = &self.api;
+        check(!api.addr.trim().is_empty(), "api.addr cannot be empty");
+        check(api.port > 0, "api.port must be greater than 0");
+        check(api.workers > 0, "api.workers must be greater than 0");
+
+        let db = &self.db;
+        check(!db.db_name.trim().is_empty(), "db.db_name cannot be empty");
+        match db.driver {
+            DbDriver::MySql => {
+                check(!db.user.trim().is_empty(), "db.user cannot be empty");
+                check(!db.addr.trim().is_empty(), "db.addr cannot be empty");
+            }
+            DbDriver::Sqlite => {
+                check(
+                    cfg!(feature = "sqlite"),
+                    "db.driver sqlite requires building with the sqlite feature",
+                );
+                check(
+                    !db.sqlite_path.trim().is_empty(),
+                    "db.sqlite_path cannot be empty",
+                );
+            }
+        }
+        check(
+            !db.migrations_dir.trim().is_empty(),
+            "db.migrations_dir cannot be empty",
+        );
+        check(db.pool_max > 0, "db.pool_max must be greater than 0");
+        check(
+            db.pool_min <= db.pool_max,
+            "db.pool_min cannot be greater than db.pool_max",
+        );
+        check(
+            db.connect_timeout_secs > 0,
+            "db.connect_timeout_secs must be greater than 0",
+        );
+        check(
+            db.acquire_timeout_secs > 0,
+            "db.acquire_timeout_secs must be greater than 0",
+        );
+        check(
+            db.io_timeout_secs > 0,
+            "db.io_timeout_secs must be greater than 0",
+        );
+
+        let risk = &self.risk;
+        check(
+            risk.history_days > 0,
+            "risk.history_days must be greater than 0",
+        );
+        check(
+            risk.history_days <= HISTORY_DAYS_MAX,
+            &format!(
+                "risk.history_days cannot be greater than {}",
+                HISTORY_DAYS_MAX
+            ),
+        );
+        if let Some(velocity) = &risk.velocity {
+            check(
+                velocity.window_secs > 0,
+                "risk.velocity.window_secs must be greater than 0",
+            );
+            check(
+                velocity.window_secs <= WINDOW_SECS_MAX,
+                &format!(
+                    "risk.velocity.window_secs cannot be greater than {}",
+                    WINDOW_SECS_MAX
+                ),
+            );
+            check(
+                velocity.max_count > 0,
+                "risk.velocity.max_count must be greater than 0",
+            );
+        }
+        if let Some(amount_outlier) = &risk.amount_outlier {
+            check(
+                amount_outlier.multiplier > Decimal::ZERO,
+                "risk.amount_outlier.multiplier must be greater than 0",
+            );
+        }
+        if let Some(first_payment) = &risk.first_payment {
+            check(
+                first_payment.min_amount >= Decimal::ZERO,
+                "risk.first_payment.min_amount cannot be negative",
+            );
+        }
+        if let Some(repeated_declines) = &risk.repeated_declines {
+            check(
+                repeated_declines.window_secs > 0,
+                "risk.repeated_declines.window_secs must be greater than 0",
+            );
+            check(
+                repeated_declines.window_secs <= WINDOW_SECS_MAX,
+                &format!(
+                    "risk.repeated_declines.window_secs cannot be greater than {}",
+                    WINDOW_SECS_MAX
+                ),
+            );
+            check(
+                repeated_declines.max_declines > 0,
+                "risk.repeated_declines.max_declines must be greater than 0",
+            );
+        }
+
+        let transactions = &self.transactions;
+        check(
+            transactions.ttl_secs > 0,
+            "transactions.ttl_secs must be greater than 0",
+        );
+        check(
+            transactions.ttl_secs <= TTL_SECS_MAX,
+            &format!(
+                "transactions.ttl_secs cannot be greater than {}",
+                TTL_SECS_MAX
+            ),
+        );
+        check(
+            transactions.sweep_interval_secs > 0,
+            "transactions.sweep_interval_secs must be greater than 0",
+        );
+        check(
+            transactions.events_heartbeat_secs > 0,
+            "transactions.events_heartbeat_secs must be greater than 0",
+        );
+
+        let webhooks = &self.webhooks;
+        check(
+            webhooks.poll_interval_secs > 0,
+            "webhooks.poll_interval_secs must be greater than 0",
+        );
+        check(
+            webhooks.batch_size > 0,
+            "webhooks.batch_size must be greater than 0",
+        );
+        check(
+            webhooks.timeout_secs > 0,
+            "webhooks.timeout_secs must be greater than 0",
+        );
+        check(
+            webhooks.max_attempts > 0,
+            "webhooks.max_attempts must be greater than 0",
+        );
+        check(
+            webhooks.base_delay_secs <= webhooks.max_delay_secs,
+            "webhooks.base_delay_secs cannot be greater than webhooks.max_delay_secs",
+        );
+
+        let outbox = &self.outbox;
+        check(
+            outbox.poll_interval_secs > 0,
+            "outbox.poll_interval_secs must be greater than 0",
+        );
+        check(
+            outbox.batch_size > 0,
+            "outbox.batch_size must be greater than 0",
+        );
+        check(
+            outbox
+                .log_file
+                .as_ref()
+                .is_none_or(|log_file| !log_file.trim().is_empty()),
+            "outbox.log_file cannot be empty, remove it to disable the sink",
+        );
+
+        let fx = &self.fx;
+        check(
+            fx.quote_ttl_secs > 0,
+            "fx.quote_ttl_secs must be greater than 0",
+        );
+        check(
+            fx.quote_ttl_secs <= QUOTE_TTL_SECS_MAX,
+            &format!(
+                "fx.quote_ttl_secs cannot be greater than {}",
+                QUOTE_TTL_SECS_MAX
+            ),
+        );
+        check(
+            fx.rates_file
+                .as_ref()
+                .is_none_or(|rates_file| !rates_file.trim().is_empty()),
+            "fx.rates_file cannot be empty, remove it to start without rates",
+        );
+
+        check(
+            self.fees
+                .schedules_file
+                .as_ref()
+                .is_none_or(|schedules_file| !schedules_file.trim().is_empty()),
+            "fees.schedules_file cannot be empty, remove it to start without fees",
+        );
+
+        errors
+    }
+}
+
+/// ## Description
+/// Serializes a secret as asterisks, keeping empty ones visible so a missing secret can be told apart
+pub(super) fn redact<S: Serializer>(secret: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    if secret.is_empty() {
+        serializer.serialize_str("")
+    } else {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+/// ## Description
+/// Reads the file received, falling back to `QRPAY_CONFIG` and then to the default path. A file that was
+/// explicitly requested must exist, while a missing default file leaves the defaults and environment only
+fn read_file(path: Option<&str>) -> TheResult<Option<Value>> {
+    let (path, explicit) = match path
+        .map(String::from)
+        .or_else(|| env::var(CONFIG_PATH_ENV).ok())
+    {
+        Some(path) => (path, true),
+        None => (String::from(DEFAULT_CONFIG_PATH), false),
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(error) if !explicit && error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(create_new_error!(format!(
+                "Could not read configuration file {}: {}",
+                path, error
+            )))
+        }
+    };
+
+    serde_json::from_str(&content).map(Some).map_err(|error| {
+        create_new_error!(format!(
+            "Could not parse configuration file {}: {}",
+            path, error
+        ))
+    })
+}
+
+/// ## Description
+/// Recursively overwrites `base` with the fields present in `layer`
+pub(super) fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// ## Description
+/// Walks both values side by side, collecting the paths of the leaves that differ. A section that is
+/// added or removed is reported as a single field
+fn collect_changes(path: String, current: &Value, other: &Value, fields: &mut Vec<String>) {
+    match (current, other) {
+        (Value::Object(current), Value::Object(other)) => {
+            let mut keys = current.keys().chain(other.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let field = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}.{}", path, key),
+                };
+                collect_changes(
+                    field,
+                    current.get(key).unwrap_or(&Value::Null),
+                    other.get(key).unwrap_or(&Value::Null),
+                    fields,
+                );
+            }
+        }
+        (current, other) if current != other => fields.push(path),
+        _ => {}
+    }
+}
+
+/// ## Description
+/// Applies every `QRPAY_{SECTION}__{FIELD}` variable over the layered configuration. Values are read as
+/// text for text fields and as JSON otherwise, so numbers, booleans and whole sections can be overridden
+///
+/// ### Returns
+/// The variables that could not be applied
+pub(super) fn apply_env_overrides(
+    layered: &mut Value,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (name, raw) in vars {
+        let Some(path) = name
+            .strip_prefix(ENV_PREFIX)
+            .filter(|path| path.contains(ENV_SEPARATOR))
+        else {
+            continue;
+        };
+        let keys = path
+            .split(ENV_SEPARATOR)
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if keys.iter().any(String::is_empty) {
+            errors.push(format!("{} is not a valid configuration variable", name));
+            continue;
+        }
+
+        let Some(field) = field_mut(layered, &keys) else {
+            errors.push(format!("{} does not match any configuration field", name));
+            continue;
+        };
+        let parsed = serde_json::from_str::<Value>(&raw);
+        *field = match (&*field, parsed) {
+            (Value::String(_), _) => Value::String(raw),
+            (Value::Number(_), Ok(parsed @ Value::Number(_)))
+            | (Value::Bool(_), Ok(parsed @ Value::Bool(_))) => parsed,
+            (Value::Number(_), _) => {
+                errors.push(format!("{} must be a number", name));
+                continue;
+            }
+            (Value::Bool(_), _) => {
+                errors.push(format!("{} must be true or false", name));
+                continue;
+            }
+            (_, Ok(parsed)) => parsed,
+            (_, Err(_)) => Value::String(raw),
+        };
+    }
+
+    errors
+}
+
+/// ## Description
+/// Finds the field at the path, creating it only inside optional sections that are still unset
+pub(super) fn field_mut<'a>(layered: &'a mut Value, keys: &[String]) -> Option<&'a mut Value> {
+    let mut current = layered;
+    let mut unset = false;
+    for key in keys {
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+            unset = true;
+        }
+        let object = current.as_object_mut()?;
+        if !unset && !object.contains_key(key) {
+            return None;
+        }
+        current = object.entry(key.clone()).or_insert(Value::Null);
+    }
+
+    Some(current)
+}
+
+fn invalid(errors: Vec<String>) -> TheError {
+    create_new_error!(format!(
+        "Invalid configuration:\n  - {}",
+        errors.join("\n  - ")
+    ))
+}
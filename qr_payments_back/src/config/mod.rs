@@ -1,45 +1,288 @@
+use chrono::NaiveTime;
 use error_mapper::{create_new_error, TheResult};
-use serde::Deserialize;
-use std::{fs::File, sync::OnceLock};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
 use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+use crate::{
+    database::migrations,
+    logging::{self, LogFormat, LogLevel},
+    modules::risk::RiskDecision,
+};
+
+mod loader;
+#[cfg(test)]
+mod tests;
+
+pub use loader::{CONFIG_PATH_ENV, DEFAULT_CONFIG_PATH};
 
 static CONFIG: OnceLock<Config> = OnceLock::new();
 
 #[derive(Debug, Default)]
 pub struct Config {
     inner: RwLock<ConfigInner>,
+    /// File the configuration was loaded from, read again on every reload
+    path: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 struct ConfigInner {
     api: ApiConfig,
     db: DbConfig,
+    risk: RiskConfig,
+    transactions: TransactionsConfig,
+    webhooks: WebhooksConfig,
+    outbox: OutboxConfig,
+    fx: FxConfig,
+    fees: FeesConfig,
+    settlements: SettlementsConfig,
+    log: LogConfig,
 }
 
-#[derive(Deserialize, Debug, Default, Clone)]
+/// Outcome of a configuration reload, as the dotted paths of the fields that changed
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct ReloadReport {
+    /// Changes already in effect
+    pub applied: Vec<String>,
+    /// Changes ignored until the service is restarted, such as the bind address or the DB pool
+    pub restart_required: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct ApiConfig {
     pub addr: String,
     pub port: u16,
     pub workers: usize,
+    /// Seconds readiness fails before the server stops, once a shutdown is requested
+    pub drain_secs: u64,
 }
 
-#[derive(Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct DbConfig {
+    pub driver: DbDriver,
+    /// Required by the MySQL driver, there is no default user
     pub user: String,
+    /// Prefer `pass_file` or the `QRPAY_DB__PASS` variable over writing it in the config file
+    #[serde(serialize_with = "loader::redact")]
     pub pass: String,
+    /// File the password is read from, such as a mounted secret
+    pub pass_file: Option<String>,
     pub addr: String,
     pub db_name: String,
+    /// Database file used by the SQLite driver
+    pub sqlite_path: String,
+    /// Connections the MySQL pool keeps open, even while idle
+    pub pool_min: usize,
+    /// Most connections the MySQL pool opens under load. Once all of them are in use, queries wait up to
+    /// `acquire_timeout_secs` for one to be released
+    pub pool_max: usize,
+    pub connect_timeout_secs: u64,
+    /// Seconds a query waits for a free connection when the pool is exhausted
+    pub acquire_timeout_secs: u64,
+    /// Read and write timeout of every connection
+    pub io_timeout_secs: u64,
+    /// Applies the pending MySQL migrations before serving requests. Off by default, so they are applied on
+    /// purpose with the `migrate` command
+    pub migrate_on_startup: bool,
+    /// Directory holding the MySQL migrations. Defaults to the one of the crate the binary was built from,
+    /// so it does not depend on the working directory
+    pub migrations_dir: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DbDriver {
+    #[default]
+    MySql,
+    /// Requires building with the `sqlite` feature
+    Sqlite,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TransactionsConfig {
+    /// Seconds an Initialized transaction waits for confirmation before it expires
+    pub ttl_secs: u64,
+    pub sweep_interval_secs: u64,
+    /// Seconds between the pings sent on the transaction events stream
+    pub events_heartbeat_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub poll_interval_secs: u64,
+    pub batch_size: u32,
+    pub timeout_secs: u64,
+    /// Attempts before a delivery is moved to the dead letter state
+    pub max_attempts: u32,
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: LogLevel,
+    pub format: LogFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct OutboxConfig {
+    pub poll_interval_secs: u64,
+    pub batch_size: u32,
+    pub webhooks_sink: bool,
+    pub channel_sink: bool,
+    /// File the events are appended to as JSON lines. The log file sink is disabled when None
+    pub log_file: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FxConfig {
+    /// JSON file with the rates stored on startup when none are stored yet, as
+    /// `{"rates": [{"from", "to", "rate"}]}`. Later changes go through `PUT /v1/fx/rates`
+    pub rates_file: Option<String>,
+    /// Seconds a quote can be locked by a payment after its creation
+    pub quote_ttl_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FeesConfig {
+    /// JSON file with the fee schedules stored on startup when none are stored yet, as `{"schedules":
+    /// [{"party", "kind", "merchants_id", "currency", "rule"}]}`. No fees are charged until schedules are
+    /// stored. Later changes go through `PUT /v1/fees/schedules`
+    pub schedules_file: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SettlementsConfig {
+    /// Local time of the daily run closing the merchant batches through the previous day and paying them out
+    pub run_at: NaiveTime,
+}
+
+/// Fraud rules configuration. A rule is only enabled when its section is present
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RiskConfig {
+    #[serde(default = "RiskConfig::default_history_days")]
+    pub history_days: u32,
+    pub velocity: Option<VelocityRuleConfig>,
+    pub amount_outlier: Option<AmountOutlierRuleConfig>,
+    pub first_payment: Option<FirstPaymentRuleConfig>,
+    pub repeated_declines: Option<RepeatedDeclinesRuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VelocityRuleConfig {
+    pub window_secs: i64,
+    pub max_count: usize,
+    #[serde(default = "RiskDecision::review")]
+    pub decision: RiskDecision,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AmountOutlierRuleConfig {
+    pub multiplier: Decimal,
+    pub min_history: usize,
+    #[serde(default = "RiskDecision::review")]
+    pub decision: RiskDecision,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FirstPaymentRuleConfig {
+    pub min_amount: Decimal,
+    #[serde(default = "RiskDecision::review")]
+    pub decision: RiskDecision,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepeatedDeclinesRuleConfig {
+    pub window_secs: i64,
+    pub max_declines: usize,
+    #[serde(default = "RiskDecision::block")]
+    pub decision: RiskDecision,
 }
 
 impl Config {
-    pub fn initialize_config() {
-        let file = File::open("config/config.json").unwrap();
-        let config = serde_json::from_reader::<_, ConfigInner>(file).unwrap();
+    /// ## Description
+    /// Loads the configuration from the file received, or the one in `QRPAY_CONFIG`, applying the
+    /// `QRPAY_*` overrides over it. Every invalid field is reported in the returned error
+    pub fn initialize_config(path: Option<&str>) -> TheResult<()> {
+        let inner = ConfigInner::load(path)?;
+        logging::set_level(inner.log.level);
+        logging::set_format(inner.log.format);
         let config = Config {
-            inner: RwLock::new(config),
+            inner: RwLock::new(inner),
+            path: path.map(String::from),
         };
 
         CONFIG.get_or_init(|| config);
+        Ok(())
+    }
+
+    /// ## Description
+    /// Loads the configuration again from the same file and environment. The reloadable sections (risk,
+    /// transactions, webhooks, outbox, fx, settlements and log) replace the cached ones and are picked up by the next
+    /// request or task iteration. The api and db sections, the fx rates file and the fee schedules file are
+    /// only read on startup, so they are kept and their changes reported as requiring a restart
+    ///
+    /// ### Returns
+    /// The changed fields, or the validation errors, in which case the current configuration is kept
+    pub async fn reload() -> TheResult<ReloadReport> {
+        CONFIG
+            .get()
+            .ok_or_else(|| create_new_error!("Could not get Configurations from local cache"))?
+            .reload_from_path()
+            .await
+    }
+
+    async fn reload_from_path(&self) -> TheResult<ReloadReport> {
+        let mut reloaded = ConfigInner::load(self.path.as_deref())?;
+
+        let mut inner = self.inner.write().await;
+        let (applied, mut restart_required) = inner
+            .changed_fields(&reloaded)?
+            .into_iter()
+            .partition::<Vec<_>, _>(|field| {
+                !field.starts_with("api.")
+                    && !field.starts_with("db.")
+                    && field != "fx.rates_file"
+                    && field != "fees.schedules_file"
+            });
+        if inner.db.pass != reloaded.db.pass {
+            restart_required.push(String::from("db.pass"));
+        }
+
+        reloaded.api = inner.api.clone();
+        reloaded.db = inner.db.clone();
+        reloaded.fx.rates_file = inner.fx.rates_file.clone();
+        reloaded.fees.schedules_file = inner.fees.schedules_file.clone();
+        logging::set_level(reloaded.log.level);
+        logging::set_format(reloaded.log.format);
+        *inner = reloaded;
+
+        Ok(ReloadReport {
+            applied,
+            restart_required,
+        })
+    }
+
+    /// ## Description
+    /// Loads the configuration the same way as `initialize_config`, without caching it
+    ///
+    /// ### Returns
+    /// The effective configuration as JSON, with its secrets redacted
+    pub fn to_redacted_json(path: Option<&str>) -> TheResult<String> {
+        serde_json::to_string_pretty(&ConfigInner::load(path)?)
+            .map_err(|error| create_new_error!(error.to_string()))
     }
 
     pub async fn get_api_config() -> TheResult<ApiConfig> {
@@ -55,6 +298,96 @@ impl Config {
             .ok_or_else(|| create_new_error!("Could not get Db Configurations from local cache"))?;
         Ok(config.inner.read().await.db.clone())
     }
+
+    pub async fn get_risk_config() -> TheResult<RiskConfig> {
+        let config = CONFIG.get().ok_or_else(|| {
+            create_new_error!("Could not get Risk Configurations from local cache")
+        })?;
+        Ok(config.inner.read().await.risk.clone())
+    }
+
+    pub async fn get_transactions_config() -> TheResult<TransactionsConfig> {
+        let config = CONFIG.get().ok_or_else(|| {
+            create_new_error!("Could not get Transactions Configurations from local cache")
+        })?;
+        Ok(config.inner.read().await.transactions.clone())
+    }
+
+    pub async fn get_webhooks_config() -> TheResult<WebhooksConfig> {
+        let config = CONFIG.get().ok_or_else(|| {
+            create_new_error!("Could not get Webhooks Configurations from local cache")
+        })?;
+        Ok(config.inner.read().await.webhooks.clone())
+    }
+
+    pub async fn get_outbox_config() -> TheResult<OutboxConfig> {
+        let config = CONFIG.get().ok_or_else(|| {
+            create_new_error!("Could not get Outbox Configurations from local cache")
+        })?;
+        Ok(config.inner.read().await.outbox.clone())
+    }
+
+    pub async fn get_fx_config() -> TheResult<FxConfig> {
+        let config = CONFIG
+            .get()
+            .ok_or_else(|| create_new_error!("Could not get Fx Configurations from local cache"))?;
+        Ok(config.inner.read().await.fx.clone())
+    }
+
+    pub async fn get_fees_config() -> TheResult<FeesConfig> {
+        let config = CONFIG.get().ok_or_else(|| {
+            create_new_error!("Could not get Fees Configurations from local cache")
+        })?;
+        Ok(config.inner.read().await.fees.clone())
+    }
+
+    pub async fn get_settlements_config() -> TheResult<SettlementsConfig> {
+        let config = CONFIG.get().ok_or_else(|| {
+            create_new_error!("Could not get Settlements Configurations from local cache")
+        })?;
+        Ok(config.inner.read().await.settlements.clone())
+    }
+}
+
+impl Default for TransactionsConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 900,
+            sweep_interval_secs: 60,
+            events_heartbeat_secs: 15,
+        }
+    }
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 5,
+            batch_size: 50,
+            timeout_secs: 10,
+            max_attempts: 8,
+            base_delay_secs: 10,
+            max_delay_secs: 3600,
+        }
+    }
+}
+
+impl RiskConfig {
+    fn default_history_days() -> u32 {
+        30
+    }
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            history_days: Self::default_history_days(),
+            velocity: None,
+            amount_outlier: None,
+            first_payment: None,
+            repeated_declines: None,
+        }
+    }
 }
 
 impl DbConfig {
@@ -65,3 +398,64 @@ impl DbConfig {
         )
     }
 }
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            driver: DbDriver::MySql,
+            user: String::new(),
+            pass: String::new(),
+            pass_file: None,
+            addr: String::from("127.0.0.1:3306"),
+            db_name: String::from("qr_payments"),
+            sqlite_path: String::from("qr_payments.db"),
+            pool_min: 10,
+            pool_max: 100,
+            connect_timeout_secs: 10,
+            acquire_timeout_secs: 5,
+            io_timeout_secs: 30,
+            migrate_on_startup: false,
+            migrations_dir: String::from(migrations::MIGRATIONS_DIR),
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            addr: String::from("127.0.0.1"),
+            port: 8090,
+            workers: std::thread::available_parallelism().map_or(1, usize::from),
+            drain_secs: 5,
+        }
+    }
+}
+
+impl Default for FxConfig {
+    fn default() -> Self {
+        Self {
+            rates_file: None,
+            quote_ttl_secs: 60,
+        }
+    }
+}
+
+impl Default for SettlementsConfig {
+    fn default() -> Self {
+        Self {
+            run_at: NaiveTime::from_hms_opt(0, 30, 0).unwrap_or(NaiveTime::MIN),
+        }
+    }
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 1,
+            batch_size: 100,
+            webhooks_sink: true,
+            channel_sink: true,
+            log_file: None,
+        }
+    }
+}
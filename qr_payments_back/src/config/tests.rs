@@ -0,0 +1,275 @@
+use std::fs;
+
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use super::{
+    loader::{apply_env_overrides, field_mut, merge},
+    Config, ConfigInner, DbConfig, VelocityRuleConfig,
+};
+use crate::modules::risk::RiskDecision;
+
+fn keys(path: &str) -> Vec<String> {
+    path.split('.').map(String::from).collect()
+}
+
+fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+    vars.iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+fn defaults() -> Value {
+    serde_json::to_value(ConfigInner::default()).unwrap()
+}
+
+/// Defaults that pass validation, as no DB user is set by default
+fn valid_config() -> ConfigInner {
+    let mut config = ConfigInner::default();
+    config.db.user = String::from("qr_payments");
+    config
+}
+
+#[test]
+fn merge_overrides_leaves_and_keeps_the_fields_missing_from_the_layer() {
+    let mut base = json!({"api": {"addr": "127.0.0.1", "port": 8090}, "log": {"level": "info"}});
+    merge(
+        &mut base,
+        json!({"api": {"port": 9000}, "fx": {"quote_ttl_secs": 30}}),
+    );
+
+    assert_eq!(
+        base,
+        json!({
+            "api": {"addr": "127.0.0.1", "port": 9000},
+            "log": {"level": "info"},
+            "fx": {"quote_ttl_secs": 30}
+        })
+    );
+}
+
+#[test]
+fn field_mut_only_creates_fields_inside_unset_sections() {
+    let mut layered = defaults();
+
+    assert_eq!(
+        field_mut(&mut layered, &keys("api.port")).cloned(),
+        Some(json!(8090))
+    );
+    assert!(field_mut(&mut layered, &keys("api.unknown")).is_none());
+    assert!(field_mut(&mut layered, &keys("unknown.port")).is_none());
+
+    //  The velocity rule is disabled by default, so its section can be created field by field
+    *field_mut(&mut layered, &keys("risk.velocity.max_count")).unwrap() = json!(3);
+    assert_eq!(layered["risk"]["velocity"], json!({"max_count": 3}));
+}
+
+#[test]
+fn env_overrides_are_parsed_by_the_type_of_the_field() {
+    let mut layered = defaults();
+    let errors = apply_env_overrides(
+        &mut layered,
+        vars(&[
+            ("QRPAY_API__PORT", "9000"),
+            ("QRPAY_API__ADDR", "0.0.0.0"),
+            ("QRPAY_DB__MIGRATE_ON_STARTUP", "true"),
+            ("QRPAY_DB__PASS", "1234"),
+            ("QRPAY_RISK__FIRST_PAYMENT", r#"{"min_amount": "500"}"#),
+            ("QRPAY_CONFIG", "ignored.json"),
+            ("HOME", "/root"),
+        ]),
+    );
+
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(layered["api"]["port"], json!(9000));
+    assert_eq!(layered["api"]["addr"], json!("0.0.0.0"));
+    assert_eq!(layered["db"]["migrate_on_startup"], json!(true));
+    //  Text fields keep the raw value, even when it would parse as a number
+    assert_eq!(layered["db"]["pass"], json!("1234"));
+    assert_eq!(
+        layered["risk"]["first_payment"],
+        json!({"min_amount": "500"})
+    );
+}
+
+#[test]
+fn env_overrides_report_every_variable_that_cannot_be_applied() {
+    let mut layered = defaults();
+    let errors = apply_env_overrides(
+        &mut layered,
+        vars(&[
+            ("QRPAY_API__PORT", "eighty"),
+            ("QRPAY_DB__MIGRATE_ON_STARTUP", "yes"),
+            ("QRPAY_API__UNKNOWN", "1"),
+            ("QRPAY_API____PORT", "1"),
+        ]),
+    );
+
+    assert_eq!(
+        errors,
+        [
+            "QRPAY_API__PORT must be a number",
+            "QRPAY_DB__MIGRATE_ON_STARTUP must be true or false",
+            "QRPAY_API__UNKNOWN does not match any configuration field",
+            "QRPAY_API____PORT is not a valid configuration variable",
+        ]
+    );
+    assert_eq!(layered["api"]["port"], json!(8090));
+}
+
+#[test]
+fn validate_reports_every_invalid_field() {
+    assert!(valid_config().validate().is_empty());
+
+    let mut config = valid_config();
+    config.api.port = 0;
+    config.db.pool_min = 20;
+    config.db.pool_max = 10;
+    config.webhooks.max_attempts = 0;
+
+    assert_eq!(
+        config.validate(),
+        [
+            "api.port must be greater than 0",
+            "db.pool_min cannot be greater than db.pool_max",
+            "webhooks.max_attempts must be greater than 0",
+        ]
+    );
+}
+
+#[test]
+fn durations_are_bounded_so_date_arithmetic_cannot_overflow() {
+    let mut config = valid_config();
+    config.risk.history_days = u32::MAX;
+    config.risk.velocity = Some(VelocityRuleConfig {
+        window_secs: i64::MAX,
+        max_count: 1,
+        decision: RiskDecision::Review,
+    });
+    config.transactions.ttl_secs = u64::MAX;
+    config.fx.quote_ttl_secs = u64::MAX;
+
+    assert_eq!(
+        config.validate(),
+        [
+            "risk.history_days cannot be greater than 3650",
+            "risk.velocity.window_secs cannot be greater than 2592000",
+            "transactions.ttl_secs cannot be greater than 604800",
+            "fx.quote_ttl_secs cannot be greater than 86400",
+        ]
+    );
+}
+
+#[test]
+fn mysql_requires_a_configured_user() {
+    assert_eq!(DbConfig::default().user, "");
+    assert!(ConfigInner::default()
+        .validate()
+        .contains(&String::from("db.user cannot be empty")));
+}
+
+#[test]
+fn load_reports_the_invalid_fields_of_the_file_in_a_single_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    fs::write(
+        &path,
+        r#"{"api": {"workers": 0}, "db": {"user": "qr_payments", "pool_max": 0}}"#,
+    )
+    .unwrap();
+
+    let error = ConfigInner::load(path.to_str()).unwrap_err();
+    let message = error.error.error_content;
+    assert!(
+        message.contains("api.workers must be greater than 0"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("db.pool_max must be greater than 0"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn secrets_are_redacted_unless_empty() {
+    let mut config = valid_config();
+    config.db.pass = String::from("s3cr3t");
+    let redacted = serde_json::to_value(&config).unwrap();
+    assert_eq!(redacted["db"]["pass"], json!("********"));
+
+    config.db.pass.clear();
+    let redacted = serde_json::to_value(&config).unwrap();
+    assert_eq!(redacted["db"]["pass"], json!(""));
+}
+
+#[test]
+fn changed_fields_are_reported_as_dotted_paths() {
+    let current = valid_config();
+    assert!(current.changed_fields(&current.clone()).unwrap().is_empty());
+
+    let mut other = current.clone();
+    other.api.port = 9000;
+    other.transactions.ttl_secs = 60;
+    other.risk.velocity = Some(VelocityRuleConfig {
+        window_secs: 60,
+        max_count: 3,
+        decision: RiskDecision::review(),
+    });
+
+    //  A section that is enabled is reported once, not field by field
+    assert_eq!(
+        current.changed_fields(&other).unwrap(),
+        ["api.port", "risk.velocity", "transactions.ttl_secs"]
+    );
+}
+
+/// Configuration loaded from a file in a temp dir, which the test rewrites before reloading
+fn config_file(content: &str) -> (tempfile::TempDir, Config) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    fs::write(&path, content).unwrap();
+    let config = Config {
+        inner: RwLock::new(ConfigInner::load(path.to_str()).unwrap()),
+        path: path.to_str().map(String::from),
+    };
+    (dir, config)
+}
+
+#[tokio::test]
+async fn reload_applies_the_reloadable_sections_and_keeps_the_rest_until_restarted() {
+    let (dir, config) = config_file(r#"{"db": {"user": "qr_payments"}}"#);
+    fs::write(
+        dir.path().join("config.json"),
+        r#"{"db": {"user": "qr_payments", "pool_max": 50}, "api": {"port": 9000}, "transactions": {"ttl_secs": 60}}"#,
+    )
+    .unwrap();
+
+    let report = config.reload_from_path().await.unwrap();
+    assert_eq!(report.applied, ["transactions.ttl_secs"]);
+    assert_eq!(report.restart_required, ["api.port", "db.pool_max"]);
+
+    let inner = config.inner.read().await;
+    assert_eq!(inner.transactions.ttl_secs, 60);
+    assert_eq!(inner.api.port, 8090);
+    assert_eq!(inner.db.pool_max, 100);
+}
+
+#[tokio::test]
+async fn invalid_reloads_keep_the_current_configuration() {
+    let (dir, config) = config_file(r#"{"db": {"user": "qr_payments"}}"#);
+    fs::write(
+        dir.path().join("config.json"),
+        r#"{"db": {"user": "qr_payments"}, "transactions": {"ttl_secs": 0}}"#,
+    )
+    .unwrap();
+
+    let error = config.reload_from_path().await.unwrap_err();
+    assert!(error
+        .error
+        .error_content
+        .contains("transactions.ttl_secs must be greater than 0"));
+    assert_ne!(config.inner.read().await.transactions.ttl_secs, 0);
+}
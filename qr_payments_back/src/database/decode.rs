@@ -0,0 +1,137 @@
+use mysql::{prelude::FromValue, Row, Value};
+
+#[cfg(test)]
+mod tests;
+
+/// Reason a MySQL row could not be decoded into one of the crate types, naming the table and column
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowError {
+    MissingColumn {
+        table: &'static str,
+        column: &'static str,
+    },
+    UnexpectedNull {
+        table: &'static str,
+        column: &'static str,
+        expected: &'static str,
+    },
+    InvalidValue {
+        table: &'static str,
+        column: &'static str,
+        expected: &'static str,
+        found: String,
+    },
+    UnknownVariant {
+        table: &'static str,
+        column: &'static str,
+        value: String,
+    },
+}
+
+impl std::fmt::Display for RowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingColumn { table, column } => {
+                write!(f, "Unknown column {} in table {}", column, table)
+            }
+            Self::UnexpectedNull {
+                table,
+                column,
+                expected,
+            } => write!(
+                f,
+                "Column {} in table {} is NULL, expected {}",
+                column, table, expected
+            ),
+            Self::InvalidValue {
+                table,
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Column {} in table {} holds {}, expected {}",
+                column, table, found, expected
+            ),
+            Self::UnknownVariant {
+                table,
+                column,
+                value,
+            } => write!(
+                f,
+                "Column {} in table {} holds unknown value '{}'",
+                column, table, value
+            ),
+        }
+    }
+}
+
+/// Types that can be decoded from a MySQL row without panicking
+pub trait DecodeRow: Sized {
+    fn decode(row: &Row) -> Result<Self, RowError>;
+}
+
+/// ## Description
+/// Reads a column of the row, converted to the requested type. Nullable columns must be requested as an
+/// `Option`, otherwise NULL is an error
+///
+/// ### Parameters
+/// - row: the row being decoded
+/// - table: table name in database, for the error
+/// - column: column name in database table
+/// - expected: name of the requested type, for the error
+pub fn column<T: FromValue>(
+    row: &Row,
+    table: &'static str,
+    column: &'static str,
+    expected: &'static str,
+) -> Result<T, RowError> {
+    let value = row
+        .columns_ref()
+        .iter()
+        .position(|candidate| candidate.name_str() == column)
+        .and_then(|index| row.as_ref(index))
+        .ok_or(RowError::MissingColumn { table, column })?;
+
+    mysql::from_value_opt::<T>(value.clone()).map_err(|_| match value {
+        Value::NULL => RowError::UnexpectedNull {
+            table,
+            column,
+            expected,
+        },
+        value => RowError::InvalidValue {
+            table,
+            column,
+            expected,
+            found: value.as_sql(true),
+        },
+    })
+}
+
+/// ## Description
+/// Reads a text column holding the name of an enum variant, parsed with the enum's `from_string`
+pub fn variant<T>(
+    row: &Row,
+    table: &'static str,
+    column_name: &'static str,
+    from_string: fn(String) -> Option<T>,
+) -> Result<T, RowError> {
+    let value = column::<String>(row, table, column_name, "String")?;
+    from_string(value.clone()).ok_or(RowError::UnknownVariant {
+        table,
+        column: column_name,
+        value,
+    })
+}
+
+/// ## Description
+/// Decodes every row of a result set, failing on the first row that can't be decoded
+pub fn rows<T: DecodeRow>(rows: Vec<Row>) -> Result<Vec<T>, RowError> {
+    rows.iter().map(T::decode).collect()
+}
+
+/// ## Description
+/// Decodes the row of a single row query, if any
+pub fn first<T: DecodeRow>(row: Option<Row>) -> Result<Option<T>, RowError> {
+    row.as_ref().map(T::decode).transpose()
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use mysql::{consts::ColumnType, prelude::FromRow, Column, Row, Value};
+use mysql_common::row::new_row;
+use rust_decimal::Decimal;
+
+use crate::modules::{transactions::Transaction, wallets::Wallet};
+
+use super::{DecodeRow, RowError};
+
+fn row(values: Vec<(&str, Value)>) -> Row {
+    let columns = values
+        .iter()
+        .map(|(name, _)| Column::new(ColumnType::MYSQL_TYPE_VAR_STRING).with_name(name.as_bytes()))
+        .collect::<Arc<[Column]>>();
+    new_row(
+        values.into_iter().map(|(_, value)| value).collect(),
+        columns,
+    )
+}
+
+fn wallet_row() -> Vec<(&'static str, Value)> {
+    vec![
+        ("ID", Value::Int(1)),
+        ("currency", Value::Bytes(b"EUR".to_vec())),
+        ("balance", Value::Bytes(b"100.50".to_vec())),
+        ("max_single_amount", Value::NULL),
+        ("max_daily_amount", Value::Bytes(b"50.00".to_vec())),
+        ("max_monthly_amount", Value::NULL),
+        ("max_daily_count", Value::NULL),
+        ("frozen", Value::Int(0)),
+    ]
+}
+
+fn transaction_row() -> Vec<(&'static str, Value)> {
+    vec![
+        ("ID", Value::Int(7)),
+        ("wallets_ID", Value::NULL),
+        ("merchants_ID", Value::NULL),
+        ("amount", Value::Bytes(b"-10.00".to_vec())),
+        ("currency", Value::Bytes(b"USD".to_vec())),
+        ("fx_quote", Value::NULL),
+        ("fx_rate", Value::NULL),
+        ("converted_amount", Value::NULL),
+        ("converted_currency", Value::NULL),
+        ("status", Value::Bytes(b"Confirmed".to_vec())),
+        ("token", Value::NULL),
+        ("errors", Value::NULL),
+        ("created_at", Value::Date(2026, 10, 19, 10, 0, 0, 0)),
+        ("reversed_at", Value::NULL),
+    ]
+}
+
+fn replace(
+    mut values: Vec<(&'static str, Value)>,
+    column: &str,
+    value: Value,
+) -> Vec<(&'static str, Value)> {
+    values
+        .iter_mut()
+        .filter(|(name, _)| *name == column)
+        .for_each(|(_, current)| *current = value.clone());
+    values
+}
+
+#[test]
+fn decodes_complete_rows_with_nullable_columns() {
+    let wallet = Wallet::decode(&row(wallet_row())).expect("wallet row should decode");
+    assert_eq!(wallet.currency.code(), "EUR");
+    assert_eq!(wallet.balance, Decimal::new(10050, 2));
+    assert_eq!(wallet.limits.max_single_amount, None);
+    assert_eq!(wallet.limits.max_daily_amount, Some(Decimal::new(5000, 2)));
+    assert!(!wallet.frozen);
+
+    let transaction =
+        Transaction::decode(&row(transaction_row())).expect("transaction row should decode");
+    let transaction = serde_json::to_value(transaction).unwrap();
+    assert_eq!(transaction["status"], "Confirmed");
+    assert_eq!(transaction["wallets_id"], serde_json::Value::Null);
+    assert_eq!(transaction["conversion"], serde_json::Value::Null);
+
+    let mut values = transaction_row();
+    for (column, value) in [
+        ("fx_quote", "Q7xWm2Lr9TcYp4Kb8NvDs3Hf6Jg1Ze5A"),
+        ("fx_rate", "0.9200000000"),
+        ("converted_amount", "-9.20"),
+        ("converted_currency", "EUR"),
+    ] {
+        values = replace(values, column, Value::Bytes(value.as_bytes().to_vec()));
+    }
+    let transaction = Transaction::decode(&row(values)).expect("converted row should decode");
+    let transaction = serde_json::to_value(transaction).unwrap();
+    assert_eq!(transaction["conversion"]["currency"], "EUR");
+    assert_eq!(transaction["conversion"]["amount"], "-9.20");
+}
+
+#[test]
+fn missing_column_names_table_and_column() {
+    let mut values = wallet_row();
+    values.retain(|(name, _)| *name != "frozen");
+
+    assert_eq!(
+        Wallet::decode(&row(values)).unwrap_err(),
+        RowError::MissingColumn {
+            table: "wallets",
+            column: "frozen",
+        }
+    );
+}
+
+#[test]
+fn null_in_non_nullable_column_names_expected_type() {
+    let values = replace(transaction_row(), "amount", Value::NULL);
+
+    assert_eq!(
+        Transaction::decode(&row(values)).unwrap_err(),
+        RowError::UnexpectedNull {
+            table: "transactions",
+            column: "amount",
+            expected: "Decimal",
+        }
+    );
+}
+
+#[test]
+fn unconvertible_value_names_expected_type() {
+    let values = replace(wallet_row(), "balance", Value::Bytes(b"lots".to_vec()));
+
+    assert_eq!(
+        Wallet::decode(&row(values)).unwrap_err(),
+        RowError::InvalidValue {
+            table: "wallets",
+            column: "balance",
+            expected: "Decimal",
+            found: String::from("'lots'"),
+        }
+    );
+}
+
+#[test]
+fn unknown_enum_value_is_an_error() {
+    let values = replace(
+        transaction_row(),
+        "status",
+        Value::Bytes(b"Settled".to_vec()),
+    );
+
+    assert_eq!(
+        Transaction::decode(&row(values)).unwrap_err(),
+        RowError::UnknownVariant {
+            table: "transactions",
+            column: "status",
+            value: String::from("Settled"),
+        }
+    );
+
+    let values = replace(wallet_row(), "currency", Value::Bytes(b"XAU".to_vec()));
+    assert_eq!(
+        Wallet::decode(&row(values)).unwrap_err(),
+        RowError::UnknownVariant {
+            table: "wallets",
+            column: "currency",
+            value: String::from("XAU"),
+        }
+    );
+}
+
+#[test]
+fn from_row_opt_returns_the_row_instead_of_panicking() {
+    let mut values = transaction_row();
+    values.retain(|(name, _)| *name != "status");
+    assert!(Transaction::from_row_opt(row(values)).is_err());
+
+    let values = replace(wallet_row(), "frozen", Value::NULL);
+    assert!(Wallet::from_row_opt(row(values)).is_err());
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use error_mapper::{create_new_error, TheResult};
+use rust_decimal::Decimal;
+
+use crate::{
+    datatypes::{
+        MerchantsIdType, SettlementBatchesIdType, TransactionsIdType, WalletsIdType,
+        WebhookDeliveriesIdType, WebhookSubscriptionsIdType,
+    },
+    modules::{
+        currencies::Currency,
+        fees::{Fee, FeeSchedule},
+        fx::{Quote, RateTable},
+        merchants::Merchant,
+        settlements::{SettlementBatch, SettlementLine},
+        transactions::Transaction,
+        wallets::{Wallet, WalletBalance},
+        webhooks::{WebhookDelivery, WebhookSubscription},
+    },
+};
+
+/// In-memory implementation of the repositories. Every operation runs while holding a single lock over
+/// all the tables, so compound operations are as atomic as their MySQL DB transactions
+#[derive(Default)]
+pub struct InMemoryStore {
+    state: Mutex<InMemoryState>,
+    /// Blocking delay applied before every operation, to stand in for a DB round trip in benchmarks
+    latency: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct InMemoryState {
+    pub wallets: BTreeMap<WalletsIdType, Wallet>,
+    /// Balances held in other currencies than their wallet's own
+    pub held_balances: BTreeMap<(WalletsIdType, Currency), Decimal>,
+    pub merchants: BTreeMap<MerchantsIdType, Merchant>,
+    pub transactions: BTreeMap<TransactionsIdType, Transaction>,
+    /// Fee ledger, as the lines of every transaction in insertion order
+    pub fees: Vec<(TransactionsIdType, Fee)>,
+    pub fee_schedules: Vec<FeeSchedule>,
+    pub settlement_batches: BTreeMap<SettlementBatchesIdType, SettlementBatch>,
+    /// Lines of every settlement batch in insertion order
+    pub settlement_lines: Vec<(SettlementBatchesIdType, SettlementLine)>,
+    pub webhook_subscriptions: BTreeMap<WebhookSubscriptionsIdType, WebhookSubscription>,
+    /// Deliveries of the webhooks. Nothing queues them, as the in-memory store records no outbox
+    pub webhook_deliveries: BTreeMap<WebhookDeliveriesIdType, WebhookDelivery>,
+    pub fx_rates: RateTable,
+    /// Open quotes, by ID
+    pub fx_quotes: BTreeMap<String, Quote>,
+    last_transactions_id: TransactionsIdType,
+}
+
+impl InMemoryStore {
+    pub fn new(wallets: Vec<Wallet>) -> Self {
+        let state = InMemoryState {
+            wallets: wallets
+                .into_iter()
+                .map(|wallet| (wallet.id, wallet))
+                .collect(),
+            ..Default::default()
+        };
+
+        Self {
+            state: Mutex::new(state),
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// ## Description
+    /// Opens balances in other currencies than their wallet's own
+    pub fn with_held_balances(mut self, balances: Vec<(WalletsIdType, WalletBalance)>) -> Self {
+        if let Ok(state) = self.state.get_mut() {
+            for (wallets_id, balance) in balances {
+                state
+                    .held_balances
+                    .insert((wallets_id, balance.currency), balance.balance);
+            }
+        }
+        self
+    }
+
+    /// ## Description
+    /// Makes every operation block the calling thread for the received latency before running
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub(crate) fn lock(&self) -> TheResult<MutexGuard<'_, InMemoryState>> {
+        if !self.latency.is_zero() {
+            std::thread::sleep(self.latency);
+        }
+        self.state
+            .lock()
+            .map_err(|error| create_new_error!(format!("In-memory store is poisoned: {}", error)))
+    }
+}
+
+impl InMemoryState {
+    /// Next auto-incremented wallet ID, as the wallets can be seeded with any IDs
+    pub fn next_wallets_id(&self) -> WalletsIdType {
+        self.wallets
+            .keys()
+            .last()
+            .map_or(1, |wallets_id| wallets_id + 1)
+    }
+
+    /// Next auto-incremented merchant ID. Merchants are never deleted, so the last one has the highest ID
+    pub fn next_merchants_id(&self) -> MerchantsIdType {
+        self.merchants.len() as MerchantsIdType + 1
+    }
+
+    /// Next auto-incremented settlement batch ID. Batches are never deleted either
+    pub fn next_settlement_batches_id(&self) -> SettlementBatchesIdType {
+        self.settlement_batches.len() as SettlementBatchesIdType + 1
+    }
+
+    /// Reserves the next auto-incremented transaction ID
+    pub fn next_transactions_id(&mut self) -> TransactionsIdType {
+        self.last_transactions_id += 1;
+        self.last_transactions_id
+    }
+}
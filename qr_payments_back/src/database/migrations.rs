@@ -0,0 +1,495 @@
+use std::{collections::BTreeMap, fmt, fs};
+
+use chrono::NaiveDateTime;
+use clap::Subcommand;
+use error_mapper::{create_new_error, TheResult};
+use mysql::{params, prelude::Queryable};
+use sha2::{Digest, Sha256};
+
+use crate::DATETIME_FORMAT;
+
+use super::DbConn;
+
+/// Default directory holding the migrations, as `{version}_{name}.up.sql` with an optional
+/// `{version}_{name}.down.sql`. Anchored to the crate the binary was built from, `db.migrations_dir`
+/// points elsewhere in deployments
+pub const MIGRATIONS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+/// Seconds to wait for another instance to finish migrating the same schema
+const LOCK_TIMEOUT_SECS: u32 = 60;
+/// Columns of the tables created by the first migration, as `(table, column, enum type)`. Its
+/// `CREATE TABLE IF NOT EXISTS` adopts the tables databases created with schema_reset/schema.sql already
+/// hold, so they must have every one of these columns, and enums their exact values
+pub(super) const INITIAL_SCHEMA_COLUMNS: &[(&str, &str, Option<&str>)] = &[
+    ("wallets", "ID", None),
+    ("wallets", "balance", None),
+    ("wallets", "max_single_amount", None),
+    ("wallets", "max_daily_amount", None),
+    ("wallets", "max_monthly_amount", None),
+    ("wallets", "max_daily_count", None),
+    ("transactions", "ID", None),
+    ("transactions", "wallets_ID", None),
+    ("transactions", "amount", None),
+    (
+        "transactions",
+        "status",
+        Some("enum('Initialized','PendingReview','Confirmed','Declined','Cancelled','Refunded','Expired','InternalError','Log')"),
+    ),
+    ("transactions", "token", None),
+    ("transactions", "errors", None),
+    ("transactions", "created_at", None),
+    ("webhook_subscriptions", "ID", None),
+    ("webhook_subscriptions", "wallets_ID", None),
+    ("webhook_subscriptions", "url", None),
+    ("webhook_subscriptions", "secret", None),
+    ("webhook_subscriptions", "active", None),
+    ("webhook_subscriptions", "created_at", None),
+    ("webhook_deliveries", "ID", None),
+    ("webhook_deliveries", "webhook_subscriptions_ID", None),
+    ("webhook_deliveries", "event_type", None),
+    ("webhook_deliveries", "payload", None),
+    (
+        "webhook_deliveries",
+        "status",
+        Some("enum('Pending','Delivered','DeadLetter')"),
+    ),
+    ("webhook_deliveries", "attempts", None),
+    ("webhook_deliveries", "next_attempt_at", None),
+    ("webhook_deliveries", "last_response_status", None),
+    ("webhook_deliveries", "last_error", None),
+    ("webhook_deliveries", "created_at", None),
+    ("webhook_deliveries", "delivered_at", None),
+    ("outbox", "ID", None),
+    ("outbox", "aggregate_type", Some("enum('Transaction','Wallet')")),
+    ("outbox", "aggregate_ID", None),
+    ("outbox", "event_type", None),
+    ("outbox", "payload", None),
+    ("outbox", "created_at", None),
+    ("outbox", "dispatched_at", None),
+];
+
+/// Numbered schema change read from the migrations directory
+pub struct Migration {
+    pub version: u32,
+    pub name: String,
+    up: String,
+    down: Option<String>,
+    /// SHA-256 of the up script, stored when applied so later edits to it are detected
+    pub checksum: String,
+}
+
+/// Row of the `schema_migrations` table
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: NaiveDateTime,
+}
+
+/// ## Description
+/// Reads the migrations from the directory, sorted by version
+pub fn load(dir: &str) -> TheResult<Vec<Migration>> {
+    let entries = fs::read_dir(dir).map_err(|error| {
+        create_new_error!(format!("Could not read migrations from {}: {}", dir, error))
+    })?;
+
+    let mut scripts = BTreeMap::<u32, (String, Option<String>, Option<String>)>::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .path();
+        let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else {
+            continue;
+        };
+        let (stem, is_up) = if let Some(stem) = file_name.strip_suffix(".up.sql") {
+            (stem, true)
+        } else if let Some(stem) = file_name.strip_suffix(".down.sql") {
+            (stem, false)
+        } else {
+            continue;
+        };
+
+        let Some((version, name)) = stem
+            .split_once('_')
+            .and_then(|(version, name)| Some((version.parse::<u32>().ok()?, name)))
+        else {
+            return Err(create_new_error!(format!(
+                "Migration file {} must be named {{version}}_{{name}}.up.sql or .down.sql",
+                file_name
+            )));
+        };
+
+        let script = fs::read_to_string(&path).map_err(|error| {
+            create_new_error!(format!("Could not read migration {}: {}", file_name, error))
+        })?;
+        let (stored_name, up, down) = scripts
+            .entry(version)
+            .or_insert_with(|| (name.to_string(), None, None));
+        if stored_name != name {
+            return Err(create_new_error!(format!(
+                "Migration version {} is used by both {} and {}",
+                version, stored_name, name
+            )));
+        }
+        if is_up {
+            *up = Some(script);
+        } else {
+            *down = Some(script);
+        }
+    }
+
+    scripts
+        .into_iter()
+        .map(|(version, (name, up, down))| {
+            let Some(up) = up else {
+                return Err(create_new_error!(format!(
+                    "Migration {:04}_{} has no up script",
+                    version, name
+                )));
+            };
+            Ok(Migration {
+                version,
+                checksum: hex::encode(Sha256::digest(up.as_bytes())),
+                name,
+                up,
+                down,
+            })
+        })
+        .collect()
+}
+
+/// ## Description
+/// Applies the pending migrations in order, after verifying the applied ones were not modified. Holds the
+/// schema's migrations lock meanwhile, so instances starting together do not run the same DDL twice
+///
+/// ### Returns
+/// The versions applied. MySQL commits DDL implicitly, so a failing migration is not rolled back and
+/// stays pending, with the previous ones kept applied
+pub fn migrate_up(conn: &mut impl Queryable, dir: &str) -> TheResult<Vec<u32>> {
+    let migrations = load(dir)?;
+    with_lock(conn, |conn| apply_pending(conn, &migrations, dir))
+}
+
+fn apply_pending(
+    conn: &mut impl Queryable,
+    migrations: &[Migration],
+    dir: &str,
+) -> TheResult<Vec<u32>> {
+    let applied = select_applied(conn)?;
+    verify(migrations, &applied, dir)?;
+
+    let last_applied = applied.last().map(|migration| migration.version);
+    let mut versions = Vec::new();
+    for migration in migrations.iter().filter(|migration| {
+        !applied
+            .iter()
+            .any(|applied| applied.version == migration.version)
+    }) {
+        if let Some(last_applied) = last_applied.filter(|last| *last > migration.version) {
+            return Err(create_new_error!(format!(
+                "Migration {:04}_{} is pending but the later version {} is already applied",
+                migration.version, migration.name, last_applied
+            )));
+        }
+        if migration.version == 1 {
+            verify_adoptable(conn)?;
+        }
+
+        conn.query_drop(&migration.up).map_err(|error| {
+            create_new_error!(format!(
+                "Migration {:04}_{} failed: {}",
+                migration.version, migration.name, error
+            ))
+        })?;
+        conn.exec_drop(
+            "INSERT INTO `schema_migrations`(`version`, `name`, `checksum`) VALUES(:version, :name, :checksum);",
+            params! {
+                "version" => migration.version,
+                "name" => &migration.name,
+                "checksum" => &migration.checksum,
+            },
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+        versions.push(migration.version);
+    }
+
+    Ok(versions)
+}
+
+/// ## Description
+/// Fails when the database holds tables of the initial schema that do not conform to it, as the ones
+/// created with schema_reset/schema.sql before the migrations may. The first migration would keep them
+/// as they are, and the later ones would fail or the application would on the missing columns
+fn verify_adoptable(conn: &mut impl Queryable) -> TheResult<()> {
+    let existing = conn
+        .query::<(String, String, String), _>(
+            "SELECT `TABLE_NAME`, `COLUMN_NAME`, `COLUMN_TYPE` FROM `information_schema`.`COLUMNS` \
+            WHERE `TABLE_SCHEMA` = DATABASE() AND `TABLE_NAME` IN ('wallets', 'transactions', 'webhook_subscriptions', 'webhook_deliveries', 'outbox');",
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+    let differences = nonconforming_columns(&existing);
+    if !differences.is_empty() {
+        return Err(create_new_error!(format!(
+            "Refusing to adopt the existing tables, which do not match migration 0001_initial_schema: {}. \
+            Recreate the schema with schema_reset/schema.sql, or bring the tables up to that migration by hand",
+            differences.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// ## Description
+/// Lists how the existing tables, given as `(table, column, column type)`, differ from the initial schema.
+/// Tables absent altogether are created by the migration, and extra columns are ignored
+pub(super) fn nonconforming_columns(existing: &[(String, String, String)]) -> Vec<String> {
+    INITIAL_SCHEMA_COLUMNS
+        .iter()
+        .filter(|(table, _, _)| existing.iter().any(|(name, _, _)| name == table))
+        .filter_map(|(table, column, enum_type)| {
+            let found = existing
+                .iter()
+                .find(|(name, found_column, _)| name == table && found_column == column);
+            match (found, enum_type) {
+                (None, _) => Some(format!("`{}`.`{}` is missing", table, column)),
+                (Some((_, _, found_type)), Some(enum_type)) if found_type != enum_type => {
+                    Some(format!(
+                        "`{}`.`{}` is {} instead of {}",
+                        table, column, found_type, enum_type
+                    ))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// ## Description
+/// Reverts the last `steps` applied migrations with their down scripts, latest first, holding the
+/// schema's migrations lock meanwhile
+///
+/// ### Returns
+/// The versions reverted
+pub fn migrate_down(conn: &mut impl Queryable, dir: &str, steps: usize) -> TheResult<Vec<u32>> {
+    let migrations = load(dir)?;
+    with_lock(conn, |conn| revert_applied(conn, &migrations, dir, steps))
+}
+
+fn revert_applied(
+    conn: &mut impl Queryable,
+    migrations: &[Migration],
+    dir: &str,
+    steps: usize,
+) -> TheResult<Vec<u32>> {
+    let applied = select_applied(conn)?;
+    verify(migrations, &applied, dir)?;
+
+    let mut versions = Vec::new();
+    for applied in applied.iter().rev().take(steps) {
+        let Some(migration) = migrations
+            .iter()
+            .find(|migration| migration.version == applied.version)
+        else {
+            continue;
+        };
+        let Some(down) = &migration.down else {
+            return Err(create_new_error!(format!(
+                "Migration {:04}_{} has no down script",
+                migration.version, migration.name
+            )));
+        };
+
+        conn.query_drop(down).map_err(|error| {
+            create_new_error!(format!(
+                "Reverting migration {:04}_{} failed: {}",
+                migration.version, migration.name, error
+            ))
+        })?;
+        conn.exec_drop(
+            "DELETE FROM `schema_migrations` WHERE `version` = :version;",
+            params! { "version" => migration.version },
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+        versions.push(migration.version);
+    }
+
+    Ok(versions)
+}
+
+/// ## Description
+/// Lists every migration along with the time it was applied, None when it's pending
+pub fn status(
+    conn: &mut impl Queryable,
+    dir: &str,
+) -> TheResult<Vec<(Migration, Option<NaiveDateTime>)>> {
+    let migrations = load(dir)?;
+    let applied = select_applied(conn)?;
+    verify(&migrations, &applied, dir)?;
+
+    Ok(migrations
+        .into_iter()
+        .map(|migration| {
+            let applied_at = applied
+                .iter()
+                .find(|applied| applied.version == migration.version)
+                .map(|applied| applied.applied_at);
+            (migration, applied_at)
+        })
+        .collect())
+}
+
+/// ## Description
+/// Reads the applied migrations, creating the tracking table on the first run
+fn select_applied(conn: &mut impl Queryable) -> TheResult<Vec<AppliedMigration>> {
+    conn.query_drop(
+        "CREATE TABLE IF NOT EXISTS `schema_migrations` (
+            `version` INT UNSIGNED PRIMARY KEY,
+            `name` VARCHAR(128) NOT NULL,
+            `checksum` CHAR(64) NOT NULL,
+            `applied_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+    .map_err(|error| create_new_error!(error.to_string()))?;
+
+    conn.query_map(
+        "SELECT `version`, `name`, `checksum`, `applied_at` FROM `schema_migrations` ORDER BY `version`;",
+        |(version, name, checksum, applied_at)| AppliedMigration {
+            version,
+            name,
+            checksum,
+            applied_at,
+        },
+    )
+    .map_err(|error| create_new_error!(error.to_string()))
+}
+
+/// ## Description
+/// Runs the operation while holding the migrations lock of the connection's schema, waiting up to
+/// `LOCK_TIMEOUT_SECS` for another instance to release it. The lock is released whatever the outcome
+fn with_lock<Q: Queryable, T>(
+    conn: &mut Q,
+    operation: impl FnOnce(&mut Q) -> TheResult<T>,
+) -> TheResult<T> {
+    let locked = conn
+        .exec_first::<Option<u8>, _, _>(
+            "SELECT GET_LOCK(CONCAT(DATABASE(), '.migrations'), ?);",
+            (LOCK_TIMEOUT_SECS,),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?
+        .flatten();
+    if locked != Some(1) {
+        return Err(create_new_error!(format!(
+            "Could not take the migrations lock within {} seconds, another instance is migrating",
+            LOCK_TIMEOUT_SECS
+        )));
+    }
+
+    let result = operation(conn);
+    let released = conn
+        .query_drop("DO RELEASE_LOCK(CONCAT(DATABASE(), '.migrations'));")
+        .map_err(|error| create_new_error!(error.to_string()));
+
+    let result = result?;
+    released?;
+    Ok(result)
+}
+
+/// ## Description
+/// Fails when an applied migration was removed from the directory or its up script changed since
+pub(super) fn verify(
+    migrations: &[Migration],
+    applied: &[AppliedMigration],
+    dir: &str,
+) -> TheResult<()> {
+    for applied in applied {
+        let Some(migration) = migrations
+            .iter()
+            .find(|migration| migration.version == applied.version)
+        else {
+            return Err(create_new_error!(format!(
+                "Applied migration {:04}_{} is missing from {}",
+                applied.version, applied.name, dir
+            )));
+        };
+
+        if migration.checksum != applied.checksum {
+            return Err(create_new_error!(format!(
+                "Migration {:04}_{} was modified after being applied, checksum {} does not match {}",
+                migration.version, migration.name, migration.checksum, applied.checksum
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Subcommands of `migrate`
+#[derive(Subcommand, Debug, Clone, Copy)]
+pub enum MigrateCommand {
+    /// Applies the pending migrations
+    Up,
+    /// Reverts the last applied migrations
+    Down {
+        #[arg(default_value_t = 1)]
+        steps: usize,
+    },
+    /// Lists every migration and when it was applied
+    Status,
+}
+
+/// Outcome of a `migrate` subcommand, displayed as the lines the binaries print
+pub enum MigrateOutcome {
+    Applied(Vec<u32>),
+    Reverted(Vec<u32>),
+    Status(Vec<(Migration, Option<NaiveDateTime>)>),
+}
+
+/// ## Description
+/// Runs a `migrate` subcommand on the migrations of the directory. The Db Pool must be initialized
+pub async fn run_command(command: MigrateCommand, dir: String) -> TheResult<MigrateOutcome> {
+    match command {
+        MigrateCommand::Up => DbConn::run(move |conn| migrate_up(conn, &dir))
+            .await
+            .map(MigrateOutcome::Applied),
+        MigrateCommand::Down { steps } => DbConn::run(move |conn| migrate_down(conn, &dir, steps))
+            .await
+            .map(MigrateOutcome::Reverted),
+        MigrateCommand::Status => DbConn::run(move |conn| status(conn, &dir))
+            .await
+            .map(MigrateOutcome::Status),
+    }
+}
+
+impl fmt::Display for MigrateOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Applied(versions) => {
+                write!(f, "Applied {} migration(s): {:?}", versions.len(), versions)
+            }
+            Self::Reverted(versions) => {
+                write!(
+                    f,
+                    "Reverted {} migration(s): {:?}",
+                    versions.len(),
+                    versions
+                )
+            }
+            Self::Status(migrations) => {
+                for (index, (migration, applied_at)) in migrations.iter().enumerate() {
+                    let applied_at = applied_at
+                        .map(|applied_at| applied_at.format(DATETIME_FORMAT).to_string())
+                        .unwrap_or_else(|| String::from("pending"));
+                    if index > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(
+                        f,
+                        "{:04}_{:<40} {}",
+                        migration.version, migration.name, applied_at
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
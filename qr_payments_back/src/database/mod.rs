@@ -1,47 +1,389 @@
-use std::sync::OnceLock;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
+use actix_web::web;
 use error_mapper::{create_new_error, TheResult};
-use mysql::{Pool, PooledConn};
+use mysql::{
+    prelude::Queryable, Conn, Opts, OptsBuilder, Pool, PoolConstraints, PoolOpts, PooledConn,
+    Transaction, TxOpts,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
 
-use crate::config::Config;
+use crate::{
+    config::{Config, DbConfig, DbDriver},
+    logging, metrics,
+    modules::{
+        fees::repository::FeeRepository, fx::repository::FxRepository,
+        merchants::repository::MerchantRepository, outbox::repository::OutboxRepository,
+        settlements::repository::SettlementRepository,
+        transactions::repository::TransactionRepository, wallets::repository::WalletRepository,
+        webhooks::repository::WebhookRepository,
+    },
+};
+
+pub mod decode;
+#[cfg(test)]
+pub mod memory;
+pub mod migrations;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(test)]
+mod tests;
 
 static MYSQL: OnceLock<DbConn> = OnceLock::new();
+static POOL_COUNTERS: PoolCounters = PoolCounters {
+    acquired: AtomicU64::new(0),
+    failed: AtomicU64::new(0),
+    wait_micros: AtomicU64::new(0),
+};
 
 pub struct DbConn {
     pool: Pool,
+    acquire_timeout: Duration,
+    pool_min: usize,
+    pool_max: usize,
+}
+
+/// Connection acquisitions from the MySQL pool since startup, counted for every repository and task
+struct PoolCounters {
+    acquired: AtomicU64,
+    failed: AtomicU64,
+    wait_micros: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolStats {
+    pub min: usize,
+    pub max: usize,
+    pub acquired: u64,
+    /// Acquisitions that timed out or could not open a connection
+    pub failed: u64,
+    pub average_wait_ms: f64,
+}
+
+/// MySQL implementation of the repositories, backed by the connection pool
+pub struct MySqlStore {
+    pool: Pool,
+    acquire_timeout: Duration,
+}
+
+/// Repositories injected into the API handlers and background tasks
+#[derive(Clone)]
+pub struct Repositories {
+    pub wallets: Arc<dyn WalletRepository>,
+    pub transactions: Arc<dyn TransactionRepository>,
+    pub merchants: Arc<dyn MerchantRepository>,
+    pub settlements: Arc<dyn SettlementRepository>,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub fx: Arc<dyn FxRepository>,
+    pub fees: Arc<dyn FeeRepository>,
+    pub outbox: Arc<dyn OutboxRepository>,
 }
 
 impl DbConn {
     pub async fn init_connection() -> TheResult<()> {
         let db_config = Config::get_db_config().await?;
-        let conn_string = db_config.build_conn_string();
+        let opts = Opts::from_url(&db_config.build_conn_string())
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let constraints =
+            PoolConstraints::new(db_config.pool_min, db_config.pool_max).ok_or_else(|| {
+                create_new_error!(format!(
+                    "Invalid Db Pool size, min: {} is greater than max: {}",
+                    db_config.pool_min, db_config.pool_max
+                ))
+            })?;
+        let io_timeout = Some(Duration::from_secs(db_config.io_timeout_secs));
+        let opts = OptsBuilder::from_opts(opts)
+            .pool_opts(PoolOpts::default().with_constraints(constraints))
+            .tcp_connect_timeout(Some(Duration::from_secs(db_config.connect_timeout_secs)))
+            .read_timeout(io_timeout)
+            .write_timeout(io_timeout);
 
-        let pool = Pool::new(conn_string.as_str())
+        //  Opening the pool connects its first connections, so it's kept off the async workers too
+        let pool = web::block(move || Pool::new(opts))
+            .await
+            .map_err(|error| create_new_error!(error.to_string()))?
             .map_err(|error| create_new_error!(error.to_string()))?;
-        MYSQL.get_or_init(|| DbConn { pool });
+        MYSQL.get_or_init(|| DbConn {
+            pool,
+            acquire_timeout: Duration::from_secs(db_config.acquire_timeout_secs),
+            pool_min: db_config.pool_min,
+            pool_max: db_config.pool_max,
+        });
 
         Ok(())
     }
-    pub async fn get_conn() -> TheResult<PooledConn> {
+    pub fn is_initialized() -> bool {
+        MYSQL.get().is_some()
+    }
+
+    /// ## Description
+    /// Round trip to the server on a pooled connection, failing when none is available in time
+    ///
+    /// ### Returns
+    /// The time taken, connection acquisition included
+    pub async fn ping() -> TheResult<Duration> {
+        let started = Instant::now();
+        Self::run(|conn| {
+            conn.query_drop("SELECT 1;")
+                .map_err(|error| create_new_error!(error.to_string()))
+        })
+        .await?;
+
+        Ok(started.elapsed())
+    }
+
+    /// ## Description
+    /// Pool size and connection acquisitions since startup. None until the pool is initialized
+    pub fn pool_stats() -> Option<PoolStats> {
+        let db_conn = MYSQL.get()?;
+        let acquired = POOL_COUNTERS.acquired.load(Ordering::Relaxed);
+        let failed = POOL_COUNTERS.failed.load(Ordering::Relaxed);
+        let wait_micros = POOL_COUNTERS.wait_micros.load(Ordering::Relaxed);
+        let attempts = acquired + failed;
+
+        Some(PoolStats {
+            min: db_conn.pool_min,
+            max: db_conn.pool_max,
+            acquired,
+            failed,
+            average_wait_ms: match attempts {
+                0 => 0.0,
+                attempts => wait_micros as f64 / attempts as f64 / 1000.0,
+            },
+        })
+    }
+
+    /// ## Description
+    /// Runs a blocking operation on a pooled connection within the blocking thread pool, so queries never
+    /// stall the async workers. Fails when no connection is released within `acquire_timeout_secs`
+    pub async fn run<T: Send + 'static>(
+        operation: impl FnOnce(&mut PooledConn) -> TheResult<T> + Send + 'static,
+    ) -> TheResult<T> {
         let db_conn = MYSQL
             .get()
             .ok_or_else(|| create_new_error!("Failed to get Db Connection from Db Pool"))?;
-        db_conn
-            .pool
-            .get_conn()
-            .map_err(|error| create_new_error!(error.to_string()))
+        let pool = db_conn.pool.clone();
+        let acquire_timeout = db_conn.acquire_timeout;
+
+        web::block(logging::propagate(move || {
+            let mut conn = acquire(&pool, acquire_timeout)?;
+            operation(&mut conn)
+        }))
+        .await
+        .map_err(|error| create_new_error!(error.to_string()))?
+    }
+}
+
+impl MySqlStore {
+    pub fn new(pool: Pool, acquire_timeout: Duration) -> Self {
+        Self {
+            pool,
+            acquire_timeout,
+        }
+    }
+    pub(crate) fn get_conn(&self) -> TheResult<PooledConn> {
+        acquire(&self.pool, self.acquire_timeout)
+    }
+}
+
+impl Repositories {
+    /// ## Description
+    /// Builds the repositories for the driver selected in the configurations, initializing its connection
+    pub async fn init(db_config: &DbConfig) -> TheResult<Self> {
+        match db_config.driver {
+            DbDriver::MySql => {
+                DbConn::init_connection().await?;
+                Self::mysql()
+            }
+            #[cfg(feature = "sqlite")]
+            DbDriver::Sqlite => Ok(Self::sqlite(sqlite::SqliteStore::open(
+                &db_config.sqlite_path,
+            )?)),
+            #[cfg(not(feature = "sqlite"))]
+            DbDriver::Sqlite => Err(create_new_error!(format!(
+                "Cannot open {}, the SQLite driver requires building with the sqlite feature",
+                db_config.sqlite_path
+            ))),
+        }
+    }
+
+    /// ## Description
+    /// Builds the repositories on top of the MySQL connection pool
+    pub fn mysql() -> TheResult<Self> {
+        let db_conn = MYSQL
+            .get()
+            .ok_or_else(|| create_new_error!("Failed to get Db Pool"))?;
+        let store = Arc::new(MySqlStore::new(
+            db_conn.pool.clone(),
+            db_conn.acquire_timeout,
+        ));
+        Ok(Self {
+            wallets: store.clone(),
+            transactions: store.clone(),
+            merchants: store.clone(),
+            settlements: store.clone(),
+            webhooks: store.clone(),
+            fx: store.clone(),
+            fees: store.clone(),
+            outbox: store,
+        })
+    }
+
+    #[cfg(feature = "sqlite")]
+    /// ## Description
+    /// Builds the repositories on top of a single SQLite connection
+    pub fn sqlite(store: sqlite::SqliteStore) -> Self {
+        let store = Arc::new(store);
+        Self {
+            wallets: store.clone(),
+            transactions: store.clone(),
+            merchants: store.clone(),
+            settlements: store.clone(),
+            webhooks: store.clone(),
+            fx: store.clone(),
+            fees: store.clone(),
+            outbox: store,
+        }
+    }
+
+    #[cfg(test)]
+    /// ## Description
+    /// Builds the repositories on top of a single in-memory store, so operations across every table stay
+    /// atomic
+    pub fn in_memory(store: memory::InMemoryStore) -> Self {
+        let store = Arc::new(store);
+        Self {
+            wallets: store.clone(),
+            transactions: store.clone(),
+            merchants: store.clone(),
+            settlements: store.clone(),
+            webhooks: store.clone(),
+            fx: store.clone(),
+            fees: store.clone(),
+            outbox: store,
+        }
     }
 }
 
-#[macro_export]
-macro_rules! get_conn_or_internal_error {
-    ($logger: expr) => {
-        match $crate::database::DbConn::get_conn().await {
-            Ok(conn) => conn,
-            Err(error) => {
-                the_logger::log_error!($logger, "Could not get db connection: {}", error);
-                return HttpResponse::InternalServerError().finish();
+/// ## Description
+/// Takes a connection from the pool, waiting up to `timeout` for one to be released, and counts the wait
+/// in the pool statistics
+fn acquire(pool: &Pool, timeout: Duration) -> TheResult<PooledConn> {
+    let started = Instant::now();
+    let conn = pool.try_get_conn(timeout);
+    let waited = started.elapsed();
+    POOL_COUNTERS
+        .wait_micros
+        .fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+    metrics::DB_POOL_WAIT.observe(waited.as_secs_f64());
+
+    match conn {
+        Ok(conn) => {
+            POOL_COUNTERS.acquired.fetch_add(1, Ordering::Relaxed);
+            Ok(conn)
+        }
+        Err(error) => {
+            POOL_COUNTERS.failed.fetch_add(1, Ordering::Relaxed);
+            Err(create_new_error!(error.to_string()))
+        }
+    }
+}
+
+/// ## Description
+/// Runs an operation inside a DB transaction, committing it when the operation succeeds and rolling it
+/// back otherwise
+pub fn in_transaction<T>(
+    conn: &mut PooledConn,
+    operation: impl FnOnce(&mut Transaction<'_>) -> TheResult<T>,
+) -> TheResult<T> {
+    in_transaction_if(conn, operation, |_| true)
+}
+
+/// ## Description
+/// Runs an operation inside a DB transaction as `in_transaction` does, committing it only if `commit`
+/// accepts its result. Operations writing before the check that decides their outcome roll back that way
+/// without failing
+pub fn in_transaction_if<T>(
+    conn: &mut PooledConn,
+    operation: impl FnOnce(&mut Transaction<'_>) -> TheResult<T>,
+    commit: impl FnOnce(&T) -> bool,
+) -> TheResult<T> {
+    let mut db_transaction = conn
+        .start_transaction(TxOpts::default())
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+    match operation(&mut db_transaction) {
+        Ok(result) if commit(&result) => {
+            db_transaction
+                .commit()
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            Ok(result)
+        }
+        Ok(result) => {
+            db_transaction.rollback().map_err(|error| {
+                metrics::ROLLBACK_FAILURES.inc();
+                create_new_error!(error.to_string())
+            })?;
+            Ok(result)
+        }
+        Err(error) => {
+            if let Err(rollback_error) = db_transaction.rollback() {
+                metrics::ROLLBACK_FAILURES.inc();
+                return Err(create_new_error!(format!(
+                    "{}. Rollback also failed: {}",
+                    error, rollback_error
+                )));
             }
+            Err(error)
         }
-    };
+    }
+}
+
+/// Connection able to run write statements, either a pooled connection or an open DB transaction, so the
+/// same db function can run on its own or as part of a larger atomic operation
+pub trait Executor: Queryable {
+    fn last_insert_id(&self) -> u64;
+    fn affected_rows(&self) -> u64;
+}
+
+impl Executor for PooledConn {
+    fn last_insert_id(&self) -> u64 {
+        Conn::last_insert_id(self)
+    }
+    fn affected_rows(&self) -> u64 {
+        Conn::affected_rows(self)
+    }
+}
+
+impl Executor for Transaction<'_> {
+    fn last_insert_id(&self) -> u64 {
+        Transaction::last_insert_id(self).unwrap_or_default()
+    }
+    fn affected_rows(&self) -> u64 {
+        Transaction::affected_rows(self)
+    }
+}
+
+/// ## Description
+/// Runs a repository operation within the blocking thread pool. Every backend is synchronous, so calling
+/// them directly from a handler would stall the async worker serving it, and every request queued on it
+pub async fn blocking<R, T>(
+    repository: &web::Data<R>,
+    operation: impl FnOnce(&R) -> TheResult<T> + Send + 'static,
+) -> TheResult<T>
+where
+    R: ?Sized + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    let repository = repository.clone();
+    web::block(logging::propagate(move || operation(&repository)))
+        .await
+        .map_err(|error| create_new_error!(error.to_string()))?
 }
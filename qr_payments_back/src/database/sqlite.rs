@@ -0,0 +1,227 @@
+use std::{
+    str::FromStr,
+    sync::{Mutex, MutexGuard},
+};
+
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{Connection, TransactionBehavior};
+use rust_decimal::Decimal;
+
+use crate::modules::currencies::Currency;
+
+/// Numbered SQLite schema changes as `(version, name, script)`, applied in order by `SqliteStore::open`.
+/// They are embedded in the binary, so a database file can be opened from any working directory
+const MIGRATIONS: &[(u32, &str, &str)] = &[
+    (
+        1,
+        "initial_schema",
+        include_str!("../../migrations/sqlite/0001_initial_schema.up.sql"),
+    ),
+    (
+        2,
+        "fx_rates_and_quotes",
+        include_str!("../../migrations/sqlite/0002_fx_rates_and_quotes.up.sql"),
+    ),
+    (
+        3,
+        "fee_schedules",
+        include_str!("../../migrations/sqlite/0003_fee_schedules.up.sql"),
+    ),
+    (
+        4,
+        "transaction_reversals",
+        include_str!("../../migrations/sqlite/0004_transaction_reversals.up.sql"),
+    ),
+    (
+        5,
+        "merchant_webhook_subscriptions",
+        include_str!("../../migrations/sqlite/0005_merchant_webhook_subscriptions.up.sql"),
+    ),
+];
+/// Development data inserted when `SqliteStore::open` creates the database
+const SEED: &str = include_str!("../../schema_reset/seed.sqlite.sql");
+
+/// SQLite implementation of the repositories. A single connection serializes every operation, and the
+/// compound ones run inside a SQLite transaction
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// ## Description
+    /// Opens the database file, applying the migrations it is missing and seeding it when it was just
+    /// created. The `:memory:` path opens a private in-memory database
+    pub fn open(path: &str) -> TheResult<Self> {
+        let mut conn =
+            Connection::open(path).map_err(|error| create_new_error!(error.to_string()))?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let created = migrate(&mut conn)?;
+        if created {
+            conn.execute_batch(SEED)
+                .map_err(|error| create_new_error!(error.to_string()))?;
+        }
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub(crate) fn lock(&self) -> TheResult<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|error| create_new_error!(format!("SQLite connection is poisoned: {}", error)))
+    }
+
+    /// ## Description
+    /// Runs an operation inside a SQLite transaction, committing it when the operation succeeds. The
+    /// transaction is rolled back when dropped otherwise. It takes the write lock when it begins, so other
+    /// processes using the file cannot write between the reads and the writes of the operation
+    pub(crate) fn in_transaction<T>(
+        &self,
+        operation: impl FnOnce(&rusqlite::Transaction<'_>) -> TheResult<T>,
+    ) -> TheResult<T> {
+        self.in_transaction_if(operation, |_| true)
+    }
+
+    /// ## Description
+    /// Runs an operation inside a SQLite transaction as `in_transaction` does, committing it only if
+    /// `commit` accepts its result, and rolling it back otherwise
+    pub(crate) fn in_transaction_if<T>(
+        &self,
+        operation: impl FnOnce(&rusqlite::Transaction<'_>) -> TheResult<T>,
+        commit: impl FnOnce(&T) -> bool,
+    ) -> TheResult<T> {
+        let mut conn = self.lock()?;
+        let db_transaction = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let result = operation(&db_transaction)?;
+        if commit(&result) {
+            db_transaction
+                .commit()
+                .map_err(|error| create_new_error!(error.to_string()))?;
+        } else {
+            db_transaction
+                .rollback()
+                .map_err(|error| create_new_error!(error.to_string()))?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// ## Description
+/// Applies the migrations the database is missing, each in its own transaction recorded in the
+/// `schema_migrations` table. Databases created before the migrations were versioned already hold the
+/// initial schema, so it is recorded as applied instead of run
+///
+/// ### Returns
+/// True if the database had no schema and was created
+fn migrate(conn: &mut Connection) -> TheResult<bool> {
+    let has_table = |conn: &Connection, table: &str| {
+        conn.query_row(
+            "SELECT COUNT(*) > 0 FROM `sqlite_master` WHERE `type` = 'table' AND `name` = ?;",
+            [table],
+            |row| row.get::<_, bool>(0),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))
+    };
+    let created = !has_table(conn, "wallets")?;
+    let versioned = has_table(conn, "schema_migrations")?;
+
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS `schema_migrations` (
+            `version` INTEGER PRIMARY KEY,
+            `name` VARCHAR(128) NOT NULL,
+            `applied_at` TEXT NOT NULL DEFAULT (datetime('now', 'localtime'))
+        );",
+    )
+    .map_err(|error| create_new_error!(error.to_string()))?;
+    if !created && !versioned {
+        let (version, name, _) = MIGRATIONS[0];
+        conn.execute(
+            "INSERT INTO `schema_migrations` (`version`, `name`) VALUES (?, ?);",
+            rusqlite::params![version, name],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    }
+
+    let current = conn
+        .query_row(
+            "SELECT COALESCE(MAX(`version`), 0) FROM `schema_migrations`;",
+            [],
+            |row| row.get::<_, u32>(0),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    for (version, name, script) in MIGRATIONS
+        .iter()
+        .filter(|(version, _, _)| *version > current)
+    {
+        let db_transaction = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        db_transaction.execute_batch(script).map_err(|error| {
+            create_new_error!(format!(
+                "Could not apply SQLite migration {}_{}: {}",
+                version, name, error
+            ))
+        })?;
+        db_transaction
+            .execute(
+                "INSERT INTO `schema_migrations` (`version`, `name`) VALUES (?, ?);",
+                rusqlite::params![version, name],
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        db_transaction
+            .commit()
+            .map_err(|error| create_new_error!(error.to_string()))?;
+    }
+
+    Ok(created)
+}
+
+/// ## Description
+/// Reads a decimal stored as TEXT
+pub(crate) fn decimal_column(row: &rusqlite::Row<'_>, column: &str) -> rusqlite::Result<Decimal> {
+    optional_decimal_column(row, column)?.ok_or_else(|| {
+        rusqlite::Error::InvalidColumnType(0, column.to_string(), rusqlite::types::Type::Null)
+    })
+}
+
+/// ## Description
+/// Reads a nullable decimal stored as TEXT
+pub(crate) fn optional_decimal_column(
+    row: &rusqlite::Row<'_>,
+    column: &str,
+) -> rusqlite::Result<Option<Decimal>> {
+    let Some(value) = row.get::<_, Option<String>>(column)? else {
+        return Ok(None);
+    };
+
+    Decimal::from_str(&value).map(Some).map_err(|error| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(error))
+    })
+}
+
+/// ## Description
+/// Reads a currency stored as its ISO 4217 code
+pub(crate) fn currency_column(row: &rusqlite::Row<'_>, column: &str) -> rusqlite::Result<Currency> {
+    Currency::from_string(row.get::<_, String>(column)?).ok_or_else(|| {
+        rusqlite::Error::InvalidColumnType(0, column.to_string(), rusqlite::types::Type::Text)
+    })
+}
+
+/// ## Description
+/// Reads an enum variant stored as its name
+pub(crate) fn variant_column<T>(
+    row: &rusqlite::Row<'_>,
+    column: &str,
+    from_string: impl FnOnce(String) -> Option<T>,
+) -> rusqlite::Result<T> {
+    from_string(row.get::<_, String>(column)?).ok_or_else(|| {
+        rusqlite::Error::InvalidColumnType(0, column.to_string(), rusqlite::types::Type::Text)
+    })
+}
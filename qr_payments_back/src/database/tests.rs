@@ -0,0 +1,253 @@
+use std::fs;
+
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+
+use super::migrations::{self, AppliedMigration, Migration};
+
+/// Directory holding the received `(file name, script)` migration files
+fn migrations_dir(files: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (file_name, script) in files {
+        fs::write(dir.path().join(file_name), script).unwrap();
+    }
+    dir
+}
+
+fn load(dir: &tempfile::TempDir) -> error_mapper::TheResult<Vec<Migration>> {
+    migrations::load(dir.path().to_str().unwrap())
+}
+
+fn applied(migration: &Migration) -> AppliedMigration {
+    AppliedMigration {
+        version: migration.version,
+        name: migration.name.clone(),
+        checksum: migration.checksum.clone(),
+        applied_at: NaiveDateTime::default(),
+    }
+}
+
+#[test]
+fn migrations_are_loaded_by_version_with_the_checksum_of_their_up_script() {
+    let dir = migrations_dir(&[
+        ("0010_add_index.up.sql", "CREATE INDEX b ON a (b);"),
+        ("0002_create_table.up.sql", "CREATE TABLE a (b INT);"),
+        ("0002_create_table.down.sql", "DROP TABLE a;"),
+        ("README.md", "Not a migration"),
+    ]);
+
+    let migrations = load(&dir).unwrap();
+    let loaded = migrations
+        .iter()
+        .map(|migration| (migration.version, migration.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(loaded, [(2, "create_table"), (10, "add_index")]);
+    assert_eq!(
+        migrations[0].checksum,
+        hex::encode(Sha256::digest("CREATE TABLE a (b INT);"))
+    );
+}
+
+#[test]
+fn malformed_migration_directories_are_rejected() {
+    for files in [
+        vec![("create_table.up.sql", "CREATE TABLE a (b INT);")],
+        vec![("0001_create_table.down.sql", "DROP TABLE a;")],
+        vec![
+            ("0001_create_table.up.sql", "CREATE TABLE a (b INT);"),
+            ("0001_create_other.up.sql", "CREATE TABLE c (d INT);"),
+        ],
+    ] {
+        assert!(load(&migrations_dir(&files)).is_err());
+    }
+
+    assert!(migrations::load("/nonexistent/migrations").is_err());
+}
+
+#[test]
+fn applied_migrations_must_match_the_directory() {
+    let dir = migrations_dir(&[
+        ("0001_create_table.up.sql", "CREATE TABLE a (b INT);"),
+        ("0002_add_index.up.sql", "CREATE INDEX b ON a (b);"),
+    ]);
+    let migrations = load(&dir).unwrap();
+    let dir_path = dir.path().to_str().unwrap();
+
+    //  Pending migrations are fine, only the applied ones are checked
+    let applied_first = [applied(&migrations[0])];
+    assert!(migrations::verify(&migrations, &applied_first, dir_path).is_ok());
+
+    fs::write(
+        dir.path().join("0001_create_table.up.sql"),
+        "CREATE TABLE a (b BIGINT);",
+    )
+    .unwrap();
+    let modified = load(&dir).unwrap();
+    assert!(migrations::verify(&modified, &applied_first, dir_path).is_err());
+
+    let removed = &migrations[1..];
+    assert!(migrations::verify(removed, &applied_first, dir_path).is_err());
+}
+
+#[test]
+fn the_default_migrations_directory_does_not_depend_on_the_working_directory() {
+    let migrations = migrations::load(migrations::MIGRATIONS_DIR).unwrap();
+    assert_eq!(
+        migrations.first().map(|migration| migration.version),
+        Some(1)
+    );
+}
+
+fn columns(columns: &[(&str, &str, &str)]) -> Vec<(String, String, String)> {
+    columns
+        .iter()
+        .map(|(table, column, column_type)| {
+            (
+                table.to_string(),
+                column.to_string(),
+                column_type.to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn only_tables_conforming_to_the_initial_schema_are_adopted() {
+    //  Tables of the initial schema as migrated, every column with a type
+    let conforming = migrations::INITIAL_SCHEMA_COLUMNS
+        .iter()
+        .map(|(table, column, enum_type)| (*table, *column, enum_type.unwrap_or("int")))
+        .collect::<Vec<_>>();
+    assert!(migrations::nonconforming_columns(&columns(&conforming)).is_empty());
+    assert!(migrations::nonconforming_columns(&[]).is_empty());
+
+    //  Tables created by the first schema.sql, before transactions were dated and wallets limited
+    let baseline = columns(&[
+        ("wallets", "ID", "int"),
+        ("wallets", "balance", "decimal(12,2)"),
+        ("transactions", "ID", "int"),
+        ("transactions", "wallets_ID", "int"),
+        ("transactions", "amount", "decimal(12,2)"),
+        (
+            "transactions",
+            "status",
+            "enum('Initialized','Confirmed','Declined','Cancelled','InternalError','Log')",
+        ),
+        ("transactions", "token", "varchar(32)"),
+        ("transactions", "errors", "varchar(128)"),
+    ]);
+    let differences = migrations::nonconforming_columns(&baseline);
+    assert_eq!(differences.len(), 6);
+    assert!(differences.contains(&String::from("`transactions`.`created_at` is missing")));
+    assert!(differences.contains(&String::from("`wallets`.`max_daily_count` is missing")));
+    assert!(differences.iter().any(|difference| difference
+        .starts_with("`transactions`.`status` is enum('Initialized','Confirmed'")));
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use rusqlite::Connection;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        database::sqlite::SqliteStore,
+        modules::{
+            outbox::{repository::OutboxRepository, sinks::OutboxSink},
+            transactions::{repository::TransactionRepository, DebitOutcome, Transaction},
+            wallets::{limits::LimitValuation, repository::WalletRepository},
+            webhooks::{repository::WebhookRepository, sink::WebhookSink},
+        },
+    };
+
+    fn count(conn: &Connection, query: &str) -> u32 {
+        conn.query_row(query, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn new_databases_are_migrated_and_seeded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("qrpay.sqlite");
+        let path = path.to_str().unwrap();
+
+        for _ in 0..2 {
+            let store = SqliteStore::open(path).unwrap();
+            let conn = store.lock().unwrap();
+            assert_eq!(count(&conn, "SELECT COUNT(*) FROM `wallets`;"), 2);
+            assert_eq!(
+                count(&conn, "SELECT MAX(`version`) FROM `schema_migrations`;"),
+                5
+            );
+        }
+    }
+
+    #[test]
+    fn unversioned_databases_keep_their_data_and_are_versioned() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("qrpay.sqlite");
+        let path = path.to_str().unwrap();
+
+        //  Databases opened before the migrations were versioned only hold the initial schema
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(include_str!(
+            "../../migrations/sqlite/0001_initial_schema.up.sql"
+        ))
+        .unwrap();
+        conn.execute("INSERT INTO `wallets` (`balance`) VALUES ('25');", [])
+            .unwrap();
+        drop(conn);
+
+        let store = SqliteStore::open(path).unwrap();
+        let conn = store.lock().unwrap();
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM `wallets`;"), 1);
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM `schema_migrations` WHERE `version` = 1;"
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn outbox_events_are_relayed_to_webhook_deliveries() {
+        let store = SqliteStore::open(":memory:").unwrap();
+        store
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO `merchants` (`wallets_ID`, `name`, `city`, `country`, `mcc`) VALUES (2, 'Cafe Central', 'Springfield', 'US', '5812');
+                INSERT INTO `webhook_subscriptions` (`merchants_ID`, `url`, `secret`) VALUES (1, 'https://merchant.example/hook', 'secret');
+                INSERT INTO `webhook_subscriptions` (`merchants_ID`, `url`, `secret`, `active`) VALUES (1, 'https://inactive.example/hook', 'secret', FALSE);
+                INSERT INTO `webhook_subscriptions` (`merchants_ID`, `url`, `secret`) VALUES (NULL, 'https://unscoped.example/hook', 'secret');",
+            )
+            .unwrap();
+
+        let mut wallet = WalletRepository::select_by_id(&store, 1).unwrap().unwrap();
+        let outcome = store
+            .insert_affecting_wallet(
+                &mut Transaction::payment(1, Decimal::from(-100)).paid_to(1),
+                &[],
+                &mut wallet,
+                &LimitValuation::default(),
+            )
+            .unwrap();
+        assert_eq!(outcome, DebitOutcome::Debited);
+
+        let sinks: Vec<Box<dyn OutboxSink>> = vec![Box::new(WebhookSink)];
+        store.relay_pending(100, &sinks).unwrap();
+        store.relay_pending(100, &sinks).unwrap();
+
+        //  Only the active subscription of the merchant paid is notified, once, of the transaction event
+        let deliveries = store.select_due_deliveries(100).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].subscriptions_id, 1);
+        assert_eq!(deliveries[0].event_type, "transaction.initialized");
+        assert_eq!(
+            count(
+                &store.lock().unwrap(),
+                "SELECT COUNT(*) FROM `outbox` WHERE `dispatched_at` IS NULL;"
+            ),
+            0
+        );
+    }
+}
@@ -1,2 +1,8 @@
 pub type WalletsIdType = u64;
 pub type TransactionsIdType = u64;
+pub type WebhookSubscriptionsIdType = u64;
+pub type WebhookDeliveriesIdType = u64;
+pub type OutboxIdType = u64;
+pub type BalanceAdjustmentsIdType = u64;
+pub type MerchantsIdType = u64;
+pub type SettlementBatchesIdType = u64;
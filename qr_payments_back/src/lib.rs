@@ -0,0 +1,63 @@
+use chrono::NaiveDateTime;
+use std::sync::OnceLock;
+
+pub const APP_NAME: &str = env!("CARGO_PKG_NAME");
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub static ALIVE_SINCE: OnceLock<NaiveDateTime> = OnceLock::new();
+pub const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+pub const TIME_FORMAT: &str = "%H:%M:%S";
+
+pub mod admin;
+pub mod api;
+pub mod config;
+pub mod database;
+pub mod datatypes;
+pub mod logging;
+pub mod metrics;
+pub mod modules;
+pub mod tasks;
+pub mod validation;
+
+/// ## Description
+/// Macro that extracts any primitive, plus Strings values from a Row element. Returns early with a
+/// `RowError` when the column is missing or can't be converted, so it must be used within a function
+/// returning `Result<_, RowError>`, usually `DecodeRow::decode`
+///
+/// ### Parameters
+/// - row: row element being decoded
+/// - field: column name in database table
+/// - table: table name in database
+/// - datatype: the type of the data to be converted from the Row element
+#[macro_export]
+macro_rules! row_to_data {
+    ($row:ident, $field:expr, $table:expr, $datatype:ty) => {
+        $crate::database::decode::column::<$datatype>(&$row, $table, $field, stringify!($datatype))?
+    };
+}
+
+/// ## Description
+/// Implements `FromRow` on top of the type's `DecodeRow` implementation. The repositories decode rows
+/// through `DecodeRow` directly, so `from_row` panicking is only reachable through the `mysql` typed
+/// query helpers
+#[macro_export]
+macro_rules! from_row_via_decode {
+    ($type:ty) => {
+        impl mysql::prelude::FromRow for $type {
+            fn from_row(row: mysql::Row) -> Self
+            where
+                Self: Sized,
+            {
+                <Self as $crate::database::decode::DecodeRow>::decode(&row)
+                    .unwrap_or_else(|error| panic!("{}", error))
+            }
+
+            fn from_row_opt(row: mysql::Row) -> Result<Self, mysql::FromRowError>
+            where
+                Self: Sized,
+            {
+                <Self as $crate::database::decode::DecodeRow>::decode(&row)
+                    .map_err(|_| mysql::FromRowError(row))
+            }
+        }
+    };
+}
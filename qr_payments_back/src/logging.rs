@@ -0,0 +1,223 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    io::Write,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::datatypes::{TransactionsIdType, WalletsIdType};
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+tokio::task_local! {
+    static CONTEXT: RefCell<LogContext>;
+}
+
+/// Least severe level written to the logs. Critical records are always written
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[default]
+    Info = 0,
+    Warning = 1,
+    Error = 2,
+    Critical = 3,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+/// Where the records are written. Text goes to the `the_logger` file, json to stdout as one object per line
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Identifiers attached to every record logged while handling a request
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct LogContext {
+    pub request_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wallets_id: Option<WalletsIdType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transactions_id: Option<TransactionsIdType>,
+}
+
+/// One line of the json output
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    timestamp: String,
+    level: &'static str,
+    file: &'static str,
+    line: u32,
+    message: &'a str,
+    #[serde(flatten)]
+    context: Option<LogContext>,
+}
+
+/// ## Description
+/// Changes the level of the records written from now on
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level as u8 >= LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn set_format(format: LogFormat) {
+    JSON_OUTPUT.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// ## Description
+/// Runs the future with the request ID attached to every record it logs
+pub async fn scope<F: Future>(request_id: String, future: F) -> F::Output {
+    let context = LogContext {
+        request_id,
+        ..Default::default()
+    };
+    CONTEXT.scope(RefCell::new(context), future).await
+}
+
+/// ## Description
+/// Wraps an operation run on a blocking thread, as with `web::block`, so the records it logs keep the
+/// context of the request that started it. Task locals are not carried over to those threads
+pub fn propagate<T>(operation: impl FnOnce() -> T) -> impl FnOnce() -> T {
+    let context = CONTEXT.try_with(|context| context.borrow().clone()).ok();
+    move || match context {
+        Some(context) => CONTEXT.sync_scope(RefCell::new(context), operation),
+        None => operation(),
+    }
+}
+
+/// ## Description
+/// Attaches the wallet to the records logged from now on by the current request. Ignored outside of one
+pub fn set_wallets_id(wallets_id: WalletsIdType) {
+    let _ = CONTEXT.try_with(|context| context.borrow_mut().wallets_id = Some(wallets_id));
+}
+
+/// ## Description
+/// Attaches the transaction to the records logged from now on by the current request. Ignored outside of one
+pub fn set_transactions_id(transactions_id: TransactionsIdType) {
+    let _ =
+        CONTEXT.try_with(|context| context.borrow_mut().transactions_id = Some(transactions_id));
+}
+
+/// ## Description
+/// ID of the request being handled, None in the background tasks
+pub fn request_id() -> Option<String> {
+    CONTEXT
+        .try_with(|context| context.borrow().request_id.clone())
+        .ok()
+}
+
+#[doc(hidden)]
+/// ## Description
+/// Context of the current request formatted for the text output, as `[request_id=.. wallets_id=..] `
+pub fn text_prefix() -> String {
+    CONTEXT
+        .try_with(|context| {
+            let context = context.borrow();
+            let mut prefix = format!("[request_id={}", context.request_id);
+            if let Some(wallets_id) = context.wallets_id {
+                prefix.push_str(&format!(" wallets_id={}", wallets_id));
+            }
+            if let Some(transactions_id) = context.transactions_id {
+                prefix.push_str(&format!(" transactions_id={}", transactions_id));
+            }
+            prefix.push_str("] ");
+            prefix
+        })
+        .unwrap_or_default()
+}
+
+#[doc(hidden)]
+/// ## Description
+/// Writes a record to stdout as a single json line, along with the context of the current request
+pub fn write_json(level: LogLevel, file: &'static str, line: u32, message: &str) {
+    let record = JsonRecord {
+        timestamp: chrono::Local::now()
+            .naive_local()
+            .format("%Y-%m-%dT%H:%M:%S%.6f")
+            .to_string(),
+        level: level.as_str(),
+        file,
+        line,
+        message,
+        context: CONTEXT.try_with(|context| context.borrow().clone()).ok(),
+    };
+    if let Ok(mut record) = serde_json::to_vec(&record) {
+        record.push(b'\n');
+        let _ = std::io::stdout().lock().write_all(&record);
+    }
+}
+
+/// ## Description
+/// `the_logger` macros filtered by the configured level, and prefixed with the context of the request
+/// being handled. Same parameters: the logger instance and the message, with `format!` arguments. With
+/// the json format the records are written to stdout instead
+#[macro_export]
+macro_rules! log_info {
+    ($logger:expr, $($msg:tt)*) => {
+        $crate::log_with_context!(Info, log_info, $logger, $($msg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! log_warning {
+    ($logger:expr, $($msg:tt)*) => {
+        $crate::log_with_context!(Warning, log_warning, $logger, $($msg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($logger:expr, $($msg:tt)*) => {
+        $crate::log_with_context!(Error, log_error, $logger, $($msg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! log_critical {
+    ($logger:expr, $($msg:tt)*) => {
+        $crate::log_with_context!(Critical, log_critical, $logger, $($msg)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! log_with_context {
+    ($level:ident, $the_logger_macro:ident, $logger:expr, $($msg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::$level) {
+            let message = format!($($msg)*);
+            if $crate::logging::json_output() {
+                $crate::logging::write_json(
+                    $crate::logging::LogLevel::$level,
+                    file!(),
+                    line!(),
+                    &message,
+                );
+            } else {
+                the_logger::$the_logger_macro!($logger, "{}{}", $crate::logging::text_prefix(), message);
+            }
+        }
+    };
+}
@@ -1,27 +1,74 @@
-use chrono::NaiveDateTime;
-use config::Config;
-use database::DbConn;
+use clap::{Parser, Subcommand};
 use error_mapper::{create_new_error, TheResult};
-use std::sync::OnceLock;
-use the_logger::{log_error, TheLogger};
+use qr_payments_backend::{
+    api,
+    config::{Config, DbDriver},
+    database::{
+        migrations::{self, MigrateCommand},
+        DbConn, Repositories,
+    },
+    log_error, log_info,
+    modules::{
+        outbox::relay::run_relay, settlements::scheduler::run_settlement_scheduler,
+        transactions::sweeper::run_expiry_sweeper, webhooks::dispatcher::run_dispatcher,
+    },
+    ALIVE_SINCE,
+};
+use the_logger::TheLogger;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc::{Receiver, Sender};
 
-const APP_NAME: &str = env!("CARGO_PKG_NAME");
-const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
-static ALIVE_SINCE: OnceLock<NaiveDateTime> = OnceLock::new();
-const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
-const TIME_FORMAT: &str = "%H:%M:%S";
+/// QR payments API server
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Configuration file. Defaults to `QRPAY_CONFIG`, then to `config/config.json`
+    #[arg(long, global = true)]
+    config: Option<String>,
+    /// Prints the effective configuration with its secrets redacted, and exits
+    #[arg(long)]
+    print_config: bool,
+    #[command(subcommand)]
+    command: Option<ServerCommand>,
+}
 
-mod api;
-mod config;
-mod database;
-mod datatypes;
-mod modules;
+#[derive(Subcommand)]
+enum ServerCommand {
+    /// Manages the schema migrations and exits without starting the API. Applies the pending ones by
+    /// default
+    Migrate {
+        #[command(subcommand)]
+        command: Option<MigrateCommand>,
+    },
+}
 
 #[tokio::main]
 async fn main() {
-    let stop_channels = match init_configurations().await {
-        Ok(channels) => channels,
+    let args = Args::parse();
+    let config_path = args.config.as_deref();
+    if args.print_config {
+        match Config::to_redacted_json(config_path) {
+            Ok(config) => println!("{}", config),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(ServerCommand::Migrate { command }) = args.command {
+        if let Err(error) = run_migrations(config_path, command.unwrap_or(MigrateCommand::Up)).await
+        {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let (stop_channels, repositories) = match init_configurations(config_path).await {
+        Ok(initialized) => initialized,
         Err(error) => {
             let logger = TheLogger::instance();
             log_error!(logger, "{}", error);
@@ -29,42 +76,88 @@ async fn main() {
         }
     };
 
-    if let Err(error) = api::start_api(stop_channels).await {
+    //  Elsewhere the configuration is only reloaded through the API
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup());
+    tokio::spawn(run_expiry_sweeper(repositories.transactions.clone()));
+    tokio::spawn(run_settlement_scheduler(
+        repositories.settlements.clone(),
+        repositories.merchants.clone(),
+    ));
+    tokio::spawn(run_dispatcher(repositories.webhooks.clone()));
+    tokio::spawn(run_relay(repositories.outbox.clone()));
+
+    if let Err(error) = api::start_api(stop_channels, repositories).await {
         let logger = TheLogger::instance();
         log_error!(logger, "Error starting Api: {}", error);
         return;
     };
 }
 
-async fn init_configurations() -> TheResult<(Sender<()>, Receiver<()>)> {
+async fn init_configurations(
+    config_path: Option<&str>,
+) -> TheResult<((Sender<()>, Receiver<()>), Repositories)> {
     ALIVE_SINCE.get_or_init(|| chrono::Local::now().naive_local());
-    Config::initialize_config();
-    if let Err(error) = DbConn::init_connection().await {
-        let error = format!("Could not initialize Db Connection: {}", error);
-        return Err(create_new_error!(error));
+    Config::initialize_config(config_path)?;
+    let db_config = Config::get_db_config().await?;
+    let repositories = match Repositories::init(&db_config).await {
+        Ok(repositories) => repositories,
+        Err(error) => {
+            let error = format!("Could not initialize Db Connection: {}", error);
+            return Err(create_new_error!(error));
+        }
+    };
+
+    if db_config.driver == DbDriver::MySql && db_config.migrate_on_startup {
+        let dir = db_config.migrations_dir.clone();
+        let versions = DbConn::run(move |conn| migrations::migrate_up(conn, &dir))
+            .await
+            .map_err(|error| create_new_error!(format!("Could not run migrations: {}", error)))?;
+        log_info!(
+            TheLogger::instance(),
+            "Applied {} pending migration(s): {:?}",
+            versions.len(),
+            versions
+        );
     }
 
     let (stop_sender, stop_receiver) = tokio::sync::mpsc::channel::<()>(5);
 
-    Ok((stop_sender, stop_receiver))
+    Ok(((stop_sender, stop_receiver), repositories))
+}
+
+async fn run_migrations(config_path: Option<&str>, command: MigrateCommand) -> TheResult<()> {
+    Config::initialize_config(config_path)?;
+    let db_config = Config::get_db_config().await?;
+    DbConn::init_connection().await?;
+    let outcome = migrations::run_command(command, db_config.migrations_dir).await?;
+    println!("{}", outcome);
+
+    Ok(())
 }
 
 /// ## Description
-/// Macro that extracts any primitive, plus Strings values from a Row element
-///
-/// ### Parameters
-/// - row: row element in FromRow implementation
-/// - field: column name in database table
-/// - table: table name in database
-/// - datatype: the type of the data to be converted from the Row element
-#[macro_export]
-macro_rules! row_to_data {
-    ($row:ident, $field:expr, $table:expr, $datatype:ty) => {
-        match $row.get::<$datatype, _>($field) {
-            Some(value) => value,
-            None => {
-                panic!("Unknown column {} in table {}", $field.to_string(), $table);
-            }
+/// Reloads the configuration every time the process receives SIGHUP
+#[cfg(unix)]
+async fn reload_on_sighup() {
+    let logger = TheLogger::instance();
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(error) => {
+            log_error!(logger, "Could not listen for SIGHUP: {}", error);
+            return;
         }
     };
+
+    while hangups.recv().await.is_some() {
+        match Config::reload().await {
+            Ok(report) => log_info!(
+                logger,
+                "Configuration reloaded on SIGHUP, applied: {:?}, restart required: {:?}",
+                report.applied,
+                report.restart_required
+            ),
+            Err(error) => log_error!(logger, "Could not reload configuration: {}", error),
+        }
+    }
 }
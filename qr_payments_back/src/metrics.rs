@@ -0,0 +1,123 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    core::Collector, CounterVec, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, Opts, Registry, TextEncoder,
+};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+use crate::modules::{currencies::Currency, transactions::TransactionStatus};
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "qrpay_http_requests_total",
+            "HTTP requests by route and status",
+        ),
+        &["method", "route", "status"],
+    ))
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "qrpay_http_request_duration_seconds",
+            "HTTP request latency by route",
+        ),
+        &["method", "route"],
+    ))
+});
+
+pub static TRANSACTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "qrpay_transactions_total",
+            "Transactions created with or moved to each status",
+        ),
+        &["status"],
+    ))
+});
+
+pub static AMOUNT_PROCESSED: LazyLock<CounterVec> = LazyLock::new(|| {
+    register(CounterVec::new(
+        Opts::new(
+            "qrpay_amount_processed_total",
+            "Absolute amount of the confirmed transactions by currency",
+        ),
+        &["currency"],
+    ))
+});
+
+pub static INSUFFICIENT_BALANCE: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new(
+        "qrpay_insufficient_balance_rejections_total",
+        "Transactions rejected because the wallet balance could not cover them",
+    ))
+});
+
+pub static DB_POOL_WAIT: LazyLock<Histogram> = LazyLock::new(|| {
+    register(Histogram::with_opts(
+        HistogramOpts::new(
+            "qrpay_db_pool_wait_seconds",
+            "Time waited for a MySQL pool connection",
+        )
+        .buckets(vec![0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]),
+    ))
+});
+
+pub static ROLLBACK_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new(
+        "qrpay_db_rollback_failures_total",
+        "DB transactions that failed and could not be rolled back either",
+    ))
+});
+
+pub static SETTLEMENT_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "qrpay_settlement_failures_total",
+            "Merchant batches that failed to close or to be paid out, retried on the next run",
+        ),
+        &["stage"],
+    ))
+});
+
+/// ## Description
+/// Counts a transaction stored with, or moved to, its current status. Confirmed ones add their amount to
+/// the amount processed in their currency
+pub fn record_transaction(status: TransactionStatus, amount: Decimal, currency: Currency) {
+    TRANSACTIONS.with_label_values(&[&status.to_string()]).inc();
+    if status == TransactionStatus::Confirmed {
+        AMOUNT_PROCESSED
+            .with_label_values(&[currency.code()])
+            .inc_by(amount.abs().to_f64().unwrap_or_default());
+    }
+}
+
+/// ## Description
+/// Renders every metric in the Prometheus text format
+pub fn render() -> String {
+    //  Metrics are registered on first use, so the ones never touched are forced to be exported as zero
+    LazyLock::force(&HTTP_REQUESTS);
+    LazyLock::force(&HTTP_REQUEST_DURATION);
+    LazyLock::force(&TRANSACTIONS);
+    LazyLock::force(&AMOUNT_PROCESSED);
+    LazyLock::force(&INSUFFICIENT_BALANCE);
+    LazyLock::force(&DB_POOL_WAIT);
+    LazyLock::force(&ROLLBACK_FAILURES);
+    LazyLock::force(&SETTLEMENT_FAILURES);
+
+    let mut buffer = Vec::new();
+    //  Encoding into a Vec only fails on invalid metric names, which are all static
+    let _ = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer);
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Registers the metric in the crate registry. Names are static, so registering never fails
+fn register<M: Collector + Clone + 'static>(metric: prometheus::Result<M>) -> M {
+    let metric = metric.expect("Invalid metric definition");
+    let _ = REGISTRY.register(Box::new(metric.clone()));
+    metric
+}
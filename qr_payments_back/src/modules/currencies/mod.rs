@@ -0,0 +1,142 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use utoipa::{
+    openapi::{schema::Type, ObjectBuilder, RefOr, Schema},
+    PartialSchema, ToSchema,
+};
+
+/// ## Description
+/// ISO 4217 currency, with its numeric code and the number of decimal places of its minor unit. Only the
+/// currencies in `SUPPORTED` exist, so a `Currency` is always one the amount columns can store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency {
+    code: &'static str,
+    numeric: &'static str,
+    minor_units: u32,
+}
+
+/// Currencies accepted by the service. The ones with three minor units (BHD, KWD...) are left out, as the
+/// amount columns are `DECIMAL(12,2)`
+const SUPPORTED: &[Currency] = &[
+    Currency::new("ARS", "032", 2),
+    Currency::new("AUD", "036", 2),
+    Currency::new("BRL", "986", 2),
+    Currency::new("CAD", "124", 2),
+    Currency::new("CHF", "756", 2),
+    Currency::new("CLP", "152", 0),
+    Currency::new("CNY", "156", 2),
+    Currency::new("COP", "170", 2),
+    Currency::new("EUR", "978", 2),
+    Currency::new("GBP", "826", 2),
+    Currency::new("JPY", "392", 0),
+    Currency::new("KRW", "410", 0),
+    Currency::new("MXN", "484", 2),
+    Currency::new("PEN", "604", 2),
+    Currency::new("PYG", "600", 0),
+    Currency::new("USD", "840", 2),
+    Currency::new("UYU", "858", 2),
+];
+
+impl Currency {
+    /// Currency of the wallets created before currencies existed, and of the new ones by default
+    pub const USD: Self = Self::new("USD", "840", 2);
+
+    const fn new(code: &'static str, numeric: &'static str, minor_units: u32) -> Self {
+        Self {
+            code,
+            numeric,
+            minor_units,
+        }
+    }
+
+    /// ## Description
+    /// Finds a supported currency by its ISO 4217 alphabetic code, which must be uppercase
+    pub fn from_code(code: &str) -> Option<Self> {
+        SUPPORTED
+            .iter()
+            .find(|currency| currency.code == code)
+            .copied()
+    }
+
+    pub(crate) fn from_string(input: String) -> Option<Self> {
+        Self::from_code(&input)
+    }
+
+    /// Every supported currency, ordered by code
+    pub fn supported() -> &'static [Self] {
+        SUPPORTED
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// ISO 4217 numeric code, three digits zero padded, as QR payloads carry it
+    pub fn numeric(&self) -> &'static str {
+        self.numeric
+    }
+
+    /// Decimal places of the currency's minor unit, e.g. 2 for USD cents and 0 for JPY
+    pub fn minor_units(&self) -> u32 {
+        self.minor_units
+    }
+
+    /// ## Description
+    /// Whether the amount can be expressed in whole minor units of the currency
+    pub fn is_exact(&self, amount: Decimal) -> bool {
+        amount.normalize().scale() <= self.minor_units
+    }
+
+    /// ## Description
+    /// Rounds an amount to the currency's minor unit. Midpoints are rounded to the nearest even unit
+    /// (banker's rounding), so rounding many amounts carries no bias in either direction
+    pub fn round(&self, amount: Decimal) -> Decimal {
+        amount.round_dp_with_strategy(self.minor_units, RoundingStrategy::MidpointNearestEven)
+    }
+
+    /// ## Description
+    /// Rounds an amount to the currency's minor unit, away from zero whenever it has a remainder. Used
+    /// where rounding must never favour the payer, such as the amount debited for a conversion
+    pub fn round_up(&self, amount: Decimal) -> Decimal {
+        amount.round_dp_with_strategy(self.minor_units, RoundingStrategy::AwayFromZero)
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self::USD
+    }
+}
+
+impl std::fmt::Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code)
+    }
+}
+
+/// Documented as its code, one of the supported ones
+impl PartialSchema for Currency {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some("ISO 4217 alphabetic code"))
+            .enum_values(Some(SUPPORTED.iter().map(Currency::code)))
+            .into()
+    }
+}
+
+impl ToSchema for Currency {}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Self::from_code(&code)
+            .ok_or_else(|| serde::de::Error::custom(format!("unsupported currency: {}", code)))
+    }
+}
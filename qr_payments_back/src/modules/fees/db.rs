@@ -0,0 +1,170 @@
+use error_mapper::{create_new_error, TheResult};
+use mysql::prelude::Queryable;
+use rust_decimal::Decimal;
+
+use crate::{
+    database::{
+        decode::{self, DecodeRow, RowError},
+        in_transaction, MySqlStore,
+    },
+    datatypes::{MerchantsIdType, TransactionsIdType},
+    from_row_via_decode,
+    modules::{currencies::Currency, transactions::TransactionKind},
+    row_to_data,
+};
+
+use super::{repository::FeeRepository, Fee, FeeParty, FeeSchedule};
+
+impl FeeRepository for MySqlStore {
+    fn select_schedules(&self) -> TheResult<Vec<FeeSchedule>> {
+        let rows = self
+            .get_conn()?
+            .query::<mysql::Row, _>("SELECT * FROM `fee_schedules` ORDER BY `ID`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn replace_schedules(&self, schedules: &[FeeSchedule]) -> TheResult<()> {
+        in_transaction(&mut self.get_conn()?, |db_transaction| {
+            FeeSchedule::replace(db_transaction, schedules)
+        })
+    }
+}
+
+impl FeeSchedule {
+    fn replace(conn: &mut mysql::Transaction<'_>, schedules: &[Self]) -> TheResult<()> {
+        conn.query_drop("DELETE FROM `fee_schedules`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let query = "INSERT INTO `fee_schedules`(`party`, `kind`, `merchants_ID`, `currency`, `rule`) VALUES(?, ?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let mut params = Vec::new();
+        for schedule in schedules {
+            let rule = serde_json::to_string(&schedule.rule)
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            params.push((
+                schedule.party.to_string(),
+                schedule.kind.map(|kind| kind.to_string()),
+                schedule.merchants_id,
+                schedule.currency.code(),
+                rule,
+            ));
+        }
+        conn.exec_batch(stmt, params)
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+}
+
+impl Fee {
+    /// ## Description
+    /// Appends lines to the fee ledger of a transaction. Must run on the same DB transaction as the
+    /// change of the transaction they belong to
+    pub(crate) fn insert_lines(
+        conn: &mut mysql::Transaction<'_>,
+        transactions_id: TransactionsIdType,
+        fees: &[Self],
+    ) -> TheResult<()> {
+        if fees.is_empty() {
+            return Ok(());
+        }
+
+        let query = "INSERT INTO `transaction_fees`(`transactions_ID`, `party`, `amount`, `currency`) VALUES(?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_batch(
+            stmt,
+            fees.iter().map(|fee| {
+                (
+                    transactions_id,
+                    fee.party.to_string(),
+                    fee.amount.to_string(),
+                    fee.currency.code(),
+                )
+            }),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Selects the fee ledger of a transaction, oldest line first
+    pub(crate) fn select_by_transactions_id(
+        conn: &mut impl Queryable,
+        transactions_id: TransactionsIdType,
+    ) -> TheResult<Vec<Self>> {
+        let query = "SELECT * FROM `transaction_fees` WHERE `transactions_ID` = ? ORDER BY `ID`;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (transactions_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+}
+
+/// Fee line along with the transaction it was charged on
+impl DecodeRow for (TransactionsIdType, Fee) {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok((
+            row_to_data!(
+                row,
+                "transactions_ID",
+                "transaction_fees",
+                TransactionsIdType
+            ),
+            Fee::decode(row)?,
+        ))
+    }
+}
+
+impl DecodeRow for Fee {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            party: decode::variant(row, "transaction_fees", "party", FeeParty::from_string)?,
+            amount: row_to_data!(row, "amount", "transaction_fees", Decimal),
+            currency: decode::variant(row, "transaction_fees", "currency", Currency::from_string)?,
+        })
+    }
+}
+
+from_row_via_decode!(Fee);
+
+impl DecodeRow for FeeSchedule {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        let kind = match row_to_data!(row, "kind", "fee_schedules", Option<String>) {
+            Some(kind) => Some(TransactionKind::from_string(kind.clone()).ok_or(
+                RowError::UnknownVariant {
+                    table: "fee_schedules",
+                    column: "kind",
+                    value: kind,
+                },
+            )?),
+            None => None,
+        };
+        let rule = row_to_data!(row, "rule", "fee_schedules", String);
+
+        Ok(Self {
+            party: decode::variant(row, "fee_schedules", "party", FeeParty::from_string)?,
+            kind,
+            merchants_id: row_to_data!(
+                row,
+                "merchants_ID",
+                "fee_schedules",
+                Option<MerchantsIdType>
+            ),
+            currency: decode::variant(row, "fee_schedules", "currency", Currency::from_string)?,
+            rule: serde_json::from_str(&rule).map_err(|_| RowError::InvalidValue {
+                table: "fee_schedules",
+                column: "rule",
+                expected: "FeeRule",
+                found: rule.clone(),
+            })?,
+        })
+    }
+}
+
+from_row_via_decode!(FeeSchedule);
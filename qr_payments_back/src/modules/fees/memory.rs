@@ -0,0 +1,16 @@
+use error_mapper::TheResult;
+
+use crate::database::memory::InMemoryStore;
+
+use super::{repository::FeeRepository, FeeSchedule};
+
+impl FeeRepository for InMemoryStore {
+    fn select_schedules(&self) -> TheResult<Vec<FeeSchedule>> {
+        Ok(self.lock()?.fee_schedules.clone())
+    }
+
+    fn replace_schedules(&self, schedules: &[FeeSchedule]) -> TheResult<()> {
+        self.lock()?.fee_schedules = schedules.to_vec();
+        Ok(())
+    }
+}
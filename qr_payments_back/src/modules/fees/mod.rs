@@ -0,0 +1,445 @@
+use error_mapper::{create_new_error, TheResult};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    datatypes::MerchantsIdType,
+    modules::{currencies::Currency, transactions::TransactionKind},
+    validation::{self, FieldError, FieldErrorCode, Sign, Validate},
+};
+
+use self::repository::FeeRepository;
+
+pub(crate) mod db;
+#[cfg(test)]
+mod memory;
+pub mod repository;
+pub mod services;
+#[cfg(feature = "sqlite")]
+pub(crate) mod sqlite;
+#[cfg(test)]
+mod tests;
+
+/// Who pays a fee. Payer fees are debited from the wallet with the payment, merchant fees are recorded for
+/// the merchant's settlement
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Default, strum::Display, PartialEq, Eq, ToSchema,
+)]
+pub enum FeeParty {
+    #[default]
+    Payer,
+    Merchant,
+}
+
+/// ## Description
+/// Line of the fee ledger of a transaction, in the transaction currency. Charges are positive, and their
+/// refunds are recorded as new negative lines
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Fee {
+    pub party: FeeParty,
+    /// Positive when charged, negative when refunded
+    pub amount: Decimal,
+    pub currency: Currency,
+}
+
+/// How a fee is computed from the absolute amount of a payment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeeRule {
+    Fixed {
+        amount: Decimal,
+    },
+    Percentage {
+        percent: Decimal,
+    },
+    /// The first tier whose `up_to` reaches the amount applies. The last tier has no `up_to`
+    Tiered {
+        tiers: Vec<FeeTier>,
+    },
+    /// Another rule, kept between the bounds present
+    Capped {
+        #[schema(no_recursion)]
+        rule: Box<FeeRule>,
+        #[serde(default)]
+        min: Option<Decimal>,
+        #[serde(default)]
+        max: Option<Decimal>,
+    },
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FeeTier {
+    #[serde(default)]
+    pub up_to: Option<Decimal>,
+    #[serde(default)]
+    pub fixed: Decimal,
+    #[serde(default)]
+    pub percent: Decimal,
+}
+
+/// ## Description
+/// Fee charged to a party on the payments in a currency, to a single merchant or to any one, of a single
+/// kind or of every kind. A schedule for the merchant takes precedence over one for the payment kind,
+/// which takes precedence over one for every payment
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FeeSchedule {
+    pub party: FeeParty,
+    pub kind: Option<TransactionKind>,
+    pub merchants_id: Option<MerchantsIdType>,
+    pub currency: Currency,
+    pub rule: FeeRule,
+}
+
+/// Amount debited for a payment, detailing its fees
+#[derive(Debug, Default, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FeeBreakdown {
+    /// Payment amount, negative as debited
+    pub amount: Decimal,
+    pub currency: Currency,
+    pub fees: Vec<Fee>,
+    /// Amount debited from the wallet, the payment plus the payer fees
+    pub total: Decimal,
+}
+
+/// Schedules as written in the schedules file and sent to `PUT /v1/fees/schedules`
+#[derive(Debug, Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize, Default))]
+pub struct NewSchedules {
+    schedules: Vec<NewSchedule>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize))]
+struct NewSchedule {
+    party: FeeParty,
+    #[serde(default)]
+    kind: Option<TransactionKind>,
+    #[serde(default)]
+    merchants_id: Option<MerchantsIdType>,
+    #[schema(value_type = Currency)]
+    currency: String,
+    rule: FeeRule,
+}
+
+/// Largest percentage a rule can charge
+const PERCENT_MAX: Decimal = Decimal::ONE_HUNDRED;
+/// Decimal places of the percentages, e.g. 2.9999%
+const PERCENT_SCALE: u32 = 4;
+
+/// ## Description
+/// Sum of the payer lines, which is what the fees add to the wallet debit
+pub fn payer_total(fees: &[Fee]) -> Decimal {
+    fees.iter()
+        .filter(|fee| fee.party == FeeParty::Payer)
+        .map(|fee| fee.amount)
+        .sum()
+}
+
+/// ## Description
+/// Lines returning every charged fee, as transactions are only ever reversed as a whole
+pub fn refunds(charged: &[Fee]) -> Vec<Fee> {
+    charged
+        .iter()
+        .filter(|fee| !fee.amount.is_zero())
+        .map(|fee| Fee {
+            party: fee.party,
+            amount: -fee.amount,
+            currency: fee.currency,
+        })
+        .collect()
+}
+
+/// ## Description
+/// Fees of a payment of `amount` in `currency`, one per party with a schedule applying to it. Fees are
+/// computed on the absolute amount and rounded to the currency, and zero fees are left out
+pub fn calculate(
+    schedules: &[FeeSchedule],
+    kind: TransactionKind,
+    merchants_id: Option<MerchantsIdType>,
+    amount: Decimal,
+    currency: Currency,
+) -> Vec<Fee> {
+    [FeeParty::Payer, FeeParty::Merchant]
+        .into_iter()
+        .filter_map(|party| {
+            //  The most specific schedule applies, the first listed one among equally specific ones
+            let schedule = schedules
+                .iter()
+                .filter(|schedule| {
+                    schedule.party == party && schedule.applies_to(kind, merchants_id, currency)
+                })
+                .rev()
+                .max_by_key(|schedule| {
+                    (schedule.merchants_id.is_some(), schedule.kind.is_some())
+                })?;
+
+            Some(Fee {
+                party,
+                amount: currency.round(schedule.rule.compute(amount.abs())),
+                currency,
+            })
+        })
+        .filter(|fee| !fee.amount.is_zero())
+        .collect()
+}
+
+/// ## Description
+/// Reads the schedules of a schedules file, validated as the ones sent to `PUT /v1/fees/schedules`
+pub fn load_schedules_file(path: &str) -> TheResult<Vec<FeeSchedule>> {
+    let content = std::fs::read_to_string(path).map_err(|error| {
+        create_new_error!(format!(
+            "Could not read fee schedules file {}: {}",
+            path, error
+        ))
+    })?;
+    let schedules = serde_json::from_str::<NewSchedules>(&content).map_err(|error| {
+        create_new_error!(format!(
+            "Could not parse fee schedules file {}: {}",
+            path, error
+        ))
+    })?;
+    let errors = schedules.validate();
+    if !errors.is_empty() {
+        let messages = errors
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect::<Vec<_>>();
+        return Err(create_new_error!(format!(
+            "Invalid fee schedules file {}: {}",
+            path,
+            messages.join("; ")
+        )));
+    }
+
+    Ok(schedules.into_schedules())
+}
+
+/// ## Description
+/// Stores the schedules of the schedules file when no schedules are stored yet. Schedules replaced
+/// through `PUT /v1/fees/schedules` are kept across restarts, and shared by every instance of the API
+///
+/// ### Returns
+/// True if the schedules were stored
+pub fn seed_schedules(
+    repository: &dyn FeeRepository,
+    schedules: Vec<FeeSchedule>,
+) -> TheResult<bool> {
+    if !repository.select_schedules()?.is_empty() {
+        return Ok(false);
+    }
+    repository.replace_schedules(&schedules)?;
+
+    Ok(true)
+}
+
+impl FeeParty {
+    pub(crate) fn from_string(input: String) -> Option<Self> {
+        match input.as_str() {
+            "Payer" => Some(Self::Payer),
+            "Merchant" => Some(Self::Merchant),
+            _ => None,
+        }
+    }
+}
+
+impl FeeRule {
+    /// ## Description
+    /// Fee for the positive `amount`, before rounding it to the currency
+    pub fn compute(&self, amount: Decimal) -> Decimal {
+        match self {
+            Self::Fixed { amount: fee } => *fee,
+            Self::Percentage { percent } => amount * percent / PERCENT_MAX,
+            Self::Tiered { tiers } => tiers
+                .iter()
+                .find(|tier| tier.up_to.is_none_or(|up_to| amount <= up_to))
+                .map_or(Decimal::ZERO, |tier| {
+                    tier.fixed + amount * tier.percent / PERCENT_MAX
+                }),
+            Self::Capped { rule, min, max } => {
+                let mut fee = rule.compute(amount);
+                if let Some(min) = min {
+                    fee = fee.max(*min);
+                }
+                if let Some(max) = max {
+                    fee = fee.min(*max);
+                }
+                fee
+            }
+        }
+    }
+
+    /// Checks the amounts of the rule are exact in the schedule currency, and its percentages in range
+    fn validate(&self, errors: &mut Vec<FieldError>, currency: Option<Currency>) {
+        let fee_amount = |errors: &mut Vec<FieldError>, field, amount| {
+            validation::amount(errors, field, amount, Sign::NonNegative);
+            if let Some(currency) = currency {
+                validation::minor_units(errors, field, amount, currency);
+            }
+        };
+
+        match self {
+            Self::Fixed { amount } => fee_amount(errors, "schedules.rule.amount", *amount),
+            Self::Percentage { percent } => percentage(errors, "schedules.rule.percent", *percent),
+            Self::Tiered { tiers } => {
+                if tiers.last().is_none_or(|tier| tier.up_to.is_some()) {
+                    errors.push(FieldError {
+                        field: "schedules.rule.tiers",
+                        code: FieldErrorCode::InvalidFormat,
+                        message: String::from(
+                            "Tiers cannot be empty, and the last one must have no up_to",
+                        ),
+                    });
+                }
+                let bounds = tiers.iter().filter_map(|tier| tier.up_to);
+                if bounds
+                    .clone()
+                    .zip(bounds.skip(1))
+                    .any(|(lower, upper)| lower >= upper)
+                {
+                    errors.push(FieldError {
+                        field: "schedules.rule.tiers",
+                        code: FieldErrorCode::InvalidFormat,
+                        message: String::from("Tiers must be sorted by increasing up_to"),
+                    });
+                }
+                for tier in tiers {
+                    if let Some(up_to) = tier.up_to {
+                        validation::amount(
+                            errors,
+                            "schedules.rule.tiers.up_to",
+                            up_to,
+                            Sign::Positive,
+                        );
+                    }
+                    fee_amount(errors, "schedules.rule.tiers.fixed", tier.fixed);
+                    percentage(errors, "schedules.rule.tiers.percent", tier.percent);
+                }
+            }
+            Self::Capped { rule, min, max } => {
+                rule.validate(errors, currency);
+                if let Some(min) = min {
+                    fee_amount(errors, "schedules.rule.min", *min);
+                }
+                if let Some(max) = max {
+                    fee_amount(errors, "schedules.rule.max", *max);
+                }
+                if let (Some(min), Some(max)) = (min, max) {
+                    if min > max {
+                        errors.push(FieldError {
+                            field: "schedules.rule.min",
+                            code: FieldErrorCode::OutOfRange,
+                            message: String::from("Minimum fee cannot be greater than the maximum"),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Checks a percentage is between 0 and 100, with at most `PERCENT_SCALE` decimal places
+fn percentage(errors: &mut Vec<FieldError>, field: &'static str, percent: Decimal) {
+    if percent.normalize().scale() > PERCENT_SCALE {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidScale,
+            message: format!(
+                "Percentage cannot have more than {} decimal places",
+                PERCENT_SCALE
+            ),
+        });
+    }
+    if percent < Decimal::ZERO || percent > PERCENT_MAX {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::OutOfRange,
+            message: format!("Percentage must be between 0 and {}", PERCENT_MAX),
+        });
+    }
+}
+
+impl FeeSchedule {
+    fn applies_to(
+        &self,
+        kind: TransactionKind,
+        merchants_id: Option<MerchantsIdType>,
+        currency: Currency,
+    ) -> bool {
+        self.currency == currency
+            && self.kind.is_none_or(|schedule_kind| schedule_kind == kind)
+            && self
+                .merchants_id
+                .is_none_or(|schedule_merchant| Some(schedule_merchant) == merchants_id)
+    }
+}
+
+impl FeeBreakdown {
+    pub fn new(amount: Decimal, currency: Currency, fees: Vec<Fee>) -> Self {
+        Self {
+            amount,
+            currency,
+            total: amount - payer_total(&fees),
+            fees,
+        }
+    }
+}
+
+impl NewSchedules {
+    /// Schedules of a validated list
+    fn into_schedules(self) -> Vec<FeeSchedule> {
+        self.schedules
+            .into_iter()
+            .filter_map(|schedule| {
+                Some(FeeSchedule {
+                    party: schedule.party,
+                    kind: schedule.kind,
+                    merchants_id: schedule.merchants_id,
+                    currency: Currency::from_code(&schedule.currency)?,
+                    rule: schedule.rule,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Validate for NewSchedules {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut keys = Vec::new();
+        for schedule in &self.schedules {
+            validation::currency(&mut errors, "schedules.currency", &schedule.currency);
+            schedule
+                .rule
+                .validate(&mut errors, Currency::from_code(&schedule.currency));
+
+            let key = (
+                schedule.party,
+                schedule.kind,
+                schedule.merchants_id,
+                &schedule.currency,
+            );
+            if keys.contains(&key) {
+                errors.push(FieldError {
+                    field: "schedules",
+                    code: FieldErrorCode::InvalidFormat,
+                    message: format!(
+                        "{} fee in {} for {} payments to {} is scheduled twice",
+                        schedule.party,
+                        schedule.currency,
+                        schedule
+                            .kind
+                            .map_or(String::from("all"), |kind| kind.to_string()),
+                        schedule
+                            .merchants_id
+                            .map_or(String::from("any merchant"), |merchants_id| {
+                                format!("merchant {}", merchants_id)
+                            })
+                    ),
+                });
+            }
+            keys.push(key);
+        }
+        errors
+    }
+}
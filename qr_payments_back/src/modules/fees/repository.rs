@@ -0,0 +1,12 @@
+use error_mapper::TheResult;
+
+use super::FeeSchedule;
+
+/// Persistence of the fee schedules, implemented by every storage backend. They are shared by every
+/// instance of the API
+pub trait FeeRepository: Send + Sync {
+    /// Selects every schedule, in the order they were listed
+    fn select_schedules(&self) -> TheResult<Vec<FeeSchedule>>;
+    /// Replaces every schedule. Fees already recorded keep their amounts
+    fn replace_schedules(&self, schedules: &[FeeSchedule]) -> TheResult<()>;
+}
@@ -0,0 +1,111 @@
+use actix_web::{get, put, web, HttpResponse};
+use the_logger::TheLogger;
+use utoipa::OpenApi;
+
+use crate::{
+    api::{
+        error::{ApiError, ErrorBody},
+        validation::ValidJson,
+    },
+    database, log_error, log_info,
+    modules::{
+        fees::{repository::FeeRepository, FeeSchedule, NewSchedules},
+        merchants::repository::MerchantRepository,
+    },
+};
+
+pub fn fees_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_schedules).service(put_schedules);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_schedules, put_schedules), tags((name = "fees")))]
+pub struct FeesApi;
+
+/// /v1/fees/schedules
+#[utoipa::path(
+    tag = "fees",
+    summary = "Lists the fee schedules currently applied",
+    responses(
+        (status = 200, description = "Fee schedules", body = Vec<FeeSchedule>),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/schedules")]
+async fn get_schedules(fees: web::Data<dyn FeeRepository>) -> Result<HttpResponse, ApiError> {
+    match database::blocking(&fees, |repository| repository.select_schedules()).await {
+        Ok(schedules) => Ok(HttpResponse::Ok().json(schedules)),
+        Err(error) => {
+            log_error!(
+                TheLogger::instance(),
+                "Could not get fee schedules: {}",
+                error
+            );
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/fees/schedules
+#[utoipa::path(
+    tag = "fees",
+    summary = "Replaces every fee schedule, recorded fees keep their amounts",
+    request_body = NewSchedules,
+    responses(
+        (status = 200, description = "New fee schedules", body = Vec<FeeSchedule>),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid schedules", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[put("/schedules")]
+async fn put_schedules(
+    body: ValidJson<NewSchedules>,
+    fees: web::Data<dyn FeeRepository>,
+    merchants: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let schedules = body.into_inner().into_schedules();
+
+    //  Schedules of a single merchant require it to exist
+    let merchants = match database::blocking(&merchants, |repository| repository.select_all()).await
+    {
+        Ok(merchants) => merchants,
+        Err(error) => {
+            log_error!(logger, "Error selecting merchants: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    if let Some(merchants_id) = schedules
+        .iter()
+        .filter_map(|schedule| schedule.merchants_id)
+        .find(|merchants_id| {
+            !merchants
+                .iter()
+                .any(|merchant| merchant.id == *merchants_id)
+        })
+    {
+        let error = ApiError::merchant_not_found(merchants_id);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+
+    match database::blocking(&fees, move |repository| {
+        repository.replace_schedules(&schedules).map(|_| schedules)
+    })
+    .await
+    {
+        Ok(schedules) => {
+            log_info!(
+                logger,
+                "Replaced fee schedules, {} schedule(s) applied",
+                schedules.len()
+            );
+            Ok(HttpResponse::Ok().json(schedules))
+        }
+        Err(error) => {
+            log_error!(logger, "Could not replace fee schedules: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
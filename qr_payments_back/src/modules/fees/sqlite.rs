@@ -0,0 +1,157 @@
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{params, Connection, Params};
+
+use crate::{
+    database::sqlite::{currency_column, decimal_column, variant_column, SqliteStore},
+    datatypes::TransactionsIdType,
+    modules::transactions::TransactionKind,
+};
+
+use super::{repository::FeeRepository, Fee, FeeParty, FeeSchedule};
+
+impl FeeRepository for SqliteStore {
+    fn select_schedules(&self) -> TheResult<Vec<FeeSchedule>> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT * FROM `fee_schedules` ORDER BY `ID`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let schedules = stmt
+            .query_map([], schedule_from_row)
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+            .collect();
+        schedules
+    }
+
+    fn replace_schedules(&self, schedules: &[FeeSchedule]) -> TheResult<()> {
+        self.in_transaction(|db_transaction| {
+            db_transaction
+                .execute("DELETE FROM `fee_schedules`;", [])
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            for schedule in schedules {
+                let rule = serde_json::to_string(&schedule.rule)
+                    .map_err(|error| create_new_error!(error.to_string()))?;
+                db_transaction
+                    .execute(
+                        "INSERT INTO `fee_schedules`(`party`, `kind`, `merchants_ID`, `currency`, `rule`) VALUES(?, ?, ?, ?, ?);",
+                        params![
+                            schedule.party.to_string(),
+                            schedule.kind.map(|kind| kind.to_string()),
+                            schedule.merchants_id,
+                            schedule.currency.code(),
+                            rule,
+                        ],
+                    )
+                    .map_err(|error| create_new_error!(error.to_string()))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// ## Description
+/// Appends lines to the fee ledger of a transaction. Must run on the same SQLite transaction as the change
+/// of the transaction they belong to
+pub fn insert_lines(
+    conn: &Connection,
+    transactions_id: TransactionsIdType,
+    fees: &[Fee],
+) -> TheResult<()> {
+    for fee in fees {
+        conn.execute(
+            "INSERT INTO `transaction_fees`(`transactions_ID`, `party`, `amount`, `currency`, `created_at`) VALUES(?, ?, ?, ?, ?);",
+            params![
+                transactions_id,
+                fee.party.to_string(),
+                fee.amount.to_string(),
+                fee.currency.code(),
+                chrono::Local::now().naive_local(),
+            ],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// ## Description
+/// Selects the fee ledger of a transaction, oldest line first
+pub fn select_by_transactions_id(
+    conn: &Connection,
+    transactions_id: TransactionsIdType,
+) -> TheResult<Vec<Fee>> {
+    let mut stmt = conn
+        .prepare("SELECT * FROM `transaction_fees` WHERE `transactions_ID` = ? ORDER BY `ID`;")
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+    let fees = stmt
+        .query_map(params![transactions_id], from_row)
+        .map_err(|error| create_new_error!(error.to_string()))?
+        .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+        .collect();
+    fees
+}
+
+/// ## Description
+/// Selects the fee lines of the transactions matching the condition on the `transactions` table, along with
+/// the ID of their transaction, oldest line first
+pub fn select_by_transactions(
+    conn: &Connection,
+    condition: &str,
+    params: impl Params,
+) -> TheResult<Vec<(TransactionsIdType, Fee)>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT `transaction_fees`.* FROM `transaction_fees` JOIN `transactions` ON `transactions`.`ID` = `transaction_fees`.`transactions_ID` WHERE {} ORDER BY `transaction_fees`.`ID`;",
+            condition
+        ))
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+    let fees = stmt
+        .query_map(params, |row| {
+            Ok((row.get("transactions_ID")?, from_row(row)?))
+        })
+        .map_err(|error| create_new_error!(error.to_string()))?
+        .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+        .collect();
+    fees
+}
+
+fn schedule_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<FeeSchedule> {
+    let kind = match row.get::<_, Option<String>>("kind")? {
+        Some(_) => Some(variant_column(row, "kind", TransactionKind::from_string)?),
+        None => None,
+    };
+    let rule = row.get::<_, String>("rule")?;
+
+    Ok(FeeSchedule {
+        party: variant_column(row, "party", FeeParty::from_string)?,
+        kind,
+        merchants_id: row.get("merchants_ID")?,
+        currency: currency_column(row, "currency")?,
+        rule: serde_json::from_str(&rule).map_err(|error| {
+            rusqlite::Error::FromSqlConversionFailure(
+                0,
+                rusqlite::types::Type::Text,
+                Box::new(error),
+            )
+        })?,
+    })
+}
+
+fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Fee> {
+    let party = row.get::<_, String>("party")?;
+
+    Ok(Fee {
+        party: FeeParty::from_string(party).ok_or_else(|| {
+            rusqlite::Error::InvalidColumnType(
+                0,
+                String::from("party"),
+                rusqlite::types::Type::Text,
+            )
+        })?,
+        amount: decimal_column(row, "amount")?,
+        currency: currency_column(row, "currency")?,
+    })
+}
@@ -0,0 +1,253 @@
+use rust_decimal::Decimal;
+use serde_json::json;
+
+use crate::{
+    modules::{currencies::Currency, transactions::TransactionKind},
+    validation::Validate,
+};
+
+use super::{calculate, payer_total, refunds, Fee, FeeParty, FeeRule, FeeSchedule, NewSchedules};
+
+fn decimal(value: &str) -> Decimal {
+    value.parse().expect("decimal should parse")
+}
+
+fn rule(rule: serde_json::Value) -> FeeRule {
+    serde_json::from_value(rule).expect("rule should deserialize")
+}
+
+fn new_schedules(schedules: serde_json::Value) -> NewSchedules {
+    serde_json::from_value(json!({ "schedules": schedules })).expect("schedules should deserialize")
+}
+
+fn fee_schedules(schedules: serde_json::Value) -> Vec<FeeSchedule> {
+    let schedules = new_schedules(schedules);
+    assert!(schedules.validate().is_empty());
+    schedules.into_schedules()
+}
+
+fn fee(party: FeeParty, amount: &str, currency: Currency) -> Fee {
+    Fee {
+        party,
+        amount: decimal(amount),
+        currency,
+    }
+}
+
+#[test]
+fn rules_compute_their_fee_from_the_amount() {
+    let cases = [
+        (json!({ "type": "fixed", "amount": "0.30" }), "250", "0.30"),
+        (
+            json!({ "type": "percentage", "percent": "2.5" }),
+            "250",
+            "6.25",
+        ),
+        (json!({ "type": "percentage", "percent": "0" }), "250", "0"),
+        (
+            json!({ "type": "capped", "rule": { "type": "percentage", "percent": "1.5" }, "min": "0.50", "max": "5" }),
+            "10",
+            "0.50",
+        ),
+        (
+            json!({ "type": "capped", "rule": { "type": "percentage", "percent": "1.5" }, "min": "0.50", "max": "5" }),
+            "100",
+            "1.5",
+        ),
+        (
+            json!({ "type": "capped", "rule": { "type": "percentage", "percent": "1.5" }, "min": "0.50", "max": "5" }),
+            "1000",
+            "5",
+        ),
+        (
+            json!({ "type": "capped", "rule": { "type": "fixed", "amount": "2" }, "max": "1" }),
+            "1000",
+            "1",
+        ),
+    ];
+
+    for (fee_rule, amount, expected) in cases {
+        assert_eq!(
+            rule(fee_rule.clone()).compute(decimal(amount)),
+            decimal(expected),
+            "{} on {}",
+            fee_rule,
+            amount
+        );
+    }
+}
+
+#[test]
+fn tiers_apply_up_to_and_including_their_bound() {
+    let tiered = rule(json!({
+        "type": "tiered",
+        "tiers": [
+            { "up_to": "100", "fixed": "0.25" },
+            { "up_to": "1000", "fixed": "0.10", "percent": "1" },
+            { "percent": "0.5" },
+        ],
+    }));
+
+    for (amount, expected) in [
+        ("0.01", "0.25"),
+        ("100", "0.25"),
+        ("100.01", "1.1001"),
+        ("1000", "10.10"),
+        ("1000.01", "5.00005"),
+    ] {
+        assert_eq!(
+            tiered.compute(decimal(amount)),
+            decimal(expected),
+            "tier of {}",
+            amount
+        );
+    }
+}
+
+#[test]
+fn the_most_specific_schedule_takes_precedence() {
+    let usd = Currency::USD;
+    let schedules = fee_schedules(json!([
+        { "party": "Payer", "currency": "USD", "rule": { "type": "fixed", "amount": "1" } },
+        { "party": "Payer", "kind": "Conversion", "currency": "USD", "rule": { "type": "fixed", "amount": "2" } },
+        { "party": "Merchant", "kind": "Payment", "currency": "USD", "rule": { "type": "percentage", "percent": "1" } },
+        { "party": "Payer", "currency": "EUR", "rule": { "type": "fixed", "amount": "3" } },
+        { "party": "Payer", "merchants_id": 7, "currency": "USD", "rule": { "type": "fixed", "amount": "0.25" } },
+        { "party": "Merchant", "merchants_id": 7, "kind": "Payment", "currency": "USD", "rule": { "type": "percentage", "percent": "0.5" } },
+    ]));
+    let fees = |kind, merchants_id| calculate(&schedules, kind, merchants_id, decimal("-50"), usd);
+
+    assert_eq!(
+        fees(TransactionKind::Payment, None),
+        vec![
+            fee(FeeParty::Payer, "1", usd),
+            fee(FeeParty::Merchant, "0.50", usd),
+        ]
+    );
+    assert_eq!(
+        fees(TransactionKind::Conversion, Some(8)),
+        vec![fee(FeeParty::Payer, "2", usd)]
+    );
+    //  The schedule of the merchant applies over the one of the payment kind
+    assert_eq!(
+        fees(TransactionKind::Conversion, Some(7)),
+        vec![fee(FeeParty::Payer, "0.25", usd)]
+    );
+    assert_eq!(
+        fees(TransactionKind::Payment, Some(7)),
+        vec![
+            fee(FeeParty::Payer, "0.25", usd),
+            fee(FeeParty::Merchant, "0.25", usd),
+        ]
+    );
+    assert!(calculate(
+        &schedules,
+        TransactionKind::Payment,
+        Some(7),
+        decimal("-50"),
+        Currency::from_code("GBP").unwrap()
+    )
+    .is_empty());
+}
+
+#[test]
+fn fees_are_rounded_to_the_currency_and_zero_fees_left_out() {
+    let jpy = Currency::from_code("JPY").unwrap();
+    let schedules = fee_schedules(json!([
+        { "party": "Payer", "currency": "USD", "rule": { "type": "percentage", "percent": "2.5" } },
+        { "party": "Payer", "currency": "JPY", "rule": { "type": "percentage", "percent": "2.5" } },
+    ]));
+
+    //  2.5% of 0.10 is 0.0025, and of 0.30 0.0075: banker's rounding takes them to 0.00 and 0.01
+    for (amount, expected) in [
+        ("-0.30", Some("0.01")),
+        ("-0.10", None),
+        ("-12.34", Some("0.31")),
+    ] {
+        let fees = calculate(
+            &schedules,
+            TransactionKind::Payment,
+            None,
+            decimal(amount),
+            Currency::USD,
+        );
+        assert_eq!(
+            fees,
+            expected
+                .map(|expected| vec![fee(FeeParty::Payer, expected, Currency::USD)])
+                .unwrap_or_default(),
+            "fees of {}",
+            amount
+        );
+    }
+    assert_eq!(
+        calculate(
+            &schedules,
+            TransactionKind::Payment,
+            None,
+            decimal("-1234"),
+            jpy
+        ),
+        vec![fee(FeeParty::Payer, "31", jpy)]
+    );
+}
+
+#[test]
+fn refunds_return_every_fee() {
+    let usd = Currency::USD;
+    let charged = vec![
+        fee(FeeParty::Payer, "1.50", usd),
+        fee(FeeParty::Merchant, "0.33", usd),
+    ];
+
+    let refunded = refunds(&charged);
+    assert_eq!(
+        refunded,
+        vec![
+            fee(FeeParty::Payer, "-1.50", usd),
+            fee(FeeParty::Merchant, "-0.33", usd),
+        ]
+    );
+    assert_eq!(
+        payer_total(&charged) + payer_total(&refunded),
+        Decimal::ZERO
+    );
+}
+
+#[test]
+fn schedules_are_validated() {
+    let fields = |schedules: serde_json::Value| {
+        new_schedules(schedules)
+            .validate()
+            .into_iter()
+            .map(|error| error.field)
+            .collect::<Vec<_>>()
+    };
+
+    assert!(fields(json!([
+        { "party": "Payer", "currency": "USD", "rule": { "type": "percentage", "percent": "2.9999" } },
+        { "party": "Payer", "kind": "Payment", "currency": "USD", "rule": { "type": "fixed", "amount": "0.30" } },
+        { "party": "Merchant", "currency": "JPY", "rule": { "type": "tiered", "tiers": [{ "up_to": "1000", "fixed": "10" }, { "percent": "1" }] } },
+    ]))
+    .is_empty());
+    assert_eq!(
+        fields(json!([
+            { "party": "Payer", "currency": "XAU", "rule": { "type": "percentage", "percent": "100.00001" } },
+            { "party": "Payer", "currency": "JPY", "rule": { "type": "fixed", "amount": "0.5" } },
+            { "party": "Payer", "currency": "JPY", "rule": { "type": "fixed", "amount": "1" } },
+            { "party": "Merchant", "currency": "USD", "rule": { "type": "tiered", "tiers": [{ "up_to": "100" }, { "up_to": "10" }] } },
+            { "party": "Merchant", "currency": "EUR", "rule": { "type": "capped", "rule": { "type": "fixed", "amount": "-1" }, "min": "2", "max": "1" } },
+        ])),
+        vec![
+            "schedules.currency",
+            "schedules.rule.percent",
+            "schedules.rule.percent",
+            "schedules.rule.amount",
+            "schedules",
+            "schedules.rule.tiers",
+            "schedules.rule.tiers",
+            "schedules.rule.amount",
+            "schedules.rule.min",
+        ]
+    );
+}
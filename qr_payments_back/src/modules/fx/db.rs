@@ -0,0 +1,171 @@
+use chrono::NaiveDateTime;
+use error_mapper::{create_new_error, TheResult};
+use mysql::{prelude::Queryable, PooledConn};
+use rust_decimal::Decimal;
+
+use crate::{
+    database::{
+        decode::{self, DecodeRow, RowError},
+        in_transaction, Executor, MySqlStore,
+    },
+    from_row_via_decode,
+    modules::currencies::Currency,
+    row_to_data,
+};
+
+use super::{repository::FxRepository, Quote, Rate, RateTable};
+
+impl FxRepository for MySqlStore {
+    fn select_rates(&self) -> TheResult<RateTable> {
+        RateTable::select(&mut self.get_conn()?)
+    }
+
+    fn replace_rates(&self, rates: &RateTable) -> TheResult<()> {
+        in_transaction(&mut self.get_conn()?, |db_transaction| {
+            rates.replace(db_transaction)
+        })
+    }
+
+    fn select_quote(&self, quotes_id: &str) -> TheResult<Option<Quote>> {
+        Quote::select_by_id(&mut self.get_conn()?, quotes_id)
+    }
+
+    fn insert_quote(&self, quote: &Quote, now: NaiveDateTime) -> TheResult<()> {
+        let mut conn = self.get_conn()?;
+        Quote::delete_expired(&mut conn, now)?;
+        quote.insert(&mut conn)
+    }
+
+    fn delete_quote(&self, quotes_id: &str) -> TheResult<bool> {
+        Quote::delete(&mut self.get_conn()?, quotes_id)
+    }
+}
+
+impl RateTable {
+    /// ## Description
+    /// Selects every rate, the table being as old as its latest update
+    fn select(conn: &mut PooledConn) -> TheResult<Self> {
+        let rows = conn
+            .query::<mysql::Row, _>(
+                "SELECT * FROM `fx_rates` ORDER BY `from_currency`, `to_currency`;",
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let mut updated_at = None;
+        let mut rates = Vec::new();
+        for row in &rows {
+            let rate_updated_at =
+                decode::column::<NaiveDateTime>(row, "fx_rates", "updated_at", "NaiveDateTime")
+                    .map_err(|error| create_new_error!(error.to_string()))?;
+            updated_at = updated_at.max(Some(rate_updated_at));
+            rates.push(Rate::decode(row).map_err(|error| create_new_error!(error.to_string()))?);
+        }
+
+        Ok(Self { rates, updated_at })
+    }
+
+    fn replace(&self, conn: &mut mysql::Transaction<'_>) -> TheResult<()> {
+        conn.query_drop("DELETE FROM `fx_rates`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let query = "INSERT INTO `fx_rates`(`from_currency`, `to_currency`, `rate`, `updated_at`) VALUES(?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_batch(
+            stmt,
+            self.rates.iter().map(|rate| {
+                (
+                    rate.from.code(),
+                    rate.to.code(),
+                    rate.rate.to_string(),
+                    self.updated_at,
+                )
+            }),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))
+    }
+}
+
+impl Quote {
+    fn select_by_id(conn: &mut PooledConn, quotes_id: &str) -> TheResult<Option<Self>> {
+        let query = "SELECT * FROM `fx_quotes` WHERE `ID` = ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, (quotes_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn insert(&self, conn: &mut PooledConn) -> TheResult<()> {
+        let query = "INSERT INTO `fx_quotes`(`ID`, `from_currency`, `to_currency`, `rate`, `amount`, `source_amount`, `created_at`, `expires_at`) VALUES(?, ?, ?, ?, ?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(
+            stmt,
+            (
+                self.id.as_str(),
+                self.from.code(),
+                self.to.code(),
+                self.rate.to_string(),
+                self.amount.to_string(),
+                self.source_amount.to_string(),
+                self.created_at,
+                self.expires_at,
+            ),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn delete_expired(conn: &mut PooledConn, now: NaiveDateTime) -> TheResult<()> {
+        let query = "DELETE FROM `fx_quotes` WHERE `expires_at` <= ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(stmt, (now,))
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Deletes the quote. Concurrent payments locking it are serialized by the row lock, so only one of
+    /// them deletes it
+    fn delete(conn: &mut PooledConn, quotes_id: &str) -> TheResult<bool> {
+        let query = "DELETE FROM `fx_quotes` WHERE `ID` = ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(stmt, (quotes_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(Executor::affected_rows(conn) > 0)
+    }
+}
+
+impl DecodeRow for Rate {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            from: decode::variant(row, "fx_rates", "from_currency", Currency::from_string)?,
+            to: decode::variant(row, "fx_rates", "to_currency", Currency::from_string)?,
+            rate: row_to_data!(row, "rate", "fx_rates", Decimal),
+        })
+    }
+}
+
+impl DecodeRow for Quote {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            id: row_to_data!(row, "ID", "fx_quotes", String),
+            from: decode::variant(row, "fx_quotes", "from_currency", Currency::from_string)?,
+            to: decode::variant(row, "fx_quotes", "to_currency", Currency::from_string)?,
+            rate: row_to_data!(row, "rate", "fx_quotes", Decimal),
+            amount: row_to_data!(row, "amount", "fx_quotes", Decimal),
+            source_amount: row_to_data!(row, "source_amount", "fx_quotes", Decimal),
+            created_at: row_to_data!(row, "created_at", "fx_quotes", NaiveDateTime),
+            expires_at: row_to_data!(row, "expires_at", "fx_quotes", NaiveDateTime),
+        })
+    }
+}
+
+from_row_via_decode!(Quote);
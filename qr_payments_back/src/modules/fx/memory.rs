@@ -0,0 +1,33 @@
+use chrono::NaiveDateTime;
+use error_mapper::TheResult;
+
+use crate::database::memory::InMemoryStore;
+
+use super::{repository::FxRepository, Quote, RateTable};
+
+impl FxRepository for InMemoryStore {
+    fn select_rates(&self) -> TheResult<RateTable> {
+        Ok(self.lock()?.fx_rates.clone())
+    }
+
+    fn replace_rates(&self, rates: &RateTable) -> TheResult<()> {
+        self.lock()?.fx_rates = rates.clone();
+        Ok(())
+    }
+
+    fn select_quote(&self, quotes_id: &str) -> TheResult<Option<Quote>> {
+        Ok(self.lock()?.fx_quotes.get(quotes_id).cloned())
+    }
+
+    fn insert_quote(&self, quote: &Quote, now: NaiveDateTime) -> TheResult<()> {
+        let mut state = self.lock()?;
+        state.fx_quotes.retain(|_, quote| quote.expires_at > now);
+        state.fx_quotes.insert(quote.id.clone(), quote.clone());
+
+        Ok(())
+    }
+
+    fn delete_quote(&self, quotes_id: &str) -> TheResult<bool> {
+        Ok(self.lock()?.fx_quotes.remove(quotes_id).is_some())
+    }
+}
@@ -0,0 +1,288 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use error_mapper::{create_new_error, TheResult};
+use rand::Rng;
+use rand_distr::Alphanumeric;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    modules::currencies::Currency,
+    validation::{self, FieldError, Validate, AMOUNT_MAX, TOKEN_LENGTH},
+};
+
+use self::repository::FxRepository;
+
+mod db;
+#[cfg(test)]
+mod memory;
+pub mod repository;
+pub mod services;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+/// Units of `to` one unit of `from` buys. Only the listed direction is quoted, inverses are not derived
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct Rate {
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: Decimal,
+}
+
+/// Rates currently quoted, with the last time they were replaced
+#[derive(Debug, Default, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RateTable {
+    pub rates: Vec<Rate>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+/// ## Description
+/// Rate locked for paying `amount` in `to` from a balance in `from`, until `expires_at`. The amount
+/// debited from the `from` balance is fixed on creation, so later rate changes don't affect it
+#[derive(Debug, Default, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Quote {
+    pub id: String,
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: Decimal,
+    /// Amount paid, in `to`
+    pub amount: Decimal,
+    /// Amount debited, in `from`, rounded up to its minor unit
+    pub source_amount: Decimal,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Side of a payment converted from the debited currency, as recorded on its transaction
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Conversion {
+    pub quotes_id: String,
+    /// Units of `currency` per unit of the debited currency
+    pub rate: Decimal,
+    /// Amount paid, with the sign of the transaction amount
+    pub amount: Decimal,
+    pub currency: Currency,
+}
+
+/// Reason a quote could not be created or locked
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuoteError {
+    /// No rate converts `from` to `to`
+    Unavailable {
+        from: Currency,
+        to: Currency,
+    },
+    /// The amount converted to `from` does not fit the amount columns
+    OutOfRange {
+        from: Currency,
+    },
+    NotFound(String),
+    Expired(String),
+    /// The quote was created for other currencies or another amount than the payment's
+    Mismatch(String),
+}
+
+/// Rate table as written in the rates file and sent to `PUT /v1/fx/rates`
+#[derive(Debug, Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize, Default))]
+pub struct NewRates {
+    rates: Vec<NewRate>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize))]
+struct NewRate {
+    #[schema(value_type = Currency)]
+    from: String,
+    #[schema(value_type = Currency)]
+    to: String,
+    /// Units of `to` one unit of `from` buys
+    rate: Decimal,
+}
+
+/// ## Description
+/// Amount of `from` that pays `amount` at `rate`. The quotient is rounded away from zero to the minor
+/// unit of `from`, so the debit always covers the amount paid, by less than one minor unit
+pub fn source_amount(amount: Decimal, rate: Decimal, from: Currency) -> Decimal {
+    from.round_up(amount / rate)
+}
+
+/// ## Description
+/// Reads the rates of a rates file, validated as the ones sent to `PUT /v1/fx/rates`
+pub fn load_rates_file(path: &str) -> TheResult<Vec<Rate>> {
+    let content = std::fs::read_to_string(path).map_err(|error| {
+        create_new_error!(format!("Could not read rates file {}: {}", path, error))
+    })?;
+    let rates = serde_json::from_str::<NewRates>(&content).map_err(|error| {
+        create_new_error!(format!("Could not parse rates file {}: {}", path, error))
+    })?;
+    let errors = rates.validate();
+    if !errors.is_empty() {
+        let messages = errors
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect::<Vec<_>>();
+        return Err(create_new_error!(format!(
+            "Invalid rates file {}: {}",
+            path,
+            messages.join("; ")
+        )));
+    }
+
+    Ok(rates.into_rates())
+}
+
+/// ## Description
+/// Stores the rates of the rates file when no rates are stored yet. Rates replaced through
+/// `PUT /v1/fx/rates` are kept across restarts, and shared by every instance of the API
+///
+/// ### Returns
+/// True if the rates were stored
+pub fn seed_rates(
+    repository: &dyn FxRepository,
+    rates: Vec<Rate>,
+    now: NaiveDateTime,
+) -> TheResult<bool> {
+    if repository.select_rates()?.updated_at.is_some() {
+        return Ok(false);
+    }
+    repository.replace_rates(&RateTable::new(rates, now))?;
+
+    Ok(true)
+}
+
+impl RateTable {
+    /// Table replacing every rate at `now`, sorted by pair
+    pub fn new(mut rates: Vec<Rate>, now: NaiveDateTime) -> Self {
+        rates.sort_by_key(|rate| (rate.from, rate.to));
+        Self {
+            rates,
+            updated_at: Some(now),
+        }
+    }
+
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<Decimal> {
+        self.rates
+            .iter()
+            .find(|rate| rate.from == from && rate.to == to)
+            .map(|rate| rate.rate)
+    }
+
+    /// ## Description
+    /// Quotes paying the positive `amount` of `to` from `from` at the current rate, open for `ttl`
+    pub fn quote(
+        &self,
+        from: Currency,
+        to: Currency,
+        amount: Decimal,
+        now: NaiveDateTime,
+        ttl: TimeDelta,
+    ) -> Result<Quote, QuoteError> {
+        let rate = self
+            .rate(from, to)
+            .ok_or(QuoteError::Unavailable { from, to })?;
+        let source_amount = source_amount(amount, rate, from);
+        if source_amount > AMOUNT_MAX {
+            return Err(QuoteError::OutOfRange { from });
+        }
+
+        Ok(Quote {
+            id: rand::rng()
+                .sample_iter(&Alphanumeric)
+                .take(TOKEN_LENGTH)
+                .map(char::from)
+                .collect(),
+            from,
+            to,
+            rate,
+            amount,
+            source_amount,
+            created_at: now,
+            expires_at: now + ttl,
+        })
+    }
+
+    /// ## Description
+    /// Quotes the same as `quote`, already expired: it is never stored, so no one else can lock it
+    pub fn spot(
+        &self,
+        from: Currency,
+        to: Currency,
+        amount: Decimal,
+        now: NaiveDateTime,
+    ) -> Result<Quote, QuoteError> {
+        self.quote(from, to, amount, now, TimeDelta::zero())
+    }
+}
+
+impl Quote {
+    /// ## Description
+    /// Checks an open quote can pay the positive `amount` of `to` from `from`. Only the payment removing it
+    /// from the stored quotes locks it
+    pub fn check(
+        &self,
+        from: Currency,
+        to: Currency,
+        amount: Decimal,
+        now: NaiveDateTime,
+    ) -> Result<(), QuoteError> {
+        if self.expires_at <= now {
+            return Err(QuoteError::Expired(self.id.clone()));
+        }
+        if self.from != from || self.to != to || self.amount != amount {
+            return Err(QuoteError::Mismatch(self.id.clone()));
+        }
+        Ok(())
+    }
+
+    /// Paid side of a payment debiting the quote, which carries the sign of the debit
+    pub fn conversion(&self) -> Conversion {
+        Conversion {
+            quotes_id: self.id.clone(),
+            rate: self.rate,
+            amount: -self.amount,
+            currency: self.to,
+        }
+    }
+}
+
+impl NewRates {
+    /// Rates of a validated table
+    fn into_rates(self) -> Vec<Rate> {
+        self.rates
+            .into_iter()
+            .filter_map(|rate| {
+                Some(Rate {
+                    from: Currency::from_code(&rate.from)?,
+                    to: Currency::from_code(&rate.to)?,
+                    rate: rate.rate,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Validate for NewRates {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut pairs = Vec::new();
+        for rate in &self.rates {
+            validation::currency(&mut errors, "rates.from", &rate.from);
+            validation::currency(&mut errors, "rates.to", &rate.to);
+            validation::pair(&mut errors, "rates", &rate.from, &rate.to);
+            validation::rate(&mut errors, "rates.rate", rate.rate);
+
+            if pairs.contains(&(&rate.from, &rate.to)) {
+                errors.push(FieldError {
+                    field: "rates",
+                    code: validation::FieldErrorCode::InvalidPair,
+                    message: format!("Rate from {} to {} is listed twice", rate.from, rate.to),
+                });
+            }
+            pairs.push((&rate.from, &rate.to));
+        }
+        errors
+    }
+}
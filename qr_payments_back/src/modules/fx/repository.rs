@@ -0,0 +1,17 @@
+use chrono::NaiveDateTime;
+use error_mapper::TheResult;
+
+use super::{Quote, RateTable};
+
+/// Persistence of the rates and the open quotes, implemented by every storage backend. They are shared by
+/// every instance of the API, so a quote created by one can be locked through another
+pub trait FxRepository: Send + Sync {
+    fn select_rates(&self) -> TheResult<RateTable>;
+    /// Replaces every rate. Open quotes keep the rate they were created with
+    fn replace_rates(&self, rates: &RateTable) -> TheResult<()>;
+    fn select_quote(&self, quotes_id: &str) -> TheResult<Option<Quote>>;
+    /// Stores an open quote, dropping the ones expired by `now` on the way
+    fn insert_quote(&self, quote: &Quote, now: NaiveDateTime) -> TheResult<()>;
+    /// Removes a quote as a payment locks it. False if another payment removed it first
+    fn delete_quote(&self, quotes_id: &str) -> TheResult<bool>;
+}
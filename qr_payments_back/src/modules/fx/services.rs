@@ -0,0 +1,203 @@
+use actix_web::{get, post, put, web, HttpResponse};
+use chrono::TimeDelta;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use the_logger::TheLogger;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    api::{
+        error::{ApiError, ErrorBody},
+        validation::ValidJson,
+    },
+    config::Config,
+    database, log_error, log_info,
+    modules::{
+        currencies::Currency,
+        fx::{repository::FxRepository, NewRates, Quote, RateTable},
+    },
+    validation::{self, FieldError, Sign, Validate},
+};
+
+pub fn fx_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_rates).service(put_rates).service(new_quote);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_rates, put_rates, new_quote), tags((name = "fx")))]
+pub struct FxApi;
+
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(serde::Serialize, Default))]
+pub(crate) struct NewQuoteRequest {
+    #[schema(value_type = Currency)]
+    from: String,
+    #[schema(value_type = Currency)]
+    to: String,
+    /// Positive amount to pay, in whole minor units of `to`
+    #[schema(example = "100.00")]
+    amount: Decimal,
+}
+
+impl Validate for NewQuoteRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        validation::currency(&mut errors, "from", &self.from);
+        validation::currency(&mut errors, "to", &self.to);
+        validation::pair(&mut errors, "to", &self.from, &self.to);
+        validation::amount(&mut errors, "amount", self.amount, Sign::Positive);
+        if let Some(to) = Currency::from_code(&self.to) {
+            validation::minor_units(&mut errors, "amount", self.amount, to);
+        }
+        errors
+    }
+}
+
+/// /v1/fx/rates
+#[utoipa::path(
+    tag = "fx",
+    summary = "Gets the rates currently quoted",
+    responses(
+        (status = 200, description = "Rates", body = RateTable),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/rates")]
+async fn get_rates(fx: web::Data<dyn FxRepository>) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+
+    match database::blocking(&fx, |repository| repository.select_rates()).await {
+        Ok(rates) => Ok(HttpResponse::Ok().json(rates)),
+        Err(error) => {
+            log_error!(logger, "Could not get rates: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/fx/rates
+#[utoipa::path(
+    tag = "fx",
+    summary = "Replaces every rate, open quotes keep their rate",
+    request_body = NewRates,
+    responses(
+        (status = 200, description = "New rates", body = RateTable),
+        (status = 422, description = "Invalid rates", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[put("/rates")]
+async fn put_rates(
+    body: ValidJson<NewRates>,
+    fx: web::Data<dyn FxRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let now = chrono::Local::now().naive_local();
+    let rates = RateTable::new(body.into_inner().into_rates(), now);
+
+    match database::blocking(&fx, move |repository| {
+        repository.replace_rates(&rates).map(|_| rates)
+    })
+    .await
+    {
+        Ok(rates) => {
+            log_info!(
+                logger,
+                "Replaced rates, {} pair(s) quoted",
+                rates.rates.len()
+            );
+            Ok(HttpResponse::Ok().json(rates))
+        }
+        Err(error) => {
+            log_error!(logger, "Could not replace rates: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/fx/quotes
+#[utoipa::path(
+    tag = "fx",
+    summary = "Quotes a payment in another currency, to be locked by it before expiring",
+    request_body = NewQuoteRequest,
+    responses(
+        (status = 200, description = "Quote", body = Quote),
+        (status = 422, description = "Invalid fields, or no rate converts the currencies", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[post("/quotes")]
+async fn new_quote(
+    body: ValidJson<NewQuoteRequest>,
+    fx: web::Data<dyn FxRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let body = body.into_inner();
+    let (Some(from), Some(to)) = (
+        Currency::from_code(&body.from),
+        Currency::from_code(&body.to),
+    ) else {
+        return Err(ApiError::validation("Unsupported currency"));
+    };
+
+    let ttl = match Config::get_fx_config().await {
+        Ok(config) => match i64::try_from(config.quote_ttl_secs)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+        {
+            Some(ttl) => ttl,
+            None => {
+                log_error!(
+                    logger,
+                    "Quote TTL of {} seconds is out of range",
+                    config.quote_ttl_secs
+                );
+                return Err(ApiError::internal());
+            }
+        },
+        Err(error) => {
+            log_error!(logger, "Could not get fx configurations: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    let now = chrono::Local::now().naive_local();
+
+    let rates = match database::blocking(&fx, |repository| repository.select_rates()).await {
+        Ok(rates) => rates,
+        Err(error) => {
+            log_error!(logger, "Could not get rates: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    let quote = match rates.quote(from, to, body.amount, now, ttl) {
+        Ok(quote) => quote,
+        Err(error) => {
+            let error = ApiError::quote(error);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+    };
+
+    match database::blocking(&fx, move |repository| {
+        repository.insert_quote(&quote, now).map(|_| quote)
+    })
+    .await
+    {
+        Ok(quote) => {
+            log_info!(
+                logger,
+                "Quoted {} {} for {} {} at {}",
+                quote.amount,
+                quote.to,
+                quote.source_amount,
+                quote.from,
+                quote.rate
+            );
+            Ok(HttpResponse::Ok().json(quote))
+        }
+        Err(error) => {
+            log_error!(logger, "Could not create quote: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
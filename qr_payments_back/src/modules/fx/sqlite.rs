@@ -0,0 +1,119 @@
+use chrono::NaiveDateTime;
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{params, OptionalExtension};
+
+use crate::database::sqlite::{currency_column, decimal_column, SqliteStore};
+
+use super::{repository::FxRepository, Quote, Rate, RateTable};
+
+impl FxRepository for SqliteStore {
+    fn select_rates(&self) -> TheResult<RateTable> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT * FROM `fx_rates` ORDER BY `from_currency`, `to_currency`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    Rate {
+                        from: currency_column(row, "from_currency")?,
+                        to: currency_column(row, "to_currency")?,
+                        rate: decimal_column(row, "rate")?,
+                    },
+                    row.get::<_, NaiveDateTime>("updated_at")?,
+                ))
+            })
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(RateTable {
+            updated_at: rows.iter().map(|(_, updated_at)| *updated_at).max(),
+            rates: rows.into_iter().map(|(rate, _)| rate).collect(),
+        })
+    }
+
+    fn replace_rates(&self, rates: &RateTable) -> TheResult<()> {
+        self.in_transaction(|db_transaction| {
+            db_transaction
+                .execute("DELETE FROM `fx_rates`;", [])
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            for rate in &rates.rates {
+                db_transaction
+                    .execute(
+                        "INSERT INTO `fx_rates`(`from_currency`, `to_currency`, `rate`, `updated_at`) VALUES(?, ?, ?, ?);",
+                        params![
+                            rate.from.code(),
+                            rate.to.code(),
+                            rate.rate.to_string(),
+                            rates.updated_at,
+                        ],
+                    )
+                    .map_err(|error| create_new_error!(error.to_string()))?;
+            }
+            Ok(())
+        })
+    }
+
+    fn select_quote(&self, quotes_id: &str) -> TheResult<Option<Quote>> {
+        self.lock()?
+            .query_row(
+                "SELECT * FROM `fx_quotes` WHERE `ID` = ?;",
+                params![quotes_id],
+                quote_from_row,
+            )
+            .optional()
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn insert_quote(&self, quote: &Quote, now: NaiveDateTime) -> TheResult<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "DELETE FROM `fx_quotes` WHERE `expires_at` <= ?;",
+            params![now],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.execute(
+            "INSERT INTO `fx_quotes`(`ID`, `from_currency`, `to_currency`, `rate`, `amount`, `source_amount`, `created_at`, `expires_at`) VALUES(?, ?, ?, ?, ?, ?, ?, ?);",
+            params![
+                quote.id,
+                quote.from.code(),
+                quote.to.code(),
+                quote.rate.to_string(),
+                quote.amount.to_string(),
+                quote.source_amount.to_string(),
+                quote.created_at,
+                quote.expires_at,
+            ],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(())
+    }
+
+    fn delete_quote(&self, quotes_id: &str) -> TheResult<bool> {
+        let affected_rows = self
+            .lock()?
+            .execute(
+                "DELETE FROM `fx_quotes` WHERE `ID` = ?;",
+                params![quotes_id],
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(affected_rows > 0)
+    }
+}
+
+fn quote_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Quote> {
+    Ok(Quote {
+        id: row.get("ID")?,
+        from: currency_column(row, "from_currency")?,
+        to: currency_column(row, "to_currency")?,
+        rate: decimal_column(row, "rate")?,
+        amount: decimal_column(row, "amount")?,
+        source_amount: decimal_column(row, "source_amount")?,
+        created_at: row.get("created_at")?,
+        expires_at: row.get("expires_at")?,
+    })
+}
@@ -0,0 +1,327 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use rust_decimal::Decimal;
+
+use crate::{database::memory::InMemoryStore, modules::currencies::Currency, validation::Validate};
+
+use super::{
+    repository::FxRepository, seed_rates, source_amount, NewRates, Quote, QuoteError, Rate,
+    RateTable,
+};
+
+fn currency(code: &str) -> Currency {
+    Currency::from_code(code).expect("currency should be supported")
+}
+
+fn decimal(value: &str) -> Decimal {
+    value.parse().expect("decimal should parse")
+}
+
+fn now() -> NaiveDateTime {
+    NaiveDateTime::parse_from_str("2026-10-19 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+fn table(rates: &[(&str, &str, &str)]) -> RateTable {
+    let rates = rates
+        .iter()
+        .map(|(from, to, rate)| Rate {
+            from: currency(from),
+            to: currency(to),
+            rate: decimal(rate),
+        })
+        .collect();
+    RateTable::new(rates, now())
+}
+
+fn store(rates: &[(&str, &str, &str)]) -> InMemoryStore {
+    let store = InMemoryStore::default();
+    store.replace_rates(&table(rates)).unwrap();
+    store
+}
+
+/// Quotes paying `amount` of `to` from `from` at the stored rates, open for a minute
+fn open_quote(store: &InMemoryStore, from: Currency, to: Currency, amount: Decimal) -> Quote {
+    let quote = store
+        .select_rates()
+        .unwrap()
+        .quote(from, to, amount, now(), TimeDelta::seconds(60))
+        .unwrap();
+    store.insert_quote(&quote, now()).unwrap();
+    quote
+}
+
+/// ## Description
+/// Rounds `units` of `10^-(minor_units + dropped)` to `minor_units` decimal places with integer arithmetic,
+/// the reference the decimal rounding is checked against
+fn reference_round(units: i64, dropped: u32, minor_units: u32, half_even: bool) -> Decimal {
+    let divisor = 10_i64.pow(dropped);
+    let (mut quotient, remainder) = (units / divisor, (units % divisor).abs());
+    let sign = units.signum();
+
+    let round_away = if half_even {
+        remainder * 2 > divisor || (remainder * 2 == divisor && quotient % 2 != 0)
+    } else {
+        remainder > 0
+    };
+    if round_away {
+        quotient += sign;
+    }
+    Decimal::new(quotient, minor_units)
+}
+
+#[test]
+fn round_is_bankers_rounding_for_every_amount() {
+    for (code, dropped) in [("USD", 1), ("USD", 3), ("JPY", 1), ("JPY", 2)] {
+        let currency = currency(code);
+        let scale = currency.minor_units() + dropped;
+        for units in -200_000..=200_000 {
+            let amount = Decimal::new(units, scale);
+            assert_eq!(
+                currency.round(amount),
+                reference_round(units, dropped, currency.minor_units(), true),
+                "{} rounding {}",
+                code,
+                amount
+            );
+        }
+    }
+}
+
+#[test]
+fn round_up_rounds_every_remainder_away_from_zero() {
+    for (code, dropped) in [("USD", 1), ("USD", 3), ("JPY", 1), ("JPY", 2)] {
+        let currency = currency(code);
+        let scale = currency.minor_units() + dropped;
+        for units in -200_000..=200_000 {
+            let amount = Decimal::new(units, scale);
+            assert_eq!(
+                currency.round_up(amount),
+                reference_round(units, dropped, currency.minor_units(), false),
+                "{} rounding up {}",
+                code,
+                amount
+            );
+        }
+    }
+}
+
+#[test]
+fn source_amount_covers_the_paid_amount_by_less_than_one_minor_unit() {
+    let pairs = [
+        ("USD", "EUR"),
+        ("USD", "JPY"),
+        ("JPY", "USD"),
+        ("CLP", "KRW"),
+    ];
+    let rates = [
+        "0.0000012345",
+        "0.0068",
+        "0.3333333333",
+        "0.92",
+        "1",
+        "1.0000000001",
+        "7.1234567891",
+        "149.87",
+        "1337.5",
+    ];
+
+    for (from, to) in pairs {
+        let (from, to) = (currency(from), currency(to));
+        let unit = Decimal::new(1, from.minor_units());
+        for rate in rates.map(decimal) {
+            for units in 1..=10_000 {
+                let amount = Decimal::new(units, to.minor_units());
+                let source = source_amount(amount, rate, from);
+
+                assert!(
+                    from.is_exact(source),
+                    "{} {} at {}: {} is not in whole {} units",
+                    amount,
+                    to,
+                    rate,
+                    source,
+                    from
+                );
+                assert!(
+                    source * rate >= amount,
+                    "{} {} at {}: {} {} does not cover it",
+                    amount,
+                    to,
+                    rate,
+                    source,
+                    from
+                );
+                assert!(
+                    (source - unit) * rate < amount,
+                    "{} {} at {}: {} {} is more than one minor unit over",
+                    amount,
+                    to,
+                    rate,
+                    source,
+                    from
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn source_amount_ignores_the_representation_of_its_inputs() {
+    let usd = Currency::USD;
+    let expected = source_amount(decimal("100"), decimal("0.92"), usd);
+
+    assert_eq!(expected, decimal("108.70"));
+    for (amount, rate) in [
+        ("100.00", "0.92"),
+        ("100", "0.9200000000"),
+        ("100.0", "0.920"),
+    ] {
+        assert_eq!(source_amount(decimal(amount), decimal(rate), usd), expected);
+    }
+    //  Exact quotients are never rounded up
+    assert_eq!(
+        source_amount(decimal("50"), decimal("0.5"), usd),
+        decimal("100")
+    );
+    assert_eq!(
+        source_amount(decimal("1499"), decimal("149.9"), currency("JPY")),
+        decimal("10")
+    );
+}
+
+#[test]
+fn quotes_are_locked_once_before_they_expire() {
+    let (usd, eur) = (Currency::USD, currency("EUR"));
+    let store = store(&[("USD", "EUR", "0.92")]);
+    let amount = decimal("100");
+
+    let quote = open_quote(&store, usd, eur, amount);
+    assert_eq!(quote.source_amount, decimal("108.70"));
+    assert_eq!(quote.expires_at, now() + TimeDelta::seconds(60));
+
+    let later = now() + TimeDelta::seconds(59);
+    let stored = store.select_quote(&quote.id).unwrap().unwrap();
+    assert_eq!(stored, quote);
+    assert_eq!(stored.check(usd, eur, amount, later), Ok(()));
+    assert!(store.delete_quote(&quote.id).unwrap());
+    assert!(!store.delete_quote(&quote.id).unwrap());
+    assert_eq!(store.select_quote(&quote.id).unwrap(), None);
+
+    let expiring = open_quote(&store, usd, eur, amount);
+    let expired = now() + TimeDelta::seconds(60);
+    assert_eq!(
+        expiring.check(usd, eur, amount, expired),
+        Err(QuoteError::Expired(expiring.id.clone()))
+    );
+    //  Expired quotes are dropped as new ones are stored
+    let rates = store.select_rates().unwrap();
+    let quote = rates
+        .quote(usd, eur, amount, expired, TimeDelta::seconds(60))
+        .unwrap();
+    store.insert_quote(&quote, expired).unwrap();
+    assert_eq!(store.select_quote(&expiring.id).unwrap(), None);
+}
+
+#[test]
+fn quotes_only_pay_the_payment_they_were_created_for() {
+    let (usd, eur, gbp) = (Currency::USD, currency("EUR"), currency("GBP"));
+    let store = store(&[("USD", "EUR", "0.92"), ("USD", "GBP", "0.79")]);
+    let quote = open_quote(&store, usd, eur, decimal("100"));
+
+    for (from, to, amount) in [(usd, eur, "100.01"), (usd, gbp, "100"), (gbp, eur, "100")] {
+        assert_eq!(
+            quote.check(from, to, decimal(amount), now()),
+            Err(QuoteError::Mismatch(quote.id.clone()))
+        );
+    }
+    assert_eq!(quote.check(usd, eur, decimal("100.00"), now()), Ok(()));
+}
+
+#[test]
+fn replacing_rates_keeps_open_quotes_at_their_rate() {
+    let (usd, eur) = (Currency::USD, currency("EUR"));
+    let store = store(&[("USD", "EUR", "0.92")]);
+    let quote = open_quote(&store, usd, eur, decimal("100"));
+
+    store
+        .replace_rates(&RateTable::new(Vec::new(), now()))
+        .unwrap();
+    assert_eq!(
+        store
+            .select_rates()
+            .unwrap()
+            .spot(usd, eur, decimal("100"), now()),
+        Err(QuoteError::Unavailable { from: usd, to: eur })
+    );
+
+    let stored = store.select_quote(&quote.id).unwrap().unwrap();
+    assert_eq!(stored.rate, decimal("0.92"));
+    assert_eq!(stored.source_amount, decimal("108.70"));
+}
+
+#[test]
+fn rates_are_seeded_only_when_none_are_stored() {
+    let (usd, eur) = (Currency::USD, currency("EUR"));
+    let store = InMemoryStore::default();
+    assert_eq!(store.select_rates().unwrap().updated_at, None);
+
+    assert!(seed_rates(&store, table(&[("USD", "EUR", "0.92")]).rates, now()).unwrap());
+    let later = now() + TimeDelta::days(1);
+    assert!(!seed_rates(&store, table(&[("USD", "EUR", "0.5")]).rates, later).unwrap());
+
+    let rates = store.select_rates().unwrap();
+    assert_eq!(rates.rate(usd, eur), Some(decimal("0.92")));
+    assert_eq!(rates.updated_at, Some(now()));
+}
+
+#[test]
+fn rates_are_quoted_in_their_direction_only() {
+    let (usd, jpy) = (Currency::USD, currency("JPY"));
+    let rates = table(&[("USD", "JPY", "149.87")]);
+
+    assert!(rates.spot(usd, jpy, decimal("1000"), now()).is_ok());
+    assert_eq!(
+        rates.spot(jpy, usd, decimal("10"), now()),
+        Err(QuoteError::Unavailable { from: jpy, to: usd })
+    );
+    //  Paying 1 USD at that rate takes more JPY than the amount columns hold
+    let rates = table(&[("JPY", "USD", "0.0000000001")]);
+    assert_eq!(
+        rates.spot(jpy, usd, decimal("1"), now()),
+        Err(QuoteError::OutOfRange { from: jpy })
+    );
+}
+
+#[test]
+fn rate_tables_are_validated() {
+    let rates = |rates: serde_json::Value| {
+        serde_json::from_value::<NewRates>(serde_json::json!({ "rates": rates }))
+            .unwrap()
+            .validate()
+            .into_iter()
+            .map(|error| error.field)
+            .collect::<Vec<_>>()
+    };
+
+    assert!(rates(serde_json::json!([
+        { "from": "USD", "to": "EUR", "rate": "0.92" },
+        { "from": "EUR", "to": "USD", "rate": "1.0869565217" },
+    ]))
+    .is_empty());
+    assert_eq!(
+        rates(serde_json::json!([
+            { "from": "USD", "to": "USD", "rate": "1" },
+            { "from": "USD", "to": "EUR", "rate": "0.92" },
+            { "from": "USD", "to": "EUR", "rate": "0.93" },
+            { "from": "usd", "to": "XAU", "rate": "0" },
+            { "from": "EUR", "to": "GBP", "rate": "0.00000000001" },
+        ])),
+        vec![
+            "rates",
+            "rates",
+            "rates.from",
+            "rates.to",
+            "rates.rate",
+            "rates.rate"
+        ]
+    );
+}
@@ -0,0 +1,128 @@
+use chrono::NaiveDateTime;
+use error_mapper::{create_new_error, TheResult};
+use mysql::prelude::Queryable;
+
+use crate::{
+    database::{
+        decode::{self, DecodeRow, RowError},
+        in_transaction, Executor, MySqlStore,
+    },
+    datatypes::{MerchantsIdType, WalletsIdType},
+    from_row_via_decode,
+    modules::{currencies::Currency, wallets::Wallet},
+    row_to_data,
+};
+
+use super::{repository::MerchantRepository, Merchant, MerchantDetails, MerchantStatus};
+
+/// Merchants along with the currency of their settlement wallet
+const SELECT_MERCHANTS: &str = "SELECT `merchants`.*, `wallets`.`currency` FROM `merchants` JOIN `wallets` ON `wallets`.`ID` = `merchants`.`wallets_ID`";
+
+impl Merchant {
+    pub(crate) fn select_by_id(
+        conn: &mut impl Queryable,
+        merchants_id: MerchantsIdType,
+    ) -> TheResult<Option<Self>> {
+        let query = format!("{} WHERE `merchants`.`ID` = ?;", SELECT_MERCHANTS);
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, (merchants_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+}
+
+impl MerchantRepository for MySqlStore {
+    fn select_all(&self) -> TheResult<Vec<Merchant>> {
+        let rows = self
+            .get_conn()?
+            .query::<mysql::Row, _>(format!("{} ORDER BY `merchants`.`ID`;", SELECT_MERCHANTS))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_by_id(&self, merchants_id: MerchantsIdType) -> TheResult<Option<Merchant>> {
+        Merchant::select_by_id(&mut self.get_conn()?, merchants_id)
+    }
+
+    fn insert(&self, details: MerchantDetails, currency: Currency) -> TheResult<Merchant> {
+        in_transaction(&mut self.get_conn()?, |db_transaction| {
+            let wallet = Wallet::insert(db_transaction, currency)?;
+
+            let query = "INSERT INTO `merchants`(`wallets_ID`, `name`, `city`, `country`, `mcc`) VALUES(?, ?, ?, ?, ?);";
+            let stmt = db_transaction
+                .prep(query)
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            db_transaction
+                .exec_drop(
+                    stmt,
+                    (
+                        wallet.id,
+                        &details.name,
+                        &details.city,
+                        &details.country,
+                        &details.mcc,
+                    ),
+                )
+                .map_err(|error| create_new_error!(error.to_string()))?;
+
+            Ok(Merchant {
+                id: Executor::last_insert_id(db_transaction),
+                wallets_id: wallet.id,
+                currency,
+                details,
+                status: MerchantStatus::default(),
+                created_at: chrono::Local::now().naive_local(),
+            })
+        })
+    }
+
+    fn update(&self, merchant: &Merchant) -> TheResult<()> {
+        if merchant.id == 0 {
+            return Err(create_new_error!(
+                "Merchant cannot have an ID of zero when updating it"
+            ));
+        }
+
+        //  Affected rows is not checked, MySQL reports zero when nothing changed
+        let query = "UPDATE `merchants` SET `name` = ?, `city` = ?, `country` = ?, `mcc` = ?, `status` = ? WHERE `ID` = ?;";
+        let mut conn = self.get_conn()?;
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(
+            stmt,
+            (
+                &merchant.details.name,
+                &merchant.details.city,
+                &merchant.details.country,
+                &merchant.details.mcc,
+                merchant.status.to_string(),
+                merchant.id,
+            ),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))
+    }
+}
+
+impl DecodeRow for Merchant {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            id: row_to_data!(row, "ID", "merchants", MerchantsIdType),
+            wallets_id: row_to_data!(row, "wallets_ID", "merchants", WalletsIdType),
+            currency: decode::variant(row, "wallets", "currency", Currency::from_string)?,
+            details: MerchantDetails {
+                name: row_to_data!(row, "name", "merchants", String),
+                city: row_to_data!(row, "city", "merchants", String),
+                country: row_to_data!(row, "country", "merchants", String),
+                mcc: row_to_data!(row, "mcc", "merchants", String),
+            },
+            status: decode::variant(row, "merchants", "status", MerchantStatus::from_string)?,
+            created_at: row_to_data!(row, "created_at", "merchants", NaiveDateTime),
+        })
+    }
+}
+
+from_row_via_decode!(Merchant);
@@ -0,0 +1,56 @@
+use error_mapper::{create_new_error, TheResult};
+
+use crate::{
+    database::memory::InMemoryStore,
+    datatypes::MerchantsIdType,
+    modules::{currencies::Currency, wallets::Wallet},
+};
+
+use super::{repository::MerchantRepository, Merchant, MerchantDetails, MerchantStatus};
+
+impl MerchantRepository for InMemoryStore {
+    fn select_all(&self) -> TheResult<Vec<Merchant>> {
+        Ok(self.lock()?.merchants.values().cloned().collect())
+    }
+
+    fn select_by_id(&self, merchants_id: MerchantsIdType) -> TheResult<Option<Merchant>> {
+        Ok(self.lock()?.merchants.get(&merchants_id).cloned())
+    }
+
+    fn insert(&self, details: MerchantDetails, currency: Currency) -> TheResult<Merchant> {
+        let mut state = self.lock()?;
+        let wallet = Wallet {
+            id: state.next_wallets_id(),
+            currency,
+            ..Default::default()
+        };
+        let merchant = Merchant {
+            id: state.next_merchants_id(),
+            wallets_id: wallet.id,
+            currency,
+            details,
+            status: MerchantStatus::default(),
+            created_at: chrono::Local::now().naive_local(),
+        };
+
+        state.wallets.insert(wallet.id, wallet);
+        state.merchants.insert(merchant.id, merchant.clone());
+
+        Ok(merchant)
+    }
+
+    fn update(&self, merchant: &Merchant) -> TheResult<()> {
+        let mut state = self.lock()?;
+        let Some(stored) = state.merchants.get_mut(&merchant.id) else {
+            return Err(create_new_error!(format!(
+                "Merchant with ID: {} does not exist",
+                merchant.id
+            )));
+        };
+
+        stored.details = merchant.details.clone();
+        stored.status = merchant.status;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    datatypes::{MerchantsIdType, WalletsIdType},
+    modules::currencies::Currency,
+    validation::{self, FieldError, FieldErrorCode, Validate},
+};
+
+mod db;
+#[cfg(test)]
+mod memory;
+pub mod qr;
+pub mod repository;
+pub mod services;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod statements;
+#[cfg(test)]
+mod tests;
+
+/// Max length of the merchant name in a QR payload, and of the `name` column in the `merchants` table
+pub const NAME_MAX_LENGTH: usize = 25;
+/// Max length of the merchant city in a QR payload, and of the `city` column in the `merchants` table
+pub const CITY_MAX_LENGTH: usize = 15;
+
+/// Business paid through QR codes. What it collects is settled into a wallet of its own, created along with
+/// the merchant
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct Merchant {
+    pub id: MerchantsIdType,
+    /// Settlement wallet
+    pub wallets_id: WalletsIdType,
+    /// Currency of the settlement wallet, in which the merchant is paid
+    pub currency: Currency,
+    #[serde(flatten)]
+    pub details: MerchantDetails,
+    /// Suspended merchants accept no new payments, the ones already started can still be closed
+    pub status: MerchantStatus,
+    pub created_at: NaiveDateTime,
+}
+
+/// What a merchant shows in its QR codes: name, city and ISO 3166-1 alpha-2 country, plus its ISO 18245
+/// merchant category code
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct MerchantDetails {
+    #[schema(max_length = 25, example = "Cafe Central")]
+    pub name: String,
+    #[schema(max_length = 15, example = "Springfield")]
+    pub city: String,
+    #[schema(pattern = "^[A-Z]{2}$", example = "US")]
+    pub country: String,
+    #[schema(pattern = "^[0-9]{4}$", example = "5812")]
+    pub mcc: String,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, strum::Display, ToSchema,
+)]
+pub enum MerchantStatus {
+    #[default]
+    Active,
+    Suspended,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize, Default))]
+pub struct NewMerchant {
+    /// Currency of the settlement wallet, which payments to the merchant are made in
+    #[schema(value_type = Currency)]
+    pub currency: String,
+    #[serde(flatten)]
+    pub details: MerchantDetails,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(Serialize, Default))]
+pub struct MerchantStatusRequest {
+    pub status: MerchantStatus,
+}
+
+impl Merchant {
+    pub fn is_active(&self) -> bool {
+        self.status == MerchantStatus::Active
+    }
+}
+
+impl MerchantStatus {
+    pub(crate) fn from_string(input: String) -> Option<Self> {
+        match input.as_str() {
+            "Active" => Some(Self::Active),
+            "Suspended" => Some(Self::Suspended),
+            _ => None,
+        }
+    }
+}
+
+impl Validate for MerchantDetails {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        text(&mut errors, "name", &self.name, NAME_MAX_LENGTH);
+        text(&mut errors, "city", &self.city, CITY_MAX_LENGTH);
+        if self.country.len() != 2 || !self.country.chars().all(|char| char.is_ascii_uppercase()) {
+            errors.push(FieldError {
+                field: "country",
+                code: FieldErrorCode::InvalidFormat,
+                message: String::from(
+                    "Country must be an ISO 3166-1 alpha-2 code of two uppercase letters",
+                ),
+            });
+        }
+        if self.mcc.len() != 4 || !self.mcc.chars().all(|char| char.is_ascii_digit()) {
+            errors.push(FieldError {
+                field: "mcc",
+                code: FieldErrorCode::InvalidFormat,
+                message: String::from("Merchant category code must be four digits"),
+            });
+        }
+        errors
+    }
+}
+
+impl Validate for NewMerchant {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        validation::currency(&mut errors, "currency", &self.currency);
+        errors.extend(self.details.validate());
+        errors
+    }
+}
+
+impl Validate for MerchantStatusRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        Vec::new()
+    }
+}
+
+/// ## Description
+/// Checks a text shown in QR codes is not blank, fits its length and is printable ASCII, so its length in
+/// the payload is its length in bytes
+fn text(errors: &mut Vec<FieldError>, field: &'static str, value: &str, max_length: usize) {
+    if value.trim().is_empty() || value.len() > max_length {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::OutOfRange,
+            message: format!("Must have between 1 and {} characters", max_length),
+        });
+    }
+    if !value
+        .chars()
+        .all(|char| char.is_ascii() && !char.is_ascii_control())
+    {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidFormat,
+            message: String::from("Must only have printable ASCII characters"),
+        });
+    }
+}
@@ -0,0 +1,71 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::Merchant;
+
+/// Globally unique identifier of the service, in the merchant account information of the payloads
+pub const GLOBAL_UNIQUE_ID: &str = "com.qrpayments";
+
+/// Merchant-presented QR code, as the text to encode in it
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct QrCode {
+    pub payload: String,
+}
+
+/// ## Description
+/// Builds the EMV merchant-presented QR payload of a merchant. With an amount the code is dynamic, meant
+/// for a single payment, and without one it is static and the payer enters the amount
+///
+/// ### Parameters
+/// - merchant: merchant paid, whose ID goes in the merchant account information
+/// - amount: amount to pay in the merchant currency, positive and in whole minor units
+pub fn payload(merchant: &Merchant, amount: Option<Decimal>) -> String {
+    let mut account = String::new();
+    field(&mut account, "00", GLOBAL_UNIQUE_ID);
+    field(&mut account, "01", &merchant.id.to_string());
+
+    let mut payload = String::new();
+    field(&mut payload, "00", "01");
+    field(
+        &mut payload,
+        "01",
+        if amount.is_some() { "12" } else { "11" },
+    );
+    field(&mut payload, "26", &account);
+    field(&mut payload, "52", &merchant.details.mcc);
+    field(&mut payload, "53", merchant.currency.numeric());
+    if let Some(amount) = amount {
+        let minor_units = merchant.currency.minor_units() as usize;
+        field(&mut payload, "54", &format!("{:.*}", minor_units, amount));
+    }
+    field(&mut payload, "58", &merchant.details.country);
+    field(&mut payload, "59", &merchant.details.name);
+    field(&mut payload, "60", &merchant.details.city);
+
+    //  The checksum covers the whole payload, its own ID and length included
+    payload.push_str("6304");
+    let checksum = crc16(payload.as_bytes());
+    payload.push_str(&format!("{:04X}", checksum));
+    payload
+}
+
+/// Appends a data object as ID, two digit length and value. Values are ASCII, so their length in bytes is
+/// their length in characters
+fn field(payload: &mut String, id: &str, value: &str) {
+    payload.push_str(&format!("{}{:02}{}", id, value.len(), value));
+}
+
+/// ## Description
+/// CRC-16/CCITT-FALSE checksum: polynomial 0x1021, initial value 0xFFFF, as EMV QR codes require
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ (u16::from(*byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
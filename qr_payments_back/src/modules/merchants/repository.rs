@@ -0,0 +1,16 @@
+use error_mapper::TheResult;
+
+use crate::{datatypes::MerchantsIdType, modules::currencies::Currency};
+
+use super::{Merchant, MerchantDetails};
+
+/// Persistence of the merchants, implemented by every storage backend. Merchants are never deleted, as
+/// their transactions keep referencing them
+pub trait MerchantRepository: Send + Sync {
+    fn select_all(&self) -> TheResult<Vec<Merchant>>;
+    fn select_by_id(&self, merchants_id: MerchantsIdType) -> TheResult<Option<Merchant>>;
+    /// Inserts the merchant along with its empty settlement wallet in the received currency, atomically
+    fn insert(&self, details: MerchantDetails, currency: Currency) -> TheResult<Merchant>;
+    /// Updates the details and status of an existing merchant
+    fn update(&self, merchant: &Merchant) -> TheResult<()>;
+}
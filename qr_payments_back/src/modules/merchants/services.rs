@@ -0,0 +1,465 @@
+use actix_web::{get, post, put, web, HttpResponse};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use the_logger::TheLogger;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    api::{
+        error::{ApiError, ErrorBody},
+        validation::ValidJson,
+    },
+    database::blocking,
+    datatypes::MerchantsIdType,
+    log_error, log_info,
+    modules::{
+        currencies::Currency,
+        merchants::{
+            qr::{self, QrCode},
+            repository::MerchantRepository,
+            statements::{self, Statement},
+            Merchant, MerchantDetails, MerchantStatusRequest, NewMerchant,
+        },
+        settlements::repository::SettlementRepository,
+        transactions::{
+            export::{self, ExportQuery, TransactionFilter, TransactionOwner},
+            repository::TransactionRepository,
+        },
+    },
+    validation::{self, FieldError, Sign, Validate},
+};
+
+pub fn merchants_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_merchants)
+        .service(new_merchant)
+        .service(get_merchant)
+        .service(put_merchant)
+        .service(put_merchant_status)
+        .service(new_qr_code)
+        .service(export_merchant_transactions)
+        .service(get_statement);
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_merchants,
+        new_merchant,
+        get_merchant,
+        put_merchant,
+        put_merchant_status,
+        new_qr_code,
+        export_merchant_transactions,
+        get_statement
+    ),
+    tags((name = "merchants"))
+)]
+pub struct MerchantsApi;
+
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(serde::Serialize, Default))]
+pub(crate) struct NewQrCodeRequest {
+    /// Amount to pay, positive and in whole minor units of the merchant currency. Codes without one let
+    /// the payer enter it
+    #[schema(example = "12.50")]
+    amount: Option<Decimal>,
+}
+
+impl Validate for NewQrCodeRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(amount) = self.amount {
+            validation::amount(&mut errors, "amount", amount, Sign::Positive);
+        }
+        errors
+    }
+}
+
+/// /v1/merchants
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Lists every merchant",
+    responses(
+        (status = 200, description = "Merchants", body = Vec<Merchant>),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("")]
+async fn get_merchants(
+    repository: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+
+    log_info!(logger, "Selecting merchants...");
+
+    match blocking(&repository, |repository| repository.select_all()).await {
+        Ok(merchants) => Ok(HttpResponse::Ok().json(merchants)),
+        Err(error) => {
+            log_error!(logger, "Could not get merchants: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/merchants
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Registers a merchant along with its settlement wallet",
+    request_body = NewMerchant,
+    responses(
+        (status = 200, description = "Merchant", body = Merchant),
+        (status = 422, description = "Invalid fields", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[post("")]
+async fn new_merchant(
+    body: ValidJson<NewMerchant>,
+    repository: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let body = body.into_inner();
+    let currency = Currency::from_code(&body.currency).unwrap_or_default();
+
+    log_info!(logger, "Received new merchant request");
+
+    let merchant = match blocking(&repository, move |repository| {
+        repository.insert(body.details, currency)
+    })
+    .await
+    {
+        Ok(merchant) => merchant,
+        Err(error) => {
+            log_error!(logger, "Could not insert merchant: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    log_info!(
+        logger,
+        "Merchant created with ID: {}, settling into wallet with ID: {}",
+        merchant.id,
+        merchant.wallets_id
+    );
+    Ok(HttpResponse::Ok().json(merchant))
+}
+
+/// /v1/merchants/{merchants_id}
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Gets a merchant",
+    params(("merchants_id" = MerchantsIdType, Path)),
+    responses(
+        (status = 200, description = "Merchant", body = Merchant),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{merchants_id}")]
+async fn get_merchant(
+    path: web::Path<MerchantsIdType>,
+    repository: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let merchant = select_merchant(&repository, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(merchant))
+}
+
+/// /v1/merchants/{merchants_id}
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Replaces the details of a merchant",
+    params(("merchants_id" = MerchantsIdType, Path)),
+    request_body = MerchantDetails,
+    responses(
+        (status = 200, description = "Updated merchant", body = Merchant),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid fields", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[put("/{merchants_id}")]
+async fn put_merchant(
+    path: web::Path<MerchantsIdType>,
+    body: ValidJson<MerchantDetails>,
+    repository: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let mut merchant = select_merchant(&repository, path.into_inner()).await?;
+
+    log_info!(
+        logger,
+        "Updating details of merchant with ID: {}",
+        merchant.id
+    );
+
+    merchant.details = body.into_inner();
+    update_merchant(&repository, merchant).await
+}
+
+/// /v1/merchants/{merchants_id}/status
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Suspends or reactivates a merchant, suspended ones cannot be paid",
+    params(("merchants_id" = MerchantsIdType, Path)),
+    request_body = MerchantStatusRequest,
+    responses(
+        (status = 200, description = "Updated merchant", body = Merchant),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid fields", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[put("/{merchants_id}/status")]
+async fn put_merchant_status(
+    path: web::Path<MerchantsIdType>,
+    body: ValidJson<MerchantStatusRequest>,
+    repository: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let mut merchant = select_merchant(&repository, path.into_inner()).await?;
+    let status = body.into_inner().status;
+
+    log_info!(
+        logger,
+        "Changing status of merchant with ID: {} from {} to {}",
+        merchant.id,
+        merchant.status,
+        status
+    );
+
+    merchant.status = status;
+    update_merchant(&repository, merchant).await
+}
+
+/// /v1/merchants/{merchants_id}/qr
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Generates the EMV QR payload of an active merchant, dynamic when it carries an amount",
+    params(("merchants_id" = MerchantsIdType, Path)),
+    request_body = NewQrCodeRequest,
+    responses(
+        (status = 200, description = "QR code", body = QrCode),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid amount, or suspended merchant", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[post("/{merchants_id}/qr")]
+async fn new_qr_code(
+    path: web::Path<MerchantsIdType>,
+    body: ValidJson<NewQrCodeRequest>,
+    repository: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let merchant = select_merchant(&repository, path.into_inner()).await?;
+    let amount = body.into_inner().amount;
+
+    if !merchant.is_active() {
+        let error = ApiError::merchant_suspended(merchant.id);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+    if let Some(amount) = amount {
+        let mut errors = Vec::new();
+        validation::minor_units(&mut errors, "amount", amount, merchant.currency);
+        if !errors.is_empty() {
+            let error = ApiError::invalid_fields(errors);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+    }
+
+    log_info!(
+        logger,
+        "Generating QR code of merchant with ID: {}",
+        merchant.id
+    );
+    Ok(HttpResponse::Ok().json(QrCode {
+        payload: qr::payload(&merchant, amount),
+    }))
+}
+
+/// /v1/merchants/{merchants_id}/transactions/export
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Streams the transactions paid to a merchant created between two days",
+    params(("merchants_id" = MerchantsIdType, Path), ExportQuery),
+    responses(
+        (
+            status = 200,
+            description = "Transactions in ID order, downloaded as an attachment. CSV starts with a header \
+                row, NDJSON has one object per line",
+            content((String = "text/csv"), (String = "application/x-ndjson"))
+        ),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid range of days", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{merchants_id}/transactions/export")]
+async fn export_merchant_transactions(
+    path: web::Path<MerchantsIdType>,
+    query: web::Query<ExportQuery>,
+    repository: web::Data<dyn MerchantRepository>,
+    transactions: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let query = query.into_inner();
+
+    let errors = query.validate();
+    if !errors.is_empty() {
+        let error = ApiError::invalid_fields(errors);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+    let merchant = select_merchant(&repository, path.into_inner()).await?;
+    let (from, to) = match query.range(chrono::Local::now().date_naive()) {
+        Ok(range) => range,
+        Err(error) => {
+            let error = ApiError::invalid_fields(vec![error]);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+    };
+
+    log_info!(
+        logger,
+        "Exporting transactions of merchant with ID: {} from {} to {}",
+        merchant.id,
+        from,
+        to
+    );
+
+    Ok(export::export_response(
+        transactions,
+        TransactionFilter::days(TransactionOwner::Merchant(merchant.id), from, to),
+        query.format,
+        &format!("merchant-{}-transactions-{}-{}", merchant.id, from, to),
+    ))
+}
+
+/// /v1/merchants/{merchants_id}/statements/{month}
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Computes the statement of a merchant for a calendar month from its payments, refunds, fees and payouts",
+    params(
+        ("merchants_id" = MerchantsIdType, Path),
+        ("month" = String, Path, pattern = "^[0-9]{4}-[0-9]{2}$", example = "2026-01"),
+    ),
+    responses(
+        (status = 200, description = "Statement", body = Statement),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid month", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{merchants_id}/statements/{month}")]
+async fn get_statement(
+    path: web::Path<(MerchantsIdType, String)>,
+    repository: web::Data<dyn MerchantRepository>,
+    transactions: web::Data<dyn TransactionRepository>,
+    settlements: web::Data<dyn SettlementRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let (merchants_id, month) = path.into_inner();
+    let merchant = select_merchant(&repository, merchants_id).await?;
+
+    let month_start = match statements::month_start(&month, chrono::Local::now().date_naive()) {
+        Ok(month_start) => month_start,
+        Err(error) => {
+            let error = ApiError::invalid_fields(vec![error]);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+    };
+
+    log_info!(
+        logger,
+        "Computing statement of merchant with ID: {} for {}",
+        merchant.id,
+        month
+    );
+
+    //  Only the movements of the month are loaded, what was booked before is summed by the repositories
+    let from = month_start.and_time(chrono::NaiveTime::MIN);
+    let to = statements::next_month_start(month_start).and_time(chrono::NaiveTime::MIN);
+    let currency = merchant.currency;
+    let payments = blocking(&transactions, move |transactions| {
+        let balance = transactions.select_merchant_balance(merchants_id, currency, from)?;
+        let payments = transactions.select_merchant_ledger(merchants_id, from, to)?;
+        Ok((balance, payments))
+    })
+    .await;
+    let batches = blocking(&settlements, move |settlements| {
+        let paid_net = settlements.select_paid_net_before(merchants_id, from)?;
+        let batches = settlements.select_paid_by_merchants_id(merchants_id, from, to)?;
+        Ok((paid_net, batches))
+    })
+    .await;
+
+    match (payments, batches) {
+        (Ok((balance, payments)), Ok((paid_net, batches))) => {
+            Ok(HttpResponse::Ok().json(Statement::new(
+                &merchant,
+                month_start,
+                balance - paid_net,
+                &payments,
+                &batches,
+            )))
+        }
+        (Err(error), _) | (_, Err(error)) => {
+            log_error!(logger, "Could not compute statement: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// ## Description
+/// Selects a merchant, failing with 404 if it does not exist
+pub(in crate::modules) async fn select_merchant(
+    repository: &web::Data<dyn MerchantRepository>,
+    merchants_id: MerchantsIdType,
+) -> Result<Merchant, ApiError> {
+    let logger = TheLogger::instance();
+
+    match blocking(repository, move |repository| {
+        repository.select_by_id(merchants_id)
+    })
+    .await
+    {
+        Ok(Some(merchant)) => Ok(merchant),
+        Ok(None) => {
+            let error = ApiError::merchant_not_found(merchants_id);
+            log_info!(logger, "{}", error);
+            Err(error)
+        }
+        Err(error) => {
+            log_error!(logger, "Could not get merchant: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// ## Description
+/// Stores the changes made to a merchant, responding with the merchant updated
+async fn update_merchant(
+    repository: &web::Data<dyn MerchantRepository>,
+    merchant: Merchant,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+
+    match blocking(repository, move |repository| {
+        repository.update(&merchant).map(|()| merchant)
+    })
+    .await
+    {
+        Ok(merchant) => {
+            log_info!(logger, "Merchant updated successfully");
+            Ok(HttpResponse::Ok().json(merchant))
+        }
+        Err(error) => {
+            log_error!(logger, "Could not update merchant: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
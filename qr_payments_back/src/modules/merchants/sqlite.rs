@@ -0,0 +1,126 @@
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    database::sqlite::{currency_column, SqliteStore},
+    datatypes::{MerchantsIdType, WalletsIdType},
+    modules::{currencies::Currency, wallets::sqlite as wallets_sqlite},
+};
+
+use super::{repository::MerchantRepository, Merchant, MerchantDetails, MerchantStatus};
+
+/// Merchants along with the currency of their settlement wallet
+const SELECT_MERCHANTS: &str = "SELECT `merchants`.*, `wallets`.`currency` FROM `merchants` JOIN `wallets` ON `wallets`.`ID` = `merchants`.`wallets_ID`";
+
+impl MerchantRepository for SqliteStore {
+    fn select_all(&self) -> TheResult<Vec<Merchant>> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare(&format!("{} ORDER BY `merchants`.`ID`;", SELECT_MERCHANTS))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let merchants = stmt
+            .query_map([], from_row)
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+            .collect();
+        merchants
+    }
+
+    fn select_by_id(&self, merchants_id: MerchantsIdType) -> TheResult<Option<Merchant>> {
+        select_by_id(&*self.lock()?, merchants_id)
+    }
+
+    fn insert(&self, details: MerchantDetails, currency: Currency) -> TheResult<Merchant> {
+        self.in_transaction(|db_transaction| {
+            let wallet = wallets_sqlite::insert(db_transaction, currency)?;
+            let created_at = chrono::Local::now().naive_local();
+
+            db_transaction
+                .execute(
+                    "INSERT INTO `merchants`(`wallets_ID`, `name`, `city`, `country`, `mcc`, `created_at`) VALUES(?, ?, ?, ?, ?, ?);",
+                    params![
+                        wallet.id,
+                        details.name,
+                        details.city,
+                        details.country,
+                        details.mcc,
+                        created_at,
+                    ],
+                )
+                .map_err(|error| create_new_error!(error.to_string()))?;
+
+            Ok(Merchant {
+                id: db_transaction.last_insert_rowid() as MerchantsIdType,
+                wallets_id: wallet.id,
+                currency,
+                details,
+                status: MerchantStatus::default(),
+                created_at,
+            })
+        })
+    }
+
+    fn update(&self, merchant: &Merchant) -> TheResult<()> {
+        let affected_rows = self
+            .lock()?
+            .execute(
+                "UPDATE `merchants` SET `name` = ?, `city` = ?, `country` = ?, `mcc` = ?, `status` = ? WHERE `ID` = ?;",
+                params![
+                    merchant.details.name,
+                    merchant.details.city,
+                    merchant.details.country,
+                    merchant.details.mcc,
+                    merchant.status.to_string(),
+                    merchant.id,
+                ],
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        if affected_rows == 0 {
+            return Err(create_new_error!(format!(
+                "Merchant with ID: {} does not exist",
+                merchant.id
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+pub(in crate::modules) fn select_by_id(
+    conn: &Connection,
+    merchants_id: MerchantsIdType,
+) -> TheResult<Option<Merchant>> {
+    conn.query_row(
+        &format!("{} WHERE `merchants`.`ID` = ?;", SELECT_MERCHANTS),
+        params![merchants_id],
+        from_row,
+    )
+    .optional()
+    .map_err(|error| create_new_error!(error.to_string()))
+}
+
+fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Merchant> {
+    let status = row.get::<_, String>("status")?;
+
+    Ok(Merchant {
+        id: row.get("ID")?,
+        wallets_id: row.get::<_, WalletsIdType>("wallets_ID")?,
+        currency: currency_column(row, "currency")?,
+        details: MerchantDetails {
+            name: row.get("name")?,
+            city: row.get("city")?,
+            country: row.get("country")?,
+            mcc: row.get("mcc")?,
+        },
+        status: MerchantStatus::from_string(status).ok_or_else(|| {
+            rusqlite::Error::InvalidColumnType(
+                0,
+                String::from("status"),
+                rusqlite::types::Type::Text,
+            )
+        })?,
+        created_at: row.get("created_at")?,
+    })
+}
@@ -0,0 +1,254 @@
+use chrono::{Months, NaiveDate, NaiveDateTime, NaiveTime};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    datatypes::{MerchantsIdType, SettlementBatchesIdType, TransactionsIdType},
+    modules::{
+        currencies::Currency,
+        fees::Fee,
+        settlements::{self, MerchantPayment, SettlementBatch, SettlementStatus},
+        transactions::TransactionStatus,
+    },
+    validation::{FieldError, FieldErrorCode},
+};
+
+use super::Merchant;
+
+/// ## Description
+/// What a merchant is owed over a calendar month, replayed from its ledger: the payments it received and the
+/// refunds of them, each on the day it happened, the merchant fees charged and returned with them, and the
+/// settlement batches paid out to it. Amounts are in the merchant currency
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct Statement {
+    pub merchants_id: MerchantsIdType,
+    pub currency: Currency,
+    /// As `YYYY-MM`
+    #[schema(example = "2026-01")]
+    pub month: String,
+    /// First and last days of the month
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Owed before the month started
+    pub opening_balance: Decimal,
+    /// Booked during the month in favour of the merchant
+    pub credits: Decimal,
+    /// Booked during the month against the merchant, negative
+    pub debits: Decimal,
+    pub closing_balance: Decimal,
+    /// Oldest first
+    pub movements: Vec<StatementMovement>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, strum::Display, ToSchema)]
+pub enum StatementMovementKind {
+    #[default]
+    Payment,
+    /// Payment given back, on the day it was refunded
+    Refund,
+    /// Merchant fees charged on a payment
+    Fee,
+    /// Merchant fees returned with a refund
+    FeeRefund,
+    /// Settlement batch credited to the settlement wallet
+    Payout,
+}
+
+/// Entry of the merchant ledger booked during the month of a statement
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct StatementMovement {
+    pub kind: StatementMovementKind,
+    /// Payment the movement comes from, None for payouts
+    pub transactions_id: Option<TransactionsIdType>,
+    /// Batch paid out, only for payouts
+    pub batches_id: Option<SettlementBatchesIdType>,
+    pub amount: Decimal,
+    /// Running balance once the movement was booked
+    pub balance: Decimal,
+    pub booked_at: NaiveDateTime,
+}
+
+impl Statement {
+    /// ## Description
+    /// Statement of the month starting on `month_start`, from the balance owed before it and the payments
+    /// made to the merchant with their fee ledgers and its settlement batches that have movements booked
+    /// during it. Only Confirmed payments and Refunded ones count, and only the Paid batches. Movements
+    /// booked out of the month are left out, as the opening balance accounts for the earlier ones
+    pub fn new(
+        merchant: &Merchant,
+        month_start: NaiveDate,
+        opening_balance: Decimal,
+        payments: &[(MerchantPayment, Vec<Fee>)],
+        batches: &[SettlementBatch],
+    ) -> Self {
+        let from = month_start.and_time(NaiveTime::MIN);
+        let to = next_month_start(month_start).and_time(NaiveTime::MIN);
+
+        let mut ledger = ledger(merchant.currency, payments, batches);
+        ledger.sort_by_key(|movement| movement.booked_at);
+
+        let mut balance = opening_balance;
+        let movements = ledger
+            .into_iter()
+            .filter(|movement| movement.booked_at >= from && movement.booked_at < to)
+            .map(|mut movement| {
+                balance += movement.amount;
+                movement.balance = balance;
+                movement
+            })
+            .collect::<Vec<_>>();
+        let sum = |credit: bool| -> Decimal {
+            movements
+                .iter()
+                .filter(|movement| (movement.amount > Decimal::ZERO) == credit)
+                .map(|movement| movement.amount)
+                .sum()
+        };
+
+        Self {
+            merchants_id: merchant.id,
+            currency: merchant.currency,
+            month: month_start.format("%Y-%m").to_string(),
+            from: month_start,
+            to: month_end(month_start),
+            opening_balance,
+            credits: sum(true),
+            debits: sum(false),
+            closing_balance: balance,
+            movements,
+        }
+    }
+}
+
+/// ## Description
+/// Sums the movements the payments made to a merchant booked before the received datetime: the payments,
+/// their refunds and the merchant fees charged and returned with them, in the merchant currency. For
+/// backends that cannot aggregate them in their queries
+#[cfg(any(test, feature = "sqlite"))]
+pub(crate) fn ledger_balance(
+    currency: Currency,
+    payments: &[(MerchantPayment, Vec<Fee>)],
+    before: NaiveDateTime,
+) -> Decimal {
+    ledger(currency, payments, &[])
+        .iter()
+        .filter(|movement| movement.booked_at < before)
+        .map(|movement| movement.amount)
+        .sum()
+}
+
+/// ## Description
+/// Every movement of the merchant ledger, unsorted and without balances. Refunds recorded before the
+/// transactions kept their date fall back to the date of the payment
+fn ledger(
+    currency: Currency,
+    payments: &[(MerchantPayment, Vec<Fee>)],
+    batches: &[SettlementBatch],
+) -> Vec<StatementMovement> {
+    let mut ledger = Vec::new();
+    let mut book = |kind, transactions_id, batches_id, amount: Decimal, booked_at| {
+        if !amount.is_zero() {
+            ledger.push(StatementMovement {
+                kind,
+                transactions_id,
+                batches_id,
+                amount,
+                balance: Decimal::ZERO,
+                booked_at,
+            });
+        }
+    };
+
+    for (payment, fees) in payments {
+        let refunded = match payment.status {
+            TransactionStatus::Confirmed => false,
+            TransactionStatus::Refunded => true,
+            _ => continue,
+        };
+        let transactions_id = Some(payment.transactions_id);
+        book(
+            StatementMovementKind::Payment,
+            transactions_id,
+            None,
+            payment.amount,
+            payment.created_at,
+        );
+        book(
+            StatementMovementKind::Fee,
+            transactions_id,
+            None,
+            -settlements::merchant_fees(payment, fees, currency, |amount| amount > Decimal::ZERO),
+            payment.created_at,
+        );
+        if refunded {
+            let refunded_at = payment.reversed_at.unwrap_or(payment.created_at);
+            book(
+                StatementMovementKind::Refund,
+                transactions_id,
+                None,
+                -payment.amount,
+                refunded_at,
+            );
+            book(
+                StatementMovementKind::FeeRefund,
+                transactions_id,
+                None,
+                -settlements::merchant_fees(payment, fees, currency, |amount| {
+                    amount < Decimal::ZERO
+                }),
+                refunded_at,
+            );
+        }
+    }
+    for batch in batches {
+        if let (SettlementStatus::Paid, Some(paid_at)) = (batch.status, batch.paid_at) {
+            book(
+                StatementMovementKind::Payout,
+                None,
+                Some(batch.id),
+                -batch.net,
+                paid_at,
+            );
+        }
+    }
+
+    ledger
+}
+
+/// ## Description
+/// Parses the month of a statement, which must have started by `today`
+///
+/// ### Returns
+/// Its first day, or the error of the `month` field
+pub fn month_start(month: &str, today: NaiveDate) -> Result<NaiveDate, FieldError> {
+    let Ok(month_start) = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d") else {
+        return Err(FieldError {
+            field: "month",
+            code: FieldErrorCode::InvalidFormat,
+            message: String::from("Month must be formatted as YYYY-MM"),
+        });
+    };
+    if month_start > today {
+        return Err(FieldError {
+            field: "month",
+            code: FieldErrorCode::OutOfRange,
+            message: String::from("Statement month has not started yet"),
+        });
+    }
+
+    Ok(month_start)
+}
+
+/// First day of the month after the one starting on `month_start`
+pub fn next_month_start(month_start: NaiveDate) -> NaiveDate {
+    month_start
+        .checked_add_months(Months::new(1))
+        .unwrap_or(month_start)
+}
+
+fn month_end(month_start: NaiveDate) -> NaiveDate {
+    next_month_start(month_start)
+        .pred_opt()
+        .unwrap_or(month_start)
+}
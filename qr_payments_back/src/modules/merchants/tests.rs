@@ -0,0 +1,275 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::{
+    modules::{
+        currencies::Currency,
+        fees::{Fee, FeeParty},
+        settlements::{MerchantPayment, SettlementBatch, SettlementStatus},
+        transactions::TransactionStatus,
+    },
+    validation::{FieldErrorCode, Validate},
+};
+
+use super::{
+    qr::{crc16, payload},
+    statements::{ledger_balance, month_start, Statement, StatementMovementKind},
+    Merchant, MerchantDetails,
+};
+
+fn details(name: &str, city: &str, country: &str) -> MerchantDetails {
+    MerchantDetails {
+        name: name.to_string(),
+        city: city.to_string(),
+        country: country.to_string(),
+        mcc: String::from("5812"),
+    }
+}
+
+fn merchant(currency: &str, details: MerchantDetails) -> Merchant {
+    Merchant {
+        id: 7,
+        wallets_id: 3,
+        currency: Currency::from_code(currency).unwrap(),
+        details,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn crc16_matches_the_ccitt_false_check_value() {
+    assert_eq!(crc16(b"123456789"), 0x29B1);
+    assert_eq!(crc16(b""), 0xFFFF);
+}
+
+#[test]
+fn payloads_carry_the_amount_only_when_dynamic() {
+    let cafe = merchant("USD", details("Cafe Central", "Springfield", "US"));
+    assert_eq!(
+        payload(&cafe, Some(Decimal::new(125, 1))),
+        "00020101021226230014com.qrpayments01017520458125303840540512.505802US5912Cafe Central6011Springfield63047110"
+    );
+
+    //  Amounts are written with the minor units of the currency, none for JPY
+    let ramen = merchant("JPY", details("Ramen Ya", "Tokyo", "JP"));
+    assert_eq!(
+        payload(&ramen, None),
+        "00020101021126230014com.qrpayments010175204581253033925802JP5908Ramen Ya6005Tokyo63041E6C"
+    );
+    assert_eq!(
+        payload(&ramen, Some(Decimal::new(1500, 0))),
+        "00020101021226230014com.qrpayments01017520458125303392540415005802JP5908Ramen Ya6005Tokyo6304D834"
+    );
+}
+
+#[test]
+fn payload_checksum_covers_everything_before_it() {
+    let payload = payload(
+        &merchant("EUR", details("Le Petit Zinc", "Paris", "FR")),
+        Some(Decimal::new(999, 2)),
+    );
+    let (covered, checksum) = payload.split_at(payload.len() - 4);
+
+    assert!(covered.ends_with("6304"));
+    assert_eq!(checksum, format!("{:04X}", crc16(covered.as_bytes())));
+}
+
+#[test]
+fn details_must_fit_the_qr_payload() {
+    let fields = |details: MerchantDetails| {
+        details
+            .validate()
+            .into_iter()
+            .map(|error| error.field)
+            .collect::<Vec<_>>()
+    };
+
+    assert!(fields(details("Cafe Central", "Springfield", "US")).is_empty());
+    assert!(fields(details(&"N".repeat(25), &"C".repeat(15), "AR")).is_empty());
+    assert_eq!(
+        fields(details(&"N".repeat(26), &"C".repeat(16), "USA")),
+        vec!["name", "city", "country"]
+    );
+    assert_eq!(
+        fields(MerchantDetails {
+            mcc: String::from("58a2"),
+            ..details("  ", "Zürich", "ch")
+        }),
+        vec!["name", "city", "country", "mcc"]
+    );
+    assert_eq!(
+        fields(details("Line\nbreak", "", "US")),
+        vec!["name", "city"]
+    );
+}
+
+#[test]
+fn statement_months_are_parsed_and_must_have_started() {
+    let today = NaiveDate::from_ymd_opt(2025, 3, 12).unwrap();
+
+    assert_eq!(
+        month_start("2025-03", today),
+        Ok(NaiveDate::from_ymd_opt(2025, 3, 1).unwrap())
+    );
+    assert_eq!(
+        month_start("2024-12", today),
+        Ok(NaiveDate::from_ymd_opt(2024, 12, 1).unwrap())
+    );
+    for month in ["2025-13", "2025", "March", "2025-03-01"] {
+        let error = month_start(month, today).unwrap_err();
+        assert_eq!(error.field, "month");
+        assert_eq!(error.code, FieldErrorCode::InvalidFormat);
+    }
+    assert_eq!(
+        month_start("2025-04", today).unwrap_err().code,
+        FieldErrorCode::OutOfRange
+    );
+}
+
+#[test]
+fn statements_replay_the_ledger_of_the_month() {
+    let cafe = merchant("USD", details("Cafe Central", "Springfield", "US"));
+    let usd = cafe.currency;
+    let at = |month: u32, day: u32| {
+        NaiveDate::from_ymd_opt(2024, month, day)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap()
+    };
+    let payment = |transactions_id: u64, amount: i64, status: TransactionStatus| MerchantPayment {
+        transactions_id,
+        merchants_id: cafe.id,
+        amount: Decimal::new(amount, 2),
+        rate: None,
+        status,
+        created_at: at(1, 1),
+        reversed_at: None,
+    };
+    let fee = |party: FeeParty, amount: i64| Fee {
+        party,
+        amount: Decimal::new(amount, 2),
+        currency: usd,
+    };
+    let batch = |id: u64, net: i64, status: SettlementStatus, paid_at| SettlementBatch {
+        id,
+        merchants_id: cafe.id,
+        currency: usd,
+        net: Decimal::new(net, 2),
+        status,
+        paid_at,
+        ..Default::default()
+    };
+
+    //  Settled and paid out in January, refunded in February, paid in February, refunded in March, and
+    //  never confirmed
+    let payments = [
+        (
+            MerchantPayment {
+                created_at: at(1, 20),
+                ..payment(1, 10000, TransactionStatus::Confirmed)
+            },
+            vec![fee(FeeParty::Merchant, 200)],
+        ),
+        (
+            MerchantPayment {
+                created_at: at(1, 25),
+                reversed_at: Some(at(2, 5)),
+                ..payment(2, 4000, TransactionStatus::Refunded)
+            },
+            vec![fee(FeeParty::Merchant, 80), fee(FeeParty::Merchant, -80)],
+        ),
+        (
+            MerchantPayment {
+                created_at: at(2, 10),
+                ..payment(3, 2500, TransactionStatus::Confirmed)
+            },
+            vec![fee(FeeParty::Merchant, 50), fee(FeeParty::Payer, 100)],
+        ),
+        (
+            MerchantPayment {
+                created_at: at(2, 20),
+                reversed_at: Some(at(3, 2)),
+                ..payment(5, 1250, TransactionStatus::Refunded)
+            },
+            Vec::new(),
+        ),
+        (
+            MerchantPayment {
+                created_at: at(2, 12),
+                ..payment(4, 990, TransactionStatus::Initialized)
+            },
+            vec![fee(FeeParty::Merchant, 20)],
+        ),
+    ];
+    let batches = [
+        batch(1, 9800, SettlementStatus::Paid, Some(at(1, 31))),
+        batch(2, 2450, SettlementStatus::Paid, Some(at(2, 15))),
+        batch(3, 1250, SettlementStatus::Pending, None),
+    ];
+
+    //  Everything booked before February, less the January payout
+    let month_start = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+    let opening_balance = ledger_balance(usd, &payments, at(2, 1) - chrono::TimeDelta::hours(9))
+        - Decimal::new(9800, 2);
+    let statement = Statement::new(&cafe, month_start, opening_balance, &payments, &batches);
+
+    assert_eq!(statement.month, "2024-02");
+    assert_eq!(statement.to, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+    assert_eq!(statement.opening_balance, Decimal::new(3920, 2));
+    assert_eq!(statement.credits, Decimal::new(3830, 2));
+    assert_eq!(statement.debits, Decimal::new(-6500, 2));
+    assert_eq!(statement.closing_balance, Decimal::new(1250, 2));
+    let movements = statement
+        .movements
+        .iter()
+        .map(|movement| {
+            (
+                movement.kind,
+                movement.transactions_id.or(movement.batches_id),
+                movement.amount,
+                movement.balance,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        movements,
+        vec![
+            (
+                StatementMovementKind::Refund,
+                Some(2),
+                Decimal::new(-4000, 2),
+                Decimal::new(-80, 2)
+            ),
+            (
+                StatementMovementKind::FeeRefund,
+                Some(2),
+                Decimal::new(80, 2),
+                Decimal::ZERO
+            ),
+            (
+                StatementMovementKind::Payment,
+                Some(3),
+                Decimal::new(2500, 2),
+                Decimal::new(2500, 2)
+            ),
+            (
+                StatementMovementKind::Fee,
+                Some(3),
+                Decimal::new(-50, 2),
+                Decimal::new(2450, 2)
+            ),
+            (
+                StatementMovementKind::Payout,
+                Some(2),
+                Decimal::new(-2450, 2),
+                Decimal::ZERO
+            ),
+            (
+                StatementMovementKind::Payment,
+                Some(5),
+                Decimal::new(1250, 2),
+                Decimal::new(1250, 2)
+            ),
+        ]
+    );
+}
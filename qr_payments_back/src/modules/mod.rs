@@ -1,2 +1,10 @@
+pub mod currencies;
+pub mod fees;
+pub mod fx;
+pub mod merchants;
+pub mod outbox;
+pub mod risk;
+pub mod settlements;
 pub mod transactions;
 pub mod wallets;
+pub mod webhooks;
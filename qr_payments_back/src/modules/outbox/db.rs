@@ -0,0 +1,126 @@
+use chrono::NaiveDateTime;
+use error_mapper::{create_new_error, TheError, TheResult};
+use mysql::prelude::Queryable;
+
+use crate::{
+    database::{
+        decode::{self, DecodeRow, RowError},
+        in_transaction, Executor, MySqlStore,
+    },
+    datatypes::OutboxIdType,
+    from_row_via_decode, row_to_data,
+};
+
+use super::{
+    repository::OutboxRepository,
+    sinks::{OutboxSink, SinkTransaction},
+    AggregateType, DomainEvent, OutboxEvent,
+};
+
+impl OutboxRepository for MySqlStore {
+    fn relay_pending(&self, batch_size: u32, sinks: &[Box<dyn OutboxSink>]) -> TheResult<()> {
+        let mut conn = self.get_conn()?;
+        let events = OutboxEvent::select_pending(&mut conn, batch_size)?;
+
+        for mut event in events {
+            in_transaction(&mut conn, |db_transaction| {
+                for sink in sinks {
+                    sink.dispatch(&mut SinkTransaction::MySql(db_transaction), &event)
+                        .map_err(|error| dispatch_error(sink.as_ref(), &event, error))?;
+                }
+                event.mark_dispatched(db_transaction)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// ## Description
+/// Error of a sink failing on an event, naming both
+pub(super) fn dispatch_error(
+    sink: &dyn OutboxSink,
+    event: &OutboxEvent,
+    error: impl std::fmt::Display,
+) -> TheError {
+    create_new_error!(format!(
+        "Sink {} failed on outbox event with ID: {}: {}",
+        sink.name(),
+        event.id,
+        error
+    ))
+}
+
+impl OutboxEvent {
+    /// ## Description
+    /// Appends an event to the outbox. Must run on the same DB transaction as the change it describes
+    pub fn append(conn: &mut impl Executor, event: &impl DomainEvent) -> TheResult<()> {
+        let payload =
+            serde_json::to_string(event).map_err(|error| create_new_error!(error.to_string()))?;
+        let query = "INSERT INTO `outbox`(`aggregate_type`, `aggregate_ID`, `event_type`, `payload`) VALUES(?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec_drop(
+            stmt,
+            (
+                event.aggregate_type().to_string(),
+                event.aggregate_id(),
+                event.event_type(),
+                payload,
+            ),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Selects the events not yet dispatched, in the order they were written
+    fn select_pending(conn: &mut impl Queryable, limit: u32) -> TheResult<Vec<Self>> {
+        let query = "SELECT * FROM `outbox` WHERE `dispatched_at` IS NULL ORDER BY `ID` LIMIT ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (limit,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn mark_dispatched(&mut self, conn: &mut impl Executor) -> TheResult<bool> {
+        let now = chrono::Local::now().naive_local();
+        let query =
+            "UPDATE `outbox` SET `dispatched_at` = ? WHERE `ID` = ? AND `dispatched_at` IS NULL;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec_drop(stmt, (now, self.id))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        self.dispatched_at = Some(now);
+
+        Ok(conn.affected_rows() > 0)
+    }
+}
+
+impl DecodeRow for OutboxEvent {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            id: row_to_data!(row, "ID", "outbox", OutboxIdType),
+            aggregate_id: row_to_data!(row, "aggregate_ID", "outbox", u64),
+            event_type: row_to_data!(row, "event_type", "outbox", String),
+            payload: row_to_data!(row, "payload", "outbox", String),
+            created_at: row_to_data!(row, "created_at", "outbox", NaiveDateTime),
+            dispatched_at: row_to_data!(row, "dispatched_at", "outbox", Option<NaiveDateTime>),
+            aggregate_type: decode::variant(
+                row,
+                "outbox",
+                "aggregate_type",
+                AggregateType::from_string,
+            )?,
+        })
+    }
+}
+
+from_row_via_decode!(OutboxEvent);
@@ -0,0 +1,12 @@
+use error_mapper::TheResult;
+
+use crate::database::memory::InMemoryStore;
+
+use super::{repository::OutboxRepository, sinks::OutboxSink};
+
+impl OutboxRepository for InMemoryStore {
+    /// The in-memory store records no outbox, changes are only published by the handlers making them
+    fn relay_pending(&self, _batch_size: u32, _sinks: &[Box<dyn OutboxSink>]) -> TheResult<()> {
+        Ok(())
+    }
+}
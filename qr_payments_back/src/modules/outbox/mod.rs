@@ -0,0 +1,141 @@
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    datatypes::{OutboxIdType, TransactionsIdType, WalletsIdType},
+    modules::currencies::Currency,
+};
+
+mod db;
+#[cfg(test)]
+mod memory;
+pub mod relay;
+pub mod repository;
+pub mod sinks;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Domain event stored in the `outbox` table, written in the same DB transaction as the change it
+/// describes, so an event exists if and only if its change was committed
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct OutboxEvent {
+    pub id: OutboxIdType,
+    pub aggregate_type: AggregateType,
+    pub aggregate_id: u64,
+    pub event_type: String,
+    pub payload: String,
+    pub created_at: NaiveDateTime,
+    pub dispatched_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, strum::Display, PartialEq)]
+pub enum AggregateType {
+    #[default]
+    Transaction,
+    Wallet,
+}
+
+/// Payload of the events describing a change in an aggregate
+pub trait DomainEvent: Serialize {
+    fn aggregate_type(&self) -> AggregateType;
+    fn aggregate_id(&self) -> u64;
+    fn event_type(&self) -> &str;
+}
+
+/// A transaction was created or changed its status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionEvent {
+    pub event_type: String,
+    pub transactions_id: TransactionsIdType,
+    pub wallets_id: Option<WalletsIdType>,
+    pub amount: Decimal,
+    /// Events written before currencies existed are in USD
+    #[serde(default)]
+    pub currency: Currency,
+    pub status: String,
+    pub occurred_at: NaiveDateTime,
+}
+
+/// A wallet changed its balance or limits. The balance is the one in the currency that changed, the
+/// wallet's own one otherwise
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletEvent {
+    pub event_type: String,
+    pub wallets_id: WalletsIdType,
+    #[serde(default)]
+    pub currency: Currency,
+    pub balance: Decimal,
+    pub occurred_at: NaiveDateTime,
+}
+
+impl AggregateType {
+    fn from_string(input: String) -> Option<Self> {
+        match input.as_str() {
+            "Transaction" => Some(Self::Transaction),
+            "Wallet" => Some(Self::Wallet),
+            _ => None,
+        }
+    }
+}
+
+impl TransactionEvent {
+    pub fn new(
+        transactions_id: TransactionsIdType,
+        wallets_id: Option<WalletsIdType>,
+        amount: Decimal,
+        currency: Currency,
+        status: String,
+    ) -> Self {
+        Self {
+            event_type: format!("transaction.{}", status.to_lowercase()),
+            transactions_id,
+            wallets_id,
+            amount,
+            currency,
+            status,
+            occurred_at: chrono::Local::now().naive_local(),
+        }
+    }
+}
+
+impl WalletEvent {
+    pub fn new(
+        event_type: &str,
+        wallets_id: WalletsIdType,
+        currency: Currency,
+        balance: Decimal,
+    ) -> Self {
+        Self {
+            event_type: format!("wallet.{}", event_type),
+            wallets_id,
+            currency,
+            balance,
+            occurred_at: chrono::Local::now().naive_local(),
+        }
+    }
+}
+
+impl DomainEvent for TransactionEvent {
+    fn aggregate_type(&self) -> AggregateType {
+        AggregateType::Transaction
+    }
+    fn aggregate_id(&self) -> u64 {
+        self.transactions_id
+    }
+    fn event_type(&self) -> &str {
+        &self.event_type
+    }
+}
+
+impl DomainEvent for WalletEvent {
+    fn aggregate_type(&self) -> AggregateType {
+        AggregateType::Wallet
+    }
+    fn aggregate_id(&self) -> u64 {
+        self.wallets_id
+    }
+    fn event_type(&self) -> &str {
+        &self.event_type
+    }
+}
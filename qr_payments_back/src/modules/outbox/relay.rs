@@ -0,0 +1,67 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::web;
+use the_logger::TheLogger;
+
+use crate::{
+    config::{Config, OutboxConfig},
+    database::blocking,
+    log_error, log_info,
+    modules::webhooks::sink::WebhookSink,
+    tasks,
+};
+
+use super::{
+    repository::OutboxRepository,
+    sinks::{ChannelSink, LogFileSink, OutboxSink},
+};
+
+/// ## Description
+/// Background task that reads the outbox in order and dispatches every event to the configured sinks.
+/// An event is only marked as dispatched once every sink accepted it, and a failure stops the batch so
+/// later events are never dispatched before earlier ones. Only one relay must run per database
+pub async fn run_relay(outbox: Arc<dyn OutboxRepository>) {
+    let logger = TheLogger::instance();
+    let outbox = web::Data::from(outbox);
+    log_info!(logger, "Outbox relay started");
+
+    loop {
+        let config = match Config::get_outbox_config().await {
+            Ok(config) => config,
+            Err(error) => {
+                log_error!(logger, "Could not get outbox configurations: {}", error);
+                OutboxConfig::default()
+            }
+        };
+
+        let sinks = build_sinks(&config);
+        let batch_size = config.batch_size;
+        let interval = Duration::from_secs(config.poll_interval_secs);
+        let outcome = blocking(&outbox, move |outbox| {
+            outbox.relay_pending(batch_size, &sinks)
+        })
+        .await;
+        if let Err(error) = &outcome {
+            log_error!(logger, "Error relaying outbox events: {}", error);
+        }
+        tasks::report("outbox_relay", interval, &outcome);
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn build_sinks(config: &OutboxConfig) -> Vec<Box<dyn OutboxSink>> {
+    let mut sinks = Vec::<Box<dyn OutboxSink>>::new();
+
+    if config.webhooks_sink {
+        sinks.push(Box::new(WebhookSink));
+    }
+    if let Some(path) = &config.log_file {
+        sinks.push(Box::new(LogFileSink::new(path.clone())));
+    }
+    if config.channel_sink {
+        sinks.push(Box::new(ChannelSink));
+    }
+
+    sinks
+}
@@ -0,0 +1,11 @@
+use error_mapper::TheResult;
+
+use super::sinks::OutboxSink;
+
+/// Reading of the outbox by the relay, implemented by every storage backend
+pub trait OutboxRepository: Send + Sync {
+    /// Dispatches the pending events in the order they were written, marking each one as dispatched in
+    /// the DB transaction its sinks ran in. A failing sink stops the batch, so later events are never
+    /// dispatched before earlier ones
+    fn relay_pending(&self, batch_size: u32, sinks: &[Box<dyn OutboxSink>]) -> TheResult<()>;
+}
@@ -0,0 +1,81 @@
+use std::{fs::OpenOptions, io::Write};
+
+use error_mapper::{create_new_error, TheResult};
+
+use crate::modules::transactions::events::publish_event;
+
+use super::{AggregateType, OutboxEvent, TransactionEvent};
+
+/// Destination of the outbox events. Sinks run inside the relay's DB transaction that marks the event as
+/// dispatched, so those writing to the database get exactly once delivery, and the rest at least once
+pub trait OutboxSink: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn dispatch(&self, conn: &mut SinkTransaction<'_, '_>, event: &OutboxEvent) -> TheResult<()>;
+}
+
+/// DB transaction of the relay, on the backend the outbox is read from
+pub enum SinkTransaction<'a, 'conn> {
+    MySql(&'a mut mysql::Transaction<'conn>),
+    #[cfg(feature = "sqlite")]
+    Sqlite(&'a rusqlite::Transaction<'conn>),
+}
+
+/// Appends every event as a JSON line to a file
+pub struct LogFileSink {
+    path: String,
+}
+
+/// Publishes the transaction events to the in-process channel the status streams read from. Handlers
+/// publish their own changes once committed, this sink adds the ones committed elsewhere, like by the
+/// admin binary. Streams skip the statuses they already sent, so an event published twice is harmless
+pub struct ChannelSink;
+
+impl LogFileSink {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+impl OutboxSink for LogFileSink {
+    fn name(&self) -> &'static str {
+        "log_file"
+    }
+
+    fn dispatch(&self, _conn: &mut SinkTransaction<'_, '_>, event: &OutboxEvent) -> TheResult<()> {
+        let line =
+            serde_json::to_string(event).map_err(|error| create_new_error!(error.to_string()))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        writeln!(file, "{}", line).map_err(|error| create_new_error!(error.to_string()))
+    }
+}
+
+impl ChannelSink {
+    /// ## Description
+    /// Publishes the event to the status channel when it describes a transaction, ignoring the rest
+    pub(crate) fn publish(event: &OutboxEvent) -> TheResult<()> {
+        if event.aggregate_type != AggregateType::Transaction {
+            return Ok(());
+        }
+
+        let transaction_event = serde_json::from_str::<TransactionEvent>(&event.payload)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        publish_event(transaction_event);
+
+        Ok(())
+    }
+}
+
+impl OutboxSink for ChannelSink {
+    fn name(&self) -> &'static str {
+        "channel"
+    }
+
+    fn dispatch(&self, _conn: &mut SinkTransaction<'_, '_>, event: &OutboxEvent) -> TheResult<()> {
+        Self::publish(event)
+    }
+}
@@ -0,0 +1,84 @@
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::params;
+
+use crate::database::sqlite::{variant_column, SqliteStore};
+
+use super::{
+    db::dispatch_error,
+    repository::OutboxRepository,
+    sinks::{OutboxSink, SinkTransaction},
+    AggregateType, DomainEvent, OutboxEvent,
+};
+
+impl OutboxRepository for SqliteStore {
+    fn relay_pending(&self, batch_size: u32, sinks: &[Box<dyn OutboxSink>]) -> TheResult<()> {
+        let events = {
+            let conn = self.lock()?;
+            let mut stmt = conn
+                .prepare(
+                    "SELECT * FROM `outbox` WHERE `dispatched_at` IS NULL ORDER BY `ID` LIMIT ?;",
+                )
+                .map_err(|error| create_new_error!(error.to_string()))?;
+
+            let events = stmt
+                .query_map([batch_size], from_row)
+                .map_err(|error| create_new_error!(error.to_string()))?
+                .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+                .collect::<TheResult<Vec<_>>>()?;
+            events
+        };
+
+        for event in events {
+            self.in_transaction(|db_transaction| {
+                for sink in sinks {
+                    sink.dispatch(&mut SinkTransaction::Sqlite(db_transaction), &event)
+                        .map_err(|error| dispatch_error(sink.as_ref(), &event, error))?;
+                }
+
+                db_transaction
+                    .execute(
+                        "UPDATE `outbox` SET `dispatched_at` = ? WHERE `ID` = ? AND `dispatched_at` IS NULL;",
+                        params![chrono::Local::now().naive_local(), event.id],
+                    )
+                    .map_err(|error| create_new_error!(error.to_string()))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// ## Description
+/// Appends an event to the outbox of the SQLite database. Must run on the same SQLite transaction as the
+/// change it describes
+pub fn append(conn: &rusqlite::Connection, event: &impl DomainEvent) -> TheResult<()> {
+    let payload =
+        serde_json::to_string(event).map_err(|error| create_new_error!(error.to_string()))?;
+    let query = "INSERT INTO `outbox`(`aggregate_type`, `aggregate_ID`, `event_type`, `payload`, `created_at`) VALUES(?, ?, ?, ?, ?);";
+
+    conn.execute(
+        query,
+        params![
+            event.aggregate_type().to_string(),
+            event.aggregate_id(),
+            event.event_type(),
+            payload,
+            chrono::Local::now().naive_local(),
+        ],
+    )
+    .map_err(|error| create_new_error!(error.to_string()))?;
+
+    Ok(())
+}
+
+fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<OutboxEvent> {
+    Ok(OutboxEvent {
+        id: row.get("ID")?,
+        aggregate_type: variant_column(row, "aggregate_type", AggregateType::from_string)?,
+        aggregate_id: row.get("aggregate_ID")?,
+        event_type: row.get("event_type")?,
+        payload: row.get("payload")?,
+        created_at: row.get("created_at")?,
+        dispatched_at: row.get("dispatched_at")?,
+    })
+}
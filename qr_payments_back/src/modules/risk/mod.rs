@@ -0,0 +1,172 @@
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RiskConfig, modules::transactions::TransactionStatus};
+
+mod rules;
+#[cfg(test)]
+mod tests;
+
+pub use rules::{AmountOutlierRule, FirstPaymentRule, RepeatedDeclinesRule, VelocityRule};
+
+/// Outcome of a risk evaluation. Variants are ordered by severity, so the most severe one wins
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub enum RiskDecision {
+    #[default]
+    Allow,
+    Review,
+    Block,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RiskVerdict {
+    pub decision: RiskDecision,
+    pub reasons: Vec<String>,
+}
+
+/// Payment being evaluated
+#[derive(Debug, Clone, Copy)]
+pub struct PaymentAttempt {
+    pub amount: Decimal,
+    pub at: NaiveDateTime,
+    /// Whether the wallet ever had a payment to the same merchant confirmed, or any payment confirmed when
+    /// paying no merchant. It covers the whole history, not only the entries evaluated
+    pub known_payee: bool,
+}
+
+/// Previous transaction of the paying wallet in any currency, as seen by the risk rules
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryEntry {
+    pub amount: Decimal,
+    /// Whether the amount is in the currency of the payment evaluated. Only those are compared with it,
+    /// while every entry counts
+    pub comparable: bool,
+    pub created_at: NaiveDateTime,
+    pub status: TransactionStatus,
+}
+
+pub trait RiskRule: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// ## Description
+    /// Evaluates a payment attempt against the wallet's recent history
+    ///
+    /// ### Returns
+    /// An Allow verdict when the rule is not triggered, otherwise the configured decision with its reasons
+    fn evaluate(&self, attempt: &PaymentAttempt, history: &[HistoryEntry]) -> RiskVerdict;
+}
+
+#[derive(Default)]
+pub struct RiskEngine {
+    rules: Vec<Box<dyn RiskRule>>,
+}
+
+impl RiskVerdict {
+    pub fn allow() -> Self {
+        Self::default()
+    }
+
+    pub fn new(decision: RiskDecision, reason: String) -> Self {
+        Self {
+            decision,
+            reasons: vec![reason],
+        }
+    }
+
+    /// ## Description
+    /// Joins the verdict reasons in a single string of at most `max_length` characters
+    pub fn summary(&self, max_length: usize) -> String {
+        self.reasons.join("; ").chars().take(max_length).collect()
+    }
+
+    fn merge(&mut self, other: RiskVerdict) {
+        if other.decision == RiskDecision::Allow {
+            return;
+        }
+        self.decision = self.decision.max(other.decision);
+        self.reasons.extend(other.reasons);
+    }
+}
+
+impl HistoryEntry {
+    pub(crate) fn is_confirmed(&self) -> bool {
+        self.status == TransactionStatus::Confirmed
+    }
+
+    pub(crate) fn is_declined(&self) -> bool {
+        self.status == TransactionStatus::Declined
+    }
+
+    /// Whether the entry is a payment the wallet went through with, whatever its outcome. Declined
+    /// payments, failed attempts and logged requests are not
+    pub(crate) fn is_payment(&self) -> bool {
+        !matches!(
+            self.status,
+            TransactionStatus::Declined | TransactionStatus::InternalError | TransactionStatus::Log
+        )
+    }
+}
+
+impl RiskDecision {
+    pub(crate) fn review() -> Self {
+        Self::Review
+    }
+
+    pub(crate) fn block() -> Self {
+        Self::Block
+    }
+}
+
+impl RiskEngine {
+    pub fn from_config(config: &RiskConfig) -> Self {
+        let mut engine = Self::default();
+
+        if let Some(velocity) = &config.velocity {
+            engine.add_rule(VelocityRule::from(velocity));
+        }
+        if let Some(amount_outlier) = &config.amount_outlier {
+            engine.add_rule(AmountOutlierRule::from(amount_outlier));
+        }
+        if let Some(first_payment) = &config.first_payment {
+            engine.add_rule(FirstPaymentRule::from(first_payment));
+        }
+        if let Some(repeated_declines) = &config.repeated_declines {
+            engine.add_rule(RepeatedDeclinesRule::from(repeated_declines));
+        }
+
+        engine
+    }
+
+    pub fn add_rule(&mut self, rule: impl RiskRule + 'static) {
+        self.rules.push(Box::new(rule));
+    }
+
+    /// ## Description
+    /// Runs every configured rule and combines their verdicts, keeping the most severe decision and all reasons
+    pub fn evaluate(&self, attempt: &PaymentAttempt, history: &[HistoryEntry]) -> RiskVerdict {
+        let mut verdict = RiskVerdict::allow();
+        for rule in &self.rules {
+            let mut rule_verdict = rule.evaluate(attempt, history);
+            rule_verdict
+                .reasons
+                .iter_mut()
+                .for_each(|reason| *reason = format!("{}: {}", rule.name(), reason));
+            verdict.merge(rule_verdict);
+        }
+
+        verdict
+    }
+}
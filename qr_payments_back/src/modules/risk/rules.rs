@@ -0,0 +1,195 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use rust_decimal::Decimal;
+
+use crate::config::{
+    AmountOutlierRuleConfig, FirstPaymentRuleConfig, RepeatedDeclinesRuleConfig, VelocityRuleConfig,
+};
+
+use super::{HistoryEntry, PaymentAttempt, RiskDecision, RiskRule, RiskVerdict};
+
+/// Flags wallets issuing too many payments within a time window. Declined payments are left to the
+/// repeated declines rule
+pub struct VelocityRule {
+    window: TimeDelta,
+    max_count: usize,
+    decision: RiskDecision,
+}
+
+/// Flags payments much larger than the wallet's average confirmed payment in the same currency
+pub struct AmountOutlierRule {
+    multiplier: Decimal,
+    min_history: usize,
+    decision: RiskDecision,
+}
+
+/// Flags a large first payment from a wallet to a merchant it never had a payment confirmed to
+pub struct FirstPaymentRule {
+    min_amount: Decimal,
+    decision: RiskDecision,
+}
+
+/// Flags wallets that had several payments declined recently
+pub struct RepeatedDeclinesRule {
+    window: TimeDelta,
+    max_declines: usize,
+    decision: RiskDecision,
+}
+
+impl RiskRule for VelocityRule {
+    fn name(&self) -> &'static str {
+        "velocity"
+    }
+
+    fn evaluate(&self, attempt: &PaymentAttempt, history: &[HistoryEntry]) -> RiskVerdict {
+        let since = attempt
+            .at
+            .checked_sub_signed(self.window)
+            .unwrap_or(NaiveDateTime::MIN);
+        let count = history
+            .iter()
+            .filter(|entry| entry.is_payment() && entry.created_at >= since)
+            .count();
+
+        if count < self.max_count {
+            return RiskVerdict::allow();
+        }
+
+        RiskVerdict::new(
+            self.decision,
+            format!(
+                "{} payments in the last {} seconds",
+                count,
+                self.window.num_seconds()
+            ),
+        )
+    }
+}
+
+impl RiskRule for AmountOutlierRule {
+    fn name(&self) -> &'static str {
+        "amount_outlier"
+    }
+
+    fn evaluate(&self, attempt: &PaymentAttempt, history: &[HistoryEntry]) -> RiskVerdict {
+        let confirmed = history
+            .iter()
+            .filter(|entry| entry.is_confirmed() && entry.comparable)
+            .map(|entry| entry.amount.abs())
+            .collect::<Vec<_>>();
+        if confirmed.is_empty() || confirmed.len() < self.min_history {
+            return RiskVerdict::allow();
+        }
+
+        let average = confirmed.iter().sum::<Decimal>() / Decimal::from(confirmed.len());
+        let threshold = average * self.multiplier;
+        if attempt.amount.abs() <= threshold {
+            return RiskVerdict::allow();
+        }
+
+        RiskVerdict::new(
+            self.decision,
+            format!(
+                "amount {} exceeds {} times the average of {}",
+                attempt.amount.abs(),
+                self.multiplier,
+                average.round_dp(2)
+            ),
+        )
+    }
+}
+
+impl RiskRule for FirstPaymentRule {
+    fn name(&self) -> &'static str {
+        "first_payment"
+    }
+
+    fn evaluate(&self, attempt: &PaymentAttempt, _history: &[HistoryEntry]) -> RiskVerdict {
+        if attempt.known_payee || attempt.amount.abs() < self.min_amount {
+            return RiskVerdict::allow();
+        }
+
+        RiskVerdict::new(
+            self.decision,
+            format!(
+                "first payment of {} is above {}",
+                attempt.amount.abs(),
+                self.min_amount
+            ),
+        )
+    }
+}
+
+impl RiskRule for RepeatedDeclinesRule {
+    fn name(&self) -> &'static str {
+        "repeated_declines"
+    }
+
+    fn evaluate(&self, attempt: &PaymentAttempt, history: &[HistoryEntry]) -> RiskVerdict {
+        let since = attempt
+            .at
+            .checked_sub_signed(self.window)
+            .unwrap_or(NaiveDateTime::MIN);
+        let declines = history
+            .iter()
+            .filter(|entry| entry.is_declined() && entry.created_at >= since)
+            .count();
+
+        if declines < self.max_declines {
+            return RiskVerdict::allow();
+        }
+
+        RiskVerdict::new(
+            self.decision,
+            format!(
+                "{} declined payments in the last {} seconds",
+                declines,
+                self.window.num_seconds()
+            ),
+        )
+    }
+}
+
+/// ## Description
+/// Window of the configured seconds, the longest one representable when out of range
+fn window(window_secs: i64) -> TimeDelta {
+    TimeDelta::try_seconds(window_secs).unwrap_or(TimeDelta::MAX)
+}
+
+impl From<&VelocityRuleConfig> for VelocityRule {
+    fn from(config: &VelocityRuleConfig) -> Self {
+        Self {
+            window: window(config.window_secs),
+            max_count: config.max_count,
+            decision: config.decision,
+        }
+    }
+}
+
+impl From<&AmountOutlierRuleConfig> for AmountOutlierRule {
+    fn from(config: &AmountOutlierRuleConfig) -> Self {
+        Self {
+            multiplier: config.multiplier,
+            min_history: config.min_history,
+            decision: config.decision,
+        }
+    }
+}
+
+impl From<&FirstPaymentRuleConfig> for FirstPaymentRule {
+    fn from(config: &FirstPaymentRuleConfig) -> Self {
+        Self {
+            min_amount: config.min_amount,
+            decision: config.decision,
+        }
+    }
+}
+
+impl From<&RepeatedDeclinesRuleConfig> for RepeatedDeclinesRule {
+    fn from(config: &RepeatedDeclinesRuleConfig) -> Self {
+        Self {
+            window: window(config.window_secs),
+            max_declines: config.max_declines,
+            decision: config.decision,
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use rust_decimal::Decimal;
+
+use crate::{
+    config::{
+        AmountOutlierRuleConfig, FirstPaymentRuleConfig, RepeatedDeclinesRuleConfig, RiskConfig,
+        VelocityRuleConfig,
+    },
+    modules::transactions::TransactionStatus,
+};
+
+use super::{
+    AmountOutlierRule, FirstPaymentRule, HistoryEntry, PaymentAttempt, RepeatedDeclinesRule,
+    RiskDecision, RiskEngine, RiskRule, VelocityRule,
+};
+
+fn now() -> NaiveDateTime {
+    NaiveDateTime::parse_from_str("2026-10-19 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+fn attempt(amount: i64) -> PaymentAttempt {
+    PaymentAttempt {
+        amount: Decimal::from(amount),
+        at: now(),
+        known_payee: false,
+    }
+}
+
+/// History entry created `secs_ago` seconds before the attempt
+fn entry(amount: i64, secs_ago: i64, status: TransactionStatus) -> HistoryEntry {
+    HistoryEntry {
+        amount: Decimal::from(amount),
+        comparable: true,
+        created_at: now() - TimeDelta::seconds(secs_ago),
+        status,
+    }
+}
+
+fn velocity(window_secs: i64, max_count: usize) -> VelocityRule {
+    VelocityRule::from(&VelocityRuleConfig {
+        window_secs,
+        max_count,
+        decision: RiskDecision::Review,
+    })
+}
+
+#[test]
+fn velocity_counts_payments_within_the_window() {
+    let rule = velocity(60, 2);
+    let history = [
+        entry(-10, 10, TransactionStatus::Confirmed),
+        entry(-10, 120, TransactionStatus::Confirmed),
+    ];
+    assert_eq!(
+        rule.evaluate(&attempt(-10), &history).decision,
+        RiskDecision::Allow
+    );
+
+    let history = [
+        entry(-10, 10, TransactionStatus::Confirmed),
+        entry(-10, 30, TransactionStatus::Initialized),
+    ];
+    let verdict = rule.evaluate(&attempt(-10), &history);
+    assert_eq!(verdict.decision, RiskDecision::Review);
+    assert_eq!(verdict.reasons, ["2 payments in the last 60 seconds"]);
+}
+
+#[test]
+fn windows_out_of_range_cover_the_whole_history() {
+    let history = [entry(-10, 10, TransactionStatus::Confirmed)];
+
+    assert_eq!(
+        velocity(i64::MAX, 1)
+            .evaluate(&attempt(-10), &history)
+            .decision,
+        RiskDecision::Review
+    );
+}
+
+#[test]
+fn velocity_ignores_declined_failed_and_logged_entries() {
+    let rule = velocity(60, 2);
+    let history = [
+        entry(-10, 10, TransactionStatus::Confirmed),
+        entry(-10, 20, TransactionStatus::Declined),
+        entry(-10, 30, TransactionStatus::InternalError),
+        entry(-10, 40, TransactionStatus::Log),
+    ];
+
+    assert_eq!(
+        rule.evaluate(&attempt(-10), &history).decision,
+        RiskDecision::Allow
+    );
+}
+
+/// History entry in another currency than the attempt's
+fn foreign(amount: i64, secs_ago: i64, status: TransactionStatus) -> HistoryEntry {
+    HistoryEntry {
+        comparable: false,
+        ..entry(amount, secs_ago, status)
+    }
+}
+
+#[test]
+fn count_rules_count_every_currency() {
+    let history = [
+        entry(-10, 10, TransactionStatus::Confirmed),
+        foreign(-10, 20, TransactionStatus::Initialized),
+    ];
+    assert_eq!(
+        velocity(60, 2).evaluate(&attempt(-10), &history).decision,
+        RiskDecision::Review
+    );
+
+    let declines = RepeatedDeclinesRule::from(&RepeatedDeclinesRuleConfig {
+        window_secs: 300,
+        max_declines: 2,
+        decision: RiskDecision::Block,
+    });
+    let history = [
+        entry(-10, 10, TransactionStatus::Declined),
+        foreign(-10, 20, TransactionStatus::Declined),
+    ];
+    assert_eq!(
+        declines.evaluate(&attempt(-10), &history).decision,
+        RiskDecision::Block
+    );
+}
+
+#[test]
+fn amount_outlier_only_compares_amounts_in_the_same_currency() {
+    let rule = AmountOutlierRule::from(&AmountOutlierRuleConfig {
+        multiplier: Decimal::from(3),
+        min_history: 1,
+        decision: RiskDecision::Review,
+    });
+    let history = [
+        entry(-100, 100, TransactionStatus::Confirmed),
+        foreign(-100000, 200, TransactionStatus::Confirmed),
+    ];
+
+    assert_eq!(
+        rule.evaluate(&attempt(-301), &history).decision,
+        RiskDecision::Review
+    );
+}
+
+#[test]
+fn amount_outlier_compares_against_the_confirmed_average() {
+    let rule = AmountOutlierRule::from(&AmountOutlierRuleConfig {
+        multiplier: Decimal::from(3),
+        min_history: 2,
+        decision: RiskDecision::Review,
+    });
+    let history = [
+        entry(-100, 100, TransactionStatus::Confirmed),
+        entry(-200, 200, TransactionStatus::Confirmed),
+        entry(-5000, 300, TransactionStatus::Refunded),
+    ];
+
+    assert_eq!(
+        rule.evaluate(&attempt(-450), &history).decision,
+        RiskDecision::Allow
+    );
+    assert_eq!(
+        rule.evaluate(&attempt(-451), &history).decision,
+        RiskDecision::Review
+    );
+
+    //  Without enough confirmed payments there is no average to compare against
+    assert_eq!(
+        rule.evaluate(&attempt(-10000), &history[..1]).decision,
+        RiskDecision::Allow
+    );
+}
+
+#[test]
+fn first_payment_only_applies_to_unknown_payees() {
+    let rule = FirstPaymentRule::from(&FirstPaymentRuleConfig {
+        min_amount: Decimal::from(1000),
+        decision: RiskDecision::Review,
+    });
+
+    assert_eq!(
+        rule.evaluate(&attempt(-999), &[]).decision,
+        RiskDecision::Allow
+    );
+    assert_eq!(
+        rule.evaluate(&attempt(-1000), &[]).decision,
+        RiskDecision::Review
+    );
+
+    //  Confirmed payments to other merchants don't make the payee known
+    assert_eq!(
+        rule.evaluate(
+            &attempt(-1000),
+            &[entry(-10, 100, TransactionStatus::Confirmed)]
+        )
+        .decision,
+        RiskDecision::Review
+    );
+    let known = PaymentAttempt {
+        known_payee: true,
+        ..attempt(-1000)
+    };
+    assert_eq!(rule.evaluate(&known, &[]).decision, RiskDecision::Allow);
+}
+
+#[test]
+fn repeated_declines_counts_declines_within_the_window() {
+    let rule = RepeatedDeclinesRule::from(&RepeatedDeclinesRuleConfig {
+        window_secs: 3600,
+        max_declines: 2,
+        decision: RiskDecision::Block,
+    });
+    let history = [
+        entry(-10, 60, TransactionStatus::Declined),
+        entry(-10, 120, TransactionStatus::Confirmed),
+        entry(-10, 7200, TransactionStatus::Declined),
+    ];
+    assert_eq!(
+        rule.evaluate(&attempt(-10), &history).decision,
+        RiskDecision::Allow
+    );
+
+    let history = [
+        entry(-10, 60, TransactionStatus::Declined),
+        entry(-10, 120, TransactionStatus::Declined),
+    ];
+    assert_eq!(
+        rule.evaluate(&attempt(-10), &history).decision,
+        RiskDecision::Block
+    );
+}
+
+#[test]
+fn engine_keeps_the_most_severe_decision_and_every_reason() {
+    let config = RiskConfig {
+        velocity: Some(VelocityRuleConfig {
+            window_secs: 60,
+            max_count: 1,
+            decision: RiskDecision::Review,
+        }),
+        repeated_declines: Some(RepeatedDeclinesRuleConfig {
+            window_secs: 60,
+            max_declines: 1,
+            decision: RiskDecision::Block,
+        }),
+        ..Default::default()
+    };
+    let history = [
+        entry(-10, 10, TransactionStatus::Confirmed),
+        entry(-10, 20, TransactionStatus::Declined),
+    ];
+
+    let verdict = RiskEngine::from_config(&config).evaluate(&attempt(-10), &history);
+    assert_eq!(verdict.decision, RiskDecision::Block);
+    assert_eq!(
+        verdict.reasons,
+        [
+            "velocity: 1 payments in the last 60 seconds",
+            "repeated_declines: 1 declined payments in the last 60 seconds",
+        ]
+    );
+
+    assert_eq!(
+        RiskEngine::from_config(&RiskConfig::default())
+            .evaluate(&attempt(-10), &history)
+            .decision,
+        RiskDecision::Allow
+    );
+}
@@ -0,0 +1,396 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use error_mapper::{create_new_error, TheResult};
+use mysql::prelude::Queryable;
+use rust_decimal::Decimal;
+
+use crate::{
+    database::{
+        decode::{self, DecodeRow, RowError},
+        in_transaction, in_transaction_if, Executor, MySqlStore,
+    },
+    datatypes::{MerchantsIdType, SettlementBatchesIdType, TransactionsIdType},
+    from_row_via_decode,
+    modules::{
+        currencies::Currency, fees::Fee, merchants::Merchant, transactions::Transaction,
+        wallets::Wallet,
+    },
+    row_to_data,
+};
+
+use super::{
+    period_cutoff, period_start, repository::SettlementRepository, MerchantPayment, PayoutOutcome,
+    SettlementBatch, SettlementLine, SettlementLineKind, SettlementStatus,
+};
+
+impl SettlementBatch {
+    /// ## Description
+    /// Inserts the batch with its lines. The unique index on the lines fails the insert, and so the DB
+    /// transaction, if another batch settled any of them meanwhile
+    fn insert(
+        &mut self,
+        conn: &mut mysql::Transaction<'_>,
+        lines: &[SettlementLine],
+    ) -> TheResult<()> {
+        let query = "INSERT INTO `settlement_batches`(`merchants_ID`, `currency`, `period_start`, `period_end`, `payments`, `refunds`, `fees`, `net`, `status`) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(
+            stmt,
+            (
+                self.merchants_id,
+                self.currency.code(),
+                self.period_start,
+                self.period_end,
+                self.payments.to_string(),
+                self.refunds.to_string(),
+                self.fees.to_string(),
+                self.net.to_string(),
+                self.status.to_string(),
+            ),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+        self.id = Executor::last_insert_id(conn);
+
+        let query = "INSERT INTO `settlement_lines`(`batches_ID`, `transactions_ID`, `kind`, `amount`, `fee`) VALUES(?, ?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_batch(
+            stmt,
+            lines.iter().map(|line| {
+                (
+                    self.id,
+                    line.transactions_id,
+                    line.kind.to_string(),
+                    line.amount.to_string(),
+                    line.fee.to_string(),
+                )
+            }),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Updates the batch status only if it still has one of the expected previous statuses, so concurrent
+    /// runs cannot pay a batch out twice
+    fn update_status_from(
+        &self,
+        conn: &mut impl Executor,
+        previous_statuses: &[SettlementStatus],
+    ) -> TheResult<bool> {
+        let placeholders = vec!["?"; previous_statuses.len()].join(", ");
+        let query = format!(
+            "UPDATE `settlement_batches` SET `status` = ?, `paid_at` = ? WHERE `ID` = ? AND `status` IN ({});",
+            placeholders
+        );
+        let mut params = vec![
+            Some(self.status.to_string()),
+            self.paid_at.map(|paid_at| paid_at.to_string()),
+            Some(self.id.to_string()),
+        ];
+        params.extend(
+            previous_statuses
+                .iter()
+                .map(|status| Some(status.to_string())),
+        );
+
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(stmt, params)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(conn.affected_rows() > 0)
+    }
+}
+
+impl SettlementRepository for MySqlStore {
+    fn close(
+        &self,
+        merchant: &Merchant,
+        period_end: NaiveDate,
+    ) -> TheResult<Option<SettlementBatch>> {
+        in_transaction(&mut self.get_conn()?, |db_transaction| {
+            let query =
+                "SELECT MAX(`period_end`) FROM `settlement_batches` WHERE `merchants_ID` = ?;";
+            let last_period_end = db_transaction
+                .exec_first::<Option<NaiveDate>, _, _>(query, (merchant.id,))
+                .map_err(|error| create_new_error!(error.to_string()))?
+                .flatten();
+            let Some(period_start) = period_start(merchant, last_period_end, period_end) else {
+                return Ok(None);
+            };
+
+            let mut lines = Vec::new();
+            let query = "SELECT * FROM `transactions` WHERE `merchants_ID` = ? AND `status` = 'Confirmed' AND `created_at` < ? \
+                    AND NOT EXISTS (SELECT 1 FROM `settlement_lines` WHERE `transactions_ID` = `transactions`.`ID` AND `kind` = 'Payment') \
+                ORDER BY `ID`;";
+            for payment in select_payments(
+                db_transaction,
+                query,
+                merchant.id,
+                Some(period_cutoff(period_end)),
+            )? {
+                let fees = Fee::select_by_transactions_id(db_transaction, payment.transactions_id)?;
+                lines.push(SettlementLine::payment(&payment, &fees, merchant.currency));
+            }
+            let query = "SELECT * FROM `transactions` WHERE `merchants_ID` = ? AND `status` = 'Refunded' \
+                    AND EXISTS (SELECT 1 FROM `settlement_lines` WHERE `transactions_ID` = `transactions`.`ID` AND `kind` = 'Payment') \
+                    AND NOT EXISTS (SELECT 1 FROM `settlement_lines` WHERE `transactions_ID` = `transactions`.`ID` AND `kind` = 'Refund') \
+                ORDER BY `ID`;";
+            for payment in select_payments(db_transaction, query, merchant.id, None)? {
+                let fees = Fee::select_by_transactions_id(db_transaction, payment.transactions_id)?;
+                lines.push(SettlementLine::refund(&payment, &fees, merchant.currency));
+            }
+            if lines.is_empty() {
+                return Ok(None);
+            }
+
+            let mut batch = SettlementBatch::new(merchant, period_start, period_end, &lines);
+            batch.insert(db_transaction, &lines)?;
+            Ok(Some(batch))
+        })
+    }
+
+    fn select_by_id(
+        &self,
+        batches_id: SettlementBatchesIdType,
+    ) -> TheResult<Option<SettlementBatch>> {
+        let mut conn = self.get_conn()?;
+        let query = "SELECT * FROM `settlement_batches` WHERE `ID` = ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, (batches_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> TheResult<Vec<SettlementBatch>> {
+        let mut conn = self.get_conn()?;
+        let query = "SELECT * FROM `settlement_batches` WHERE `merchants_ID` = ? AND `period_end` BETWEEN ? AND ? ORDER BY `period_end` DESC;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (merchants_id, from, to))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_paid_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<SettlementBatch>> {
+        let mut conn = self.get_conn()?;
+        let query = "SELECT * FROM `settlement_batches` WHERE `merchants_ID` = ? AND `status` = 'Paid' AND `paid_at` >= ? AND `paid_at` < ? ORDER BY `paid_at`, `ID`;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (merchants_id, from, to))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_paid_net_before(
+        &self,
+        merchants_id: MerchantsIdType,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal> {
+        let mut conn = self.get_conn()?;
+        let query = "SELECT COALESCE(SUM(`net`), 0) FROM `settlement_batches` WHERE `merchants_ID` = ? AND `status` = 'Paid' AND `paid_at` < ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let net = conn
+            .exec_first::<Decimal, _, _>(stmt, (merchants_id, before))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        Ok(net.unwrap_or_default())
+    }
+
+    fn select_unpaid(&self) -> TheResult<Vec<SettlementBatch>> {
+        let rows = self
+            .get_conn()?
+            .query::<mysql::Row, _>(
+                "SELECT * FROM `settlement_batches` WHERE `status` IN ('Pending', 'Held') ORDER BY `ID`;",
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_lines(&self, batches_id: SettlementBatchesIdType) -> TheResult<Vec<SettlementLine>> {
+        let mut conn = self.get_conn()?;
+        let query = "SELECT * FROM `settlement_lines` WHERE `batches_ID` = ? ORDER BY `ID`;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (batches_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn hold(&self, batch: &mut SettlementBatch) -> TheResult<bool> {
+        let mut held = batch.clone();
+        held.status = SettlementStatus::Held;
+        if !held.update_status_from(&mut self.get_conn()?, &[SettlementStatus::Pending])? {
+            return Ok(false);
+        }
+
+        *batch = held;
+        Ok(true)
+    }
+
+    fn pay_out(
+        &self,
+        batch: &mut SettlementBatch,
+        merchant: &Merchant,
+    ) -> TheResult<PayoutOutcome> {
+        let mut paid = batch.clone();
+        paid.status = SettlementStatus::Paid;
+        paid.paid_at = Some(chrono::Local::now().naive_local());
+
+        let mut conn = self.get_conn()?;
+        let outcome = in_transaction_if(
+            &mut conn,
+            |db_transaction| {
+                if !paid.update_status_from(
+                    db_transaction,
+                    &[SettlementStatus::Pending, SettlementStatus::Held],
+                )? {
+                    return Ok(PayoutOutcome::NotUnpaid);
+                }
+                //  Adding zero changes no row, which the balance update would take for a failure
+                if paid.net.is_zero() {
+                    return Ok(PayoutOutcome::Paid);
+                }
+
+                let Some(mut wallet) = Wallet::select_by_id(db_transaction, merchant.wallets_id)?
+                else {
+                    return Err(create_new_error!(format!(
+                        "Settlement wallet with ID: {} no longer exists",
+                        merchant.wallets_id
+                    )));
+                };
+                if wallet.affect_balance(db_transaction, paid.net, paid.currency)? {
+                    return Ok(PayoutOutcome::Paid);
+                }
+                if paid.net < Decimal::ZERO {
+                    return Ok(PayoutOutcome::Held);
+                }
+                Err(create_new_error!(format!(
+                    "Could not credit settlement wallet with ID: {}",
+                    merchant.wallets_id
+                )))
+            },
+            |outcome| *outcome == PayoutOutcome::Paid,
+        )?;
+
+        match outcome {
+            PayoutOutcome::Paid => *batch = paid,
+            PayoutOutcome::Held => {
+                let mut held = batch.clone();
+                held.status = SettlementStatus::Held;
+                held.update_status_from(&mut conn, &[SettlementStatus::Pending])?;
+                *batch = held;
+            }
+            PayoutOutcome::NotUnpaid => {}
+        }
+        Ok(outcome)
+    }
+}
+
+/// ## Description
+/// Selects transactions made to a merchant as the settlements account for them. The query takes the
+/// merchant ID, followed by the cutoff when one is received
+fn select_payments(
+    conn: &mut impl Queryable,
+    query: &str,
+    merchants_id: MerchantsIdType,
+    cutoff: Option<NaiveDateTime>,
+) -> TheResult<Vec<MerchantPayment>> {
+    let stmt = conn
+        .prep(query)
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    let rows = match cutoff {
+        Some(cutoff) => conn.exec::<mysql::Row, _, _>(stmt, (merchants_id, cutoff)),
+        None => conn.exec::<mysql::Row, _, _>(stmt, (merchants_id,)),
+    }
+    .map_err(|error| create_new_error!(error.to_string()))?;
+
+    let transactions =
+        decode::rows::<Transaction>(rows).map_err(|error| create_new_error!(error.to_string()))?;
+    Ok(transactions
+        .iter()
+        .filter_map(Transaction::to_merchant_payment)
+        .collect())
+}
+
+impl DecodeRow for SettlementBatch {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            id: row_to_data!(row, "ID", "settlement_batches", SettlementBatchesIdType),
+            merchants_id: row_to_data!(row, "merchants_ID", "settlement_batches", MerchantsIdType),
+            currency: decode::variant(
+                row,
+                "settlement_batches",
+                "currency",
+                Currency::from_string,
+            )?,
+            period_start: row_to_data!(row, "period_start", "settlement_batches", NaiveDate),
+            period_end: row_to_data!(row, "period_end", "settlement_batches", NaiveDate),
+            payments: row_to_data!(row, "payments", "settlement_batches", Decimal),
+            refunds: row_to_data!(row, "refunds", "settlement_batches", Decimal),
+            fees: row_to_data!(row, "fees", "settlement_batches", Decimal),
+            net: row_to_data!(row, "net", "settlement_batches", Decimal),
+            status: decode::variant(
+                row,
+                "settlement_batches",
+                "status",
+                SettlementStatus::from_string,
+            )?,
+            created_at: row_to_data!(row, "created_at", "settlement_batches", NaiveDateTime),
+            paid_at: row_to_data!(row, "paid_at", "settlement_batches", Option<NaiveDateTime>),
+        })
+    }
+}
+
+from_row_via_decode!(SettlementBatch);
+
+impl DecodeRow for SettlementLine {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            transactions_id: row_to_data!(
+                row,
+                "transactions_ID",
+                "settlement_lines",
+                TransactionsIdType
+            ),
+            kind: decode::variant(
+                row,
+                "settlement_lines",
+                "kind",
+                SettlementLineKind::from_string,
+            )?,
+            amount: row_to_data!(row, "amount", "settlement_lines", Decimal),
+            fee: row_to_data!(row, "fee", "settlement_lines", Decimal),
+        })
+    }
+}
+
+from_row_via_decode!(SettlementLine);
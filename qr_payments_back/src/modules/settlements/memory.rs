@@ -0,0 +1,249 @@
+use std::collections::BTreeSet;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use error_mapper::{create_new_error, TheResult};
+use rust_decimal::Decimal;
+
+use crate::{
+    database::memory::{InMemoryState, InMemoryStore},
+    datatypes::{MerchantsIdType, SettlementBatchesIdType, TransactionsIdType},
+    modules::{
+        fees::Fee,
+        merchants::Merchant,
+        transactions::{Transaction, TransactionStatus},
+    },
+};
+
+use super::{
+    period_cutoff, period_start, repository::SettlementRepository, PayoutOutcome, SettlementBatch,
+    SettlementLine, SettlementLineKind, SettlementStatus,
+};
+
+impl SettlementRepository for InMemoryStore {
+    fn close(
+        &self,
+        merchant: &Merchant,
+        period_end: NaiveDate,
+    ) -> TheResult<Option<SettlementBatch>> {
+        let mut state = self.lock()?;
+        let last_period_end = state
+            .settlement_batches
+            .values()
+            .filter(|batch| batch.merchants_id == merchant.id)
+            .map(|batch| batch.period_end)
+            .max();
+        let Some(period_start) = period_start(merchant, last_period_end, period_end) else {
+            return Ok(None);
+        };
+
+        let cutoff = period_cutoff(period_end);
+        let paid = settled(&state, SettlementLineKind::Payment);
+        let refunded = settled(&state, SettlementLineKind::Refund);
+        let payments = state
+            .transactions
+            .values()
+            .filter_map(Transaction::to_merchant_payment)
+            .filter(|payment| payment.merchants_id == merchant.id)
+            .collect::<Vec<_>>();
+
+        let mut lines = payments
+            .iter()
+            .filter(|payment| {
+                payment.status == TransactionStatus::Confirmed
+                    && payment.created_at < cutoff
+                    && !paid.contains(&payment.transactions_id)
+            })
+            .map(|payment| {
+                let fees = fees(&state, payment.transactions_id);
+                SettlementLine::payment(payment, &fees, merchant.currency)
+            })
+            .collect::<Vec<_>>();
+        lines.extend(
+            payments
+                .iter()
+                .filter(|payment| {
+                    payment.status == TransactionStatus::Refunded
+                        && paid.contains(&payment.transactions_id)
+                        && !refunded.contains(&payment.transactions_id)
+                })
+                .map(|payment| {
+                    let fees = fees(&state, payment.transactions_id);
+                    SettlementLine::refund(payment, &fees, merchant.currency)
+                }),
+        );
+        if lines.is_empty() {
+            return Ok(None);
+        }
+
+        let mut batch = SettlementBatch::new(merchant, period_start, period_end, &lines);
+        batch.id = state.next_settlement_batches_id();
+        state.settlement_batches.insert(batch.id, batch.clone());
+        state
+            .settlement_lines
+            .extend(lines.into_iter().map(|line| (batch.id, line)));
+
+        Ok(Some(batch))
+    }
+
+    fn select_by_id(
+        &self,
+        batches_id: SettlementBatchesIdType,
+    ) -> TheResult<Option<SettlementBatch>> {
+        Ok(self.lock()?.settlement_batches.get(&batches_id).cloned())
+    }
+
+    fn select_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> TheResult<Vec<SettlementBatch>> {
+        let mut batches = self
+            .lock()?
+            .settlement_batches
+            .values()
+            .filter(|batch| {
+                batch.merchants_id == merchants_id
+                    && batch.period_end >= from
+                    && batch.period_end <= to
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        batches.sort_by_key(|batch| std::cmp::Reverse(batch.period_end));
+
+        Ok(batches)
+    }
+
+    fn select_paid_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<SettlementBatch>> {
+        let mut batches = self
+            .lock()?
+            .settlement_batches
+            .values()
+            .filter(|batch| {
+                batch.merchants_id == merchants_id
+                    && batch.status == SettlementStatus::Paid
+                    && batch
+                        .paid_at
+                        .is_some_and(|paid_at| paid_at >= from && paid_at < to)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        batches.sort_by_key(|batch| batch.paid_at);
+
+        Ok(batches)
+    }
+
+    fn select_paid_net_before(
+        &self,
+        merchants_id: MerchantsIdType,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal> {
+        Ok(self
+            .lock()?
+            .settlement_batches
+            .values()
+            .filter(|batch| {
+                batch.merchants_id == merchants_id
+                    && batch.status == SettlementStatus::Paid
+                    && batch.paid_at.is_some_and(|paid_at| paid_at < before)
+            })
+            .map(|batch| batch.net)
+            .sum())
+    }
+
+    fn select_unpaid(&self) -> TheResult<Vec<SettlementBatch>> {
+        Ok(self
+            .lock()?
+            .settlement_batches
+            .values()
+            .filter(|batch| !batch.is_paid())
+            .cloned()
+            .collect())
+    }
+
+    fn select_lines(&self, batches_id: SettlementBatchesIdType) -> TheResult<Vec<SettlementLine>> {
+        Ok(self
+            .lock()?
+            .settlement_lines
+            .iter()
+            .filter(|(id, _)| *id == batches_id)
+            .map(|(_, line)| line.clone())
+            .collect())
+    }
+
+    fn hold(&self, batch: &mut SettlementBatch) -> TheResult<bool> {
+        let mut state = self.lock()?;
+        let Some(stored) = state.settlement_batches.get_mut(&batch.id) else {
+            return Ok(false);
+        };
+        if stored.status != SettlementStatus::Pending {
+            return Ok(false);
+        }
+
+        stored.status = SettlementStatus::Held;
+        batch.status = SettlementStatus::Held;
+
+        Ok(true)
+    }
+
+    fn pay_out(
+        &self,
+        batch: &mut SettlementBatch,
+        merchant: &Merchant,
+    ) -> TheResult<PayoutOutcome> {
+        let mut state = self.lock()?;
+        match state.settlement_batches.get(&batch.id) {
+            Some(stored) if !stored.is_paid() => {}
+            _ => return Ok(PayoutOutcome::NotUnpaid),
+        }
+        let Some(wallet) = state.wallets.get_mut(&merchant.wallets_id) else {
+            return Err(create_new_error!(format!(
+                "Settlement wallet with ID: {} no longer exists",
+                merchant.wallets_id
+            )));
+        };
+        if wallet.currency != batch.currency {
+            return Err(create_new_error!(format!(
+                "Settlement wallet with ID: {} does not hold {}",
+                merchant.wallets_id, batch.currency
+            )));
+        }
+        if wallet.balance + batch.net < Decimal::ZERO {
+            batch.status = SettlementStatus::Held;
+            state.settlement_batches.insert(batch.id, batch.clone());
+            return Ok(PayoutOutcome::Held);
+        }
+
+        wallet.balance += batch.net;
+        batch.status = SettlementStatus::Paid;
+        batch.paid_at = Some(chrono::Local::now().naive_local());
+        state.settlement_batches.insert(batch.id, batch.clone());
+
+        Ok(PayoutOutcome::Paid)
+    }
+}
+
+/// Transactions with a line of the received kind in any batch
+fn settled(state: &InMemoryState, kind: SettlementLineKind) -> BTreeSet<TransactionsIdType> {
+    state
+        .settlement_lines
+        .iter()
+        .filter(|(_, line)| line.kind == kind)
+        .map(|(_, line)| line.transactions_id)
+        .collect()
+}
+
+/// Fee ledger of a transaction, oldest line first
+fn fees(state: &InMemoryState, transactions_id: TransactionsIdType) -> Vec<Fee> {
+    state
+        .fees
+        .iter()
+        .filter(|(id, _)| *id == transactions_id)
+        .map(|(_, fee)| fee.clone())
+        .collect()
+}
@@ -0,0 +1,361 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    datatypes::{MerchantsIdType, SettlementBatchesIdType, TransactionsIdType},
+    modules::{
+        currencies::Currency,
+        fees::{Fee, FeeParty},
+        merchants::Merchant,
+        transactions::TransactionStatus,
+    },
+    validation::{FieldError, FieldErrorCode, Validate},
+};
+
+mod db;
+#[cfg(test)]
+mod memory;
+pub mod repository;
+pub mod scheduler;
+pub mod services;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+/// Days a settlement report covers when its request sets no start
+pub const REPORT_DEFAULT_DAYS: i64 = 30;
+/// Most days a single settlement report can cover
+pub const REPORT_MAX_DAYS: i64 = 366;
+
+/// ## Description
+/// Payout to a merchant of what it collected over a period, credited to its settlement wallet. Amounts are in
+/// the merchant currency
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct SettlementBatch {
+    pub id: SettlementBatchesIdType,
+    pub merchants_id: MerchantsIdType,
+    pub currency: Currency,
+    /// First and last days closed by the batch. Payments confirmed late are settled by the batch closing
+    /// after their confirmation
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    /// Sum of the payments settled
+    pub payments: Decimal,
+    /// Sum of the refunds of payments settled by earlier batches, negative
+    pub refunds: Decimal,
+    /// Merchant fees charged on the payments, net of the ones returned with the refunds
+    pub fees: Decimal,
+    /// Amount credited to the settlement wallet, negative when the refunds exceed the payments
+    pub net: Decimal,
+    pub status: SettlementStatus,
+    pub created_at: NaiveDateTime,
+    pub paid_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, strum::Display, ToSchema,
+)]
+pub enum SettlementStatus {
+    /// Closed and waiting for its payout
+    #[default]
+    Pending,
+    /// Not paid out because its merchant is suspended, or its negative net exceeds the settlement wallet
+    /// balance. Retried on every run until it can be paid
+    Held,
+    Paid,
+}
+
+/// Outcome of paying out a batch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PayoutOutcome {
+    Paid,
+    /// Held, as the settlement wallet does not cover the negative net of the batch
+    Held,
+    /// Nothing was changed, the batch was no longer unpaid
+    NotUnpaid,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, strum::Display, ToSchema,
+)]
+pub enum SettlementLineKind {
+    #[default]
+    Payment,
+    /// Refund of a payment settled by an earlier batch
+    Refund,
+}
+
+/// ## Description
+/// Transaction settled by a batch, in the merchant currency. Refund lines are negative, as they deduct a
+/// payment settled before along with its fees
+#[derive(Debug, Default, Clone, PartialEq, Serialize, ToSchema)]
+pub struct SettlementLine {
+    pub transactions_id: TransactionsIdType,
+    pub kind: SettlementLineKind,
+    pub amount: Decimal,
+    pub fee: Decimal,
+}
+
+/// Settlement batch along with the transactions it settles
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct SettlementBatchDetail {
+    #[serde(flatten)]
+    pub batch: SettlementBatch,
+    pub lines: Vec<SettlementLine>,
+}
+
+/// ## Description
+/// Batches of a merchant closing between two days, with their totals
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct SettlementReport {
+    pub merchants_id: MerchantsIdType,
+    pub currency: Currency,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub payments: Decimal,
+    pub refunds: Decimal,
+    pub fees: Decimal,
+    pub net: Decimal,
+    /// Part of the net already credited to the settlement wallet
+    pub paid: Decimal,
+    /// Newest first
+    pub batches: Vec<SettlementBatch>,
+}
+
+/// Range of days of a settlement report, as sent in its query string
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SettlementReportQuery {
+    /// Defaults to `REPORT_DEFAULT_DAYS` (30) days before `to`
+    pub from: Option<NaiveDate>,
+    /// Defaults to today, at most `REPORT_MAX_DAYS` (366) days after `from`
+    pub to: Option<NaiveDate>,
+}
+
+/// Payment made to a merchant, as the settlements account for it
+#[derive(Debug, Clone)]
+pub struct MerchantPayment {
+    pub transactions_id: TransactionsIdType,
+    pub merchants_id: MerchantsIdType,
+    /// Amount paid, positive and in the merchant currency
+    pub amount: Decimal,
+    /// Rate of a converted payment, whose fees were charged in the currency it was debited in
+    pub rate: Option<Decimal>,
+    pub status: TransactionStatus,
+    pub created_at: NaiveDateTime,
+    /// When a refund gave the payment back
+    pub reversed_at: Option<NaiveDateTime>,
+}
+
+impl SettlementStatus {
+    pub(crate) fn from_string(input: String) -> Option<Self> {
+        match input.as_str() {
+            "Pending" => Some(Self::Pending),
+            "Held" => Some(Self::Held),
+            "Paid" => Some(Self::Paid),
+            _ => None,
+        }
+    }
+}
+
+impl SettlementLineKind {
+    pub(crate) fn from_string(input: String) -> Option<Self> {
+        match input.as_str() {
+            "Payment" => Some(Self::Payment),
+            "Refund" => Some(Self::Refund),
+            _ => None,
+        }
+    }
+}
+
+impl SettlementLine {
+    /// ## Description
+    /// Line settling a payment, its merchant fees deducted
+    pub fn payment(payment: &MerchantPayment, fees: &[Fee], currency: Currency) -> Self {
+        Self {
+            transactions_id: payment.transactions_id,
+            kind: SettlementLineKind::Payment,
+            amount: payment.amount,
+            fee: merchant_fees(payment, fees, currency, |amount| amount > Decimal::ZERO),
+        }
+    }
+
+    /// ## Description
+    /// Line deducting the refund of a payment settled before, the merchant fees refunded with it given back
+    pub fn refund(payment: &MerchantPayment, fees: &[Fee], currency: Currency) -> Self {
+        Self {
+            transactions_id: payment.transactions_id,
+            kind: SettlementLineKind::Refund,
+            amount: -payment.amount,
+            fee: merchant_fees(payment, fees, currency, |amount| amount < Decimal::ZERO),
+        }
+    }
+
+    pub fn net(&self) -> Decimal {
+        self.amount - self.fee
+    }
+}
+
+/// ## Description
+/// Sum of the merchant lines of a fee ledger selected by their amount, in the merchant currency. The fees of
+/// converted payments are brought to it at the payment rate
+pub(crate) fn merchant_fees(
+    payment: &MerchantPayment,
+    fees: &[Fee],
+    currency: Currency,
+    selected: impl Fn(Decimal) -> bool,
+) -> Decimal {
+    fees.iter()
+        .filter(|fee| fee.party == FeeParty::Merchant && selected(fee.amount))
+        .map(|fee| match payment.rate {
+            Some(rate) if fee.currency != currency => currency.round(fee.amount * rate),
+            _ => fee.amount,
+        })
+        .sum()
+}
+
+impl SettlementBatch {
+    /// ## Description
+    /// Pending batch of a merchant closing the received days with its lines, unsaved
+    pub fn new(
+        merchant: &Merchant,
+        period_start: NaiveDate,
+        period_end: NaiveDate,
+        lines: &[SettlementLine],
+    ) -> Self {
+        let sum = |kind: SettlementLineKind| -> Decimal {
+            lines
+                .iter()
+                .filter(|line| line.kind == kind)
+                .map(|line| line.amount)
+                .sum()
+        };
+        let fees = lines.iter().map(|line| line.fee).sum();
+        let payments = sum(SettlementLineKind::Payment);
+        let refunds = sum(SettlementLineKind::Refund);
+
+        Self {
+            id: SettlementBatchesIdType::default(),
+            merchants_id: merchant.id,
+            currency: merchant.currency,
+            period_start,
+            period_end,
+            payments,
+            refunds,
+            fees,
+            net: payments + refunds - fees,
+            status: SettlementStatus::default(),
+            created_at: chrono::Local::now().naive_local(),
+            paid_at: None,
+        }
+    }
+
+    pub fn is_paid(&self) -> bool {
+        self.status == SettlementStatus::Paid
+    }
+}
+
+/// ## Description
+/// First day the next batch of a merchant closes: the day after its last batch, or the day it was created
+///
+/// ### Returns
+/// None when the merchant has nothing left to close through `period_end`
+pub fn period_start(
+    merchant: &Merchant,
+    last_period_end: Option<NaiveDate>,
+    period_end: NaiveDate,
+) -> Option<NaiveDate> {
+    let period_start = match last_period_end {
+        Some(last_period_end) => last_period_end.succ_opt()?,
+        None => merchant.created_at.date(),
+    };
+
+    (period_start <= period_end).then_some(period_start)
+}
+
+/// ## Description
+/// Start of the day after `period_end`. Payments created before it are settled by the batch closing that day
+pub fn period_cutoff(period_end: NaiveDate) -> NaiveDateTime {
+    period_end
+        .succ_opt()
+        .unwrap_or(period_end)
+        .and_time(chrono::NaiveTime::MIN)
+}
+
+impl SettlementReport {
+    /// ## Description
+    /// Report of the batches of a merchant closing between the received days, adding up their totals
+    pub fn new(
+        merchant: &Merchant,
+        from: NaiveDate,
+        to: NaiveDate,
+        batches: Vec<SettlementBatch>,
+    ) -> Self {
+        Self {
+            merchants_id: merchant.id,
+            currency: merchant.currency,
+            from,
+            to,
+            payments: batches.iter().map(|batch| batch.payments).sum(),
+            refunds: batches.iter().map(|batch| batch.refunds).sum(),
+            fees: batches.iter().map(|batch| batch.fees).sum(),
+            net: batches.iter().map(|batch| batch.net).sum(),
+            paid: batches
+                .iter()
+                .filter(|batch| batch.is_paid())
+                .map(|batch| batch.net)
+                .sum(),
+            batches,
+        }
+    }
+}
+
+impl SettlementReportQuery {
+    /// ## Description
+    /// Days the report covers, applying the defaults of the bounds not sent
+    ///
+    /// ### Returns
+    /// The first and last days, or an error when the default start would fall before the earliest date
+    pub fn range(&self, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), FieldError> {
+        let to = self.to.unwrap_or(today);
+        let from = match self.from {
+            Some(from) => from,
+            None => to
+                .checked_sub_signed(chrono::TimeDelta::days(REPORT_DEFAULT_DAYS))
+                .ok_or_else(|| FieldError {
+                    field: "to",
+                    code: FieldErrorCode::OutOfRange,
+                    message: String::from(
+                        "Report ends too early to start a default range before it",
+                    ),
+                })?,
+        };
+        Ok((from, to))
+    }
+}
+
+impl Validate for SettlementReportQuery {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let (from, to) = match self.range(chrono::Local::now().date_naive()) {
+            Ok(range) => range,
+            Err(error) => return vec![error],
+        };
+        if from > to {
+            errors.push(FieldError {
+                field: "from",
+                code: FieldErrorCode::OutOfRange,
+                message: String::from("Report cannot start after it ends"),
+            });
+        } else if (to - from).num_days() >= REPORT_MAX_DAYS {
+            errors.push(FieldError {
+                field: "from",
+                code: FieldErrorCode::OutOfRange,
+                message: format!("Report cannot cover more than {} days", REPORT_MAX_DAYS),
+            });
+        }
+        errors
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use error_mapper::TheResult;
+use rust_decimal::Decimal;
+
+use crate::{
+    datatypes::{MerchantsIdType, SettlementBatchesIdType},
+    modules::merchants::Merchant,
+};
+
+use super::{PayoutOutcome, SettlementBatch, SettlementLine};
+
+/// Persistence of the settlement batches, implemented by every storage backend. A transaction is settled by
+/// a single batch, and its refund deducted by a single one too
+pub trait SettlementRepository: Send + Sync {
+    /// Closes the days of the merchant through `period_end` into a Pending batch, settling the payments
+    /// confirmed before the end of that day and deducting the refunds of the ones settled before, as long
+    /// as no batch holds them yet. Returns None, creating nothing, when the days were already closed or
+    /// have nothing to settle
+    fn close(
+        &self,
+        merchant: &Merchant,
+        period_end: NaiveDate,
+    ) -> TheResult<Option<SettlementBatch>>;
+    fn select_by_id(
+        &self,
+        batches_id: SettlementBatchesIdType,
+    ) -> TheResult<Option<SettlementBatch>>;
+    /// Selects the batches of a merchant whose period ends between the received days, newest first
+    fn select_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> TheResult<Vec<SettlementBatch>>;
+    /// Selects the batches of a merchant paid out in the range, oldest payout first
+    fn select_paid_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<SettlementBatch>>;
+    /// Sums the nets of the batches of a merchant paid out before the datetime
+    fn select_paid_net_before(
+        &self,
+        merchants_id: MerchantsIdType,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal>;
+    /// Selects the batches not paid out yet, Pending and Held, oldest first
+    fn select_unpaid(&self) -> TheResult<Vec<SettlementBatch>>;
+    /// Selects the lines of a batch, in the order they were settled
+    fn select_lines(&self, batches_id: SettlementBatchesIdType) -> TheResult<Vec<SettlementLine>>;
+    /// Moves a Pending batch to Held. Returns false if it was no longer Pending
+    fn hold(&self, batch: &mut SettlementBatch) -> TheResult<bool>;
+    /// Marks an unpaid batch as Paid and credits its net to the settlement wallet of its merchant
+    /// atomically, a zero net crediting nothing. A negative net the wallet balance does not cover holds the
+    /// batch instead, leaving the wallet untouched
+    fn pay_out(&self, batch: &mut SettlementBatch, merchant: &Merchant)
+        -> TheResult<PayoutOutcome>;
+}
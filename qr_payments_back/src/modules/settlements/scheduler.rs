@@ -0,0 +1,196 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::web;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use error_mapper::TheResult;
+use the_logger::TheLogger;
+
+use crate::{
+    config::{Config, SettlementsConfig},
+    database::blocking,
+    log_critical, log_error, log_info, metrics,
+    modules::merchants::repository::MerchantRepository,
+    tasks,
+};
+
+use super::{repository::SettlementRepository, PayoutOutcome};
+
+/// ## Description
+/// Background task that closes the merchant batches through the previous day and pays them out, once on
+/// startup to catch up on the runs missed while stopped and then daily at `run_at`
+pub async fn run_settlement_scheduler(
+    settlements: Arc<dyn SettlementRepository>,
+    merchants: Arc<dyn MerchantRepository>,
+) {
+    let logger = TheLogger::instance();
+    let settlements = web::Data::from(settlements);
+    let merchants = web::Data::from(merchants);
+    log_info!(logger, "Settlement scheduler started");
+
+    loop {
+        let config = match Config::get_settlements_config().await {
+            Ok(config) => config,
+            Err(error) => {
+                log_error!(
+                    logger,
+                    "Could not get settlements configurations: {}",
+                    error
+                );
+                SettlementsConfig::default()
+            }
+        };
+
+        let now = chrono::Local::now().naive_local();
+        let period_end = now.date() - TimeDelta::days(1);
+        //  Merchants failing alone are counted in the metrics instead of failing the run
+        let outcome = match settle(&settlements, &merchants, period_end).await {
+            Ok(0) => Ok(()),
+            Ok(failures) => {
+                log_error!(
+                    logger,
+                    "{} settlement(s) failed, retried on the next run",
+                    failures
+                );
+                Ok(())
+            }
+            Err(error) => {
+                log_error!(logger, "Error settling merchants: {}", error);
+                Err(error)
+            }
+        };
+
+        let wait = next_run(chrono::Local::now().naive_local(), config.run_at);
+        tasks::report("settlement_scheduler", wait, &outcome);
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// ## Description
+/// Closes a batch through `period_end` for every merchant, then pays out the unpaid batches, holding the
+/// ones of suspended merchants. A merchant failing does not stop the others, its failure being logged and
+/// counted in `qrpay_settlement_failures_total`
+///
+/// ### Returns
+/// The number of merchants or batches that failed, once all of them were attempted. An error only when
+/// the merchants or the unpaid batches could not be selected
+pub(crate) async fn settle(
+    settlements: &web::Data<dyn SettlementRepository>,
+    merchants: &web::Data<dyn MerchantRepository>,
+    period_end: NaiveDate,
+) -> TheResult<usize> {
+    let logger = TheLogger::instance();
+    let mut failures = 0;
+
+    let all_merchants = blocking(merchants, |merchants| merchants.select_all()).await?;
+    for merchant in all_merchants.iter().cloned() {
+        let merchants_id = merchant.id;
+        match blocking(settlements, move |settlements| {
+            settlements.close(&merchant, period_end)
+        })
+        .await
+        {
+            Ok(Some(batch)) => log_info!(
+                logger,
+                "Settlement batch with ID: {} closed for merchant with ID: {}, net {} {}",
+                batch.id,
+                batch.merchants_id,
+                batch.net,
+                batch.currency
+            ),
+            Ok(None) => {}
+            Err(error) => {
+                failures += 1;
+                metrics::SETTLEMENT_FAILURES
+                    .with_label_values(&["close"])
+                    .inc();
+                log_critical!(
+                    logger,
+                    "Error closing settlement batch of merchant with ID: {}: {}",
+                    merchants_id,
+                    error
+                );
+            }
+        }
+    }
+
+    let batches = blocking(settlements, |settlements| settlements.select_unpaid()).await?;
+    for mut batch in batches {
+        let batches_id = batch.id;
+        let Some(merchant) = all_merchants
+            .iter()
+            .find(|merchant| merchant.id == batch.merchants_id)
+            .cloned()
+        else {
+            failures += 1;
+            metrics::SETTLEMENT_FAILURES
+                .with_label_values(&["pay_out"])
+                .inc();
+            log_critical!(
+                logger,
+                "Merchant of settlement batch with ID: {} was not found",
+                batches_id
+            );
+            continue;
+        };
+
+        let suspended = !merchant.is_active();
+        match blocking(settlements, move |settlements| {
+            if suspended {
+                settlements.hold(&mut batch).map(|held| {
+                    if held {
+                        PayoutOutcome::Held
+                    } else {
+                        PayoutOutcome::NotUnpaid
+                    }
+                })
+            } else {
+                settlements.pay_out(&mut batch, &merchant)
+            }
+        })
+        .await
+        {
+            Ok(PayoutOutcome::Held) if suspended => log_info!(
+                logger,
+                "Settlement batch with ID: {} held, its merchant is suspended",
+                batches_id
+            ),
+            Ok(PayoutOutcome::Held) => log_info!(
+                logger,
+                "Settlement batch with ID: {} held, its settlement wallet does not cover its net",
+                batches_id
+            ),
+            Ok(PayoutOutcome::Paid) => {
+                log_info!(logger, "Settlement batch with ID: {} paid out", batches_id)
+            }
+            //  Already held, or paid by another instance meanwhile
+            Ok(PayoutOutcome::NotUnpaid) => {}
+            Err(error) => {
+                failures += 1;
+                metrics::SETTLEMENT_FAILURES
+                    .with_label_values(&["pay_out"])
+                    .inc();
+                log_critical!(
+                    logger,
+                    "Error paying out settlement batch with ID: {}: {}",
+                    batches_id,
+                    error
+                );
+            }
+        }
+    }
+
+    Ok(failures)
+}
+
+/// ## Description
+/// Time left from `now` until the next `run_at`, which is tomorrow if it already passed today
+pub(super) fn next_run(now: NaiveDateTime, run_at: NaiveTime) -> Duration {
+    let mut next = now.date().and_time(run_at);
+    if next <= now {
+        next += TimeDelta::days(1);
+    }
+
+    (next - now).to_std().unwrap_or_default()
+}
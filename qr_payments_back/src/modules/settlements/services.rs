@@ -0,0 +1,149 @@
+use actix_web::{get, web, HttpResponse};
+use the_logger::TheLogger;
+use utoipa::OpenApi;
+
+use crate::{
+    api::error::{ApiError, ErrorBody},
+    database::blocking,
+    datatypes::{MerchantsIdType, SettlementBatchesIdType},
+    log_error, log_info,
+    modules::merchants::{repository::MerchantRepository, services::select_merchant},
+    validation::Validate,
+};
+
+use super::{
+    repository::SettlementRepository, SettlementBatchDetail, SettlementReport,
+    SettlementReportQuery,
+};
+
+/// ## Description
+/// Settlement routes, nested under the `/merchants` scope
+pub fn settlements_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_settlements).service(get_settlement);
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_settlements, get_settlement), tags((name = "merchants")))]
+pub struct SettlementsApi;
+
+/// /v1/merchants/{merchants_id}/settlements
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Reports the settlement batches of a merchant whose period ends between two days, newest first",
+    params(("merchants_id" = MerchantsIdType, Path), SettlementReportQuery),
+    responses(
+        (status = 200, description = "Settlement report", body = SettlementReport),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid range of days", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{merchants_id}/settlements")]
+async fn get_settlements(
+    path: web::Path<MerchantsIdType>,
+    query: web::Query<SettlementReportQuery>,
+    settlements: web::Data<dyn SettlementRepository>,
+    merchants: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let query = query.into_inner();
+
+    let errors = query.validate();
+    if !errors.is_empty() {
+        let error = ApiError::invalid_fields(errors);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+    let merchant = select_merchant(&merchants, path.into_inner()).await?;
+    let (from, to) = match query.range(chrono::Local::now().date_naive()) {
+        Ok(range) => range,
+        Err(error) => {
+            let error = ApiError::invalid_fields(vec![error]);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+    };
+
+    log_info!(
+        logger,
+        "Selecting settlements of merchant with ID: {} from {} to {}",
+        merchant.id,
+        from,
+        to
+    );
+
+    let merchants_id = merchant.id;
+    match blocking(&settlements, move |settlements| {
+        settlements.select_by_merchants_id(merchants_id, from, to)
+    })
+    .await
+    {
+        Ok(batches) => {
+            Ok(HttpResponse::Ok().json(SettlementReport::new(&merchant, from, to, batches)))
+        }
+        Err(error) => {
+            log_error!(logger, "Could not get settlements: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/merchants/{merchants_id}/settlements/{batches_id}
+#[utoipa::path(
+    tag = "merchants",
+    summary = "Gets a settlement batch of a merchant with the transactions it settles",
+    params(
+        ("merchants_id" = MerchantsIdType, Path),
+        ("batches_id" = SettlementBatchesIdType, Path),
+    ),
+    responses(
+        (status = 200, description = "Settlement batch", body = SettlementBatchDetail),
+        (status = 404, description = "Merchant or settlement batch not found", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{merchants_id}/settlements/{batches_id}")]
+async fn get_settlement(
+    path: web::Path<(MerchantsIdType, SettlementBatchesIdType)>,
+    settlements: web::Data<dyn SettlementRepository>,
+    merchants: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let (merchants_id, batches_id) = path.into_inner();
+    let merchant = select_merchant(&merchants, merchants_id).await?;
+
+    log_info!(
+        logger,
+        "Selecting settlement batch with ID: {} of merchant with ID: {}",
+        batches_id,
+        merchant.id
+    );
+
+    let detail = match blocking(&settlements, move |settlements| {
+        let Some(batch) = settlements.select_by_id(batches_id)? else {
+            return Ok(None);
+        };
+        let lines = settlements.select_lines(batch.id)?;
+        Ok(Some(SettlementBatchDetail { batch, lines }))
+    })
+    .await
+    {
+        Ok(detail) => detail,
+        Err(error) => {
+            log_error!(logger, "Could not get settlement batch: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    //  Batches of other merchants are reported as missing, not to reveal they exist
+    match detail {
+        Some(detail) if detail.batch.merchants_id == merchant.id => {
+            Ok(HttpResponse::Ok().json(detail))
+        }
+        _ => {
+            let error = ApiError::settlement_not_found(batches_id);
+            log_info!(logger, "{}", error);
+            Err(error)
+        }
+    }
+}
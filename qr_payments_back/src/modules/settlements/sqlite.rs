@@ -0,0 +1,322 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{params, Connection, OptionalExtension, Params};
+use rust_decimal::Decimal;
+
+use crate::{
+    database::sqlite::{currency_column, decimal_column, SqliteStore},
+    datatypes::{MerchantsIdType, SettlementBatchesIdType},
+    modules::{
+        fees::sqlite as fees_sqlite,
+        merchants::Merchant,
+        transactions::{sqlite as transactions_sqlite, Transaction},
+        wallets::sqlite as wallets_sqlite,
+    },
+};
+
+use super::{
+    period_cutoff, period_start, repository::SettlementRepository, PayoutOutcome, SettlementBatch,
+    SettlementLine, SettlementLineKind, SettlementStatus,
+};
+
+impl SettlementRepository for SqliteStore {
+    fn close(
+        &self,
+        merchant: &Merchant,
+        period_end: NaiveDate,
+    ) -> TheResult<Option<SettlementBatch>> {
+        self.in_transaction(|db_transaction| {
+            let last_period_end = db_transaction
+                .query_row(
+                    "SELECT MAX(`period_end`) FROM `settlement_batches` WHERE `merchants_ID` = ?;",
+                    params![merchant.id],
+                    |row| row.get::<_, Option<NaiveDate>>(0),
+                )
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            let Some(period_start) = period_start(merchant, last_period_end, period_end) else {
+                return Ok(None);
+            };
+
+            let mut lines = Vec::new();
+            let payments = transactions_sqlite::select(
+                db_transaction,
+                "SELECT * FROM `transactions` WHERE `merchants_ID` = ? AND `status` = 'Confirmed' AND `created_at` < ? \
+                    AND NOT EXISTS (SELECT 1 FROM `settlement_lines` WHERE `transactions_ID` = `transactions`.`ID` AND `kind` = 'Payment') \
+                ORDER BY `ID`;",
+                params![merchant.id, period_cutoff(period_end)],
+            )?;
+            for payment in payments.iter().filter_map(Transaction::to_merchant_payment) {
+                let fees = fees_sqlite::select_by_transactions_id(db_transaction, payment.transactions_id)?;
+                lines.push(SettlementLine::payment(&payment, &fees, merchant.currency));
+            }
+            let refunds = transactions_sqlite::select(
+                db_transaction,
+                "SELECT * FROM `transactions` WHERE `merchants_ID` = ? AND `status` = 'Refunded' \
+                    AND EXISTS (SELECT 1 FROM `settlement_lines` WHERE `transactions_ID` = `transactions`.`ID` AND `kind` = 'Payment') \
+                    AND NOT EXISTS (SELECT 1 FROM `settlement_lines` WHERE `transactions_ID` = `transactions`.`ID` AND `kind` = 'Refund') \
+                ORDER BY `ID`;",
+                params![merchant.id],
+            )?;
+            for payment in refunds.iter().filter_map(Transaction::to_merchant_payment) {
+                let fees = fees_sqlite::select_by_transactions_id(db_transaction, payment.transactions_id)?;
+                lines.push(SettlementLine::refund(&payment, &fees, merchant.currency));
+            }
+            if lines.is_empty() {
+                return Ok(None);
+            }
+
+            let mut batch = SettlementBatch::new(merchant, period_start, period_end, &lines);
+            insert(db_transaction, &mut batch, &lines)?;
+            Ok(Some(batch))
+        })
+    }
+
+    fn select_by_id(
+        &self,
+        batches_id: SettlementBatchesIdType,
+    ) -> TheResult<Option<SettlementBatch>> {
+        self.lock()?
+            .query_row(
+                "SELECT * FROM `settlement_batches` WHERE `ID` = ?;",
+                params![batches_id],
+                from_row,
+            )
+            .optional()
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> TheResult<Vec<SettlementBatch>> {
+        select(
+            &*self.lock()?,
+            "SELECT * FROM `settlement_batches` WHERE `merchants_ID` = ? AND `period_end` BETWEEN ? AND ? ORDER BY `period_end` DESC;",
+            params![merchants_id, from, to],
+        )
+    }
+
+    fn select_paid_by_merchants_id(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<SettlementBatch>> {
+        select(
+            &*self.lock()?,
+            "SELECT * FROM `settlement_batches` WHERE `merchants_ID` = ? AND `status` = 'Paid' AND `paid_at` >= ? AND `paid_at` < ? ORDER BY `paid_at`, `ID`;",
+            params![merchants_id, from, to],
+        )
+    }
+
+    fn select_paid_net_before(
+        &self,
+        merchants_id: MerchantsIdType,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal> {
+        //  Decimals are stored as TEXT, so they are added up here rather than by SQLite
+        let batches = select(
+            &*self.lock()?,
+            "SELECT * FROM `settlement_batches` WHERE `merchants_ID` = ? AND `status` = 'Paid' AND `paid_at` < ?;",
+            params![merchants_id, before],
+        )?;
+        Ok(batches.iter().map(|batch| batch.net).sum())
+    }
+
+    fn select_unpaid(&self) -> TheResult<Vec<SettlementBatch>> {
+        select(
+            &*self.lock()?,
+            "SELECT * FROM `settlement_batches` WHERE `status` IN ('Pending', 'Held') ORDER BY `ID`;",
+            [],
+        )
+    }
+
+    fn select_lines(&self, batches_id: SettlementBatchesIdType) -> TheResult<Vec<SettlementLine>> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT * FROM `settlement_lines` WHERE `batches_ID` = ? ORDER BY `ID`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let lines = stmt
+            .query_map(params![batches_id], line_from_row)
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+            .collect();
+        lines
+    }
+
+    fn hold(&self, batch: &mut SettlementBatch) -> TheResult<bool> {
+        let affected_rows = self
+            .lock()?
+            .execute(
+                "UPDATE `settlement_batches` SET `status` = 'Held' WHERE `ID` = ? AND `status` = 'Pending';",
+                params![batch.id],
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        if affected_rows == 0 {
+            return Ok(false);
+        }
+        batch.status = SettlementStatus::Held;
+        Ok(true)
+    }
+
+    fn pay_out(
+        &self,
+        batch: &mut SettlementBatch,
+        merchant: &Merchant,
+    ) -> TheResult<PayoutOutcome> {
+        let paid_at = chrono::Local::now().naive_local();
+
+        let outcome = self.in_transaction_if(
+            |db_transaction| {
+                let affected_rows = db_transaction
+                    .execute(
+                        "UPDATE `settlement_batches` SET `status` = 'Paid', `paid_at` = ? WHERE `ID` = ? AND `status` IN ('Pending', 'Held');",
+                        params![paid_at, batch.id],
+                    )
+                    .map_err(|error| create_new_error!(error.to_string()))?;
+                if affected_rows == 0 {
+                    return Ok(PayoutOutcome::NotUnpaid);
+                }
+                if batch.net.is_zero() {
+                    return Ok(PayoutOutcome::Paid);
+                }
+
+                let Some(mut wallet) =
+                    wallets_sqlite::select_by_id(db_transaction, merchant.wallets_id)?
+                else {
+                    return Err(create_new_error!(format!(
+                        "Settlement wallet with ID: {} no longer exists",
+                        merchant.wallets_id
+                    )));
+                };
+                if wallets_sqlite::affect_balance(
+                    db_transaction,
+                    &mut wallet,
+                    batch.net,
+                    batch.currency,
+                )? {
+                    return Ok(PayoutOutcome::Paid);
+                }
+                if batch.net < Decimal::ZERO {
+                    return Ok(PayoutOutcome::Held);
+                }
+                Err(create_new_error!(format!(
+                    "Could not credit settlement wallet with ID: {}",
+                    merchant.wallets_id
+                )))
+            },
+            |outcome| *outcome == PayoutOutcome::Paid,
+        )?;
+
+        match outcome {
+            PayoutOutcome::Paid => {
+                batch.status = SettlementStatus::Paid;
+                batch.paid_at = Some(paid_at);
+            }
+            PayoutOutcome::Held => {
+                self.hold(batch)?;
+                batch.status = SettlementStatus::Held;
+            }
+            PayoutOutcome::NotUnpaid => {}
+        }
+        Ok(outcome)
+    }
+}
+
+/// ## Description
+/// Inserts the batch with its lines. The unique index on the lines fails the insert, and so the SQLite
+/// transaction, if another batch settled any of them meanwhile
+fn insert(
+    conn: &Connection,
+    batch: &mut SettlementBatch,
+    lines: &[SettlementLine],
+) -> TheResult<()> {
+    conn.execute(
+        "INSERT INTO `settlement_batches`(`merchants_ID`, `currency`, `period_start`, `period_end`, `payments`, `refunds`, `fees`, `net`, `status`, `created_at`) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+        params![
+            batch.merchants_id,
+            batch.currency.code(),
+            batch.period_start,
+            batch.period_end,
+            batch.payments.to_string(),
+            batch.refunds.to_string(),
+            batch.fees.to_string(),
+            batch.net.to_string(),
+            batch.status.to_string(),
+            batch.created_at,
+        ],
+    )
+    .map_err(|error| create_new_error!(error.to_string()))?;
+    batch.id = conn.last_insert_rowid() as SettlementBatchesIdType;
+
+    for line in lines {
+        conn.execute(
+            "INSERT INTO `settlement_lines`(`batches_ID`, `transactions_ID`, `kind`, `amount`, `fee`) VALUES(?, ?, ?, ?, ?);",
+            params![
+                batch.id,
+                line.transactions_id,
+                line.kind.to_string(),
+                line.amount.to_string(),
+                line.fee.to_string(),
+            ],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    }
+
+    Ok(())
+}
+
+fn select(conn: &Connection, query: &str, params: impl Params) -> TheResult<Vec<SettlementBatch>> {
+    let mut stmt = conn
+        .prepare(query)
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+    let batches = stmt
+        .query_map(params, from_row)
+        .map_err(|error| create_new_error!(error.to_string()))?
+        .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+        .collect();
+    batches
+}
+
+fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SettlementBatch> {
+    let status = row.get::<_, String>("status")?;
+
+    Ok(SettlementBatch {
+        id: row.get("ID")?,
+        merchants_id: row.get("merchants_ID")?,
+        currency: currency_column(row, "currency")?,
+        period_start: row.get("period_start")?,
+        period_end: row.get("period_end")?,
+        payments: decimal_column(row, "payments")?,
+        refunds: decimal_column(row, "refunds")?,
+        fees: decimal_column(row, "fees")?,
+        net: decimal_column(row, "net")?,
+        status: SettlementStatus::from_string(status).ok_or_else(|| {
+            rusqlite::Error::InvalidColumnType(
+                0,
+                String::from("status"),
+                rusqlite::types::Type::Text,
+            )
+        })?,
+        created_at: row.get("created_at")?,
+        paid_at: row.get("paid_at")?,
+    })
+}
+
+fn line_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SettlementLine> {
+    let kind = row.get::<_, String>("kind")?;
+
+    Ok(SettlementLine {
+        transactions_id: row.get("transactions_ID")?,
+        kind: SettlementLineKind::from_string(kind).ok_or_else(|| {
+            rusqlite::Error::InvalidColumnType(0, String::from("kind"), rusqlite::types::Type::Text)
+        })?,
+        amount: decimal_column(row, "amount")?,
+        fee: decimal_column(row, "fee")?,
+    })
+}
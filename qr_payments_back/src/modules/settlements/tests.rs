@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveTime};
+use rust_decimal::Decimal;
+
+use crate::{
+    modules::{
+        currencies::Currency,
+        fees::{Fee, FeeParty},
+        merchants::Merchant,
+        transactions::TransactionStatus,
+    },
+    validation::{FieldErrorCode, Validate},
+};
+
+use super::{
+    period_cutoff, period_start, scheduler::next_run, MerchantPayment, SettlementBatch,
+    SettlementLine, SettlementLineKind, SettlementReportQuery,
+};
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn merchant(currency: &str) -> Merchant {
+    Merchant {
+        id: 4,
+        wallets_id: 9,
+        currency: Currency::from_code(currency).unwrap(),
+        created_at: date(2025, 3, 10).and_hms_opt(15, 20, 0).unwrap(),
+        ..Default::default()
+    }
+}
+
+fn payment(amount: Decimal, rate: Option<Decimal>) -> MerchantPayment {
+    MerchantPayment {
+        transactions_id: 12,
+        merchants_id: 4,
+        amount,
+        rate,
+        status: TransactionStatus::Confirmed,
+        created_at: date(2025, 3, 11).and_hms_opt(9, 0, 0).unwrap(),
+        reversed_at: None,
+    }
+}
+
+fn fee(party: FeeParty, amount: Decimal, currency: &str) -> Fee {
+    Fee {
+        party,
+        amount,
+        currency: Currency::from_code(currency).unwrap(),
+    }
+}
+
+#[test]
+fn payment_lines_deduct_the_merchant_fees_only() {
+    let usd = Currency::from_code("USD").unwrap();
+    let fees = [
+        fee(FeeParty::Merchant, Decimal::new(150, 2), "USD"),
+        fee(FeeParty::Payer, Decimal::new(50, 2), "USD"),
+        //  Refunded fee entries of the payment are left for its refund line
+        fee(FeeParty::Merchant, Decimal::new(-150, 2), "USD"),
+    ];
+
+    let line = SettlementLine::payment(&payment(Decimal::new(5000, 2), None), &fees, usd);
+    assert_eq!(line.kind, SettlementLineKind::Payment);
+    assert_eq!(line.amount, Decimal::new(5000, 2));
+    assert_eq!(line.fee, Decimal::new(150, 2));
+    assert_eq!(line.net(), Decimal::new(4850, 2));
+
+    let line = SettlementLine::refund(&payment(Decimal::new(5000, 2), None), &fees, usd);
+    assert_eq!(line.kind, SettlementLineKind::Refund);
+    assert_eq!(line.amount, Decimal::new(-5000, 2));
+    assert_eq!(line.fee, Decimal::new(-150, 2));
+    assert_eq!(line.net(), Decimal::new(-4850, 2));
+}
+
+#[test]
+fn fees_of_converted_payments_are_brought_to_the_merchant_currency() {
+    let eur = Currency::from_code("EUR").unwrap();
+    //  Paid 92.00 EUR from a USD wallet at 0.92, charged 1.00 USD
+    let fees = [fee(FeeParty::Merchant, Decimal::new(100, 2), "USD")];
+
+    let line = SettlementLine::payment(
+        &payment(Decimal::new(9200, 2), Some(Decimal::new(92, 2))),
+        &fees,
+        eur,
+    );
+    assert_eq!(line.fee, Decimal::new(92, 2));
+    assert_eq!(line.net(), Decimal::new(9108, 2));
+}
+
+#[test]
+fn batches_add_up_their_lines() {
+    let lines = [
+        SettlementLine {
+            transactions_id: 1,
+            kind: SettlementLineKind::Payment,
+            amount: Decimal::new(10000, 2),
+            fee: Decimal::new(200, 2),
+        },
+        SettlementLine {
+            transactions_id: 2,
+            kind: SettlementLineKind::Payment,
+            amount: Decimal::new(5000, 2),
+            fee: Decimal::new(100, 2),
+        },
+        SettlementLine {
+            transactions_id: 3,
+            kind: SettlementLineKind::Refund,
+            amount: Decimal::new(-4000, 2),
+            fee: Decimal::new(-80, 2),
+        },
+    ];
+
+    let batch = SettlementBatch::new(
+        &merchant("USD"),
+        date(2025, 3, 10),
+        date(2025, 3, 12),
+        &lines,
+    );
+    assert_eq!(batch.merchants_id, 4);
+    assert_eq!(batch.payments, Decimal::new(15000, 2));
+    assert_eq!(batch.refunds, Decimal::new(-4000, 2));
+    assert_eq!(batch.fees, Decimal::new(220, 2));
+    assert_eq!(batch.net, Decimal::new(10780, 2));
+    assert!(!batch.is_paid());
+}
+
+#[test]
+fn periods_start_after_the_last_batch_or_on_the_merchant_creation() {
+    let merchant = merchant("USD");
+
+    assert_eq!(
+        period_start(&merchant, None, date(2025, 3, 12)),
+        Some(date(2025, 3, 10))
+    );
+    assert_eq!(
+        period_start(&merchant, Some(date(2025, 3, 12)), date(2025, 3, 15)),
+        Some(date(2025, 3, 13))
+    );
+    //  Nothing left to close
+    assert_eq!(
+        period_start(&merchant, Some(date(2025, 3, 12)), date(2025, 3, 12)),
+        None
+    );
+    assert_eq!(period_start(&merchant, None, date(2025, 3, 9)), None);
+
+    assert_eq!(
+        period_cutoff(date(2025, 3, 12)),
+        date(2025, 3, 13).and_time(NaiveTime::MIN)
+    );
+}
+
+#[test]
+fn runs_are_scheduled_for_the_next_run_at() {
+    let run_at = NaiveTime::from_hms_opt(0, 30, 0).unwrap();
+
+    let now = date(2025, 3, 12).and_hms_opt(0, 10, 0).unwrap();
+    assert_eq!(next_run(now, run_at), Duration::from_secs(20 * 60));
+
+    //  Already ran today
+    let now = date(2025, 3, 12).and_hms_opt(0, 30, 0).unwrap();
+    assert_eq!(next_run(now, run_at), Duration::from_secs(24 * 3600));
+    let now = date(2025, 3, 12).and_hms_opt(18, 0, 0).unwrap();
+    assert_eq!(
+        next_run(now, run_at),
+        Duration::from_secs(6 * 3600 + 30 * 60)
+    );
+}
+
+#[test]
+fn report_ranges_default_and_are_bounded() {
+    let today = date(2025, 3, 12);
+    let query = SettlementReportQuery {
+        from: None,
+        to: None,
+    };
+    assert_eq!(query.range(today), Ok((date(2025, 2, 10), today)));
+    assert!(query.validate().is_empty());
+
+    let query = SettlementReportQuery {
+        from: Some(date(2025, 3, 1)),
+        to: Some(date(2025, 3, 5)),
+    };
+    assert_eq!(query.range(today), Ok((date(2025, 3, 1), date(2025, 3, 5))));
+    assert!(query.validate().is_empty());
+
+    let reversed = SettlementReportQuery {
+        from: Some(date(2025, 3, 6)),
+        to: Some(date(2025, 3, 5)),
+    };
+    assert_eq!(reversed.validate()[0].field, "from");
+
+    let too_long = SettlementReportQuery {
+        from: Some(date(2024, 3, 4)),
+        to: Some(date(2025, 3, 5)),
+    };
+    assert_eq!(too_long.validate()[0].field, "from");
+
+    //  There are no days to start a default range before the earliest date
+    let earliest = SettlementReportQuery {
+        from: None,
+        to: Some(NaiveDate::MIN),
+    };
+    let error = earliest.range(today).unwrap_err();
+    assert_eq!(error.field, "to");
+    assert_eq!(error.code, FieldErrorCode::OutOfRange);
+    assert_eq!(earliest.validate(), [error]);
+}
@@ -1,20 +1,37 @@
+use chrono::NaiveDateTime;
 use error_mapper::{create_new_error, TheResult};
-use mysql::{
-    prelude::{FromRow, Queryable},
-    PooledConn,
-};
+use mysql::{params, prelude::Queryable, PooledConn};
 use rust_decimal::Decimal;
 
 use crate::{
-    datatypes::{TransactionsIdType, WalletsIdType},
+    database::{
+        decode::{self, DecodeRow, RowError},
+        in_transaction, in_transaction_if, Executor, MySqlStore,
+    },
+    datatypes::{MerchantsIdType, TransactionsIdType, WalletsIdType},
+    from_row_via_decode,
+    modules::{
+        currencies::Currency,
+        fees::{self, Fee},
+        fx::Conversion,
+        outbox::OutboxEvent,
+        settlements::MerchantPayment,
+        wallets::{
+            limits::{LimitValuation, WalletOutflow},
+            Wallet,
+        },
+    },
     row_to_data,
 };
 
-use super::{Transaction, TransactionStatus};
+use super::{
+    export::TransactionFilter, merchant_ledger, repository::TransactionRepository, DebitOutcome,
+    Transaction, TransactionStatus,
+};
 
 impl Transaction {
     pub(super) fn select_by_token_and_wallets_id(
-        conn: &mut PooledConn,
+        conn: &mut impl Queryable,
         wallets_id: WalletsIdType,
         token: String,
     ) -> TheResult<Option<Self>> {
@@ -25,48 +42,219 @@ impl Transaction {
             .prep(query)
             .map_err(|error| create_new_error!(error.to_string()))?;
 
-        let result = conn
-            .exec_first_opt::<Transaction, _, _>(stmt, params)
-            .map_err(|error| create_new_error!(error.to_string()))?
-            .transpose()
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, params)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    pub(super) fn select_by_id(
+        conn: &mut impl Queryable,
+        transactions_id: TransactionsIdType,
+    ) -> TheResult<Option<Self>> {
+        let query = "SELECT * FROM `transactions` WHERE `ID` = ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, (transactions_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    pub(super) fn select_by_status(
+        conn: &mut impl Queryable,
+        status: TransactionStatus,
+    ) -> TheResult<Vec<Self>> {
+        let query = "SELECT * FROM `transactions` WHERE `status` = ? ORDER BY `ID`;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (status.to_string(),))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Selects the transactions of a wallet created since the received datetime, newest first
+    pub(super) fn select_history_by_wallets_id(
+        conn: &mut impl Queryable,
+        wallets_id: WalletsIdType,
+        since: NaiveDateTime,
+    ) -> TheResult<Vec<Self>> {
+        let query = "SELECT * FROM `transactions` WHERE `wallets_ID` = ? AND `created_at` >= ? ORDER BY `created_at` DESC;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (wallets_id, since))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Whether the wallet ever had a payment to the merchant confirmed, or any payment confirmed when no
+    /// merchant is received
+    pub(super) fn has_confirmed_payment(
+        conn: &mut impl Queryable,
+        wallets_id: WalletsIdType,
+        merchants_id: Option<MerchantsIdType>,
+    ) -> TheResult<bool> {
+        let query = "SELECT EXISTS(SELECT 1 FROM `transactions` WHERE `wallets_ID` = ? AND `status` = 'Confirmed' AND (? IS NULL OR `merchants_ID` = ?));";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec_first::<bool, _, _>(stmt, (wallets_id, merchants_id, merchants_id))
+            .map(|exists| exists.unwrap_or(false))
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Selects the latest transactions, optionally of a single wallet and status, newest first
+    pub(crate) fn select_filtered(
+        conn: &mut PooledConn,
+        wallets_id: Option<WalletsIdType>,
+        status: Option<TransactionStatus>,
+        limit: u32,
+    ) -> TheResult<Vec<Self>> {
+        let mut query = String::from("SELECT * FROM `transactions` WHERE TRUE");
+        let mut params = Vec::<String>::new();
+
+        //  Optional filters
+        if let Some(wallets_id) = wallets_id {
+            query.push_str(" AND `wallets_ID` = ?");
+            params.push(wallets_id.to_string());
+        }
+        if let Some(status) = status {
+            query.push_str(" AND `status` = ?");
+            params.push(status.to_string());
+        }
+
+        query.push_str(" ORDER BY `ID` DESC LIMIT ?;");
+        params.push(limit.to_string());
+
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, params)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Selects a page of the transactions matching the filter, in ID order. Keyset paginated, so every page
+    /// costs the same however deep the export goes
+    pub(super) fn select_page(
+        conn: &mut impl Queryable,
+        filter: &TransactionFilter,
+        after_id: TransactionsIdType,
+        limit: u32,
+    ) -> TheResult<Vec<Self>> {
+        let query = format!(
+            "SELECT * FROM `transactions` WHERE `{}` = ? AND `status` <> 'Log' AND `created_at` >= ? AND `created_at` < ? AND `ID` > ? ORDER BY `ID` LIMIT ?;",
+            filter.owner.column()
+        );
+        let stmt = conn
+            .prep(query)
             .map_err(|error| create_new_error!(error.to_string()))?;
 
-        Ok(result)
+        let rows = conn
+            .exec::<mysql::Row, _, _>(
+                stmt,
+                (filter.owner.id(), filter.from, filter.to, after_id, limit),
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
     }
 
-    pub(super) fn insert(&mut self, conn: &mut PooledConn) -> TheResult<String> {
+    /// ## Description
+    /// Aggregates the outgoing amounts of a wallet per currency for the current day and month. Only
+    /// transactions that effectively debited the wallet (Initialized or Confirmed) are considered
+    pub(super) fn select_outflow_by_wallets_id(
+        conn: &mut impl Queryable,
+        wallets_id: WalletsIdType,
+    ) -> TheResult<Vec<WalletOutflow>> {
+        let query = "SELECT `currency`, \
+                COALESCE(SUM(CASE WHEN `created_at` >= CURDATE() THEN -`amount` END), 0) AS `daily_amount`, \
+                COALESCE(SUM(-`amount`), 0) AS `monthly_amount`, \
+                COUNT(CASE WHEN `created_at` >= CURDATE() THEN 1 END) AS `daily_count` \
+            FROM `transactions` \
+            WHERE `wallets_ID` = ? AND `amount` < 0 AND `status` IN ('Initialized', 'Confirmed') \
+                AND `created_at` >= DATE_FORMAT(CURDATE(), '%Y-%m-01') \
+            GROUP BY `currency`;";
+
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec::<mysql::Row, _, _>(stmt, (wallets_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .iter()
+            .map(|row| {
+                WalletOutflow::decode(row).map_err(|error| create_new_error!(error.to_string()))
+            })
+            .collect()
+    }
+
+    /// ## Description
+    /// Inserts the transaction with its fee lines, recording its event in the outbox within the same DB
+    /// transaction
+    pub(super) fn insert(
+        &mut self,
+        conn: &mut mysql::Transaction<'_>,
+        fees: &[Fee],
+    ) -> TheResult<String> {
         let Some(token) = self.token.clone() else {
             return Err(create_new_error!(
                 "Cannot proceess transaction without token"
             ));
         };
 
-        let query = "INSERT INTO `transactions`(`wallets_ID`, `amount`, `status`, `token`) VALUES(?, ?, ?, ?);";
+        let query = "INSERT INTO `transactions`(`wallets_ID`, `merchants_ID`, `amount`, `currency`, `fx_quote`, `fx_rate`, `converted_amount`, `converted_currency`, `status`, `token`, `errors`) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);";
         let stmt = conn
             .prep(query)
             .map_err(|error| create_new_error!(error.to_string()))?;
+        let conversion = self.conversion.as_ref();
         let params = vec![
             self.wallets_id.map(|id| id.to_string()),
+            self.merchants_id.map(|id| id.to_string()),
             Some(self.amount.to_string()),
+            Some(self.currency.to_string()),
+            conversion.map(|conversion| conversion.quotes_id.clone()),
+            conversion.map(|conversion| conversion.rate.to_string()),
+            conversion.map(|conversion| conversion.amount.to_string()),
+            conversion.map(|conversion| conversion.currency.to_string()),
             Some(self.status.to_string()),
             Some(token.clone()),
+            self.errors.clone(),
         ];
 
         conn.exec_drop(stmt, params)
             .map_err(|error| create_new_error!(error.to_string()))?;
-        let last_id = conn.last_insert_id();
+        let last_id = Executor::last_insert_id(conn);
         self.id = last_id;
+        Fee::insert_lines(conn, self.id, fees)?;
+        OutboxEvent::append(conn, &self.to_event())?;
 
         Ok(token)
     }
 
     pub(super) fn log(&self, conn: &mut PooledConn) -> TheResult<bool> {
-        let query = "INSERT INTO `transactions`(`amount`, `status`, `errors`) VALUES(?, ?, ?);";
+        let query = "INSERT INTO `transactions`(`amount`, `currency`, `status`, `errors`) VALUES(?, ?, ?, ?);";
         let stmt = conn
             .prep(query)
             .map_err(|error| create_new_error!(error.to_string()))?;
         let params = vec![
             Some(self.amount.to_string()),
+            Some(self.currency.to_string()),
             Some(TransactionStatus::Log.to_string()),
             self.errors.clone(),
         ];
@@ -77,56 +265,456 @@ impl Transaction {
         Ok(conn.affected_rows() > 0)
     }
 
-    pub(super) fn update_status_and_error(&self, conn: &mut PooledConn) -> TheResult<bool> {
+    /// ## Description
+    /// Selects the Initialized transactions created before the received datetime
+    pub(super) fn select_initialized_before(
+        conn: &mut impl Queryable,
+        before: NaiveDateTime,
+    ) -> TheResult<Vec<Self>> {
+        let query = "SELECT * FROM `transactions` WHERE `status` = 'Initialized' AND `created_at` < ? ORDER BY `ID`;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (before,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Updates the transaction status and errors only if it still has the expected previous status, so
+    /// concurrent requests cannot apply the same transition twice. The status event is recorded in the
+    /// outbox
+    pub(super) fn update_status_from(
+        &self,
+        conn: &mut mysql::Transaction<'_>,
+        previous_status: TransactionStatus,
+    ) -> TheResult<bool> {
         if self.id == 0 {
             return Err(create_new_error!(
                 "Transaction ID cannot be zero for an update operation"
             ));
         }
 
-        let mut query = String::from("UPDATE `transactions` SET `status` = ?");
-        let mut params = vec![self.status.to_string()];
+        let query = "UPDATE `transactions` SET `status` = ?, `errors` = COALESCE(?, `errors`) WHERE `ID` = ? AND `status` = ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
 
-        //  Optional params
-        if let Some(error) = &self.errors {
-            query.push_str(", `error` = ?");
-            params.push(error.clone());
+        conn.exec_drop(
+            stmt,
+            (
+                self.status.to_string(),
+                self.errors.as_deref(),
+                self.id,
+                previous_status.to_string(),
+            ),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+        if conn.affected_rows() == 0 {
+            return Ok(false);
         }
+        OutboxEvent::append(conn, &self.to_event())?;
 
-        //  Complete query
-        query.push_str(" WHERE `ID` = ?;");
-        params.push(self.id.to_string());
+        Ok(true)
+    }
 
-        let stmt = conn
-            .prep(query)
+    /// ## Description
+    /// Moves the transaction to a closing status and returns its amount to the wallet, atomically. Its fees
+    /// are refunded with new lines, and the payer ones returned to the wallet with the amount
+    ///
+    /// ### Returns
+    /// False if the transaction was no longer in its previous status, in which case nothing was changed
+    pub(super) fn reverse(
+        &mut self,
+        conn: &mut PooledConn,
+        status: TransactionStatus,
+    ) -> TheResult<bool> {
+        let previous_status = self.status;
+        self.status = status;
+        self.reversed_at = Some(chrono::Local::now().naive_local());
+
+        let result = in_transaction(conn, |db_transaction| {
+            if !self.update_status_from(db_transaction, previous_status)? {
+                return Ok(false);
+            }
+            let query = "UPDATE `transactions` SET `reversed_at` = ? WHERE `ID` = ?;";
+            let stmt = db_transaction
+                .prep(query)
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            db_transaction
+                .exec_drop(stmt, (self.reversed_at, self.id))
+                .map_err(|error| create_new_error!(error.to_string()))?;
+
+            let Some(wallets_id) = self.wallets_id else {
+                return Err(create_new_error!(format!(
+                    "Transaction with ID: {} has no wallet to return its amount to",
+                    self.id
+                )));
+            };
+            let Some(mut wallet) = Wallet::select_by_id(db_transaction, wallets_id)? else {
+                return Err(create_new_error!(format!(
+                    "Wallet with ID: {} no longer exists",
+                    wallets_id
+                )));
+            };
+            let charged = Fee::select_by_transactions_id(db_transaction, self.id)?;
+            let refunds = fees::refunds(&charged);
+            Fee::insert_lines(db_transaction, self.id, &refunds)?;
+            let returned = -self.amount - fees::payer_total(&refunds);
+            if !wallet.affect_balance(db_transaction, returned, self.currency)? {
+                return Err(create_new_error!(format!(
+                    "Could not return amount to wallet with ID: {}",
+                    wallets_id
+                )));
+            }
+
+            Ok(true)
+        });
+
+        if !matches!(result, Ok(true)) {
+            self.status = previous_status;
+            self.reversed_at = None;
+        }
+        result
+    }
+
+    /// ## Description
+    /// Inserts the transaction and applies its amount plus the payer fees to the wallet in a single DB
+    /// transaction. The wallet row is locked first, so its limits are checked against an outflow no
+    /// concurrent payment can change until the debit is committed, and the debit is guarded by the balance
+    /// as updated by the committed payments
+    pub(super) fn insert_affecting_wallet(
+        &mut self,
+        conn: &mut mysql::Transaction<'_>,
+        fees: &[Fee],
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<DebitOutcome> {
+        let Some(locked) = Wallet::select_for_update(conn, wallet.id)? else {
+            return Err(create_new_error!("Could not affect wallet balance"));
+        };
+        *wallet = locked;
+
+        let wallets_id = wallet.id;
+        if let Some(breach) = self.limit_breach(wallet, valuation, || {
+            Self::select_outflow_by_wallets_id(conn, wallets_id)
+        })? {
+            return Ok(DebitOutcome::LimitExceeded(breach));
+        }
+
+        //  The guarded debit goes first, so a balance spent concurrently leaves nothing written
+        let debit = self.amount - fees::payer_total(fees);
+        if !wallet.affect_balance(conn, debit, self.currency)? {
+            return Ok(DebitOutcome::InsufficientFunds);
+        }
+        self.insert(conn, fees)?;
+
+        Ok(DebitOutcome::Debited)
+    }
+
+    /// ## Description
+    /// Selects the Confirmed and Refunded payments made to the merchant that were made or refunded in the
+    /// range, and then the fee lines of them all in a single query
+    pub(super) fn select_merchant_ledger(
+        conn: &mut impl Queryable,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<(MerchantPayment, Vec<Fee>)>> {
+        let params = params! {
+            "merchants_id" => merchants_id,
+            "from" => from,
+            "to" => to,
+        };
+
+        let rows = conn
+            .exec::<mysql::Row, _, _>(
+                format!(
+                    "SELECT * FROM `transactions` WHERE {} ORDER BY `ID`;",
+                    MERCHANT_LEDGER_CONDITION
+                ),
+                params.clone(),
+            )
             .map_err(|error| create_new_error!(error.to_string()))?;
+        let transactions =
+            decode::rows::<Self>(rows).map_err(|error| create_new_error!(error.to_string()))?;
+        if transactions.is_empty() {
+            return Ok(Vec::new());
+        }
 
-        conn.exec_drop(stmt, params)
+        let rows = conn
+            .exec::<mysql::Row, _, _>(
+                format!(
+                    "SELECT `transaction_fees`.* FROM `transaction_fees` JOIN `transactions` ON `transactions`.`ID` = `transaction_fees`.`transactions_ID` WHERE {} ORDER BY `transaction_fees`.`ID`;",
+                    MERCHANT_LEDGER_CONDITION
+                ),
+                params,
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let fees = decode::rows::<(TransactionsIdType, Fee)>(rows)
             .map_err(|error| create_new_error!(error.to_string()))?;
 
-        Ok(conn.affected_rows() > 0)
+        Ok(merchant_ledger(&transactions, fees))
+    }
+
+    /// ## Description
+    /// Sums the movements the payments made to the merchant booked before the datetime. Fees in another
+    /// currency are brought to the merchant one at the payment rate, rounded half to even as
+    /// `Currency::round` does, so the sum matches the one of the statement movements
+    pub(super) fn select_merchant_balance(
+        conn: &mut impl Queryable,
+        merchants_id: MerchantsIdType,
+        currency: Currency,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal> {
+        let paid = "-IF(`transactions`.`fx_quote` IS NULL, `transactions`.`amount`, `transactions`.`converted_amount`)";
+        let minor = "`transaction_fees`.`amount` * `transactions`.`fx_rate` * :scale";
+        let fee = format!(
+            "IF(`transaction_fees`.`currency` <> :currency AND `transactions`.`fx_rate` IS NOT NULL, \
+                IF(ABS({minor} - TRUNCATE({minor}, 0)) = 0.5, 2 * ROUND({minor} / 2, 0), ROUND({minor}, 0)) / :scale, \
+                `transaction_fees`.`amount`)",
+            minor = minor
+        );
+        let refunded_before = "`transactions`.`status` = 'Refunded' AND COALESCE(`transactions`.`reversed_at`, `transactions`.`created_at`) < :before";
+        let paid_before = "`transactions`.`status` IN ('Confirmed', 'Refunded') AND `transactions`.`created_at` < :before";
+        let query = format!(
+            "SELECT \
+                (SELECT COALESCE(SUM({paid}), 0) FROM `transactions` WHERE `merchants_ID` = :merchants_id AND {paid_before}) \
+                - (SELECT COALESCE(SUM({paid}), 0) FROM `transactions` WHERE `merchants_ID` = :merchants_id AND {refunded_before}) \
+                - (SELECT COALESCE(SUM({fee}), 0) FROM `transaction_fees` JOIN `transactions` ON `transactions`.`ID` = `transaction_fees`.`transactions_ID` \
+                    WHERE `transactions`.`merchants_ID` = :merchants_id AND {paid_before} AND `transaction_fees`.`party` = 'Merchant' AND `transaction_fees`.`amount` > 0) \
+                - (SELECT COALESCE(SUM({fee}), 0) FROM `transaction_fees` JOIN `transactions` ON `transactions`.`ID` = `transaction_fees`.`transactions_ID` \
+                    WHERE `transactions`.`merchants_ID` = :merchants_id AND {refunded_before} AND `transaction_fees`.`party` = 'Merchant' AND `transaction_fees`.`amount` < 0) \
+                AS `balance`;",
+            paid = paid,
+            fee = fee,
+            paid_before = paid_before,
+            refunded_before = refunded_before
+        );
+
+        let balance = conn
+            .exec_first::<Decimal, _, _>(
+                query,
+                params! {
+                    "merchants_id" => merchants_id,
+                    "currency" => currency.to_string(),
+                    "scale" => 10u64.pow(currency.minor_units()),
+                    "before" => before,
+                },
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(balance.unwrap_or_default())
+    }
+}
+
+/// Condition on the `transactions` table selecting the Confirmed and Refunded payments made to a merchant
+/// that were made or refunded in a range, given as the `merchants_id`, `from` and `to` named parameters
+const MERCHANT_LEDGER_CONDITION: &str = "`transactions`.`merchants_ID` = :merchants_id AND (\
+    (`transactions`.`status` IN ('Confirmed', 'Refunded') AND `transactions`.`created_at` >= :from AND `transactions`.`created_at` < :to) \
+    OR (`transactions`.`status` = 'Refunded' AND COALESCE(`transactions`.`reversed_at`, `transactions`.`created_at`) >= :from AND COALESCE(`transactions`.`reversed_at`, `transactions`.`created_at`) < :to))";
+
+impl TransactionRepository for MySqlStore {
+    fn select_by_token_and_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        token: String,
+    ) -> TheResult<Option<Transaction>> {
+        Transaction::select_by_token_and_wallets_id(&mut self.get_conn()?, wallets_id, token)
+    }
+
+    fn select_by_id(&self, transactions_id: TransactionsIdType) -> TheResult<Option<Transaction>> {
+        Transaction::select_by_id(&mut self.get_conn()?, transactions_id)
+    }
+
+    fn select_by_status(&self, status: TransactionStatus) -> TheResult<Vec<Transaction>> {
+        Transaction::select_by_status(&mut self.get_conn()?, status)
+    }
+
+    fn select_history_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        since: NaiveDateTime,
+    ) -> TheResult<Vec<Transaction>> {
+        Transaction::select_history_by_wallets_id(&mut self.get_conn()?, wallets_id, since)
+    }
+
+    fn has_confirmed_payment(
+        &self,
+        wallets_id: WalletsIdType,
+        merchants_id: Option<MerchantsIdType>,
+    ) -> TheResult<bool> {
+        Transaction::has_confirmed_payment(&mut self.get_conn()?, wallets_id, merchants_id)
+    }
+
+    fn select_outflow_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+    ) -> TheResult<Vec<WalletOutflow>> {
+        Transaction::select_outflow_by_wallets_id(&mut self.get_conn()?, wallets_id)
+    }
+
+    fn select_page(
+        &self,
+        filter: &TransactionFilter,
+        after_id: TransactionsIdType,
+        limit: u32,
+    ) -> TheResult<Vec<Transaction>> {
+        Transaction::select_page(&mut self.get_conn()?, filter, after_id, limit)
+    }
+
+    fn select_initialized_before(&self, before: NaiveDateTime) -> TheResult<Vec<Transaction>> {
+        Transaction::select_initialized_before(&mut self.get_conn()?, before)
+    }
+
+    fn log(&self, transaction: &Transaction) -> TheResult<bool> {
+        transaction.log(&mut self.get_conn()?)
+    }
+
+    fn insert(&self, transaction: &mut Transaction, fees: &[Fee]) -> TheResult<String> {
+        in_transaction(&mut self.get_conn()?, |db_transaction| {
+            transaction.insert(db_transaction, fees)
+        })
+    }
+
+    fn insert_affecting_wallet(
+        &self,
+        transaction: &mut Transaction,
+        fees: &[Fee],
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<DebitOutcome> {
+        in_transaction(&mut self.get_conn()?, |db_transaction| {
+            transaction.insert_affecting_wallet(db_transaction, fees, wallet, valuation)
+        })
+    }
+
+    fn update_status_from(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+    ) -> TheResult<bool> {
+        in_transaction(&mut self.get_conn()?, |db_transaction| {
+            transaction.update_status_from(db_transaction, previous_status)
+        })
+    }
+
+    fn update_status_affecting_wallet(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<Option<DebitOutcome>> {
+        in_transaction_if(
+            &mut self.get_conn()?,
+            |db_transaction| {
+                let Some(locked) = Wallet::select_for_update(db_transaction, wallet.id)? else {
+                    return Err(create_new_error!("Could not affect wallet balance"));
+                };
+                *wallet = locked;
+                if let Some(breach) = transaction.limit_breach(wallet, valuation, || {
+                    Transaction::select_outflow_by_wallets_id(db_transaction, locked.id)
+                })? {
+                    return Ok(Some(DebitOutcome::LimitExceeded(breach)));
+                }
+                //  The guarded update locks the transaction row before the debit, so a concurrent request
+                //  that moved it first leaves the wallet alone
+                if !transaction.update_status_from(db_transaction, previous_status)? {
+                    return Ok(None);
+                }
+
+                let fees = Fee::select_by_transactions_id(db_transaction, transaction.id)?;
+                let debit = transaction.amount - fees::payer_total(&fees);
+                if !wallet.affect_balance(db_transaction, debit, transaction.currency)? {
+                    return Ok(Some(DebitOutcome::InsufficientFunds));
+                }
+                Ok(Some(DebitOutcome::Debited))
+            },
+            |outcome| *outcome == Some(DebitOutcome::Debited),
+        )
+    }
+
+    fn reverse(&self, transaction: &mut Transaction, status: TransactionStatus) -> TheResult<bool> {
+        transaction.reverse(&mut self.get_conn()?, status)
+    }
+
+    fn select_fees(&self, transactions_id: TransactionsIdType) -> TheResult<Vec<Fee>> {
+        Fee::select_by_transactions_id(&mut self.get_conn()?, transactions_id)
+    }
+
+    fn select_merchant_ledger(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<(MerchantPayment, Vec<Fee>)>> {
+        Transaction::select_merchant_ledger(&mut self.get_conn()?, merchants_id, from, to)
+    }
+
+    fn select_merchant_balance(
+        &self,
+        merchants_id: MerchantsIdType,
+        currency: Currency,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal> {
+        Transaction::select_merchant_balance(&mut self.get_conn()?, merchants_id, currency, before)
     }
 }
 
-impl FromRow for Transaction {
-    fn from_row(_row: mysql::Row) -> Self
-    where
-        Self: Sized,
-    {
-        unimplemented!()
-    }
-    fn from_row_opt(row: mysql::Row) -> Result<Self, mysql::FromRowError>
-    where
-        Self: Sized,
-    {
+impl DecodeRow for Transaction {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
         Ok(Self {
             id: row_to_data!(row, "ID", "transactions", TransactionsIdType),
             wallets_id: row_to_data!(row, "wallets_ID", "transactions", Option<WalletsIdType>),
+            merchants_id: row_to_data!(
+                row,
+                "merchants_ID",
+                "transactions",
+                Option<MerchantsIdType>
+            ),
             amount: row_to_data!(row, "amount", "transactions", Decimal),
+            currency: decode::variant(row, "transactions", "currency", Currency::from_string)?,
+            conversion: match row_to_data!(row, "fx_quote", "transactions", Option<String>) {
+                Some(quotes_id) => Some(Conversion {
+                    quotes_id,
+                    rate: row_to_data!(row, "fx_rate", "transactions", Decimal),
+                    amount: row_to_data!(row, "converted_amount", "transactions", Decimal),
+                    currency: decode::variant(
+                        row,
+                        "transactions",
+                        "converted_currency",
+                        Currency::from_string,
+                    )?,
+                }),
+                None => None,
+            },
             token: row_to_data!(row, "token", "transactions", Option<String>),
             errors: row_to_data!(row, "errors", "transactions", Option<String>),
-            status: TransactionStatus::from_string(row_to_data!(row, "status", "transactions", String))
-                .ok_or(mysql::FromRowError(row))?,
+            created_at: row_to_data!(row, "created_at", "transactions", NaiveDateTime),
+            reversed_at: row_to_data!(row, "reversed_at", "transactions", Option<NaiveDateTime>),
+            status: decode::variant(
+                row,
+                "transactions",
+                "status",
+                TransactionStatus::from_string,
+            )?,
+        })
+    }
+}
+
+from_row_via_decode!(Transaction);
+
+impl DecodeRow for WalletOutflow {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            currency: decode::variant(row, "transactions", "currency", Currency::from_string)?,
+            daily_amount: row_to_data!(row, "daily_amount", "transactions", Decimal),
+            monthly_amount: row_to_data!(row, "monthly_amount", "transactions", Decimal),
+            daily_count: row_to_data!(row, "daily_count", "transactions", u32),
         })
     }
 }
@@ -0,0 +1,171 @@
+use std::{sync::OnceLock, time::Duration};
+
+use actix_web::web::{self, Bytes};
+use futures_util::{stream, Stream};
+use the_logger::TheLogger;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::{Instant, Interval},
+};
+
+use crate::{
+    database::blocking, datatypes::TransactionsIdType, log_error, log_info,
+    modules::outbox::TransactionEvent,
+};
+
+use super::{repository::TransactionRepository, Transaction, TransactionStatus};
+
+/// Capacity of the status channel. Lagging subscribers re-read the status from the database
+const CHANNEL_CAPACITY: usize = 1024;
+
+static STATUS_CHANNEL: OnceLock<broadcast::Sender<TransactionEvent>> = OnceLock::new();
+
+struct EventStreamState {
+    transactions_id: TransactionsIdType,
+    receiver: broadcast::Receiver<TransactionEvent>,
+    repository: web::Data<dyn TransactionRepository>,
+    heartbeat: Interval,
+    last_status: TransactionStatus,
+    pending: Option<Bytes>,
+    closed: bool,
+}
+
+/// ## Description
+/// Publishes the transaction's current status to the stream subscribers. Must only be called once the
+/// status change was committed
+pub(super) fn publish_status(transaction: &Transaction) {
+    publish_event(transaction.to_event());
+}
+
+/// ## Description
+/// Publishes a committed transaction event to the stream subscribers
+pub(crate) fn publish_event(event: TransactionEvent) {
+    //  Sending only fails when nobody is subscribed, which is not an error for a broadcast
+    let _ = sender().send(event);
+}
+
+/// ## Description
+/// Subscribes to the status changes of every transaction. Subscribe before reading the current status,
+/// so no change is lost in between
+pub(super) fn subscribe() -> broadcast::Receiver<TransactionEvent> {
+    sender().subscribe()
+}
+
+/// ## Description
+/// Builds the Server-Sent Events stream of a transaction. It starts with the current status, sends a
+/// ping every `heartbeat_secs` and ends after sending a terminal status
+pub(super) fn status_stream(
+    transaction: &Transaction,
+    receiver: broadcast::Receiver<TransactionEvent>,
+    repository: web::Data<dyn TransactionRepository>,
+    heartbeat_secs: u64,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let period = Duration::from_secs(heartbeat_secs.max(1));
+    let state = EventStreamState {
+        transactions_id: transaction.id,
+        receiver,
+        repository,
+        heartbeat: tokio::time::interval_at(Instant::now() + period, period),
+        last_status: transaction.status,
+        pending: Some(status_frame(&transaction.to_event())),
+        closed: transaction.status.is_terminal(),
+    };
+
+    stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+
+        if let Some(frame) = state.pending.take() {
+            let next = if state.closed { None } else { Some(state) };
+            return Some((Ok(frame), next));
+        }
+
+        let frame = next_frame(&mut state).await?;
+        let next = if state.closed { None } else { Some(state) };
+        Some((Ok(frame), next))
+    })
+}
+
+async fn next_frame(state: &mut EventStreamState) -> Option<Bytes> {
+    loop {
+        tokio::select! {
+            _ = state.heartbeat.tick() => return Some(ping_frame()),
+            result = state.receiver.recv() => match result {
+                Ok(event) if event.transactions_id == state.transactions_id => {
+                    let Some(status) = TransactionStatus::from_string(event.status.clone()) else {
+                        continue;
+                    };
+                    if let Some(frame) = state.advance(status, &event) {
+                        return Some(frame);
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    log_info!(
+                        TheLogger::instance(),
+                        "Event stream of transaction with ID: {} skipped {} events, resyncing",
+                        state.transactions_id,
+                        skipped
+                    );
+                    if let Some(frame) = resync(state).await {
+                        return Some(frame);
+                    }
+                }
+                Err(RecvError::Closed) => return None,
+            },
+        }
+    }
+}
+
+/// ## Description
+/// Re-reads the transaction after the subscriber lagged behind, in case its own event was dropped
+async fn resync(state: &mut EventStreamState) -> Option<Bytes> {
+    let logger = TheLogger::instance();
+
+    let transactions_id = state.transactions_id;
+    match blocking(&state.repository, move |repository| {
+        repository.select_by_id(transactions_id)
+    })
+    .await
+    {
+        Ok(Some(transaction)) => state.advance(transaction.status, &transaction.to_event()),
+        Ok(None) => None,
+        Err(error) => {
+            log_error!(
+                logger,
+                "Could not resync transaction with ID: {}: {}",
+                transactions_id,
+                error
+            );
+            None
+        }
+    }
+}
+
+impl EventStreamState {
+    /// Returns the frame to send when the status differs from the last one sent
+    fn advance(&mut self, status: TransactionStatus, event: &TransactionEvent) -> Option<Bytes> {
+        if status == self.last_status {
+            return None;
+        }
+
+        self.last_status = status;
+        self.closed = status.is_terminal();
+        Some(status_frame(event))
+    }
+}
+
+fn sender() -> &'static broadcast::Sender<TransactionEvent> {
+    STATUS_CHANNEL.get_or_init(|| broadcast::channel(CHANNEL_CAPACITY).0)
+}
+
+fn status_frame(event: &TransactionEvent) -> Bytes {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    Bytes::from(format!("event: status\ndata: {}\n\n", data))
+}
+
+fn ping_frame() -> Bytes {
+    Bytes::from(format!(
+        "event: ping\ndata: {}\n\n",
+        chrono::Utc::now().timestamp()
+    ))
+}
@@ -0,0 +1,318 @@
+use actix_web::{http::header, web, HttpResponse};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use futures_util::{stream, StreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use the_logger::TheLogger;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    api::error::ApiError,
+    database::blocking,
+    datatypes::{MerchantsIdType, TransactionsIdType, WalletsIdType},
+    log_error,
+    modules::currencies::Currency,
+    validation::{FieldError, FieldErrorCode, Validate},
+    DATETIME_FORMAT,
+};
+
+use super::{repository::TransactionRepository, Transaction, TransactionStatus};
+
+/// Transactions read from the repository at a time while streaming an export
+pub const EXPORT_PAGE_SIZE: u32 = 500;
+/// Days an export covers when its request sets no start
+pub const EXPORT_DEFAULT_DAYS: i64 = 30;
+
+const CSV_HEADER: &str =
+    "id,wallets_id,merchants_id,amount,currency,paid_amount,paid_currency,rate,status,created_at\n";
+
+/// Whose transactions are exported: the ones debited from a wallet, or the ones paid to a merchant
+#[derive(Debug, Clone, Copy)]
+pub enum TransactionOwner {
+    Wallet(WalletsIdType),
+    Merchant(MerchantsIdType),
+}
+
+/// Transactions of an owner created within `[from, to)`. Rejected requests, recorded with the Log status,
+/// are not transactions and never match
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionFilter {
+    pub owner: TransactionOwner,
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// Range of days and format of an export, as sent in its query string
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// Defaults to `EXPORT_DEFAULT_DAYS` (30) days before `to`
+    pub from: Option<NaiveDate>,
+    /// Defaults to today, included
+    pub to: Option<NaiveDate>,
+    #[serde(default)]
+    #[param(inline)]
+    pub format: ExportFormat,
+}
+
+/// ## Description
+/// Transaction as exported. The token is left out, and a converted payment carries the amount paid in
+/// the merchant currency along with the amount debited
+#[derive(Debug, Serialize)]
+struct ExportRow {
+    id: TransactionsIdType,
+    wallets_id: Option<WalletsIdType>,
+    merchants_id: Option<MerchantsIdType>,
+    amount: Decimal,
+    currency: Currency,
+    paid_amount: Decimal,
+    paid_currency: Currency,
+    rate: Option<Decimal>,
+    status: TransactionStatus,
+    created_at: String,
+}
+
+impl TransactionOwner {
+    /// Column of the transactions table referencing the owner
+    pub(super) fn column(&self) -> &'static str {
+        match self {
+            Self::Wallet(_) => "wallets_ID",
+            Self::Merchant(_) => "merchants_ID",
+        }
+    }
+
+    pub(super) fn id(&self) -> u64 {
+        match *self {
+            Self::Wallet(wallets_id) => wallets_id,
+            Self::Merchant(merchants_id) => merchants_id,
+        }
+    }
+}
+
+impl TransactionFilter {
+    /// ## Description
+    /// Filter of the transactions of the owner created from the first day to the last one, both included
+    pub fn days(owner: TransactionOwner, from: NaiveDate, to: NaiveDate) -> Self {
+        Self {
+            owner,
+            from: from.and_time(NaiveTime::MIN),
+            to: to.succ_opt().unwrap_or(to).and_time(NaiveTime::MIN),
+        }
+    }
+
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        let owned = match self.owner {
+            TransactionOwner::Wallet(wallets_id) => transaction.wallets_id == Some(wallets_id),
+            TransactionOwner::Merchant(merchants_id) => {
+                transaction.merchants_id == Some(merchants_id)
+            }
+        };
+        owned
+            && transaction.status != TransactionStatus::Log
+            && transaction.created_at >= self.from
+            && transaction.created_at < self.to
+    }
+}
+
+impl ExportQuery {
+    /// ## Description
+    /// Days the export covers, applying the defaults of the bounds not sent
+    ///
+    /// ### Returns
+    /// The first and last days, or an error when the default start would fall before the earliest date
+    pub fn range(&self, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), FieldError> {
+        let to = self.to.unwrap_or(today);
+        let from = match self.from {
+            Some(from) => from,
+            None => to
+                .checked_sub_signed(TimeDelta::days(EXPORT_DEFAULT_DAYS))
+                .ok_or_else(|| FieldError {
+                    field: "to",
+                    code: FieldErrorCode::OutOfRange,
+                    message: String::from(
+                        "Export ends too early to start a default range before it",
+                    ),
+                })?,
+        };
+        Ok((from, to))
+    }
+}
+
+impl Validate for ExportQuery {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let (from, to) = match self.range(chrono::Local::now().date_naive()) {
+            Ok(range) => range,
+            Err(error) => return vec![error],
+        };
+        if from > to {
+            errors.push(FieldError {
+                field: "from",
+                code: FieldErrorCode::OutOfRange,
+                message: String::from("Export cannot start after it ends"),
+            });
+        }
+        errors
+    }
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    /// ## Description
+    /// Renders a page of transactions, one per line
+    fn render(&self, transactions: &[Transaction]) -> String {
+        let mut chunk = String::new();
+        for row in transactions.iter().map(ExportRow::from) {
+            match self {
+                Self::Csv => chunk.push_str(&row.to_csv()),
+                Self::Ndjson => {
+                    if let Ok(line) = serde_json::to_string(&row) {
+                        chunk.push_str(&line);
+                        chunk.push('\n');
+                    }
+                }
+            }
+        }
+        chunk
+    }
+}
+
+impl From<&Transaction> for ExportRow {
+    fn from(transaction: &Transaction) -> Self {
+        let (paid_amount, paid_currency) = transaction
+            .conversion
+            .as_ref()
+            .map_or((transaction.amount, transaction.currency), |conversion| {
+                (conversion.amount, conversion.currency)
+            });
+
+        Self {
+            id: transaction.id,
+            wallets_id: transaction.wallets_id,
+            merchants_id: transaction.merchants_id,
+            amount: transaction.amount,
+            currency: transaction.currency,
+            paid_amount,
+            paid_currency,
+            rate: transaction
+                .conversion
+                .as_ref()
+                .map(|conversion| conversion.rate),
+            status: transaction.status,
+            created_at: transaction.created_at.format(DATETIME_FORMAT).to_string(),
+        }
+    }
+}
+
+impl ExportRow {
+    /// Every field is a number, a code or a datetime, so none of them needs quoting
+    fn to_csv(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            self.id,
+            optional(self.wallets_id.map(|id| id.to_string())),
+            optional(self.merchants_id.map(|id| id.to_string())),
+            self.amount,
+            self.currency,
+            self.paid_amount,
+            self.paid_currency,
+            optional(self.rate.map(|rate| rate.to_string())),
+            self.status,
+            self.created_at
+        )
+    }
+}
+
+/// ## Description
+/// Streams the transactions matching the filter in ID order, reading them a page at a time so an export
+/// never holds more than `EXPORT_PAGE_SIZE` of them. The status is sent before the first page, so a page
+/// failing to load aborts the response midway
+///
+/// ### Returns
+/// The response, downloaded as `{name}.{csv|ndjson}`
+pub(crate) fn export_response(
+    repository: web::Data<dyn TransactionRepository>,
+    filter: TransactionFilter,
+    format: ExportFormat,
+    name: &str,
+) -> HttpResponse {
+    let header = match format {
+        ExportFormat::Csv => Some(Ok(web::Bytes::from_static(CSV_HEADER.as_bytes()))),
+        ExportFormat::Ndjson => None,
+    };
+    let pages = stream::unfold(Some(0), move |after_id| {
+        let repository = repository.clone();
+        async move { next_page(repository, filter, format, after_id?).await }
+    });
+
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.{}\"", name, format.extension()),
+        ))
+        .streaming(stream::iter(header).chain(pages))
+}
+
+/// ## Description
+/// Reads and renders the page after `after_id`
+///
+/// ### Returns
+/// The chunk with the ID to continue from, None once the last page was sent
+async fn next_page(
+    repository: web::Data<dyn TransactionRepository>,
+    filter: TransactionFilter,
+    format: ExportFormat,
+    after_id: TransactionsIdType,
+) -> Option<(
+    Result<web::Bytes, actix_web::Error>,
+    Option<TransactionsIdType>,
+)> {
+    let logger = TheLogger::instance();
+
+    let page = match blocking(&repository, move |repository| {
+        repository.select_page(&filter, after_id, EXPORT_PAGE_SIZE)
+    })
+    .await
+    {
+        Ok(page) => page,
+        Err(error) => {
+            log_error!(logger, "Could not export transactions: {}", error);
+            return Some((Err(ApiError::internal().into()), None));
+        }
+    };
+    if page.is_empty() {
+        return None;
+    }
+
+    //  A short page is the last one
+    let next = if page.len() < EXPORT_PAGE_SIZE as usize {
+        None
+    } else {
+        page.last().map(|transaction| transaction.id)
+    };
+    Some((Ok(web::Bytes::from(format.render(&page))), next))
+}
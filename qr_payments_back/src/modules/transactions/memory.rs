@@ -0,0 +1,468 @@
+use chrono::{Datelike, NaiveDateTime};
+use error_mapper::{create_new_error, TheResult};
+use rust_decimal::Decimal;
+
+use crate::{
+    database::memory::{InMemoryState, InMemoryStore},
+    datatypes::{MerchantsIdType, TransactionsIdType, WalletsIdType},
+    modules::{
+        currencies::Currency,
+        fees::{self, Fee},
+        merchants::statements,
+        settlements::MerchantPayment,
+        wallets::{
+            limits::{LimitValuation, WalletOutflow},
+            Wallet,
+        },
+    },
+};
+
+use super::{
+    aggregate_outflow, export::TransactionFilter, merchant_ledger,
+    repository::TransactionRepository, DebitOutcome, Transaction, TransactionStatus,
+};
+
+impl TransactionRepository for InMemoryStore {
+    fn select_by_token_and_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        token: String,
+    ) -> TheResult<Option<Transaction>> {
+        Ok(self
+            .lock()?
+            .transactions
+            .values()
+            .find(|transaction| {
+                transaction.wallets_id == Some(wallets_id)
+                    && transaction.token.as_ref() == Some(&token)
+            })
+            .cloned())
+    }
+
+    fn select_by_id(&self, transactions_id: TransactionsIdType) -> TheResult<Option<Transaction>> {
+        Ok(self.lock()?.transactions.get(&transactions_id).cloned())
+    }
+
+    fn select_by_status(&self, status: TransactionStatus) -> TheResult<Vec<Transaction>> {
+        Ok(self
+            .lock()?
+            .transactions
+            .values()
+            .filter(|transaction| transaction.status == status)
+            .cloned()
+            .collect())
+    }
+
+    fn select_history_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        since: NaiveDateTime,
+    ) -> TheResult<Vec<Transaction>> {
+        let mut history = self
+            .lock()?
+            .transactions
+            .values()
+            .filter(|transaction| {
+                transaction.wallets_id == Some(wallets_id) && transaction.created_at >= since
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        history.sort_by_key(|transaction| std::cmp::Reverse(transaction.created_at));
+
+        Ok(history)
+    }
+
+    fn has_confirmed_payment(
+        &self,
+        wallets_id: WalletsIdType,
+        merchants_id: Option<MerchantsIdType>,
+    ) -> TheResult<bool> {
+        Ok(self.lock()?.transactions.values().any(|transaction| {
+            transaction.wallets_id == Some(wallets_id)
+                && transaction.status == TransactionStatus::Confirmed
+                && merchants_id
+                    .is_none_or(|merchants_id| transaction.merchants_id == Some(merchants_id))
+        }))
+    }
+
+    fn select_outflow_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+    ) -> TheResult<Vec<WalletOutflow>> {
+        Ok(outflow(&*self.lock()?, wallets_id))
+    }
+
+    fn select_page(
+        &self,
+        filter: &TransactionFilter,
+        after_id: TransactionsIdType,
+        limit: u32,
+    ) -> TheResult<Vec<Transaction>> {
+        Ok(self
+            .lock()?
+            .transactions
+            .range(after_id + 1..)
+            .map(|(_, transaction)| transaction)
+            .filter(|transaction| filter.matches(transaction))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn select_initialized_before(&self, before: NaiveDateTime) -> TheResult<Vec<Transaction>> {
+        Ok(self
+            .lock()?
+            .transactions
+            .values()
+            .filter(|transaction| {
+                transaction.status == TransactionStatus::Initialized
+                    && transaction.created_at < before
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn log(&self, transaction: &Transaction) -> TheResult<bool> {
+        let mut state = self.lock()?;
+        let id = state.next_transactions_id();
+        let logged = Transaction {
+            id,
+            wallets_id: None,
+            merchants_id: None,
+            amount: transaction.amount,
+            currency: transaction.currency,
+            conversion: None,
+            status: TransactionStatus::Log,
+            token: None,
+            errors: transaction.errors.clone(),
+            created_at: chrono::Local::now().naive_local(),
+            reversed_at: None,
+        };
+        state.transactions.insert(id, logged);
+
+        Ok(true)
+    }
+
+    fn insert(&self, transaction: &mut Transaction, fees: &[Fee]) -> TheResult<String> {
+        insert(&mut *self.lock()?, transaction, fees)
+    }
+
+    fn insert_affecting_wallet(
+        &self,
+        transaction: &mut Transaction,
+        fees: &[Fee],
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<DebitOutcome> {
+        let mut state = self.lock()?;
+        if !holds(&state, wallet, transaction.currency) {
+            return Err(create_new_error!("Could not affect wallet balance"));
+        }
+        if let Some(stored) = state.wallets.get(&wallet.id) {
+            *wallet = *stored;
+        }
+
+        if let Some(breach) =
+            transaction.limit_breach(wallet, valuation, || Ok(outflow(&state, wallet.id)))?
+        {
+            return Ok(DebitOutcome::LimitExceeded(breach));
+        }
+
+        if !affect_balance(
+            &mut state,
+            wallet,
+            transaction.amount - fees::payer_total(fees),
+            transaction.currency,
+        ) {
+            return Ok(DebitOutcome::InsufficientFunds);
+        }
+        insert(&mut state, transaction, fees)?;
+
+        Ok(DebitOutcome::Debited)
+    }
+
+    fn update_status_from(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+    ) -> TheResult<bool> {
+        if transaction.id == 0 {
+            return Err(create_new_error!(
+                "Transaction ID cannot be zero for an update operation"
+            ));
+        }
+
+        let mut state = self.lock()?;
+        let Some(stored) = state.transactions.get_mut(&transaction.id) else {
+            return Ok(false);
+        };
+        if stored.status != previous_status {
+            return Ok(false);
+        }
+        update_status_and_error(stored, transaction);
+
+        Ok(true)
+    }
+
+    fn update_status_affecting_wallet(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<Option<DebitOutcome>> {
+        let mut state = self.lock()?;
+        if !holds(&state, wallet, transaction.currency) {
+            return Err(create_new_error!("Could not affect wallet balance"));
+        }
+        if let Some(stored) = state.wallets.get(&wallet.id) {
+            *wallet = *stored;
+        }
+        if let Some(breach) =
+            transaction.limit_breach(wallet, valuation, || Ok(outflow(&state, wallet.id)))?
+        {
+            return Ok(Some(DebitOutcome::LimitExceeded(breach)));
+        }
+        match state.transactions.get(&transaction.id) {
+            Some(stored) if stored.status == previous_status => {}
+            Some(_) => return Ok(None),
+            None => return Err(create_new_error!("Could not update transaction status")),
+        }
+
+        let debit = transaction.amount - fees::payer_total(&lines(&state, transaction.id));
+        if !affect_balance(&mut state, wallet, debit, transaction.currency) {
+            return Ok(Some(DebitOutcome::InsufficientFunds));
+        }
+        if let Some(stored) = state.transactions.get_mut(&transaction.id) {
+            update_status_and_error(stored, transaction);
+        }
+
+        Ok(Some(DebitOutcome::Debited))
+    }
+
+    fn reverse(&self, transaction: &mut Transaction, status: TransactionStatus) -> TheResult<bool> {
+        let mut state = self.lock()?;
+        let Some(stored) = state.transactions.get(&transaction.id) else {
+            return Ok(false);
+        };
+        if stored.status != transaction.status {
+            return Ok(false);
+        }
+
+        let Some(wallets_id) = transaction.wallets_id else {
+            return Err(create_new_error!(format!(
+                "Transaction with ID: {} has no wallet to return its amount to",
+                transaction.id
+            )));
+        };
+        let Some(mut wallet) = state.wallets.get(&wallets_id).copied() else {
+            return Err(create_new_error!(format!(
+                "Wallet with ID: {} no longer exists",
+                wallets_id
+            )));
+        };
+
+        if !holds(&state, &wallet, transaction.currency) {
+            return Err(create_new_error!(format!(
+                "Could not return amount to wallet with ID: {}",
+                wallets_id
+            )));
+        }
+
+        let refunds = fees::refunds(&lines(&state, transaction.id));
+        if !affect_balance(
+            &mut state,
+            &mut wallet,
+            -transaction.amount - fees::payer_total(&refunds),
+            transaction.currency,
+        ) {
+            return Err(create_new_error!(format!(
+                "Could not return amount to wallet with ID: {}",
+                wallets_id
+            )));
+        }
+        transaction.status = status;
+        transaction.reversed_at = Some(chrono::Local::now().naive_local());
+        if let Some(stored) = state.transactions.get_mut(&transaction.id) {
+            stored.status = status;
+            stored.reversed_at = transaction.reversed_at;
+        }
+        state
+            .fees
+            .extend(refunds.into_iter().map(|fee| (transaction.id, fee)));
+
+        Ok(true)
+    }
+
+    fn select_fees(&self, transactions_id: TransactionsIdType) -> TheResult<Vec<Fee>> {
+        Ok(lines(&*self.lock()?, transactions_id))
+    }
+
+    fn select_merchant_ledger(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<(MerchantPayment, Vec<Fee>)>> {
+        let state = self.lock()?;
+        let booked = |at: NaiveDateTime| at >= from && at < to;
+
+        Ok(merchant_ledger_of(&state, |transaction| {
+            transaction.merchants_id == Some(merchants_id)
+                && match transaction.status {
+                    TransactionStatus::Confirmed => booked(transaction.created_at),
+                    TransactionStatus::Refunded => {
+                        booked(transaction.created_at)
+                            || booked(transaction.reversed_at.unwrap_or(transaction.created_at))
+                    }
+                    _ => false,
+                }
+        }))
+    }
+
+    fn select_merchant_balance(
+        &self,
+        merchants_id: MerchantsIdType,
+        currency: Currency,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal> {
+        let payments = merchant_ledger_of(&*self.lock()?, |transaction| {
+            transaction.merchants_id == Some(merchants_id)
+                && matches!(
+                    transaction.status,
+                    TransactionStatus::Confirmed | TransactionStatus::Refunded
+                )
+                && transaction.created_at < before
+        });
+        Ok(statements::ledger_balance(currency, &payments, before))
+    }
+}
+
+fn merchant_ledger_of(
+    state: &InMemoryState,
+    selected: impl Fn(&Transaction) -> bool,
+) -> Vec<(MerchantPayment, Vec<Fee>)> {
+    let transactions = state
+        .transactions
+        .values()
+        .filter(|transaction| selected(transaction))
+        .cloned()
+        .collect::<Vec<_>>();
+    let fees = state
+        .fees
+        .iter()
+        .filter(|(transactions_id, _)| {
+            state
+                .transactions
+                .get(transactions_id)
+                .is_some_and(&selected)
+        })
+        .cloned()
+        .collect();
+
+    merchant_ledger(&transactions, fees)
+}
+
+fn insert(
+    state: &mut InMemoryState,
+    transaction: &mut Transaction,
+    fees: &[Fee],
+) -> TheResult<String> {
+    let Some(token) = transaction.token.clone() else {
+        return Err(create_new_error!(
+            "Cannot proceess transaction without token"
+        ));
+    };
+
+    transaction.id = state.next_transactions_id();
+    state
+        .transactions
+        .insert(transaction.id, transaction.clone());
+    state
+        .fees
+        .extend(fees.iter().map(|fee| (transaction.id, fee.clone())));
+
+    Ok(token)
+}
+
+/// Outgoing amounts of a wallet per currency for the current day and month, counting the transactions
+/// that effectively debited it (Initialized or Confirmed)
+fn outflow(state: &InMemoryState, wallets_id: WalletsIdType) -> Vec<WalletOutflow> {
+    let today = chrono::Local::now().date_naive();
+    let day_start = today.and_time(chrono::NaiveTime::MIN);
+    let month_start = today
+        .with_day(1)
+        .unwrap_or(today)
+        .and_time(chrono::NaiveTime::MIN);
+
+    let debits = state.transactions.values().filter(|transaction| {
+        transaction.wallets_id == Some(wallets_id)
+            && transaction.amount < Decimal::ZERO
+            && matches!(
+                transaction.status,
+                TransactionStatus::Initialized | TransactionStatus::Confirmed
+            )
+            && transaction.created_at >= month_start
+    });
+
+    aggregate_outflow(debits, day_start)
+}
+
+/// Fee ledger of a transaction, oldest line first
+fn lines(state: &InMemoryState, transactions_id: TransactionsIdType) -> Vec<Fee> {
+    state
+        .fees
+        .iter()
+        .filter(|(id, _)| *id == transactions_id)
+        .map(|(_, fee)| fee.clone())
+        .collect()
+}
+
+fn update_status_and_error(stored: &mut Transaction, transaction: &Transaction) {
+    stored.status = transaction.status;
+    if let Some(error) = &transaction.errors {
+        stored.errors = Some(error.clone());
+    }
+}
+
+/// Whether the wallet exists and holds a balance in the currency, as the MySQL implementation requires
+fn holds(state: &InMemoryState, wallet: &Wallet, currency: Currency) -> bool {
+    state.wallets.contains_key(&wallet.id)
+        && (currency == wallet.currency || state.held_balances.contains_key(&(wallet.id, currency)))
+}
+
+/// ## Description
+/// Applies the amount to the stored balance under the store lock, as the MySQL implementation does with a
+/// relative update, and copies the resulting wallet into the received one
+///
+/// ### Returns
+/// False, leaving the balance untouched, if the wallet does not exist, does not hold the currency or the
+/// balance in it would go below zero
+fn affect_balance(
+    state: &mut InMemoryState,
+    wallet: &mut Wallet,
+    amount: Decimal,
+    currency: Currency,
+) -> bool {
+    if currency != wallet.currency {
+        let Some(balance) = state.held_balances.get_mut(&(wallet.id, currency)) else {
+            return false;
+        };
+        if *balance + amount < Decimal::ZERO {
+            return false;
+        }
+        *balance += amount;
+        return true;
+    }
+
+    let Some(stored) = state.wallets.get_mut(&wallet.id) else {
+        return false;
+    };
+    if stored.balance + amount < Decimal::ZERO {
+        return false;
+    }
+    stored.balance += amount;
+    *wallet = *stored;
+
+    true
+}
@@ -1,57 +1,172 @@
-use crate::datatypes::{TransactionsIdType, WalletsIdType};
+use std::collections::BTreeMap;
+
+use crate::{
+    datatypes::{MerchantsIdType, TransactionsIdType, WalletsIdType},
+    modules::{
+        currencies::Currency,
+        fees::{Fee, FeeBreakdown},
+        fx::{Conversion, Quote},
+        outbox::TransactionEvent,
+        risk::HistoryEntry,
+        settlements::MerchantPayment,
+        wallets::{
+            limits::{LimitBreach, LimitValuation, WalletOutflow},
+            Wallet,
+        },
+    },
+};
+use chrono::NaiveDateTime;
+use error_mapper::TheResult;
 use rand::Rng;
 use rand_distr::Alphanumeric;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 mod db;
+pub(crate) mod events;
+pub mod export;
+#[cfg(test)]
+mod memory;
+pub mod repository;
 pub mod services;
+#[cfg(feature = "sqlite")]
+pub(crate) mod sqlite;
+pub mod sweeper;
+#[cfg(test)]
+mod tests;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-struct Transaction {
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+pub struct Transaction {
     id: TransactionsIdType,
     wallets_id: Option<WalletsIdType>,
+    /// Merchant paid, if the payment was made to one
+    merchants_id: Option<MerchantsIdType>,
     amount: Decimal,
+    currency: Currency,
+    /// Set when the payment was made in another currency, `amount` being what was debited for it
+    conversion: Option<Conversion>,
     status: TransactionStatus,
     token: Option<String>,
     errors: Option<String>,
+    created_at: NaiveDateTime,
+    /// When a cancellation, refund or expiry returned the amount to the wallet
+    reversed_at: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, strum::Display, PartialEq, PartialOrd)]
-enum TransactionStatus {
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    Default,
+    strum::Display,
+    PartialEq,
+    PartialOrd,
+    ToSchema,
+)]
+pub enum TransactionStatus {
     #[default]
     Initialized,
+    PendingReview,
     Confirmed,
     Declined,
     Cancelled,
+    Refunded,
+    Expired,
     InternalError,
     Log,
 }
 
+/// What a payment does, which selects the fee schedules applying to it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, strum::Display, PartialEq, Eq, ToSchema)]
+pub enum TransactionKind {
+    Payment,
+    /// Payment converted from the wallet currency
+    Conversion,
+}
+
+/// Token of a new transaction, with the amount it debits
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct NewTransactionResponse {
+    pub token: String,
+    pub breakdown: FeeBreakdown,
+}
+
+/// Outcome of debiting a wallet for a transaction. The checks run inside the DB transaction applying the
+/// debit, so concurrent payments of the same wallet cannot both pass them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebitOutcome {
+    Debited,
+    /// Nothing was written, the debit would breach a limit of the wallet
+    LimitExceeded(LimitBreach),
+    /// Nothing was written, the wallet does not hold enough balance in the currency for the debit
+    InsufficientFunds,
+}
+
 impl Transaction {
-    fn new(amount: Decimal) -> Self {
+    fn new(amount: Decimal, currency: Currency) -> Self {
         Self {
             id: TransactionsIdType::default(),
             wallets_id: None,
+            merchants_id: None,
             amount,
+            currency,
+            conversion: None,
             token: None,
             status: TransactionStatus::default(),
             errors: None,
+            created_at: chrono::Local::now().naive_local(),
+            reversed_at: None,
         }
     }
 
-    fn validate(&self) -> Option<String> {
-        if self.amount == Decimal::ZERO {
-            return Some(String::from("Amount cannot be zero for transaction"));
+    /// ## Description
+    /// Turns the payment into a conversion at the locked quote: the quote's source amount is debited in
+    /// its source currency, and the amount paid is recorded with the rate
+    fn convert(&mut self, quote: &Quote) {
+        self.amount = -quote.source_amount;
+        self.currency = quote.from;
+        self.conversion = Some(quote.conversion());
+    }
+
+    fn kind(&self) -> TransactionKind {
+        match self.conversion {
+            Some(_) => TransactionKind::Conversion,
+            None => TransactionKind::Payment,
+        }
+    }
+
+    /// ## Description
+    /// Checks the transaction against the wallet limits, given what the wallet already spent in every
+    /// currency. Limits are expressed in the wallet currency, so the amounts in other currencies are valued
+    /// in it
+    fn limit_breach(
+        &self,
+        wallet: &Wallet,
+        valuation: &LimitValuation,
+        outflows: impl FnOnce() -> TheResult<Vec<WalletOutflow>>,
+    ) -> TheResult<Option<LimitBreach>> {
+        if self.amount >= Decimal::ZERO {
+            return Ok(None);
         }
+        let amount = match valuation.value(self.amount, self.currency) {
+            Some(amount) => amount,
+            None if wallet.limits.limits_amounts() => return Ok(Some(LimitBreach::Unrated)),
+            //  Without amount limits only the count is checked, which needs no rate
+            None => self.amount,
+        };
 
-        None
+        Ok(wallet
+            .limits
+            .evaluate(amount, &valuation.outflow(&outflows()?)))
     }
 
     fn validate_previous_status(&self, previous_status: TransactionStatus) -> Option<bool> {
-        let previous_valid_status = self.status.previous_state();
-        if let Some(previous_valid) = previous_valid_status {
-            if previous_status != previous_valid {
+        let previous_valid_statuses = self.status.previous_states();
+        if let Some(previous_valid) = previous_valid_statuses {
+            if !previous_valid.contains(&previous_status) {
                 return Some(false)
             }
         }
@@ -59,6 +174,45 @@ impl Transaction {
         None
     }
 
+    /// History entry as seen by a payment in the received currency
+    fn to_history_entry(&self, currency: Currency) -> HistoryEntry {
+        HistoryEntry {
+            amount: self.amount,
+            comparable: self.currency == currency,
+            created_at: self.created_at,
+            status: self.status,
+        }
+    }
+
+    /// ## Description
+    /// Payment as its merchant settlements account for it, None when paid to no merchant
+    pub(crate) fn to_merchant_payment(&self) -> Option<MerchantPayment> {
+        let paid = self
+            .conversion
+            .as_ref()
+            .map_or(self.amount, |conversion| conversion.amount);
+
+        Some(MerchantPayment {
+            transactions_id: self.id,
+            merchants_id: self.merchants_id?,
+            amount: -paid,
+            rate: self.conversion.as_ref().map(|conversion| conversion.rate),
+            status: self.status,
+            created_at: self.created_at,
+            reversed_at: self.reversed_at,
+        })
+    }
+
+    fn to_event(&self) -> TransactionEvent {
+        TransactionEvent::new(
+            self.id,
+            self.wallets_id,
+            self.amount,
+            self.currency,
+            self.status.to_string(),
+        )
+    }
+
     fn generate_token(&mut self) {
         self.token = Some(
             rand::rng()
@@ -70,26 +224,145 @@ impl Transaction {
     }
 }
 
+/// ## Description
+/// Pairs the payments made to a merchant with their fee lines, received along with the ID of the
+/// transaction they were charged on
+fn merchant_ledger(
+    transactions: &[Transaction],
+    fees: Vec<(TransactionsIdType, Fee)>,
+) -> Vec<(MerchantPayment, Vec<Fee>)> {
+    let mut ledgers = BTreeMap::<TransactionsIdType, Vec<Fee>>::new();
+    for (transactions_id, fee) in fees {
+        ledgers.entry(transactions_id).or_default().push(fee);
+    }
+
+    transactions
+        .iter()
+        .filter_map(|transaction| {
+            let payment = transaction.to_merchant_payment()?;
+            Some((payment, ledgers.remove(&transaction.id).unwrap_or_default()))
+        })
+        .collect()
+}
+
+/// ## Description
+/// Adds up the debits of a wallet per currency, for backends that cannot aggregate them in their queries.
+/// Every debit counts for the month, the ones created since `day_start` for the day too
+#[cfg(any(test, feature = "sqlite"))]
+fn aggregate_outflow<'a>(
+    debits: impl Iterator<Item = &'a Transaction>,
+    day_start: NaiveDateTime,
+) -> Vec<WalletOutflow> {
+    let mut outflows: Vec<WalletOutflow> = Vec::new();
+    for transaction in debits {
+        let index = match outflows
+            .iter()
+            .position(|outflow| outflow.currency == transaction.currency)
+        {
+            Some(index) => index,
+            None => {
+                outflows.push(WalletOutflow {
+                    currency: transaction.currency,
+                    ..WalletOutflow::default()
+                });
+                outflows.len() - 1
+            }
+        };
+        let outflow = &mut outflows[index];
+        outflow.monthly_amount -= transaction.amount;
+        if transaction.created_at >= day_start {
+            outflow.daily_amount -= transaction.amount;
+            outflow.daily_count += 1;
+        }
+    }
+    outflows
+}
+
+#[cfg(test)]
+impl Transaction {
+    /// Payment from a wallet in the default currency, ready to be inserted
+    pub(crate) fn payment(wallets_id: WalletsIdType, amount: Decimal) -> Self {
+        let mut transaction = Self::new(amount, Currency::default());
+        transaction.wallets_id = Some(wallets_id);
+        transaction.generate_token();
+        transaction
+    }
+
+    /// The same payment, debited from the balance held in another currency
+    pub(crate) fn in_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    /// The same payment, made to a merchant
+    pub(crate) fn paid_to(mut self, merchants_id: MerchantsIdType) -> Self {
+        self.merchants_id = Some(merchants_id);
+        self
+    }
+
+    /// The same transaction, created at another time
+    pub(crate) fn made_at(mut self, created_at: NaiveDateTime) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
+    /// The same transaction, moved to another status
+    pub(crate) fn with_status(mut self, status: TransactionStatus) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl TransactionKind {
+    pub(crate) fn from_string(input: String) -> Option<Self> {
+        match input.as_str() {
+            "Payment" => Some(Self::Payment),
+            "Conversion" => Some(Self::Conversion),
+            _ => None,
+        }
+    }
+}
+
 impl TransactionStatus {
-    fn from_string(input: String) -> Option<Self> {
+    pub(crate) fn from_string(input: String) -> Option<Self> {
         match input.as_str() {
             "Initialized" => Some(Self::Initialized),
+            "PendingReview" => Some(Self::PendingReview),
             "Confirmed" => Some(Self::Confirmed),
             "Declined" => Some(Self::Declined),
             "Cancelled" => Some(Self::Cancelled),
+            "Refunded" => Some(Self::Refunded),
+            "Expired" => Some(Self::Expired),
             "InternalError" => Some(Self::InternalError),
             "Log" => Some(Self::Log),
             _ => None,
         }
     }
 
-    fn previous_state(&self) -> Option<Self> {
+    /// Statuses after which the transaction is not expected to change anymore
+    fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Self::Confirmed
+                | Self::Declined
+                | Self::Cancelled
+                | Self::Refunded
+                | Self::Expired
+                | Self::InternalError
+                | Self::Log
+        )
+    }
+
+    fn previous_states(&self) -> Option<&'static [Self]> {
         match self {
             Self::Initialized => None,
-            Self::Confirmed => Some(Self::Initialized),
-            Self::Declined => Some(Self::Initialized),
-            Self::Cancelled => Some(Self::Initialized),
-            Self::InternalError => Some(Self::Initialized),
+            Self::PendingReview => None,
+            Self::Confirmed => Some(&[Self::Initialized]),
+            Self::Declined => Some(&[Self::Initialized, Self::PendingReview]),
+            Self::Cancelled => Some(&[Self::Initialized]),
+            Self::Refunded => Some(&[Self::Confirmed]),
+            Self::Expired => Some(&[Self::Initialized]),
+            Self::InternalError => Some(&[Self::Initialized]),
             Self::Log => None,
         }
     }
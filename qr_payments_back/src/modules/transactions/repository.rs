@@ -0,0 +1,113 @@
+use chrono::NaiveDateTime;
+use error_mapper::TheResult;
+use rust_decimal::Decimal;
+
+use crate::{
+    datatypes::{MerchantsIdType, TransactionsIdType, WalletsIdType},
+    modules::{
+        currencies::Currency,
+        fees::Fee,
+        settlements::MerchantPayment,
+        wallets::{
+            limits::{LimitValuation, WalletOutflow},
+            Wallet,
+        },
+    },
+};
+
+use super::{export::TransactionFilter, DebitOutcome, Transaction, TransactionStatus};
+
+/// Persistence of the transactions, implemented by every storage backend. Operations that change a
+/// transaction together with its wallet are atomic, and every change is recorded in the outbox
+pub trait TransactionRepository: Send + Sync {
+    fn select_by_token_and_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        token: String,
+    ) -> TheResult<Option<Transaction>>;
+    fn select_by_id(&self, transactions_id: TransactionsIdType) -> TheResult<Option<Transaction>>;
+    fn select_by_status(&self, status: TransactionStatus) -> TheResult<Vec<Transaction>>;
+    /// Selects the transactions of a wallet created since the received datetime, newest first
+    fn select_history_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        since: NaiveDateTime,
+    ) -> TheResult<Vec<Transaction>>;
+    /// Whether the wallet ever had a payment to the merchant confirmed, or any payment confirmed when no
+    /// merchant is received
+    fn has_confirmed_payment(
+        &self,
+        wallets_id: WalletsIdType,
+        merchants_id: Option<MerchantsIdType>,
+    ) -> TheResult<bool>;
+    /// Aggregates the amounts debited from a wallet on the current day and month, one outflow per currency
+    fn select_outflow_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+    ) -> TheResult<Vec<WalletOutflow>>;
+    /// Selects up to `limit` transactions matching the filter with an ID above `after_id`, in ID order
+    fn select_page(
+        &self,
+        filter: &TransactionFilter,
+        after_id: TransactionsIdType,
+        limit: u32,
+    ) -> TheResult<Vec<Transaction>>;
+    /// Selects the Initialized transactions created before the received datetime
+    fn select_initialized_before(&self, before: NaiveDateTime) -> TheResult<Vec<Transaction>>;
+    /// Records a rejected request as a transaction with the Log status
+    fn log(&self, transaction: &Transaction) -> TheResult<bool>;
+    /// Inserts the transaction with its fee lines
+    fn insert(&self, transaction: &mut Transaction, fees: &[Fee]) -> TheResult<String>;
+    /// Inserts the transaction with its fee lines, and applies its amount plus the payer fees to the wallet
+    /// balance in its currency in the same DB transaction. The wallet limits and balance are checked within
+    /// it, valuing other currencies as received, nothing being written when they don't allow the debit
+    fn insert_affecting_wallet(
+        &self,
+        transaction: &mut Transaction,
+        fees: &[Fee],
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<DebitOutcome>;
+    /// Updates the transaction status and errors only if it still has the previous status. Returns false if
+    /// another request changed it first, in which case nothing was changed
+    fn update_status_from(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+    ) -> TheResult<bool>;
+    /// Moves the transaction from its previous status and applies its amount plus its recorded payer fees to
+    /// the wallet in the same DB transaction, checking the wallet limits and balance within it as when
+    /// inserting. The guarded status update goes before the debit, so concurrent requests cannot both debit
+    /// the wallet. Returns None if the transaction no longer had the previous status. Nothing is written
+    /// unless the wallet is debited
+    fn update_status_affecting_wallet(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<Option<DebitOutcome>>;
+    /// Moves the transaction to a closing status and returns its amount to the wallet atomically, refunding
+    /// its fees. Returns false if the transaction was no longer in its previous status, in which case
+    /// nothing was changed
+    fn reverse(&self, transaction: &mut Transaction, status: TransactionStatus) -> TheResult<bool>;
+    /// Selects the fee ledger of the transaction, oldest line first
+    fn select_fees(&self, transactions_id: TransactionsIdType) -> TheResult<Vec<Fee>>;
+    /// Selects the Confirmed and Refunded payments made to a merchant that were made or refunded in the
+    /// range, in ID order, each with its fee ledger. The fee lines of them all are selected at once
+    fn select_merchant_ledger(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<(MerchantPayment, Vec<Fee>)>>;
+    /// Sums what the payments made to a merchant booked before the datetime, as its statements book them:
+    /// the Confirmed and Refunded payments, the refunds, and the merchant fees charged and returned with
+    /// them, in the merchant currency
+    fn select_merchant_balance(
+        &self,
+        merchants_id: MerchantsIdType,
+        currency: Currency,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal>;
+}
@@ -1,52 +1,194 @@
-use actix_web::{post, web, HttpResponse};
+use actix_web::{get, http::StatusCode, post, put, web, HttpResponse};
+use chrono::TimeDelta;
 use rust_decimal::Decimal;
 use serde::Deserialize;
-use the_logger::{log_critical, log_error, log_info, TheLogger};
+use the_logger::TheLogger;
+use utoipa::{OpenApi, ToSchema};
 
 use crate::{
-    datatypes::WalletsIdType,
-    get_conn_or_internal_error,
+    api::{
+        error::{ApiError, ErrorBody, ErrorCode},
+        validation::ValidJson,
+    },
+    config::Config,
+    database::blocking,
+    datatypes::{MerchantsIdType, TransactionsIdType, WalletsIdType},
+    log_critical, log_error, log_info, logging, metrics,
     modules::{
-        transactions::{Transaction, TransactionStatus},
-        wallets::Wallet,
+        currencies::Currency,
+        fees::{self, repository::FeeRepository, Fee, FeeBreakdown},
+        fx::{repository::FxRepository, Quote, QuoteError},
+        merchants::{repository::MerchantRepository, Merchant},
+        risk::{PaymentAttempt, RiskDecision, RiskEngine},
+        transactions::{
+            events::{publish_status, status_stream, subscribe},
+            repository::TransactionRepository,
+            DebitOutcome, NewTransactionResponse, Transaction, TransactionStatus,
+        },
+        wallets::{limits::LimitValuation, repository::WalletRepository, Wallet, WalletBalance},
     },
+    validation::{self, FieldError, Sign, Validate},
 };
 
+/// Max length of the `errors` column in the `transactions` table
+const ERRORS_MAX_LENGTH: usize = 128;
+
 pub fn transactions_services(cfg: &mut web::ServiceConfig) {
-    cfg.service(new_transaction).service(confirm_transaction);
+    cfg.service(new_transaction)
+        .service(confirm_transaction)
+        .service(cancel_transaction)
+        .service(refund_transaction)
+        .service(get_pending_reviews)
+        .service(review_transaction)
+        .service(transaction_fees)
+        .service(transaction_events);
 }
 
-#[derive(Deserialize)]
-struct NewTransactionRequest {
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        new_transaction,
+        confirm_transaction,
+        cancel_transaction,
+        refund_transaction,
+        get_pending_reviews,
+        review_transaction,
+        transaction_fees,
+        transaction_events
+    ),
+    tags((name = "transactions"))
+)]
+pub struct TransactionsApi;
+
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(serde::Serialize, Default))]
+pub(crate) struct NewTransactionRequest {
     wallets_id: WalletsIdType,
+    /// Merchant paid, which must be active and other than the one settling into the wallet
+    merchants_id: Option<MerchantsIdType>,
+    /// Negative, in whole minor units of the currency
+    #[schema(example = "-100.00")]
     amount: Decimal,
+    /// Defaults to the merchant currency, or to the wallet currency when paying no merchant
+    #[schema(value_type = Option<Currency>)]
+    currency: Option<String>,
+    /// Pays by converting from the wallet currency, at the current rate unless a quote is locked
+    #[serde(default)]
+    convert: bool,
+    /// Quote to lock for the conversion, which implies `convert`
+    #[schema(pattern = "^[A-Za-z0-9]{32}$")]
+    quote_id: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct PostTransactionRequest {
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(serde::Serialize, Default))]
+pub(crate) struct PostTransactionRequest {
     wallets_id: WalletsIdType,
+    #[schema(pattern = "^[A-Za-z0-9]{32}$")]
     transaction_token: String,
 }
 
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(serde::Serialize, Default))]
+pub(crate) struct ReviewTransactionRequest {
+    approved: bool,
+}
+
+impl Validate for NewTransactionRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        validation::identifier(&mut errors, "wallets_id", self.wallets_id);
+        if let Some(merchants_id) = self.merchants_id {
+            validation::identifier(&mut errors, "merchants_id", merchants_id);
+        }
+        //  Payments debit the wallet, credits are not accepted through the API
+        validation::amount(&mut errors, "amount", self.amount, Sign::Negative);
+        if let Some(currency) = &self.currency {
+            validation::currency(&mut errors, "currency", currency);
+            if let Some(currency) = Currency::from_code(currency) {
+                validation::minor_units(&mut errors, "amount", self.amount, currency);
+            }
+        }
+        if let Some(quote_id) = &self.quote_id {
+            validation::token(&mut errors, "quote_id", quote_id);
+        }
+        errors
+    }
+}
+
+impl Validate for PostTransactionRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        validation::identifier(&mut errors, "wallets_id", self.wallets_id);
+        validation::token(&mut errors, "transaction_token", &self.transaction_token);
+        errors
+    }
+}
+
+impl Validate for ReviewTransactionRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        Vec::new()
+    }
+}
+
+/// /v1/transactions
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Starts a payment, debiting the wallet",
+    request_body = NewTransactionRequest,
+    responses(
+        (
+            status = 200,
+            description = "Token to confirm the transaction with, and the amount debited",
+            body = NewTransactionResponse
+        ),
+        (
+            status = 202,
+            description = "Flagged for review, token of the pending transaction and the amount it will debit",
+            body = NewTransactionResponse
+        ),
+        (status = 403, description = "Declined by the risk rules", body = ErrorBody),
+        (status = 404, description = "Wallet, merchant or quote not found", body = ErrorBody),
+        (
+            status = 422,
+            description = "Invalid fields, frozen wallet, suspended merchant, insufficient funds, limit \
+                exceeded or unusable quote",
+            body = ErrorBody
+        ),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
 #[post("")]
-async fn new_transaction(body: web::Json<NewTransactionRequest>) -> HttpResponse {
+async fn new_transaction(
+    body: ValidJson<NewTransactionRequest>,
+    wallets: web::Data<dyn WalletRepository>,
+    merchants: web::Data<dyn MerchantRepository>,
+    repository: web::Data<dyn TransactionRepository>,
+    fx: web::Data<dyn FxRepository>,
+    fee_schedules: web::Data<dyn FeeRepository>,
+) -> Result<HttpResponse, ApiError> {
     let logger = TheLogger::instance();
-    let mut conn = get_conn_or_internal_error!(logger);
     let body = body.into_inner();
+    logging::set_wallets_id(body.wallets_id);
 
     log_info!(logger, "Received new transaction request. Processing");
 
-    let mut transaction = Transaction::new(body.amount);
+    let requested_currency = body.currency.as_deref().and_then(Currency::from_code);
+    let mut transaction = Transaction::new(body.amount, requested_currency.unwrap_or_default());
 
     //  Validate wallet exists
-    let mut wallet = match Wallet::select_by_id(&mut conn, body.wallets_id) {
+    let mut wallet = match blocking(&wallets, move |wallets| {
+        wallets.select_by_id(body.wallets_id)
+    })
+    .await
+    {
         Ok(Some(wallet)) => wallet,
         Ok(None) => {
             let msg = format!("Invalid wallet received with ID: {}", body.wallets_id);
             log_info!(logger, "{}", msg);
             transaction.errors = Some(msg);
 
-            match transaction.log(&mut conn) {
+            match blocking(&repository, move |repository| repository.log(&transaction)).await {
                 Ok(true) => {}
                 Ok(false) => {
                     log_info!(logger, "Could not log transaction. No details available");
@@ -56,112 +198,360 @@ async fn new_transaction(body: web::Json<NewTransactionRequest>) -> HttpResponse
                 }
             }
 
-            return HttpResponse::BadRequest().json(format!(
-                "Wallet with ID: {} does not exist. Aborting transaction",
-                body.wallets_id
-            ));
+            return Err(ApiError::wallet_not_found(body.wallets_id));
         }
         Err(error) => {
             log_error!(logger, "Error selecting wallet: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            return Err(ApiError::internal());
         }
     };
 
-    //  Validate transaction amount first
-    if let Some(error) = transaction.validate() {
-        log_info!(logger, "Invalid transaction: {}", error);
-        return HttpResponse::PreconditionFailed().json(error);
+    //  Validate the wallet can be used, and its balance
+    if wallet.frozen {
+        let error = ApiError::wallet_frozen(wallet.id);
+        log_info!(logger, "{}", error);
+        return Err(error);
     }
 
-    //  Then validate wallet balance
-    if !wallet.validate_balance(body.amount) {
-        let msg = "Wallet has insufficient balance for transaction";
-        log_info!(logger, "{}", msg);
-        return HttpResponse::PreconditionFailed().json(msg);
+    //  Payments to a merchant require it to be active, and are made in the currency it settles in
+    let merchant = match body.merchants_id {
+        Some(merchants_id) => {
+            Some(select_active_merchant(&merchants, merchants_id, &wallet).await?)
+        }
+        None => None,
+    };
+    if let (Some(merchant), Some(requested_currency)) = (&merchant, requested_currency) {
+        if requested_currency != merchant.currency {
+            let error = ApiError::validation(format!(
+                "Merchant with ID: {} is paid in {}, not in {}",
+                merchant.id, merchant.currency, requested_currency
+            ));
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+    }
+    transaction.merchants_id = body.merchants_id;
+
+    //  Payments are in the wallet currency unless another one is requested, and are debited from the
+    //  balance the wallet holds in that currency
+    let currency = requested_currency
+        .or(merchant.map(|merchant| merchant.currency))
+        .unwrap_or(wallet.currency);
+    transaction.currency = currency;
+    let mut errors = Vec::new();
+    validation::minor_units(&mut errors, "amount", body.amount, currency);
+    if !errors.is_empty() {
+        let error = ApiError::invalid_fields(errors);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+
+    //  Unless a conversion is requested, in which case the quoted amount is debited from the wallet
+    //  currency instead. Everything from here on checks the debited side. A requested quote is only
+    //  locked once the payment passed its checks, so a rejected payment leaves it open
+    let mut requested_quote = None;
+    if body.convert || body.quote_id.is_some() {
+        if currency == wallet.currency {
+            let error = ApiError::validation(format!(
+                "Payments in the wallet currency, {}, need no conversion",
+                currency
+            ));
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+
+        let quote = price_conversion(
+            &fx,
+            body.quote_id.clone(),
+            wallet.currency,
+            currency,
+            -body.amount,
+        )
+        .await?;
+        log_info!(
+            logger,
+            "Converting {} {} from {} {} at {}",
+            quote.amount,
+            quote.to,
+            quote.source_amount,
+            quote.from,
+            quote.rate
+        );
+        transaction.convert(&quote);
+        if body.quote_id.is_some() {
+            requested_quote = Some(quote);
+        }
+    }
+    let (amount, currency) = (transaction.amount, transaction.currency);
+
+    //  Fees are computed on the debited side too. The payer ones are debited with the amount, while the
+    //  limits and the fraud rules only consider the amount
+    let schedules = match blocking(&fee_schedules, |repository| repository.select_schedules()).await
+    {
+        Ok(schedules) => schedules,
+        Err(error) => {
+            log_error!(logger, "Could not get fee schedules: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    let fees = fees::calculate(
+        &schedules,
+        transaction.kind(),
+        transaction.merchants_id,
+        amount,
+        currency,
+    );
+    let breakdown = FeeBreakdown::new(amount, currency, fees);
+
+    let Some(balance) = balance_in(&wallets, wallet, currency).await? else {
+        let error = ApiError::currency_mismatch(wallet.id, currency);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    };
+    if !balance.validate(breakdown.total) {
+        let error = ApiError::insufficient_funds();
+        log_info!(logger, "{}", error);
+        metrics::INSUFFICIENT_BALANCE.inc();
+        return Err(error);
+    }
+
+    //  And the wallet spending limits against what it already spent in every currency. Limits are
+    //  expressed in the wallet currency, the other currencies being valued in it at the current rates.
+    //  They are checked again while debiting, where concurrent payments are serialized, this check
+    //  rejecting early the payments that would otherwise be flagged for review
+    let valuation = limit_valuation(&fx, wallet.currency).await?;
+    let outflows = match blocking(&repository, move |repository| {
+        repository.select_outflow_by_wallets_id(wallet.id)
+    })
+    .await
+    {
+        Ok(outflows) => outflows,
+        Err(error) => {
+            log_error!(logger, "Error aggregating wallet outflow: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    if let Ok(Some(breach)) = transaction.limit_breach(&wallet, &valuation, || Ok(outflows)) {
+        log_info!(
+            logger,
+            "Wallet with ID: {} breached limit: {}",
+            wallet.id,
+            breach
+        );
+        return Err(ApiError::limit_exceeded(breach));
+    }
+
+    //  Evaluate the fraud rules against the wallet history before debiting it. Every currency counts,
+    //  while only the amounts in the same one are compared
+    let risk_config = match Config::get_risk_config().await {
+        Ok(risk_config) => risk_config,
+        Err(error) => {
+            log_error!(logger, "Could not get risk configurations: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    let Some(since) = TimeDelta::try_days(risk_config.history_days.into())
+        .and_then(|history| transaction.created_at.checked_sub_signed(history))
+    else {
+        log_error!(
+            logger,
+            "Risk history of {} days is out of range",
+            risk_config.history_days
+        );
+        return Err(ApiError::internal());
+    };
+    let history = match blocking(&repository, move |repository| {
+        repository.select_history_by_wallets_id(wallet.id, since)
+    })
+    .await
+    {
+        Ok(history) => history
+            .iter()
+            .map(|transaction| transaction.to_history_entry(currency))
+            .collect::<Vec<_>>(),
+        Err(error) => {
+            log_error!(logger, "Error selecting wallet history: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    let merchants_id = transaction.merchants_id;
+    let known_payee = match blocking(&repository, move |repository| {
+        repository.has_confirmed_payment(wallet.id, merchants_id)
+    })
+    .await
+    {
+        Ok(known_payee) => known_payee,
+        Err(error) => {
+            log_error!(logger, "Error selecting wallet payees: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    let attempt = PaymentAttempt {
+        amount,
+        at: transaction.created_at,
+        known_payee,
+    };
+    let verdict = RiskEngine::from_config(&risk_config).evaluate(&attempt, &history);
+
+    //  Declined payments don't use their quote, every other one locks it now
+    if let Some(quote) = &requested_quote {
+        if verdict.decision != RiskDecision::Block {
+            lock_quote(&fx, quote.id.clone()).await?;
+        }
     }
 
     //  If everything is okay, generate token and proceed with transaction
     transaction.generate_token();
     transaction.wallets_id = Some(wallet.id);
-    let token = match transaction.insert(&mut conn) {
-        Ok(id) => id,
-        Err(error) => {
-            log_error!(logger, "Could not insert transaction: {}", error);
-            return HttpResponse::InternalServerError().finish();
+
+    //  Flagged payments are stored without debiting the wallet, either to be reviewed or as declined
+    if verdict.decision != RiskDecision::Allow {
+        log_info!(
+            logger,
+            "Transaction flagged with decision {}: {}",
+            verdict.decision,
+            verdict.reasons.join("; ")
+        );
+        transaction.status = match verdict.decision {
+            RiskDecision::Review => TransactionStatus::PendingReview,
+            _ => TransactionStatus::Declined,
+        };
+        transaction.errors = Some(verdict.summary(ERRORS_MAX_LENGTH));
+
+        //  Payments pending review keep their fees, to be charged when approved
+        let status = transaction.status;
+        let fees = match verdict.decision {
+            RiskDecision::Review => breakdown.fees.clone(),
+            _ => Vec::new(),
+        };
+        let token = match blocking(&repository, move |repository| {
+            repository.insert(&mut transaction, &fees)
+        })
+        .await
+        {
+            Ok(token) => {
+                metrics::record_transaction(status, amount, currency);
+                token
+            }
+            Err(error) => {
+                log_error!(logger, "Could not insert flagged transaction: {}", error);
+                return Err(ApiError::internal());
+            }
+        };
+
+        if verdict.decision == RiskDecision::Review {
+            return Ok(HttpResponse::Accepted().json(NewTransactionResponse { token, breakdown }));
         }
-    };
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            ErrorCode::TransactionDeclined,
+            "Transaction was declined by the risk rules",
+        ));
+    }
 
-    //  Affect wallet balance
-    match wallet.affect_balance(&mut conn, body.amount) {
-        Ok(true) => {
+    //  Insert the transaction and debit the wallet in a single operation, which checks the balance and the
+    //  limits again against the concurrent payments. A copy is moved to the blocking pool, keeping the
+    //  original to record a failed attempt
+    let mut attempt = transaction.clone();
+    let fees = breakdown.fees.clone();
+    match blocking(&repository, move |repository| {
+        repository.insert_affecting_wallet(&mut attempt, &fees, &mut wallet, &valuation)
+    })
+    .await
+    {
+        Ok(DebitOutcome::Debited) => {
             log_info!(
                 logger,
                 "Transaction approved, continue to confirmation stage"
             );
-            return HttpResponse::Ok().json(token);
+            metrics::record_transaction(TransactionStatus::Initialized, amount, currency);
+            return Ok(HttpResponse::Ok().json(NewTransactionResponse {
+                token: transaction.token.unwrap_or_default(),
+                breakdown,
+            }));
         }
-        Ok(false) => {
-            log_error!(
+        Ok(DebitOutcome::LimitExceeded(breach)) => {
+            log_info!(
                 logger,
-                "Could not affect wallet balance, rolling back transaction"
+                "Wallet with ID: {} breached limit while debiting: {}",
+                wallet.id,
+                breach
             );
-            transaction.errors = Some(String::from("Could not affect wallet balance"));
+            release_quote(&fx, requested_quote).await;
+            return Err(ApiError::limit_exceeded(breach));
+        }
+        Ok(DebitOutcome::InsufficientFunds) => {
+            let error = ApiError::insufficient_funds();
+            log_info!(logger, "{}", error);
+            metrics::INSUFFICIENT_BALANCE.inc();
+            release_quote(&fx, requested_quote).await;
+            return Err(error);
         }
         Err(error) => {
             log_error!(
                 logger,
-                "Error affecting wallet balance, rolling back transaction: {}",
+                "Error affecting wallet balance, transaction rolled back: {}",
                 error
             );
             transaction.errors = Some(String::from("MySql error while affecting wallet balance"));
         }
     }
 
-    //  From this point onwards, the processing is to handle an error state. Every response will be 500
+    //  From this point onwards, the processing is to handle an error state. Every response will be 500.
+    //  The failed attempt is kept as an InternalError transaction
     transaction.status = TransactionStatus::InternalError;
-
-    //  If roll back fails, log a crit error
-    let crit_error = match transaction.update_status_and_error(&mut conn) {
-        Ok(true) => None,
-        Ok(false) => Some(String::from("Unable to roll back transaction")),
-        Err(error) => Some(format!("Error while rolling back transaction: {}", error)),
-    };
-
-    if let Some(error) = crit_error {
-        log_critical!(
-            logger,
-            "Critical error while rolling back transaction: {}",
-            error
-        )
-    } else {
-        log_info!(logger, "Transaction rolled back successfully");
+    metrics::record_transaction(TransactionStatus::InternalError, amount, currency);
+    if let Err(error) = blocking(&repository, move |repository| {
+        repository.insert(&mut transaction, &[])
+    })
+    .await
+    {
+        log_error!(logger, "Could not record failed transaction: {}", error);
     }
 
-    HttpResponse::InternalServerError().finish()
+    Err(ApiError::internal())
 }
 
 /// /v1/transactions/confirm
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Confirms an initialized transaction",
+    request_body = PostTransactionRequest,
+    responses(
+        (status = 200, description = "Transaction moved to its new status"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 409, description = "Invalid status transition, or the status changed concurrently", body = ErrorBody),
+        (status = 422, description = "Invalid fields", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
 #[post("/confirm")]
-async fn confirm_transaction(body: web::Json<PostTransactionRequest>) -> HttpResponse {
+async fn confirm_transaction(
+    body: ValidJson<PostTransactionRequest>,
+    repository: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
     let body = body.into_inner();
     let logger = TheLogger::instance();
-    let mut conn = get_conn_or_internal_error!(logger);
+    logging::set_wallets_id(body.wallets_id);
 
     log_info!(logger, "Received Confirm Transaction request");
 
-    let mut transaction = match Transaction::select_by_token_and_wallets_id(
-        &mut conn,
-        body.wallets_id,
-        body.transaction_token,
-    ) {
-        Ok(Some(transaction)) => transaction,
+    let mut transaction = match blocking(&repository, move |repository| {
+        repository.select_by_token_and_wallets_id(body.wallets_id, body.transaction_token)
+    })
+    .await
+    {
+        Ok(Some(transaction)) => {
+            logging::set_transactions_id(transaction.id);
+            transaction
+        }
         Ok(None) => {
             log_info!(logger, "No transaction found for received params");
-            return HttpResponse::NotFound().json("No transaction found for the required criteria");
+            return Err(ApiError::transaction_not_found());
         }
         Err(error) => {
             log_error!(logger, "Error searching for transaction: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            return Err(ApiError::internal());
         }
     };
 
@@ -169,25 +559,689 @@ async fn confirm_transaction(body: web::Json<PostTransactionRequest>) -> HttpRes
     let previous_status = transaction.status;
     transaction.status = TransactionStatus::Confirmed;
     if transaction.validate_previous_status(previous_status) == Some(false) {
-        log_info!(logger, "Transaction with ID: {} has an invalid previous status: {}", transaction.id, previous_status);
-        return HttpResponse::PreconditionFailed().json("Transaction has an invalid previous status")
+        log_info!(
+            logger,
+            "Transaction with ID: {} has an invalid previous status: {}",
+            transaction.id,
+            previous_status
+        );
+        return Err(ApiError::invalid_transition(
+            previous_status,
+            transaction.status,
+        ));
     }
-    match transaction.update_status_and_error(&mut conn) {
+    //  The update is guarded, so a cancellation or expiry that returned the amount meanwhile is kept
+    let confirmed = transaction.clone();
+    match blocking(&repository, move |repository| {
+        repository.update_status_from(&confirmed, previous_status)
+    })
+    .await
+    {
         Ok(true) => {}
         Ok(false) => {
-            log_error!(
+            log_info!(
                 logger,
-                "Could not confirm transaction. No extra details provided"
+                "Transaction with ID: {} changed status while confirming",
+                transaction.id
             );
-            return HttpResponse::InternalServerError().finish();
+            return Err(ApiError::concurrent_modification());
         }
         Err(error) => {
             log_error!(logger, "Could not confirm transaction: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            return Err(ApiError::internal());
         }
     }
 
     log_info!(logger, "Transaction confirmed");
+    publish_status(&transaction);
+    metrics::record_transaction(transaction.status, transaction.amount, transaction.currency);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// /v1/transactions/cancel
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Cancels an initialized transaction, returning the amount to the wallet",
+    request_body = PostTransactionRequest,
+    responses(
+        (status = 200, description = "Transaction moved to its new status"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 409, description = "Invalid status transition, or the status changed concurrently", body = ErrorBody),
+        (status = 422, description = "Invalid fields", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[post("/cancel")]
+async fn cancel_transaction(
+    body: ValidJson<PostTransactionRequest>,
+    repository: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
+    log_info!(TheLogger::instance(), "Received Cancel Transaction request");
+    reverse_transaction(body.into_inner(), repository, TransactionStatus::Cancelled).await
+}
+
+/// /v1/transactions/refund
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Refunds a confirmed transaction, returning the amount to the wallet",
+    request_body = PostTransactionRequest,
+    responses(
+        (status = 200, description = "Transaction moved to its new status"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 409, description = "Invalid status transition, or the status changed concurrently", body = ErrorBody),
+        (status = 422, description = "Invalid fields", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[post("/refund")]
+async fn refund_transaction(
+    body: ValidJson<PostTransactionRequest>,
+    repository: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
+    log_info!(TheLogger::instance(), "Received Refund Transaction request");
+    reverse_transaction(body.into_inner(), repository, TransactionStatus::Refunded).await
+}
+
+/// ## Description
+/// Moves the requested transaction to a closing status that returns its amount to the wallet
+async fn reverse_transaction(
+    body: PostTransactionRequest,
+    repository: web::Data<dyn TransactionRepository>,
+    status: TransactionStatus,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    logging::set_wallets_id(body.wallets_id);
+
+    let mut transaction = match blocking(&repository, move |repository| {
+        repository.select_by_token_and_wallets_id(body.wallets_id, body.transaction_token)
+    })
+    .await
+    {
+        Ok(Some(transaction)) => {
+            logging::set_transactions_id(transaction.id);
+            transaction
+        }
+        Ok(None) => {
+            log_info!(logger, "No transaction found for received params");
+            return Err(ApiError::transaction_not_found());
+        }
+        Err(error) => {
+            log_error!(logger, "Error searching for transaction: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    let previous_status = transaction.status;
+    transaction.status = status;
+    if transaction.validate_previous_status(previous_status) == Some(false) {
+        log_info!(
+            logger,
+            "Transaction with ID: {} has an invalid previous status: {}",
+            transaction.id,
+            previous_status
+        );
+        return Err(ApiError::invalid_transition(previous_status, status));
+    }
+    transaction.status = previous_status;
+
+    let transactions_id = transaction.id;
+    let transaction = match blocking(&repository, move |repository| {
+        repository
+            .reverse(&mut transaction, status)
+            .map(|reversed| (reversed, transaction))
+    })
+    .await
+    {
+        Ok((true, transaction)) => transaction,
+        Ok((false, _)) => {
+            log_info!(
+                logger,
+                "Transaction with ID: {} changed its status concurrently",
+                transactions_id
+            );
+            return Err(ApiError::concurrent_modification());
+        }
+        Err(error) => {
+            log_critical!(
+                logger,
+                "Error moving transaction with ID: {} to status {}: {}",
+                transactions_id,
+                status,
+                error
+            );
+            return Err(ApiError::internal());
+        }
+    };
+
+    log_info!(logger, "Transaction moved to status {}", status);
+    publish_status(&transaction);
+    metrics::record_transaction(transaction.status, transaction.amount, transaction.currency);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// /v1/transactions/reviews
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Lists the transactions pending review",
+    responses(
+        (status = 200, description = "Transactions", body = Vec<Transaction>),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/reviews")]
+async fn get_pending_reviews(
+    repository: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+
+    log_info!(logger, "Selecting transactions pending review...");
+
+    match blocking(&repository, |repository| {
+        repository.select_by_status(TransactionStatus::PendingReview)
+    })
+    .await
+    {
+        Ok(transactions) => Ok(HttpResponse::Ok().json(transactions)),
+        Err(error) => {
+            log_error!(
+                logger,
+                "Could not get transactions pending review: {}",
+                error
+            );
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/transactions/reviews/{transactions_id}
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Approves or rejects a transaction pending review",
+    params(("transactions_id" = TransactionsIdType, Path)),
+    request_body = ReviewTransactionRequest,
+    responses(
+        (
+            status = 200,
+            description = "Token and amount debited when approved, empty when rejected",
+            body = NewTransactionResponse
+        ),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 409, description = "Not pending review, or the status changed concurrently", body = ErrorBody),
+        (status = 422, description = "Insufficient funds or limit exceeded", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[put("/reviews/{transactions_id}")]
+async fn review_transaction(
+    path: web::Path<TransactionsIdType>,
+    body: ValidJson<ReviewTransactionRequest>,
+    wallets: web::Data<dyn WalletRepository>,
+    repository: web::Data<dyn TransactionRepository>,
+    fx: web::Data<dyn FxRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let transactions_id = path.into_inner();
+    logging::set_transactions_id(transactions_id);
+    let body = body.into_inner();
+
+    log_info!(
+        logger,
+        "Received review for transaction with ID: {}, approved: {}",
+        transactions_id,
+        body.approved
+    );
+
+    let mut transaction = match blocking(&repository, move |repository| {
+        repository.select_by_id(transactions_id)
+    })
+    .await
+    {
+        Ok(Some(transaction)) => transaction,
+        Ok(None) => {
+            log_info!(logger, "No transaction found with ID: {}", transactions_id);
+            return Err(ApiError::transaction_not_found());
+        }
+        Err(error) => {
+            log_error!(logger, "Error searching for transaction: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    if transaction.status != TransactionStatus::PendingReview {
+        log_info!(
+            logger,
+            "Transaction with ID: {} is not pending review, status: {}",
+            transaction.id,
+            transaction.status
+        );
+        return Err(ApiError::invalid_transition(
+            transaction.status,
+            TransactionStatus::Initialized,
+        ));
+    }
+
+    //  Rejected transactions are declined, the wallet was never debited. The update is guarded, so a
+    //  concurrent approval that debited the wallet is never overwritten
+    if !body.approved {
+        transaction.status = TransactionStatus::Declined;
+        let rejected = transaction.clone();
+        return match blocking(&repository, move |repository| {
+            repository.update_status_from(&rejected, TransactionStatus::PendingReview)
+        })
+        .await
+        {
+            Ok(true) => {
+                log_info!(logger, "Transaction rejected");
+                publish_status(&transaction);
+                metrics::record_transaction(
+                    transaction.status,
+                    transaction.amount,
+                    transaction.currency,
+                );
+                Ok(HttpResponse::Ok().finish())
+            }
+            Ok(false) => {
+                log_info!(
+                    logger,
+                    "Transaction with ID: {} was reviewed concurrently",
+                    transaction.id
+                );
+                Err(ApiError::concurrent_modification())
+            }
+            Err(error) => {
+                log_error!(logger, "Could not reject transaction: {}", error);
+                Err(ApiError::internal())
+            }
+        };
+    }
+
+    //  Approved transactions debit the wallet and continue to the confirmation stage
+    let Some(wallets_id) = transaction.wallets_id else {
+        log_error!(
+            logger,
+            "Transaction with ID: {} has no wallet",
+            transaction.id
+        );
+        return Err(ApiError::internal());
+    };
+    logging::set_wallets_id(wallets_id);
+    let mut wallet = match blocking(&wallets, move |wallets| wallets.select_by_id(wallets_id)).await
+    {
+        Ok(Some(wallet)) => wallet,
+        Ok(None) => {
+            log_error!(logger, "Wallet with ID: {} no longer exists", wallets_id);
+            return Err(ApiError::internal());
+        }
+        Err(error) => {
+            log_error!(logger, "Error selecting wallet: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    if wallet.frozen {
+        let error = ApiError::wallet_frozen(wallet.id);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+    //  The fees were recorded when the payment was flagged, and are debited with it now
+    let fees = match blocking(&repository, move |repository| {
+        repository.select_fees(transactions_id)
+    })
+    .await
+    {
+        Ok(fees) => fees,
+        Err(error) => {
+            log_error!(logger, "Error selecting transaction fees: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+    let breakdown = FeeBreakdown::new(transaction.amount, transaction.currency, fees);
+
+    //  The wallet could have stopped holding the currency while the transaction was waiting
+    let Some(balance) = balance_in(&wallets, wallet, transaction.currency).await? else {
+        let error = ApiError::currency_mismatch(wallet.id, transaction.currency);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    };
+    if !balance.validate(breakdown.total) {
+        let error = ApiError::insufficient_funds();
+        log_info!(logger, "{}", error);
+        metrics::INSUFFICIENT_BALANCE.inc();
+        return Err(error);
+    }
+
+    //  Debit the wallet and move the transaction forward in a single operation. The balance and the limits
+    //  are checked again while debiting, as the wallet could have spent more while the transaction waited
+    transaction.status = TransactionStatus::Initialized;
+    let approved = transaction.clone();
+    let valuation = limit_valuation(&fx, wallet.currency).await?;
+    match blocking(&repository, move |repository| {
+        repository.update_status_affecting_wallet(
+            &approved,
+            TransactionStatus::PendingReview,
+            &mut wallet,
+            &valuation,
+        )
+    })
+    .await
+    {
+        Ok(Some(DebitOutcome::Debited)) => {}
+        Ok(None) => {
+            log_info!(
+                logger,
+                "Transaction with ID: {} was reviewed concurrently",
+                transaction.id
+            );
+            return Err(ApiError::concurrent_modification());
+        }
+        Ok(Some(DebitOutcome::LimitExceeded(breach))) => {
+            log_info!(
+                logger,
+                "Wallet with ID: {} breached limit: {}",
+                wallets_id,
+                breach
+            );
+            return Err(ApiError::limit_exceeded(breach));
+        }
+        Ok(Some(DebitOutcome::InsufficientFunds)) => {
+            let error = ApiError::insufficient_funds();
+            log_info!(logger, "{}", error);
+            metrics::INSUFFICIENT_BALANCE.inc();
+            return Err(error);
+        }
+        Err(error) => {
+            log_error!(
+                logger,
+                "Could not approve transaction with ID: {}: {}",
+                transaction.id,
+                error
+            );
+            return Err(ApiError::internal());
+        }
+    }
+
+    log_info!(
+        logger,
+        "Transaction approved, continue to confirmation stage"
+    );
+    publish_status(&transaction);
+    metrics::record_transaction(transaction.status, transaction.amount, transaction.currency);
+    Ok(HttpResponse::Ok().json(NewTransactionResponse {
+        token: transaction.token.unwrap_or_default(),
+        breakdown,
+    }))
+}
+
+/// /v1/transactions/{transactions_id}/fees
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Fee ledger of a transaction, refunds as negative lines",
+    params(("transactions_id" = TransactionsIdType, Path)),
+    responses(
+        (status = 200, description = "Fee lines, oldest first", body = Vec<Fee>),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{transactions_id}/fees")]
+async fn transaction_fees(
+    path: web::Path<TransactionsIdType>,
+    repository: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let transactions_id = path.into_inner();
+    logging::set_transactions_id(transactions_id);
+
+    log_info!(
+        logger,
+        "Selecting fees of transaction with ID: {}",
+        transactions_id
+    );
+
+    let fees = blocking(&repository, move |repository| {
+        repository
+            .select_by_id(transactions_id)
+            .and_then(|transaction| {
+                transaction
+                    .map(|_| repository.select_fees(transactions_id))
+                    .transpose()
+            })
+    })
+    .await;
+    match fees {
+        Ok(Some(fees)) => Ok(HttpResponse::Ok().json(fees)),
+        Ok(None) => {
+            log_info!(logger, "No transaction found with ID: {}", transactions_id);
+            Err(ApiError::transaction_not_found())
+        }
+        Err(error) => {
+            log_error!(logger, "Error selecting transaction fees: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// ## Description
+/// Values amounts in the wallet currency at the current rates, for its limits
+async fn limit_valuation(
+    fx: &web::Data<dyn FxRepository>,
+    currency: Currency,
+) -> Result<LimitValuation, ApiError> {
+    let logger = TheLogger::instance();
+    match blocking(fx, |fx| fx.select_rates()).await {
+        Ok(rates) => Ok(LimitValuation::new(currency, &rates)),
+        Err(error) => {
+            log_error!(logger, "Could not read rates: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// ## Description
+/// Prices paying the positive `amount` of `to` from `from` with the requested quote, checked without
+/// locking it. Without one, the payment is converted at the current rate
+async fn price_conversion(
+    fx: &web::Data<dyn FxRepository>,
+    quote_id: Option<String>,
+    from: Currency,
+    to: Currency,
+    amount: Decimal,
+) -> Result<Quote, ApiError> {
+    let logger = TheLogger::instance();
+    let now = chrono::Local::now().naive_local();
+
+    let priced = blocking(fx, move |fx| match quote_id {
+        Some(quote_id) => Ok(match fx.select_quote(&quote_id)? {
+            Some(quote) => quote.check(from, to, amount, now).map(|_| quote),
+            None => Err(QuoteError::NotFound(quote_id)),
+        }),
+        None => Ok(fx.select_rates()?.spot(from, to, amount, now)),
+    })
+    .await;
+    match priced {
+        Ok(Ok(quote)) => Ok(quote),
+        Ok(Err(error)) => {
+            let error = ApiError::quote(error);
+            log_info!(logger, "{}", error);
+            Err(error)
+        }
+        Err(error) => {
+            log_error!(logger, "Could not price conversion: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// ## Description
+/// Locks a quote for the payment using it, failing if another payment locked it first
+async fn lock_quote(fx: &web::Data<dyn FxRepository>, quote_id: String) -> Result<(), ApiError> {
+    let logger = TheLogger::instance();
+    let locked_id = quote_id.clone();
+
+    match blocking(fx, move |fx| fx.delete_quote(&locked_id)).await {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            let error = ApiError::quote(QuoteError::NotFound(quote_id));
+            log_info!(logger, "{}", error);
+            Err(error)
+        }
+        Err(error) => {
+            log_error!(logger, "Could not lock quote: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// ## Description
+/// Reopens the quote of a payment refused while debiting, so it can still be used until it expires
+async fn release_quote(fx: &web::Data<dyn FxRepository>, quote: Option<Quote>) {
+    let logger = TheLogger::instance();
+    let Some(quote) = quote else {
+        return;
+    };
+    let now = chrono::Local::now().naive_local();
+
+    if let Err(error) = blocking(fx, move |fx| fx.insert_quote(&quote, now)).await {
+        log_error!(logger, "Could not release quote: {}", error);
+    }
+}
+
+/// ## Description
+/// Selects the merchant a payment is made to, failing unless it exists, is active and is not paid from its
+/// own settlement wallet
+async fn select_active_merchant(
+    merchants: &web::Data<dyn MerchantRepository>,
+    merchants_id: MerchantsIdType,
+    wallet: &Wallet,
+) -> Result<Merchant, ApiError> {
+    let logger = TheLogger::instance();
+
+    let merchant = match blocking(merchants, move |merchants| {
+        merchants.select_by_id(merchants_id)
+    })
+    .await
+    {
+        Ok(Some(merchant)) => merchant,
+        Ok(None) => {
+            let error = ApiError::merchant_not_found(merchants_id);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+        Err(error) => {
+            log_error!(logger, "Error selecting merchant: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    if !merchant.is_active() {
+        let error = ApiError::merchant_suspended(merchant.id);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+    if merchant.wallets_id == wallet.id {
+        let error = ApiError::validation(format!(
+            "Merchant with ID: {} cannot be paid from its own settlement wallet",
+            merchant.id
+        ));
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+
+    Ok(merchant)
+}
+
+/// ## Description
+/// Finds the balance the wallet holds in the currency, if any. The wallet currency's balance is already
+/// loaded, the other ones are selected
+async fn balance_in(
+    wallets: &web::Data<dyn WalletRepository>,
+    wallet: Wallet,
+    currency: Currency,
+) -> Result<Option<WalletBalance>, ApiError> {
+    if currency == wallet.currency {
+        return Ok(Some(wallet.own_balance()));
+    }
+
+    match blocking(wallets, move |wallets| wallets.select_balances(&wallet)).await {
+        Ok(balances) => Ok(balances
+            .into_iter()
+            .find(|balance| balance.currency == currency)),
+        Err(error) => {
+            log_error!(
+                TheLogger::instance(),
+                "Error selecting wallet balances: {}",
+                error
+            );
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/transactions/{transactions_id}/events
+#[utoipa::path(
+    tag = "transactions",
+    summary = "Server-sent events with every status change of a transaction",
+    params(("transactions_id" = TransactionsIdType, Path)),
+    responses(
+        (status = 200, description = "Event stream", content_type = "text/event-stream", body = String),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{transactions_id}/events")]
+async fn transaction_events(
+    path: web::Path<TransactionsIdType>,
+    repository: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let transactions_id = path.into_inner();
+    logging::set_transactions_id(transactions_id);
+
+    log_info!(
+        logger,
+        "Opening events stream for transaction with ID: {}",
+        transactions_id
+    );
+
+    let heartbeat_secs = match Config::get_transactions_config().await {
+        Ok(config) => config.events_heartbeat_secs,
+        Err(error) => {
+            log_error!(
+                logger,
+                "Could not get transactions configurations: {}",
+                error
+            );
+            return Err(ApiError::internal());
+        }
+    };
+
+    //  Subscribe before reading the transaction, so a status change in between is not lost
+    let receiver = subscribe();
+    let transaction = match blocking(&repository, move |repository| {
+        repository.select_by_id(transactions_id)
+    })
+    .await
+    {
+        Ok(Some(transaction)) => transaction,
+        Ok(None) => {
+            log_info!(logger, "No transaction found with ID: {}", transactions_id);
+            return Err(ApiError::transaction_not_found());
+        }
+        Err(error) => {
+            log_error!(logger, "Error searching for transaction: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
 
-    HttpResponse::Ok().finish()
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(status_stream(
+            &transaction,
+            receiver,
+            repository,
+            heartbeat_secs,
+        )))
 }
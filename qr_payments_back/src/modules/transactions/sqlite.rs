@@ -0,0 +1,506 @@
+use chrono::{Datelike, NaiveDateTime};
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{named_params, params, types::ToSql, Connection, OptionalExtension, Params};
+use rust_decimal::Decimal;
+
+use crate::{
+    database::sqlite::{currency_column, decimal_column, SqliteStore},
+    datatypes::{MerchantsIdType, TransactionsIdType, WalletsIdType},
+    modules::{
+        currencies::Currency,
+        fees::{self, sqlite as fees_sqlite, Fee},
+        fx::Conversion,
+        merchants::statements,
+        outbox::sqlite::append,
+        settlements::MerchantPayment,
+        wallets::{
+            limits::{LimitValuation, WalletOutflow},
+            sqlite as wallets_sqlite, Wallet,
+        },
+    },
+};
+
+use super::{
+    aggregate_outflow, export::TransactionFilter, merchant_ledger,
+    repository::TransactionRepository, DebitOutcome, Transaction, TransactionStatus,
+};
+
+impl TransactionRepository for SqliteStore {
+    fn select_by_token_and_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        token: String,
+    ) -> TheResult<Option<Transaction>> {
+        self.lock()?
+            .query_row(
+                "SELECT * FROM `transactions` WHERE `wallets_ID` = ? AND `token` = ?;",
+                params![wallets_id, token],
+                from_row,
+            )
+            .optional()
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_by_id(&self, transactions_id: TransactionsIdType) -> TheResult<Option<Transaction>> {
+        select_by_id(&*self.lock()?, transactions_id)
+    }
+
+    fn select_by_status(&self, status: TransactionStatus) -> TheResult<Vec<Transaction>> {
+        select(
+            &*self.lock()?,
+            "SELECT * FROM `transactions` WHERE `status` = ? ORDER BY `ID`;",
+            params![status.to_string()],
+        )
+    }
+
+    fn select_history_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+        since: NaiveDateTime,
+    ) -> TheResult<Vec<Transaction>> {
+        select(
+            &*self.lock()?,
+            "SELECT * FROM `transactions` WHERE `wallets_ID` = ? AND `created_at` >= ? ORDER BY `created_at` DESC;",
+            params![wallets_id, since],
+        )
+    }
+
+    fn has_confirmed_payment(
+        &self,
+        wallets_id: WalletsIdType,
+        merchants_id: Option<MerchantsIdType>,
+    ) -> TheResult<bool> {
+        self.lock()?
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM `transactions` WHERE `wallets_ID` = ?1 AND `status` = 'Confirmed' AND (?2 IS NULL OR `merchants_ID` = ?2));",
+                params![wallets_id, merchants_id],
+                |row| row.get(0),
+            )
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_outflow_by_wallets_id(
+        &self,
+        wallets_id: WalletsIdType,
+    ) -> TheResult<Vec<WalletOutflow>> {
+        select_outflow(&*self.lock()?, wallets_id)
+    }
+
+    fn select_page(
+        &self,
+        filter: &TransactionFilter,
+        after_id: TransactionsIdType,
+        limit: u32,
+    ) -> TheResult<Vec<Transaction>> {
+        let query = format!(
+            "SELECT * FROM `transactions` WHERE `{}` = ? AND `status` <> 'Log' AND `created_at` >= ? AND `created_at` < ? AND `ID` > ? ORDER BY `ID` LIMIT ?;",
+            filter.owner.column()
+        );
+        select(
+            &*self.lock()?,
+            &query,
+            params![filter.owner.id(), filter.from, filter.to, after_id, limit],
+        )
+    }
+
+    fn select_initialized_before(&self, before: NaiveDateTime) -> TheResult<Vec<Transaction>> {
+        select(
+            &*self.lock()?,
+            "SELECT * FROM `transactions` WHERE `status` = 'Initialized' AND `created_at` < ? ORDER BY `ID`;",
+            params![before],
+        )
+    }
+
+    fn log(&self, transaction: &Transaction) -> TheResult<bool> {
+        let affected_rows = self
+            .lock()?
+            .execute(
+                "INSERT INTO `transactions`(`amount`, `currency`, `status`, `errors`, `created_at`) VALUES(?, ?, ?, ?, ?);",
+                params![
+                    transaction.amount.to_string(),
+                    transaction.currency.code(),
+                    TransactionStatus::Log.to_string(),
+                    transaction.errors,
+                    chrono::Local::now().naive_local(),
+                ],
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn insert(&self, transaction: &mut Transaction, fees: &[Fee]) -> TheResult<String> {
+        self.in_transaction(|db_transaction| insert(db_transaction, transaction, fees))
+    }
+
+    fn insert_affecting_wallet(
+        &self,
+        transaction: &mut Transaction,
+        fees: &[Fee],
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<DebitOutcome> {
+        //  The connection is held for the whole SQLite transaction, so no concurrent payment can change the
+        //  outflow between the limits check and the debit
+        self.in_transaction(|db_transaction| {
+            let Some(stored) = wallets_sqlite::select_by_id(db_transaction, wallet.id)? else {
+                return Err(create_new_error!("Could not affect wallet balance"));
+            };
+            *wallet = stored;
+
+            if let Some(breach) = transaction.limit_breach(wallet, valuation, || {
+                select_outflow(db_transaction, wallet.id)
+            })? {
+                return Ok(DebitOutcome::LimitExceeded(breach));
+            }
+
+            //  The guarded debit goes first, so an insufficient balance leaves nothing written
+            if !wallets_sqlite::affect_balance(
+                db_transaction,
+                wallet,
+                transaction.amount - fees::payer_total(fees),
+                transaction.currency,
+            )? {
+                return Ok(DebitOutcome::InsufficientFunds);
+            }
+            insert(db_transaction, transaction, fees)?;
+
+            Ok(DebitOutcome::Debited)
+        })
+    }
+
+    fn update_status_from(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+    ) -> TheResult<bool> {
+        self.in_transaction(|db_transaction| {
+            update_status_from(db_transaction, transaction, previous_status)
+        })
+    }
+
+    fn update_status_affecting_wallet(
+        &self,
+        transaction: &Transaction,
+        previous_status: TransactionStatus,
+        wallet: &mut Wallet,
+        valuation: &LimitValuation,
+    ) -> TheResult<Option<DebitOutcome>> {
+        self.in_transaction_if(
+            |db_transaction| {
+                let Some(stored) = wallets_sqlite::select_by_id(db_transaction, wallet.id)? else {
+                    return Err(create_new_error!("Could not affect wallet balance"));
+                };
+                *wallet = stored;
+                if let Some(breach) = transaction.limit_breach(wallet, valuation, || {
+                    select_outflow(db_transaction, wallet.id)
+                })? {
+                    return Ok(Some(DebitOutcome::LimitExceeded(breach)));
+                }
+                if !update_status_from(db_transaction, transaction, previous_status)? {
+                    return Ok(None);
+                }
+
+                let fees = fees_sqlite::select_by_transactions_id(db_transaction, transaction.id)?;
+                if !wallets_sqlite::affect_balance(
+                    db_transaction,
+                    wallet,
+                    transaction.amount - fees::payer_total(&fees),
+                    transaction.currency,
+                )? {
+                    return Ok(Some(DebitOutcome::InsufficientFunds));
+                }
+                Ok(Some(DebitOutcome::Debited))
+            },
+            |outcome| *outcome == Some(DebitOutcome::Debited),
+        )
+    }
+
+    fn reverse(&self, transaction: &mut Transaction, status: TransactionStatus) -> TheResult<bool> {
+        if transaction.id == 0 {
+            return Err(create_new_error!(
+                "Transaction ID cannot be zero for an update operation"
+            ));
+        }
+
+        let reversed_at = chrono::Local::now().naive_local();
+        let reversed = self.in_transaction(|db_transaction| {
+            let affected_rows = db_transaction
+                .execute(
+                    "UPDATE `transactions` SET `status` = ?, `reversed_at` = ? WHERE `ID` = ? AND `status` = ?;",
+                    params![
+                        status.to_string(),
+                        reversed_at,
+                        transaction.id,
+                        transaction.status.to_string()
+                    ],
+                )
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            if affected_rows == 0 {
+                return Ok(false);
+            }
+
+            let Some(wallets_id) = transaction.wallets_id else {
+                return Err(create_new_error!(format!(
+                    "Transaction with ID: {} has no wallet to return its amount to",
+                    transaction.id
+                )));
+            };
+            let Some(mut wallet) = wallets_sqlite::select_by_id(db_transaction, wallets_id)? else {
+                return Err(create_new_error!(format!(
+                    "Wallet with ID: {} no longer exists",
+                    wallets_id
+                )));
+            };
+            let charged = fees_sqlite::select_by_transactions_id(db_transaction, transaction.id)?;
+            let refunds = fees::refunds(&charged);
+            fees_sqlite::insert_lines(db_transaction, transaction.id, &refunds)?;
+            if !wallets_sqlite::affect_balance(
+                db_transaction,
+                &mut wallet,
+                -transaction.amount - fees::payer_total(&refunds),
+                transaction.currency,
+            )? {
+                return Err(create_new_error!(format!(
+                    "Could not return amount to wallet with ID: {}",
+                    wallets_id
+                )));
+            }
+
+            let mut reversed = transaction.clone();
+            reversed.status = status;
+            reversed.reversed_at = Some(reversed_at);
+            append(db_transaction, &reversed.to_event())?;
+
+            Ok(true)
+        })?;
+
+        if reversed {
+            transaction.status = status;
+            transaction.reversed_at = Some(reversed_at);
+        }
+        Ok(reversed)
+    }
+
+    fn select_fees(&self, transactions_id: TransactionsIdType) -> TheResult<Vec<Fee>> {
+        fees_sqlite::select_by_transactions_id(&*self.lock()?, transactions_id)
+    }
+
+    fn select_merchant_ledger(
+        &self,
+        merchants_id: MerchantsIdType,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> TheResult<Vec<(MerchantPayment, Vec<Fee>)>> {
+        let condition = "`transactions`.`merchants_ID` = :merchants_id AND (\
+            (`transactions`.`status` IN ('Confirmed', 'Refunded') AND `transactions`.`created_at` >= :from AND `transactions`.`created_at` < :to) \
+            OR (`transactions`.`status` = 'Refunded' AND COALESCE(`transactions`.`reversed_at`, `transactions`.`created_at`) >= :from AND COALESCE(`transactions`.`reversed_at`, `transactions`.`created_at`) < :to))";
+        let params = named_params! {
+            ":merchants_id": merchants_id,
+            ":from": from,
+            ":to": to,
+        };
+
+        select_merchant_ledger(&*self.lock()?, condition, params)
+    }
+
+    fn select_merchant_balance(
+        &self,
+        merchants_id: MerchantsIdType,
+        currency: Currency,
+        before: NaiveDateTime,
+    ) -> TheResult<Decimal> {
+        //  Decimals are stored as TEXT, so the payments booked before are added up here rather than by
+        //  SQLite. Refunds are booked after their payment, so these hold every movement booked before
+        let condition = "`transactions`.`merchants_ID` = :merchants_id AND `transactions`.`status` IN ('Confirmed', 'Refunded') AND `transactions`.`created_at` < :before";
+        let params = named_params! {
+            ":merchants_id": merchants_id,
+            ":before": before,
+        };
+
+        let payments = select_merchant_ledger(&*self.lock()?, condition, params)?;
+        Ok(statements::ledger_balance(currency, &payments, before))
+    }
+}
+
+/// ## Description
+/// Selects the payments matching the condition on the `transactions` table, and then the fee lines of
+/// them all in a single query
+fn select_merchant_ledger(
+    conn: &Connection,
+    condition: &str,
+    params: &[(&str, &dyn ToSql)],
+) -> TheResult<Vec<(MerchantPayment, Vec<Fee>)>> {
+    let transactions = select(
+        conn,
+        &format!(
+            "SELECT * FROM `transactions` WHERE {} ORDER BY `ID`;",
+            condition
+        ),
+        params,
+    )?;
+    if transactions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let fees = fees_sqlite::select_by_transactions(conn, condition, params)?;
+    Ok(merchant_ledger(&transactions, fees))
+}
+
+fn select_by_id(
+    conn: &Connection,
+    transactions_id: TransactionsIdType,
+) -> TheResult<Option<Transaction>> {
+    conn.query_row(
+        "SELECT * FROM `transactions` WHERE `ID` = ?;",
+        params![transactions_id],
+        from_row,
+    )
+    .optional()
+    .map_err(|error| create_new_error!(error.to_string()))
+}
+
+pub(in crate::modules) fn select(
+    conn: &Connection,
+    query: &str,
+    params: impl Params,
+) -> TheResult<Vec<Transaction>> {
+    let mut stmt = conn
+        .prepare(query)
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+    let transactions = stmt
+        .query_map(params, from_row)
+        .map_err(|error| create_new_error!(error.to_string()))?
+        .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+        .collect();
+    transactions
+}
+
+/// ## Description
+/// Aggregates the outgoing amounts of a wallet per currency for the current day and month. Only
+/// transactions that effectively debited the wallet (Initialized or Confirmed) are considered
+fn select_outflow(conn: &Connection, wallets_id: WalletsIdType) -> TheResult<Vec<WalletOutflow>> {
+    let today = chrono::Local::now().date_naive();
+    let day_start = today.and_time(chrono::NaiveTime::MIN);
+    let month_start = today
+        .with_day(1)
+        .unwrap_or(today)
+        .and_time(chrono::NaiveTime::MIN);
+
+    //  Amounts are stored as TEXT, so they are added here instead of relying on SQLite's float SUM
+    let debits = select(
+        conn,
+        "SELECT * FROM `transactions` WHERE `wallets_ID` = ? AND `status` IN ('Initialized', 'Confirmed') AND `created_at` >= ?;",
+        params![wallets_id, month_start],
+    )?;
+
+    Ok(aggregate_outflow(
+        debits
+            .iter()
+            .filter(|transaction| transaction.amount < Decimal::ZERO),
+        day_start,
+    ))
+}
+
+/// ## Description
+/// Inserts the transaction with its fee lines, recording its event in the outbox within the same SQLite
+/// transaction
+fn insert(conn: &Connection, transaction: &mut Transaction, fees: &[Fee]) -> TheResult<String> {
+    let Some(token) = transaction.token.clone() else {
+        return Err(create_new_error!(
+            "Cannot proceess transaction without token"
+        ));
+    };
+
+    let conversion = transaction.conversion.as_ref();
+    conn.execute(
+        "INSERT INTO `transactions`(`wallets_ID`, `merchants_ID`, `amount`, `currency`, `fx_quote`, `fx_rate`, `converted_amount`, `converted_currency`, `status`, `token`, `errors`, `created_at`) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+        params![
+            transaction.wallets_id,
+            transaction.merchants_id,
+            transaction.amount.to_string(),
+            transaction.currency.code(),
+            conversion.map(|conversion| conversion.quotes_id.clone()),
+            conversion.map(|conversion| conversion.rate.to_string()),
+            conversion.map(|conversion| conversion.amount.to_string()),
+            conversion.map(|conversion| conversion.currency.code()),
+            transaction.status.to_string(),
+            token,
+            transaction.errors,
+            transaction.created_at,
+        ],
+    )
+    .map_err(|error| create_new_error!(error.to_string()))?;
+    transaction.id = conn.last_insert_rowid() as TransactionsIdType;
+    fees_sqlite::insert_lines(conn, transaction.id, fees)?;
+    append(conn, &transaction.to_event())?;
+
+    Ok(token)
+}
+
+/// ## Description
+/// Updates the transaction status and errors only if it still has the previous status, recording the
+/// status event in the outbox
+fn update_status_from(
+    conn: &Connection,
+    transaction: &Transaction,
+    previous_status: TransactionStatus,
+) -> TheResult<bool> {
+    if transaction.id == 0 {
+        return Err(create_new_error!(
+            "Transaction ID cannot be zero for an update operation"
+        ));
+    }
+
+    let affected_rows = conn
+        .execute(
+            "UPDATE `transactions` SET `status` = ?, `errors` = COALESCE(?, `errors`) WHERE `ID` = ? AND `status` = ?;",
+            params![
+                transaction.status.to_string(),
+                transaction.errors,
+                transaction.id,
+                previous_status.to_string()
+            ],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+    if affected_rows == 0 {
+        return Ok(false);
+    }
+    append(conn, &transaction.to_event())?;
+
+    Ok(true)
+}
+
+fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Transaction> {
+    let status = row.get::<_, String>("status")?;
+
+    Ok(Transaction {
+        id: row.get("ID")?,
+        wallets_id: row.get("wallets_ID")?,
+        merchants_id: row.get("merchants_ID")?,
+        amount: decimal_column(row, "amount")?,
+        currency: currency_column(row, "currency")?,
+        conversion: match row.get::<_, Option<String>>("fx_quote")? {
+            Some(quotes_id) => Some(Conversion {
+                quotes_id,
+                rate: decimal_column(row, "fx_rate")?,
+                amount: decimal_column(row, "converted_amount")?,
+                currency: currency_column(row, "converted_currency")?,
+            }),
+            None => None,
+        },
+        token: row.get("token")?,
+        errors: row.get("errors")?,
+        created_at: row.get("created_at")?,
+        reversed_at: row.get("reversed_at")?,
+        status: TransactionStatus::from_string(status).ok_or_else(|| {
+            rusqlite::Error::InvalidColumnType(
+                0,
+                String::from("status"),
+                rusqlite::types::Type::Text,
+            )
+        })?,
+    })
+}
@@ -0,0 +1,102 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::web;
+
+use chrono::TimeDelta;
+use error_mapper::{create_new_error, TheResult};
+use the_logger::TheLogger;
+
+use crate::{
+    config::{Config, TransactionsConfig},
+    database::blocking,
+    log_critical, log_error, log_info, metrics, tasks,
+};
+
+use super::{events::publish_status, repository::TransactionRepository, TransactionStatus};
+
+/// ## Description
+/// Background task that expires the Initialized transactions not confirmed within `ttl_secs`, returning
+/// their amount to the wallet
+pub async fn run_expiry_sweeper(repository: Arc<dyn TransactionRepository>) {
+    let logger = TheLogger::instance();
+    let repository = web::Data::from(repository);
+    log_info!(logger, "Transactions expiry sweeper started");
+
+    loop {
+        let config = match Config::get_transactions_config().await {
+            Ok(config) => config,
+            Err(error) => {
+                log_error!(
+                    logger,
+                    "Could not get transactions configurations: {}",
+                    error
+                );
+                TransactionsConfig::default()
+            }
+        };
+
+        let interval = Duration::from_secs(config.sweep_interval_secs);
+        let outcome = expire_transactions(&repository, &config).await;
+        if let Err(error) = &outcome {
+            log_error!(logger, "Error expiring transactions: {}", error);
+        }
+        tasks::report("expiry_sweeper", interval, &outcome);
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+pub(super) async fn expire_transactions(
+    repository: &web::Data<dyn TransactionRepository>,
+    config: &TransactionsConfig,
+) -> TheResult<()> {
+    let logger = TheLogger::instance();
+
+    let cutoff = i64::try_from(config.ttl_secs)
+        .ok()
+        .and_then(TimeDelta::try_seconds)
+        .and_then(|ttl| chrono::Local::now().naive_local().checked_sub_signed(ttl))
+        .ok_or_else(|| {
+            create_new_error!(format!(
+                "Transactions TTL of {} seconds is out of range",
+                config.ttl_secs
+            ))
+        })?;
+    let transactions = blocking(repository, move |repository| {
+        repository.select_initialized_before(cutoff)
+    })
+    .await?;
+
+    for mut transaction in transactions {
+        let transactions_id = transaction.id;
+        match blocking(repository, move |repository| {
+            repository
+                .reverse(&mut transaction, TransactionStatus::Expired)
+                .map(|expired| (expired, transaction))
+        })
+        .await
+        {
+            Ok((true, transaction)) => {
+                log_info!(logger, "Transaction with ID: {} expired", transaction.id);
+                publish_status(&transaction);
+                metrics::record_transaction(
+                    transaction.status,
+                    transaction.amount,
+                    transaction.currency,
+                );
+            }
+            //  Confirmed or cancelled while sweeping
+            Ok((false, _)) => {}
+            Err(error) => {
+                log_critical!(
+                    logger,
+                    "Error expiring transaction with ID: {}: {}",
+                    transactions_id,
+                    error
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
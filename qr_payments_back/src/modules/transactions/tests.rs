@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use actix_web::web;
+use futures_util::StreamExt;
+use rust_decimal::Decimal;
+
+use crate::{
+    database::memory::InMemoryStore,
+    datatypes::TransactionsIdType,
+    modules::outbox::{sinks::ChannelSink, AggregateType, OutboxEvent, WalletEvent},
+};
+
+use super::{
+    events::{status_stream, subscribe},
+    repository::TransactionRepository,
+    Transaction, TransactionStatus,
+};
+
+/// IDs no other test creates, as every test shares the status channel
+const STREAMED_ID: TransactionsIdType = 900_001;
+const RELAYED_ID: TransactionsIdType = 900_002;
+
+fn transaction(id: TransactionsIdType, status: TransactionStatus) -> Transaction {
+    let mut transaction = Transaction::payment(1, Decimal::from(-100));
+    transaction.id = id;
+    transaction.status = status;
+    transaction
+}
+
+/// Outbox event of the transaction, as the relay reads it
+fn outbox_event(transaction: &Transaction) -> OutboxEvent {
+    OutboxEvent {
+        aggregate_type: AggregateType::Transaction,
+        aggregate_id: transaction.id,
+        payload: serde_json::to_string(&transaction.to_event()).unwrap(),
+        ..Default::default()
+    }
+}
+
+#[actix_web::test]
+async fn outbox_transaction_events_are_published_to_the_status_channel() {
+    let mut receiver = subscribe();
+
+    let wallet_event = OutboxEvent {
+        aggregate_type: AggregateType::Wallet,
+        payload: serde_json::to_string(&WalletEvent::new(
+            "balance_changed",
+            1,
+            Default::default(),
+            Decimal::ZERO,
+        ))
+        .unwrap(),
+        ..Default::default()
+    };
+    ChannelSink::publish(&wallet_event).unwrap();
+    let confirmed = transaction(RELAYED_ID, TransactionStatus::Confirmed);
+    ChannelSink::publish(&outbox_event(&confirmed)).unwrap();
+
+    loop {
+        let event = receiver.recv().await.expect("event should be published");
+        if event.transactions_id == RELAYED_ID {
+            assert_eq!(event.status, "Confirmed");
+            break;
+        }
+    }
+}
+
+#[actix_web::test]
+async fn status_stream_ends_after_the_relayed_terminal_status() {
+    let initialized = transaction(STREAMED_ID, TransactionStatus::Initialized);
+    let repository: Arc<dyn TransactionRepository> = Arc::new(InMemoryStore::default());
+    let stream = status_stream(&initialized, subscribe(), web::Data::from(repository), 60);
+
+    //  Published twice, as both the handler and the relay do, and only sent once
+    let confirmed = transaction(STREAMED_ID, TransactionStatus::Confirmed);
+    ChannelSink::publish(&outbox_event(&confirmed)).unwrap();
+    ChannelSink::publish(&outbox_event(&confirmed)).unwrap();
+
+    let frames = stream
+        .map(|frame| String::from_utf8(frame.unwrap().to_vec()).unwrap())
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0].contains("\"status\":\"Initialized\""));
+    assert!(frames[1].contains("\"status\":\"Confirmed\""));
+}
@@ -1,70 +1,350 @@
 use error_mapper::{create_new_error, TheResult};
-use mysql::{
-    prelude::{FromRow, Queryable},
-    PooledConn,
-};
+use mysql::{prelude::Queryable, PooledConn};
 use rust_decimal::Decimal;
 
-use crate::{datatypes::WalletsIdType, row_to_data};
+use crate::{
+    database::{
+        decode::{self, DecodeRow, RowError},
+        in_transaction, Executor, MySqlStore,
+    },
+    datatypes::WalletsIdType,
+    from_row_via_decode,
+    modules::{
+        currencies::Currency,
+        outbox::{OutboxEvent, WalletEvent},
+    },
+    row_to_data,
+};
 
-use super::Wallet;
+use super::{
+    limits::WalletLimits, repository::WalletRepository, BalanceAdjustment, Wallet, WalletBalance,
+};
 
 impl Wallet {
-    pub(super) fn select_all(conn: &mut PooledConn) -> TheResult<Vec<Wallet>> {
-        conn.query::<Wallet, _>("SELECT * FROM wallets")
-            .map_err(|error| create_new_error!(error.to_string()))
+    pub(super) fn select_all(conn: &mut impl Queryable) -> TheResult<Vec<Wallet>> {
+        let rows = conn
+            .query::<mysql::Row, _>("SELECT * FROM wallets")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
     }
-    pub(in crate::modules) fn select_by_id(
-        conn: &mut PooledConn,
+    pub(crate) fn select_by_id(
+        conn: &mut impl Queryable,
         wallet_id: WalletsIdType,
     ) -> TheResult<Option<Wallet>> {
         let query = "SELECT * FROM wallets WHERE ID = ?;";
         let stmt = conn
             .prep(query)
             .map_err(|error| create_new_error!(error.to_string()))?;
-        conn.exec_first(stmt, (wallet_id,))
-            .map_err(|error| create_new_error!(error.to_string()))
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, (wallet_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+    /// ## Description
+    /// Selects the wallet locking its row until the DB transaction ends, so the operations debiting the
+    /// wallet are serialized
+    pub(crate) fn select_for_update(
+        conn: &mut mysql::Transaction<'_>,
+        wallet_id: WalletsIdType,
+    ) -> TheResult<Option<Wallet>> {
+        let query = "SELECT * FROM wallets WHERE ID = ? FOR UPDATE;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, (wallet_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+    /// ## Description
+    /// Selects the balances the wallet holds in other currencies than its own, by currency
+    pub(crate) fn select_held_balances(
+        &self,
+        conn: &mut impl Queryable,
+    ) -> TheResult<Vec<WalletBalance>> {
+        let query = "SELECT * FROM `wallet_balances` WHERE `wallets_ID` = ? ORDER BY `currency`;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (self.id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
     }
+    /// ## Description
+    /// Applies an amount to the wallet balance in the received currency, recording the change in the
+    /// outbox within the same DB transaction. The amount is added by the UPDATE itself, guarded against
+    /// leaving the balance negative, so a balance read before the DB transaction is never written back
+    ///
+    /// ### Returns
+    /// False if the wallet does not exist, does not hold a balance in the currency, or the balance is not
+    /// enough for a debit, in which case nothing was changed
     pub(in crate::modules) fn affect_balance(
         &mut self,
-        conn: &mut PooledConn,
+        conn: &mut mysql::Transaction<'_>,
         requested_amount: Decimal,
+        currency: Currency,
     ) -> TheResult<bool> {
         if self.id == 0 {
             return Err(create_new_error!(
                 "Wallet cannot have an ID of zero when affecting its balance"
             ));
         }
+        if currency != self.currency {
+            return self.affect_held_balance(conn, requested_amount, currency);
+        }
 
-        self.balance += requested_amount;
-        let query = "UPDATE `wallets` SET `balance` = ? WHERE ID = ?";
+        let query =
+            "UPDATE `wallets` SET `balance` = `balance` + ? WHERE ID = ? AND `balance` + ? >= 0";
         let stmt = conn
             .prep(query)
             .map_err(|error| create_new_error!(error.to_string()))?;
-        let params = vec![self.balance.to_string(), self.id.to_string()];
+        let amount = requested_amount.to_string();
+        conn.exec_drop(stmt, (amount.as_str(), self.id, amount.as_str()))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        if conn.affected_rows() == 0 {
+            return Ok(false);
+        }
+
+        //  The event carries the balance as updated within this DB transaction
+        let stmt = conn
+            .prep("SELECT `balance` FROM `wallets` WHERE ID = ?")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        self.balance = conn
+            .exec_first::<Decimal, _, _>(stmt, (self.id,))
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .ok_or_else(|| create_new_error!("Wallet disappeared while affecting its balance"))?;
+        OutboxEvent::append(
+            conn,
+            &WalletEvent::new("balance_changed", self.id, self.currency, self.balance),
+        )?;
+
+        Ok(true)
+    }
+    /// ## Description
+    /// Applies an amount to the balance held in a currency other than the wallet's own, with the same
+    /// guarded relative update as the wallet's own balance
+    fn affect_held_balance(
+        &self,
+        conn: &mut mysql::Transaction<'_>,
+        requested_amount: Decimal,
+        currency: Currency,
+    ) -> TheResult<bool> {
+        let query = "UPDATE `wallet_balances` SET `balance` = `balance` + ? WHERE `wallets_ID` = ? AND `currency` = ? AND `balance` + ? >= 0";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let amount = requested_amount.to_string();
+        conn.exec_drop(
+            stmt,
+            (amount.as_str(), self.id, currency.code(), amount.as_str()),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+        if conn.affected_rows() == 0 {
+            return Ok(false);
+        }
+
+        let query =
+            "SELECT `balance` FROM `wallet_balances` WHERE `wallets_ID` = ? AND `currency` = ?";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let balance = conn
+            .exec_first::<Decimal, _, _>(stmt, (self.id, currency.code()))
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .ok_or_else(|| create_new_error!("Held balance disappeared while affecting it"))?;
+        OutboxEvent::append(
+            conn,
+            &WalletEvent::new("balance_changed", self.id, currency, balance),
+        )?;
+
+        Ok(true)
+    }
+    /// ## Description
+    /// Inserts an empty wallet in the received currency, recording its creation in the outbox within the
+    /// same DB transaction
+    pub(crate) fn insert(
+        conn: &mut mysql::Transaction<'_>,
+        currency: Currency,
+    ) -> TheResult<Wallet> {
+        let stmt = conn
+            .prep("INSERT INTO `wallets`(`currency`, `balance`) VALUES(?, 0);")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(stmt, (currency.code(),))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let wallet = Wallet {
+            id: Executor::last_insert_id(conn),
+            currency,
+            ..Default::default()
+        };
+        OutboxEvent::append(
+            conn,
+            &WalletEvent::new("created", wallet.id, wallet.currency, wallet.balance),
+        )?;
+
+        Ok(wallet)
+    }
+    /// ## Description
+    /// Applies a manual amount to the wallet balance in the received currency, recording it as an
+    /// adjustment along with its reason within the same DB transaction. Adjusting a currency the wallet
+    /// does not hold yet opens a balance in it
+    /// ### Returns
+    /// `None` when the balance would go negative, leaving it untouched
+    pub(crate) fn adjust_balance(
+        &mut self,
+        conn: &mut mysql::Transaction<'_>,
+        amount: Decimal,
+        currency: Currency,
+        reason: &str,
+    ) -> TheResult<Option<BalanceAdjustment>> {
+        if currency != self.currency {
+            let stmt = conn
+                .prep(
+                    "INSERT IGNORE INTO `wallet_balances`(`wallets_ID`, `currency`) VALUES(?, ?);",
+                )
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            conn.exec_drop(stmt, (self.id, currency.code()))
+                .map_err(|error| create_new_error!(error.to_string()))?;
+        }
+        if !self.affect_balance(conn, amount, currency)? {
+            return Ok(None);
+        }
 
-        conn.exec_drop(stmt, params)
+        let query = "INSERT INTO `balance_adjustments`(`wallets_ID`, `amount`, `currency`, `reason`) VALUES(?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        conn.exec_drop(stmt, (self.id, amount.to_string(), currency.code(), reason))
             .map_err(|error| create_new_error!(error.to_string()))?;
 
-        Ok(conn.affected_rows() > 0)
+        Ok(Some(BalanceAdjustment {
+            id: Executor::last_insert_id(conn),
+            wallets_id: self.id,
+            amount,
+            currency,
+            reason: reason.to_string(),
+            created_at: chrono::Local::now().naive_local(),
+        }))
+    }
+    /// ## Description
+    /// Freezes or unfreezes the wallet, recording the change in the outbox
+    pub(crate) fn update_frozen(&mut self, conn: &mut PooledConn, frozen: bool) -> TheResult<()> {
+        if self.id == 0 {
+            return Err(create_new_error!(
+                "Wallet cannot have an ID of zero when freezing it"
+            ));
+        }
+
+        let event_type = if frozen { "frozen" } else { "unfrozen" };
+        in_transaction(conn, |db_transaction| {
+            let stmt = db_transaction
+                .prep("UPDATE `wallets` SET `frozen` = ? WHERE ID = ?")
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            db_transaction
+                .exec_drop(stmt, (frozen, self.id))
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            OutboxEvent::append(
+                db_transaction,
+                &WalletEvent::new(event_type, self.id, self.currency, self.balance),
+            )
+        })?;
+        self.frozen = frozen;
+
+        Ok(())
+    }
+    pub(super) fn update_limits(
+        &mut self,
+        conn: &mut PooledConn,
+        limits: WalletLimits,
+    ) -> TheResult<()> {
+        if self.id == 0 {
+            return Err(create_new_error!(
+                "Wallet cannot have an ID of zero when updating its limits"
+            ));
+        }
+
+        self.limits = limits;
+        let query = "UPDATE `wallets` SET `max_single_amount` = ?, `max_daily_amount` = ?, `max_monthly_amount` = ?, `max_daily_count` = ? WHERE ID = ?";
+        let params = vec![
+            limits.max_single_amount.map(|amount| amount.to_string()),
+            limits.max_daily_amount.map(|amount| amount.to_string()),
+            limits.max_monthly_amount.map(|amount| amount.to_string()),
+            limits.max_daily_count.map(|count| count.to_string()),
+            Some(self.id.to_string()),
+        ];
+
+        //  Affected rows is not checked, MySQL reports zero when the limits did not change
+        in_transaction(conn, |db_transaction| {
+            let stmt = db_transaction
+                .prep(query)
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            db_transaction
+                .exec_drop(stmt, params)
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            OutboxEvent::append(
+                db_transaction,
+                &WalletEvent::new("limits_updated", self.id, self.currency, self.balance),
+            )
+        })
+    }
+}
+
+impl WalletRepository for MySqlStore {
+    fn select_all(&self) -> TheResult<Vec<Wallet>> {
+        Wallet::select_all(&mut self.get_conn()?)
+    }
+
+    fn select_by_id(&self, wallets_id: WalletsIdType) -> TheResult<Option<Wallet>> {
+        Wallet::select_by_id(&mut self.get_conn()?, wallets_id)
+    }
+
+    fn select_balances(&self, wallet: &Wallet) -> TheResult<Vec<WalletBalance>> {
+        let mut balances = vec![wallet.own_balance()];
+        balances.extend(wallet.select_held_balances(&mut self.get_conn()?)?);
+        Ok(balances)
+    }
+
+    fn update_limits(&self, wallet: &mut Wallet, limits: WalletLimits) -> TheResult<()> {
+        wallet.update_limits(&mut self.get_conn()?, limits)
     }
 }
 
-impl FromRow for Wallet {
-    fn from_row(row: mysql::Row) -> Self
-    where
-        Self: Sized,
-    {
-        Self {
+impl DecodeRow for Wallet {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
             id: row_to_data!(row, "ID", "wallets", WalletsIdType),
+            currency: decode::variant(row, "wallets", "currency", Currency::from_string)?,
             balance: row_to_data!(row, "balance", "wallets", Decimal),
-        }
+            limits: WalletLimits {
+                max_single_amount: row_to_data!(
+                    row,
+                    "max_single_amount",
+                    "wallets",
+                    Option<Decimal>
+                ),
+                max_daily_amount: row_to_data!(row, "max_daily_amount", "wallets", Option<Decimal>),
+                max_monthly_amount: row_to_data!(
+                    row,
+                    "max_monthly_amount",
+                    "wallets",
+                    Option<Decimal>
+                ),
+                max_daily_count: row_to_data!(row, "max_daily_count", "wallets", Option<u32>),
+            },
+            frozen: row_to_data!(row, "frozen", "wallets", bool),
+        })
     }
+}
+
+from_row_via_decode!(Wallet);
 
-    fn from_row_opt(_row: mysql::Row) -> Result<Self, mysql::FromRowError>
-    where
-        Self: Sized,
-    {
-        unimplemented!()
+impl DecodeRow for WalletBalance {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            currency: decode::variant(row, "wallet_balances", "currency", Currency::from_string)?,
+            balance: row_to_data!(row, "balance", "wallet_balances", Decimal),
+        })
     }
 }
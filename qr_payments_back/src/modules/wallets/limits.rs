@@ -0,0 +1,194 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    modules::{currencies::Currency, fx::RateTable},
+    validation::{self, FieldError, Sign, Validate},
+};
+
+/// Spending limits configured for a wallet. A `None` value means the limit is not enforced
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct WalletLimits {
+    pub max_single_amount: Option<Decimal>,
+    pub max_daily_amount: Option<Decimal>,
+    pub max_monthly_amount: Option<Decimal>,
+    pub max_daily_count: Option<u32>,
+}
+
+/// Outgoing amounts already committed by a wallet in a currency, aggregated from the `transactions` table.
+/// Amounts are expressed as positive values
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WalletOutflow {
+    pub currency: Currency,
+    pub daily_amount: Decimal,
+    pub monthly_amount: Decimal,
+    pub daily_count: u32,
+}
+
+/// ## Description
+/// Rates valuing amounts in other currencies in the wallet currency, the one its limits are expressed in.
+/// A currency is valued at its rate to the wallet currency, or at the inverse of the rate the other way
+#[derive(Debug, Default, Clone)]
+pub struct LimitValuation {
+    currency: Currency,
+    rates: Vec<(Currency, Decimal)>,
+}
+
+/// Limit breached by a transaction. Its Display value is the error code returned to the client
+#[derive(Debug, Clone, Copy, PartialEq, strum::Display)]
+pub enum LimitBreach {
+    #[strum(serialize = "SINGLE_AMOUNT_LIMIT_EXCEEDED")]
+    SingleAmount,
+    #[strum(serialize = "DAILY_AMOUNT_LIMIT_EXCEEDED")]
+    DailyAmount,
+    #[strum(serialize = "MONTHLY_AMOUNT_LIMIT_EXCEEDED")]
+    MonthlyAmount,
+    #[strum(serialize = "DAILY_COUNT_LIMIT_EXCEEDED")]
+    DailyCount,
+    /// The amount is in a currency with no rate to the wallet currency, so it cannot be checked against
+    /// the amount limits
+    #[strum(serialize = "LIMIT_RATE_UNAVAILABLE")]
+    Unrated,
+}
+
+impl Validate for WalletLimits {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let amounts = [
+            ("max_single_amount", self.max_single_amount),
+            ("max_daily_amount", self.max_daily_amount),
+            ("max_monthly_amount", self.max_monthly_amount),
+        ];
+        for (field, amount) in amounts {
+            if let Some(amount) = amount {
+                validation::amount(&mut errors, field, amount, Sign::NonNegative);
+            }
+        }
+        errors
+    }
+}
+
+impl WalletLimits {
+    /// ## Description
+    /// Checks a requested amount against the wallet limits, considering what the wallet already spent.
+    /// Credits (positive amounts) are not subject to spending limits
+    ///
+    /// ### Returns
+    /// The first limit breached, or None if the amount is allowed
+    pub fn evaluate(
+        &self,
+        requested_amount: Decimal,
+        outflow: &WalletOutflow,
+    ) -> Option<LimitBreach> {
+        if requested_amount >= Decimal::ZERO {
+            return None;
+        }
+        let amount = requested_amount.abs();
+
+        if self.max_single_amount.is_some_and(|max| amount > max) {
+            return Some(LimitBreach::SingleAmount);
+        }
+        if self
+            .max_daily_amount
+            .is_some_and(|max| outflow.daily_amount + amount > max)
+        {
+            return Some(LimitBreach::DailyAmount);
+        }
+        if self
+            .max_monthly_amount
+            .is_some_and(|max| outflow.monthly_amount + amount > max)
+        {
+            return Some(LimitBreach::MonthlyAmount);
+        }
+        if self
+            .max_daily_count
+            .is_some_and(|max| outflow.daily_count >= max)
+        {
+            return Some(LimitBreach::DailyCount);
+        }
+
+        None
+    }
+
+    /// Whether any limit is on the amounts, rather than on the number of transactions
+    pub fn limits_amounts(&self) -> bool {
+        self.max_single_amount.is_some()
+            || self.max_daily_amount.is_some()
+            || self.max_monthly_amount.is_some()
+    }
+}
+
+impl LimitValuation {
+    /// ## Description
+    /// Valuation in the received wallet currency out of the rates currently quoted
+    pub fn new(currency: Currency, rates: &RateTable) -> Self {
+        let mut valued = rates
+            .rates
+            .iter()
+            .filter(|rate| rate.to == currency && rate.from != currency)
+            .map(|rate| (rate.from, rate.rate))
+            .collect::<Vec<_>>();
+        for rate in &rates.rates {
+            if rate.from == currency
+                && rate.rate > Decimal::ZERO
+                && !valued.iter().any(|(from, _)| *from == rate.to)
+            {
+                valued.push((rate.to, Decimal::ONE / rate.rate));
+            }
+        }
+
+        Self {
+            currency,
+            rates: valued,
+        }
+    }
+
+    /// ## Description
+    /// Values an amount in the wallet currency, rounded to its minor unit
+    ///
+    /// ### Returns
+    /// None if there is no rate between the currencies
+    pub fn value(&self, amount: Decimal, currency: Currency) -> Option<Decimal> {
+        if currency == self.currency {
+            return Some(amount);
+        }
+        self.rates
+            .iter()
+            .find(|(from, _)| *from == currency)
+            .map(|(_, rate)| self.currency.round(amount * rate))
+    }
+
+    /// ## Description
+    /// Adds up what a wallet spent in every currency, valued in the wallet currency. Every transaction
+    /// counts, while the amounts in currencies without a rate are left out
+    pub fn outflow(&self, outflows: &[WalletOutflow]) -> WalletOutflow {
+        let mut total = WalletOutflow {
+            currency: self.currency,
+            ..WalletOutflow::default()
+        };
+        for outflow in outflows {
+            total.daily_count += outflow.daily_count;
+            if let (Some(daily_amount), Some(monthly_amount)) = (
+                self.value(outflow.daily_amount, outflow.currency),
+                self.value(outflow.monthly_amount, outflow.currency),
+            ) {
+                total.daily_amount += daily_amount;
+                total.monthly_amount += monthly_amount;
+            }
+        }
+        total
+    }
+}
+
+impl LimitBreach {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::SingleAmount => "Amount exceeds the wallet's maximum per transaction",
+            Self::DailyAmount => "Amount exceeds the wallet's daily spending limit",
+            Self::MonthlyAmount => "Amount exceeds the wallet's monthly spending limit",
+            Self::DailyCount => "Wallet reached its maximum number of transactions for today",
+            Self::Unrated => "There is no rate to check the amount against the wallet's limits",
+        }
+    }
+}
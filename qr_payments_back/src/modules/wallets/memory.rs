@@ -0,0 +1,44 @@
+use error_mapper::{create_new_error, TheResult};
+
+use crate::{database::memory::InMemoryStore, datatypes::WalletsIdType};
+
+use super::{limits::WalletLimits, repository::WalletRepository, Wallet, WalletBalance};
+
+impl WalletRepository for InMemoryStore {
+    fn select_all(&self) -> TheResult<Vec<Wallet>> {
+        Ok(self.lock()?.wallets.values().copied().collect())
+    }
+
+    fn select_by_id(&self, wallets_id: WalletsIdType) -> TheResult<Option<Wallet>> {
+        Ok(self.lock()?.wallets.get(&wallets_id).copied())
+    }
+
+    fn select_balances(&self, wallet: &Wallet) -> TheResult<Vec<WalletBalance>> {
+        let state = self.lock()?;
+        let held = state
+            .held_balances
+            .iter()
+            .filter(|((wallets_id, _), _)| *wallets_id == wallet.id)
+            .map(|((_, currency), balance)| WalletBalance {
+                currency: *currency,
+                balance: *balance,
+            });
+
+        Ok(std::iter::once(wallet.own_balance()).chain(held).collect())
+    }
+
+    fn update_limits(&self, wallet: &mut Wallet, limits: WalletLimits) -> TheResult<()> {
+        let mut state = self.lock()?;
+        let Some(stored) = state.wallets.get_mut(&wallet.id) else {
+            return Err(create_new_error!(format!(
+                "Wallet with ID: {} does not exist",
+                wallet.id
+            )));
+        };
+
+        stored.limits = limits;
+        *wallet = *stored;
+
+        Ok(())
+    }
+}
@@ -1,19 +1,69 @@
+use chrono::NaiveDateTime;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::datatypes::WalletsIdType;
+use crate::{
+    datatypes::{BalanceAdjustmentsIdType, WalletsIdType},
+    modules::currencies::Currency,
+};
 
 mod db;
+pub mod limits;
+#[cfg(test)]
+mod memory;
+pub mod reconciliation;
+pub mod repository;
 pub mod services;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(test)]
+mod tests;
 
-#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+use limits::WalletLimits;
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, ToSchema)]
 pub struct Wallet {
     pub id: WalletsIdType,
+    /// Currency of `balance` and of the limits
+    pub currency: Currency,
     pub balance: Decimal,
+    pub limits: WalletLimits,
+    /// Frozen wallets accept no new transactions, the ones already started can still be closed
+    pub frozen: bool,
+}
+
+/// Balance held by a wallet in one currency. The wallet currency is held in the wallet itself, the other
+/// currencies in `wallet_balances`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct WalletBalance {
+    pub currency: Currency,
+    pub balance: Decimal,
+}
+
+/// Manual change to a wallet balance, kept along with its reason for auditing and reconciliation
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceAdjustment {
+    pub id: BalanceAdjustmentsIdType,
+    pub wallets_id: WalletsIdType,
+    pub amount: Decimal,
+    pub currency: Currency,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
 }
 
 impl Wallet {
-    pub(in crate::modules) fn validate_balance(&self, requested_amount: Decimal) -> bool {
+    /// Balance of the wallet in its own currency
+    pub fn own_balance(&self) -> WalletBalance {
+        WalletBalance {
+            currency: self.currency,
+            balance: self.balance,
+        }
+    }
+}
+
+impl WalletBalance {
+    pub(crate) fn validate(&self, requested_amount: Decimal) -> bool {
         //  If requested amount is positive it's a credit, authorize without further validations
         match requested_amount.cmp(&Decimal::ZERO) {
             std::cmp::Ordering::Equal | std::cmp::Ordering::Greater => return true,
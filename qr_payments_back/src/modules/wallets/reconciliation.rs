@@ -0,0 +1,80 @@
+use error_mapper::{create_new_error, TheResult};
+use mysql::prelude::Queryable;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    database::decode::{self, DecodeRow, RowError},
+    datatypes::WalletsIdType,
+    modules::currencies::Currency,
+    row_to_data,
+};
+
+/// Wallet balance in one currency compared against what its adjustments, transactions and settlement payouts
+/// in that currency account for
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconciliationEntry {
+    pub wallets_id: WalletsIdType,
+    pub currency: Currency,
+    pub balance: Decimal,
+    /// Sum of the manual adjustments, opening balance included
+    pub adjustments: Decimal,
+    /// Sum of the transactions holding their amount, Initialized and Confirmed
+    pub transactions: Decimal,
+    /// Sum of the payer fees charged on those transactions, debited with their amounts
+    pub fees: Decimal,
+    /// Sum of the nets of the paid settlement batches, when the wallet is the settlement wallet of a merchant
+    pub payouts: Decimal,
+    pub initialized_count: u64,
+    pub pending_review_count: u64,
+}
+
+impl ReconciliationEntry {
+    pub fn expected_balance(&self) -> Decimal {
+        self.adjustments + self.transactions - self.fees + self.payouts
+    }
+
+    /// Positive when the wallet holds more than its history explains
+    pub fn difference(&self) -> Decimal {
+        self.balance - self.expected_balance()
+    }
+}
+
+/// ## Description
+/// Builds the reconciliation entry of every balance of every wallet, the one in the wallet currency and the
+/// ones held in other currencies
+pub(crate) fn select_report(conn: &mut impl Queryable) -> TheResult<Vec<ReconciliationEntry>> {
+    let query = "SELECT `held`.`wallets_ID`, `held`.`currency`, `held`.`balance`, \
+            COALESCE((SELECT SUM(`amount`) FROM `balance_adjustments` WHERE `wallets_ID` = `held`.`wallets_ID` AND `currency` = `held`.`currency`), 0) AS `adjustments`, \
+            COALESCE((SELECT SUM(`amount`) FROM `transactions` WHERE `wallets_ID` = `held`.`wallets_ID` AND `currency` = `held`.`currency` AND `status` IN ('Initialized', 'Confirmed')), 0) AS `transactions`, \
+            COALESCE((SELECT SUM(`transaction_fees`.`amount`) FROM `transaction_fees` JOIN `transactions` ON `transactions`.`ID` = `transaction_fees`.`transactions_ID` WHERE `transactions`.`wallets_ID` = `held`.`wallets_ID` AND `transactions`.`currency` = `held`.`currency` AND `transactions`.`status` IN ('Initialized', 'Confirmed') AND `transaction_fees`.`party` = 'Payer'), 0) AS `fees`, \
+            COALESCE((SELECT SUM(`settlement_batches`.`net`) FROM `settlement_batches` JOIN `merchants` ON `merchants`.`ID` = `settlement_batches`.`merchants_ID` WHERE `merchants`.`wallets_ID` = `held`.`wallets_ID` AND `settlement_batches`.`currency` = `held`.`currency` AND `settlement_batches`.`status` = 'Paid'), 0) AS `payouts`, \
+            (SELECT COUNT(*) FROM `transactions` WHERE `wallets_ID` = `held`.`wallets_ID` AND `currency` = `held`.`currency` AND `status` = 'Initialized') AS `initialized_count`, \
+            (SELECT COUNT(*) FROM `transactions` WHERE `wallets_ID` = `held`.`wallets_ID` AND `currency` = `held`.`currency` AND `status` = 'PendingReview') AS `pending_review_count` \
+        FROM ( \
+            SELECT `ID` AS `wallets_ID`, `currency`, `balance` FROM `wallets` \
+            UNION ALL SELECT `wallets_ID`, `currency`, `balance` FROM `wallet_balances` \
+        ) AS `held` \
+        ORDER BY `held`.`wallets_ID`, `held`.`currency`;";
+
+    let rows = conn
+        .query::<mysql::Row, _>(query)
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+}
+
+impl DecodeRow for ReconciliationEntry {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            wallets_id: row_to_data!(row, "wallets_ID", "wallets", WalletsIdType),
+            currency: decode::variant(row, "wallets", "currency", Currency::from_string)?,
+            balance: row_to_data!(row, "balance", "wallets", Decimal),
+            adjustments: row_to_data!(row, "adjustments", "balance_adjustments", Decimal),
+            transactions: row_to_data!(row, "transactions", "transactions", Decimal),
+            fees: row_to_data!(row, "fees", "transaction_fees", Decimal),
+            payouts: row_to_data!(row, "payouts", "settlement_batches", Decimal),
+            initialized_count: row_to_data!(row, "initialized_count", "transactions", u64),
+            pending_review_count: row_to_data!(row, "pending_review_count", "transactions", u64),
+        })
+    }
+}
@@ -0,0 +1,15 @@
+use error_mapper::TheResult;
+
+use crate::datatypes::WalletsIdType;
+
+use super::{limits::WalletLimits, Wallet, WalletBalance};
+
+/// Persistence of the wallets, implemented by every storage backend
+pub trait WalletRepository: Send + Sync {
+    fn select_all(&self) -> TheResult<Vec<Wallet>>;
+    fn select_by_id(&self, wallets_id: WalletsIdType) -> TheResult<Option<Wallet>>;
+    /// Selects every balance the wallet holds, the one in its own currency first and the rest by currency
+    fn select_balances(&self, wallet: &Wallet) -> TheResult<Vec<WalletBalance>>;
+    /// Updates the wallet limits, recording the change in the outbox
+    fn update_limits(&self, wallet: &mut Wallet, limits: WalletLimits) -> TheResult<()>;
+}
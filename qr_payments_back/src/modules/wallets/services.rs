@@ -1,58 +1,304 @@
-use actix_web::{get, web, HttpResponse};
-use the_logger::{log_error, log_info, TheLogger};
+use actix_web::{get, put, web, HttpResponse};
+use the_logger::TheLogger;
+use utoipa::OpenApi;
 
-use crate::{datatypes::WalletsIdType, get_conn_or_internal_error, modules::wallets::Wallet};
+use crate::{
+    api::{
+        error::{ApiError, ErrorBody},
+        validation::ValidJson,
+    },
+    database::blocking,
+    datatypes::WalletsIdType,
+    log_error, log_info, logging,
+    modules::{
+        transactions::{
+            export::{self, ExportQuery, TransactionFilter, TransactionOwner},
+            repository::TransactionRepository,
+        },
+        wallets::{limits::WalletLimits, repository::WalletRepository, Wallet, WalletBalance},
+    },
+    validation::Validate,
+};
 
 pub fn wallets_services(cfg: &mut web::ServiceConfig) {
-    cfg.service(get_wallets).service(get_wallet);
+    cfg.service(get_wallets)
+        .service(get_wallet)
+        .service(get_wallet_balances)
+        .service(export_wallet_transactions)
+        .service(put_wallet_limits);
 }
 
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_wallets,
+        get_wallet,
+        get_wallet_balances,
+        export_wallet_transactions,
+        put_wallet_limits
+    ),
+    tags((name = "wallets"))
+)]
+pub struct WalletsApi;
+
 /// /v1/wallets
+#[utoipa::path(
+    tag = "wallets",
+    summary = "Lists every wallet",
+    responses(
+        (status = 200, description = "Wallets", body = Vec<Wallet>),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
 #[get("")]
-async fn get_wallets() -> HttpResponse {
+async fn get_wallets(
+    repository: web::Data<dyn WalletRepository>,
+) -> Result<HttpResponse, ApiError> {
     let logger = TheLogger::instance();
 
     log_info!(logger, "Selecting wallets...");
 
-    let mut conn = get_conn_or_internal_error!(logger);
-
-    let wallets = match Wallet::select_all(&mut conn) {
+    let wallets = match blocking(&repository, |repository| repository.select_all()).await {
         Ok(wallets) => wallets,
         Err(error) => {
             log_error!(logger, "Could not get wallets: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            return Err(ApiError::internal());
         }
     };
 
     log_info!(logger, "Wallets selected successfully!");
 
-    HttpResponse::Ok().json(wallets)
+    Ok(HttpResponse::Ok().json(wallets))
 }
 
-/// /v1/wallets
+/// /v1/wallets/{wallets_id}
+#[utoipa::path(
+    tag = "wallets",
+    summary = "Gets a wallet",
+    params(("wallets_id" = WalletsIdType, Path)),
+    responses(
+        (status = 200, description = "Wallet", body = Wallet),
+        (status = 404, description = "Wallet not found", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
 #[get("/{wallets_id}")]
-async fn get_wallet(path: web::Path<WalletsIdType>) -> HttpResponse {
+async fn get_wallet(
+    path: web::Path<WalletsIdType>,
+    repository: web::Data<dyn WalletRepository>,
+) -> Result<HttpResponse, ApiError> {
     let logger = TheLogger::instance();
     let wallets_id = path.into_inner();
+    logging::set_wallets_id(wallets_id);
 
     log_info!(logger, "Selecting wallet with ID: {}", wallets_id);
 
-    let mut conn = get_conn_or_internal_error!(logger);
-
-    let wallet = match Wallet::select_by_id(&mut conn, wallets_id) {
+    let wallet = match blocking(&repository, move |repository| {
+        repository.select_by_id(wallets_id)
+    })
+    .await
+    {
         Ok(wallet) => wallet,
         Err(error) => {
             log_error!(logger, "Could not get wallet: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            return Err(ApiError::internal());
         }
     };
 
     if let Some(wallet) = wallet {
         log_info!(logger, "Wallet found. Sending Ok response");
-        return HttpResponse::Ok().json(wallet);
+        return Ok(HttpResponse::Ok().json(wallet));
     }
 
-    let msg = format!("Wallet with ID: {} was not found", wallets_id);
-    log_info!(logger, "{}", msg);
-    HttpResponse::NotFound().json(msg)
+    let error = ApiError::wallet_not_found(wallets_id);
+    log_info!(logger, "{}", error);
+    Err(error)
+}
+
+/// /v1/wallets/{wallets_id}/balances
+#[utoipa::path(
+    tag = "wallets",
+    summary = "Lists every balance of a wallet, its own currency first",
+    params(("wallets_id" = WalletsIdType, Path)),
+    responses(
+        (status = 200, description = "Balances", body = Vec<WalletBalance>),
+        (status = 404, description = "Wallet not found", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{wallets_id}/balances")]
+async fn get_wallet_balances(
+    path: web::Path<WalletsIdType>,
+    repository: web::Data<dyn WalletRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let wallets_id = path.into_inner();
+    logging::set_wallets_id(wallets_id);
+
+    log_info!(
+        logger,
+        "Selecting balances of wallet with ID: {}",
+        wallets_id
+    );
+
+    let balances = match blocking(&repository, move |repository| {
+        let Some(wallet) = repository.select_by_id(wallets_id)? else {
+            return Ok(None);
+        };
+        repository.select_balances(&wallet).map(Some)
+    })
+    .await
+    {
+        Ok(Some(balances)) => balances,
+        Ok(None) => {
+            let error = ApiError::wallet_not_found(wallets_id);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+        Err(error) => {
+            log_error!(logger, "Could not get wallet balances: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    log_info!(logger, "Wallet balances selected successfully!");
+    Ok(HttpResponse::Ok().json(balances))
+}
+
+/// /v1/wallets/{wallets_id}/transactions/export
+#[utoipa::path(
+    tag = "wallets",
+    summary = "Streams the transactions of a wallet created between two days",
+    params(("wallets_id" = WalletsIdType, Path), ExportQuery),
+    responses(
+        (
+            status = 200,
+            description = "Transactions in ID order, downloaded as an attachment. CSV starts with a header \
+                row, NDJSON has one object per line",
+            content((String = "text/csv"), (String = "application/x-ndjson"))
+        ),
+        (status = 404, description = "Wallet not found", body = ErrorBody),
+        (status = 422, description = "Invalid range of days", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/{wallets_id}/transactions/export")]
+async fn export_wallet_transactions(
+    path: web::Path<WalletsIdType>,
+    query: web::Query<ExportQuery>,
+    repository: web::Data<dyn WalletRepository>,
+    transactions: web::Data<dyn TransactionRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let wallets_id = path.into_inner();
+    let query = query.into_inner();
+    logging::set_wallets_id(wallets_id);
+
+    let errors = query.validate();
+    if !errors.is_empty() {
+        let error = ApiError::invalid_fields(errors);
+        log_info!(logger, "{}", error);
+        return Err(error);
+    }
+
+    match blocking(&repository, move |repository| {
+        repository.select_by_id(wallets_id)
+    })
+    .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let error = ApiError::wallet_not_found(wallets_id);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+        Err(error) => {
+            log_error!(logger, "Could not get wallet: {}", error);
+            return Err(ApiError::internal());
+        }
+    }
+
+    let (from, to) = match query.range(chrono::Local::now().date_naive()) {
+        Ok(range) => range,
+        Err(error) => {
+            let error = ApiError::invalid_fields(vec![error]);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+    };
+    log_info!(
+        logger,
+        "Exporting transactions of wallet with ID: {} from {} to {}",
+        wallets_id,
+        from,
+        to
+    );
+
+    Ok(export::export_response(
+        transactions,
+        TransactionFilter::days(TransactionOwner::Wallet(wallets_id), from, to),
+        query.format,
+        &format!("wallet-{}-transactions-{}-{}", wallets_id, from, to),
+    ))
+}
+
+/// /v1/wallets/{wallets_id}/limits
+#[utoipa::path(
+    tag = "wallets",
+    summary = "Replaces the spending limits of a wallet",
+    params(("wallets_id" = WalletsIdType, Path)),
+    request_body = WalletLimits,
+    responses(
+        (status = 200, description = "Wallet with its new limits", body = Wallet),
+        (status = 404, description = "Wallet not found", body = ErrorBody),
+        (status = 422, description = "Invalid limits", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[put("/{wallets_id}/limits")]
+async fn put_wallet_limits(
+    path: web::Path<WalletsIdType>,
+    body: ValidJson<WalletLimits>,
+    repository: web::Data<dyn WalletRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let wallets_id = path.into_inner();
+    logging::set_wallets_id(wallets_id);
+    let limits = body.into_inner();
+
+    log_info!(logger, "Updating limits for wallet with ID: {}", wallets_id);
+
+    let mut wallet = match blocking(&repository, move |repository| {
+        repository.select_by_id(wallets_id)
+    })
+    .await
+    {
+        Ok(Some(wallet)) => wallet,
+        Ok(None) => {
+            let error = ApiError::wallet_not_found(wallets_id);
+            log_info!(logger, "{}", error);
+            return Err(error);
+        }
+        Err(error) => {
+            log_error!(logger, "Could not get wallet: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    let wallet = match blocking(&repository, move |repository| {
+        repository
+            .update_limits(&mut wallet, limits)
+            .map(|()| wallet)
+    })
+    .await
+    {
+        Ok(wallet) => wallet,
+        Err(error) => {
+            log_error!(logger, "Could not update wallet limits: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    log_info!(logger, "Wallet limits updated successfully");
+    Ok(HttpResponse::Ok().json(wallet))
 }
@@ -0,0 +1,311 @@
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+
+use crate::{
+    database::sqlite::{currency_column, decimal_column, optional_decimal_column, SqliteStore},
+    datatypes::WalletsIdType,
+    modules::{
+        currencies::Currency,
+        outbox::{sqlite::append, WalletEvent},
+    },
+};
+
+use super::{limits::WalletLimits, repository::WalletRepository, Wallet, WalletBalance};
+
+impl WalletRepository for SqliteStore {
+    fn select_all(&self) -> TheResult<Vec<Wallet>> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT * FROM `wallets`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let wallets = stmt
+            .query_map([], from_row)
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+            .collect();
+        wallets
+    }
+
+    fn select_by_id(&self, wallets_id: WalletsIdType) -> TheResult<Option<Wallet>> {
+        select_by_id(&*self.lock()?, wallets_id)
+    }
+
+    fn select_balances(&self, wallet: &Wallet) -> TheResult<Vec<WalletBalance>> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT * FROM `wallet_balances` WHERE `wallets_ID` = ? ORDER BY `currency`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let mut balances = vec![wallet.own_balance()];
+        for balance in stmt
+            .query_map(params![wallet.id], |row| {
+                Ok(WalletBalance {
+                    currency: currency_column(row, "currency")?,
+                    balance: decimal_column(row, "balance")?,
+                })
+            })
+            .map_err(|error| create_new_error!(error.to_string()))?
+        {
+            balances.push(balance.map_err(|error| create_new_error!(error.to_string()))?);
+        }
+
+        Ok(balances)
+    }
+
+    fn update_limits(&self, wallet: &mut Wallet, limits: WalletLimits) -> TheResult<()> {
+        if wallet.id == 0 {
+            return Err(create_new_error!(
+                "Wallet cannot have an ID of zero when updating its limits"
+            ));
+        }
+
+        let query = "UPDATE `wallets` SET `max_single_amount` = ?, `max_daily_amount` = ?, `max_monthly_amount` = ?, `max_daily_count` = ? WHERE `ID` = ?;";
+        self.in_transaction(|db_transaction| {
+            db_transaction
+                .execute(
+                    query,
+                    params![
+                        limits.max_single_amount.map(|amount| amount.to_string()),
+                        limits.max_daily_amount.map(|amount| amount.to_string()),
+                        limits.max_monthly_amount.map(|amount| amount.to_string()),
+                        limits.max_daily_count,
+                        wallet.id,
+                    ],
+                )
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            append(
+                db_transaction,
+                &WalletEvent::new("limits_updated", wallet.id, wallet.currency, wallet.balance),
+            )
+        })?;
+        wallet.limits = limits;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl SqliteStore {
+    /// ## Description
+    /// Replaces the seeded wallets with the received ones
+    pub(crate) fn replace_wallets(&self, wallets: &[Wallet]) -> TheResult<()> {
+        self.in_transaction(|db_transaction| {
+            db_transaction
+                .execute("DELETE FROM `wallets`;", [])
+                .map_err(|error| create_new_error!(error.to_string()))?;
+            for wallet in wallets {
+                db_transaction
+                    .execute(
+                        "INSERT INTO `wallets`(`ID`, `currency`, `balance`) VALUES(?, ?, ?);",
+                        params![
+                            wallet.id,
+                            wallet.currency.code(),
+                            wallet.balance.to_string()
+                        ],
+                    )
+                    .map_err(|error| create_new_error!(error.to_string()))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// ## Description
+    /// Opens balances in other currencies than their wallet's own
+    pub(crate) fn insert_held_balances(
+        &self,
+        balances: &[(WalletsIdType, WalletBalance)],
+    ) -> TheResult<()> {
+        self.in_transaction(|db_transaction| {
+            for (wallets_id, balance) in balances {
+                db_transaction
+                    .execute(
+                        "INSERT INTO `wallet_balances`(`wallets_ID`, `currency`, `balance`) VALUES(?, ?, ?);",
+                        params![
+                            wallets_id,
+                            balance.currency.code(),
+                            balance.balance.to_string()
+                        ],
+                    )
+                    .map_err(|error| create_new_error!(error.to_string()))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+pub(in crate::modules) fn select_by_id(
+    conn: &Connection,
+    wallets_id: WalletsIdType,
+) -> TheResult<Option<Wallet>> {
+    conn.query_row(
+        "SELECT * FROM `wallets` WHERE `ID` = ?;",
+        params![wallets_id],
+        from_row,
+    )
+    .optional()
+    .map_err(|error| create_new_error!(error.to_string()))
+}
+
+/// ## Description
+/// Inserts an empty wallet in the received currency, recording its creation in the outbox within the same
+/// SQLite transaction
+pub(in crate::modules) fn insert(conn: &Connection, currency: Currency) -> TheResult<Wallet> {
+    conn.execute(
+        "INSERT INTO `wallets`(`currency`, `balance`) VALUES(?, '0');",
+        params![currency.code()],
+    )
+    .map_err(|error| create_new_error!(error.to_string()))?;
+    let wallet = Wallet {
+        id: conn.last_insert_rowid() as WalletsIdType,
+        currency,
+        ..Default::default()
+    };
+    append(
+        conn,
+        &WalletEvent::new("created", wallet.id, wallet.currency, wallet.balance),
+    )?;
+
+    Ok(wallet)
+}
+
+/// ## Description
+/// Applies an amount to the wallet balance in the received currency, recording the change in the outbox
+/// within the same SQLite transaction. Decimals are stored as TEXT, so the new balance is computed here
+/// from the balance read in the transaction rather than from the received wallet, and is only written if
+/// it does not go below zero and the stored balance is still the one read
+///
+/// ### Returns
+/// False if the wallet does not exist, does not hold a balance in the currency, or the balance would go
+/// below zero
+pub(in crate::modules) fn affect_balance(
+    conn: &Connection,
+    wallet: &mut Wallet,
+    requested_amount: Decimal,
+    currency: Currency,
+) -> TheResult<bool> {
+    if wallet.id == 0 {
+        return Err(create_new_error!(
+            "Wallet cannot have an ID of zero when affecting its balance"
+        ));
+    }
+    if currency != wallet.currency {
+        return affect_held_balance(conn, wallet.id, requested_amount, currency);
+    }
+
+    let Some(balance) = conn
+        .query_row(
+            "SELECT `balance` FROM `wallets` WHERE `ID` = ?;",
+            params![wallet.id],
+            |row| decimal_column(row, "balance"),
+        )
+        .optional()
+        .map_err(|error| create_new_error!(error.to_string()))?
+    else {
+        return Ok(false);
+    };
+
+    let Some(balance) = guarded_balance(balance, requested_amount) else {
+        return Ok(false);
+    };
+    let updated = conn
+        .execute(
+            "UPDATE `wallets` SET `balance` = ? WHERE `ID` = ? AND `balance` = ?;",
+            params![balance.new.to_string(), wallet.id, balance.old.to_string()],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    let balance = balance.new;
+
+    wallet.balance = balance;
+    append(
+        conn,
+        &WalletEvent::new(
+            "balance_changed",
+            wallet.id,
+            wallet.currency,
+            wallet.balance,
+        ),
+    )?;
+
+    Ok(true)
+}
+
+/// ## Description
+/// Applies an amount to the balance held in a currency other than the wallet's own, with the same guards
+/// as the wallet's own balance
+fn affect_held_balance(
+    conn: &Connection,
+    wallets_id: WalletsIdType,
+    requested_amount: Decimal,
+    currency: Currency,
+) -> TheResult<bool> {
+    let Some(balance) = conn
+        .query_row(
+            "SELECT `balance` FROM `wallet_balances` WHERE `wallets_ID` = ? AND `currency` = ?;",
+            params![wallets_id, currency.code()],
+            |row| decimal_column(row, "balance"),
+        )
+        .optional()
+        .map_err(|error| create_new_error!(error.to_string()))?
+    else {
+        return Ok(false);
+    };
+
+    let Some(balance) = guarded_balance(balance, requested_amount) else {
+        return Ok(false);
+    };
+    let updated = conn
+        .execute(
+            "UPDATE `wallet_balances` SET `balance` = ? \
+            WHERE `wallets_ID` = ? AND `currency` = ? AND `balance` = ?;",
+            params![
+                balance.new.to_string(),
+                wallets_id,
+                currency.code(),
+                balance.old.to_string()
+            ],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    let balance = balance.new;
+    append(
+        conn,
+        &WalletEvent::new("balance_changed", wallets_id, currency, balance),
+    )?;
+
+    Ok(true)
+}
+
+/// Balance read from a wallet and the one to write in its place
+struct BalanceChange {
+    old: Decimal,
+    new: Decimal,
+}
+
+/// ## Description
+/// Balance resulting from applying an amount to the one read, which cannot go below zero
+fn guarded_balance(balance: Decimal, requested_amount: Decimal) -> Option<BalanceChange> {
+    let new = balance + requested_amount;
+    (new >= Decimal::ZERO).then_some(BalanceChange { old: balance, new })
+}
+
+fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Wallet> {
+    Ok(Wallet {
+        id: row.get("ID")?,
+        currency: currency_column(row, "currency")?,
+        balance: decimal_column(row, "balance")?,
+        limits: WalletLimits {
+            max_single_amount: optional_decimal_column(row, "max_single_amount")?,
+            max_daily_amount: optional_decimal_column(row, "max_daily_amount")?,
+            max_monthly_amount: optional_decimal_column(row, "max_monthly_amount")?,
+            max_daily_count: row.get("max_daily_count")?,
+        },
+        frozen: row.get("frozen")?,
+    })
+}
@@ -0,0 +1,236 @@
+use rust_decimal::Decimal;
+
+use super::{
+    limits::{LimitBreach, LimitValuation, WalletLimits, WalletOutflow},
+    reconciliation::ReconciliationEntry,
+};
+use crate::modules::{
+    currencies::Currency,
+    fx::{Rate, RateTable},
+};
+
+fn decimal(value: &str) -> Decimal {
+    value.parse().expect("decimal should parse")
+}
+
+fn outflow(daily_amount: &str, monthly_amount: &str, daily_count: u32) -> WalletOutflow {
+    WalletOutflow {
+        currency: Currency::default(),
+        daily_amount: decimal(daily_amount),
+        monthly_amount: decimal(monthly_amount),
+        daily_count,
+    }
+}
+
+#[test]
+fn unset_limits_allow_any_amount() {
+    let limits = WalletLimits::default();
+
+    assert_eq!(
+        limits.evaluate(decimal("-1000000"), &outflow("5000", "90000", 500)),
+        None
+    );
+}
+
+#[test]
+fn credits_are_not_subject_to_limits() {
+    let limits = WalletLimits {
+        max_single_amount: Some(decimal("10")),
+        max_daily_count: Some(0),
+        ..Default::default()
+    };
+
+    assert_eq!(limits.evaluate(decimal("500"), &outflow("0", "0", 0)), None);
+    assert_eq!(limits.evaluate(Decimal::ZERO, &outflow("0", "0", 0)), None);
+}
+
+#[test]
+fn amount_limits_are_inclusive_of_their_maximum() {
+    let limits = WalletLimits {
+        max_single_amount: Some(decimal("100")),
+        max_daily_amount: Some(decimal("250")),
+        max_monthly_amount: Some(decimal("1000")),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        limits.evaluate(decimal("-100"), &outflow("0", "0", 0)),
+        None
+    );
+    assert_eq!(
+        limits.evaluate(decimal("-100.01"), &outflow("0", "0", 0)),
+        Some(LimitBreach::SingleAmount)
+    );
+
+    assert_eq!(
+        limits.evaluate(decimal("-50"), &outflow("200", "200", 2)),
+        None
+    );
+    assert_eq!(
+        limits.evaluate(decimal("-50.01"), &outflow("200", "200", 2)),
+        Some(LimitBreach::DailyAmount)
+    );
+
+    assert_eq!(
+        limits.evaluate(decimal("-50"), &outflow("0", "950", 0)),
+        None
+    );
+    assert_eq!(
+        limits.evaluate(decimal("-50.01"), &outflow("0", "950", 0)),
+        Some(LimitBreach::MonthlyAmount)
+    );
+}
+
+#[test]
+fn daily_count_counts_the_payments_already_made() {
+    let limits = WalletLimits {
+        max_daily_count: Some(3),
+        ..Default::default()
+    };
+
+    assert_eq!(limits.evaluate(decimal("-1"), &outflow("2", "2", 2)), None);
+    assert_eq!(
+        limits.evaluate(decimal("-1"), &outflow("3", "3", 3)),
+        Some(LimitBreach::DailyCount)
+    );
+}
+
+#[test]
+fn first_limit_breached_is_reported() {
+    let limits = WalletLimits {
+        max_single_amount: Some(decimal("10")),
+        max_daily_amount: Some(decimal("10")),
+        max_monthly_amount: Some(decimal("10")),
+        max_daily_count: Some(1),
+    };
+
+    assert_eq!(
+        limits.evaluate(decimal("-20"), &outflow("10", "10", 1)),
+        Some(LimitBreach::SingleAmount)
+    );
+    assert_eq!(
+        limits.evaluate(decimal("-5"), &outflow("10", "10", 1)),
+        Some(LimitBreach::DailyAmount)
+    );
+    assert_eq!(
+        limits.evaluate(decimal("-5"), &outflow("0", "10", 1)),
+        Some(LimitBreach::MonthlyAmount)
+    );
+    assert_eq!(
+        limits.evaluate(decimal("-5"), &outflow("0", "0", 1)),
+        Some(LimitBreach::DailyCount)
+    );
+}
+
+fn currency(code: &str) -> Currency {
+    Currency::from_code(code).expect("currency should be supported")
+}
+
+#[test]
+fn valuation_values_other_currencies_at_the_direct_or_inverse_rate() {
+    let rate = |from: &str, to: &str, rate: &str| Rate {
+        from: currency(from),
+        to: currency(to),
+        rate: decimal(rate),
+    };
+    let rates = RateTable {
+        rates: vec![rate("EUR", "USD", "1.1"), rate("USD", "MXN", "20")],
+        updated_at: None,
+    };
+    let valuation = LimitValuation::new(currency("USD"), &rates);
+
+    assert_eq!(
+        valuation.value(decimal("-10"), currency("USD")),
+        Some(decimal("-10"))
+    );
+    assert_eq!(
+        valuation.value(decimal("-10"), currency("EUR")),
+        Some(decimal("-11.00"))
+    );
+    assert_eq!(
+        valuation.value(decimal("-30"), currency("MXN")),
+        Some(decimal("-1.50"))
+    );
+    assert_eq!(valuation.value(decimal("-10"), currency("GBP")), None);
+}
+
+#[test]
+fn valuation_counts_every_currency_and_values_the_rated_ones() {
+    let rates = RateTable {
+        rates: vec![Rate {
+            from: currency("EUR"),
+            to: currency("USD"),
+            rate: decimal("2"),
+        }],
+        updated_at: None,
+    };
+    let valuation = LimitValuation::new(currency("USD"), &rates);
+    let in_currency = |code: &str, outflow: WalletOutflow| WalletOutflow {
+        currency: currency(code),
+        ..outflow
+    };
+
+    let total = valuation.outflow(&[
+        in_currency("USD", outflow("10", "100", 1)),
+        in_currency("EUR", outflow("5", "50", 2)),
+        in_currency("GBP", outflow("1000", "1000", 3)),
+    ]);
+    assert_eq!(total.currency, currency("USD"));
+    assert_eq!(total.daily_amount, decimal("20"));
+    assert_eq!(total.monthly_amount, decimal("200"));
+    assert_eq!(total.daily_count, 6);
+}
+
+fn reconciliation_entry(
+    balance: &str,
+    adjustments: &str,
+    transactions: &str,
+    fees: &str,
+    payouts: &str,
+) -> ReconciliationEntry {
+    ReconciliationEntry {
+        wallets_id: 1,
+        currency: Currency::USD,
+        balance: decimal(balance),
+        adjustments: decimal(adjustments),
+        transactions: decimal(transactions),
+        fees: decimal(fees),
+        payouts: decimal(payouts),
+        initialized_count: 0,
+        pending_review_count: 0,
+    }
+}
+
+#[test]
+fn expected_balance_adds_adjustments_and_transactions_net_of_payer_fees() {
+    //  Opening balance of 1000, 300 paid out and 2.50 charged on it
+    let entry = reconciliation_entry("697.50", "1000", "-300", "2.50", "0");
+
+    assert_eq!(entry.expected_balance(), decimal("697.50"));
+    assert_eq!(entry.difference(), Decimal::ZERO);
+}
+
+#[test]
+fn difference_is_positive_when_the_balance_exceeds_its_history() {
+    assert_eq!(
+        reconciliation_entry("710", "1000", "-300", "0", "0").difference(),
+        decimal("10")
+    );
+    assert_eq!(
+        reconciliation_entry("690", "1000", "-300", "0", "0").difference(),
+        decimal("-10")
+    );
+}
+
+#[test]
+fn expected_balance_of_a_settlement_wallet_adds_its_paid_batches() {
+    //  Settlement wallet credited 97 by a paid batch, which 10 were then spent from
+    let entry = reconciliation_entry("87", "0", "-10", "0", "97");
+
+    assert_eq!(entry.expected_balance(), decimal("87"));
+    assert_eq!(entry.difference(), Decimal::ZERO);
+    assert_eq!(
+        reconciliation_entry("87", "0", "-10", "0", "0").difference(),
+        decimal("97")
+    );
+}
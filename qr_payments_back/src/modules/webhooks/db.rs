@@ -0,0 +1,308 @@
+use chrono::NaiveDateTime;
+use error_mapper::{create_new_error, TheResult};
+use mysql::{prelude::Queryable, PooledConn};
+
+use crate::{
+    database::{
+        decode::{self, DecodeRow, RowError},
+        Executor, MySqlStore,
+    },
+    datatypes::{
+        MerchantsIdType, TransactionsIdType, WebhookDeliveriesIdType, WebhookSubscriptionsIdType,
+    },
+    from_row_via_decode,
+    modules::outbox::TransactionEvent,
+    row_to_data,
+};
+
+use super::{repository::WebhookRepository, DeliveryStatus, WebhookDelivery, WebhookSubscription};
+
+impl WebhookRepository for MySqlStore {
+    fn select_subscriptions(&self) -> TheResult<Vec<WebhookSubscription>> {
+        WebhookSubscription::select_all(&mut self.get_conn()?)
+    }
+
+    fn select_subscription_by_id(
+        &self,
+        subscriptions_id: WebhookSubscriptionsIdType,
+    ) -> TheResult<Option<WebhookSubscription>> {
+        WebhookSubscription::select_by_id(&mut self.get_conn()?, subscriptions_id)
+    }
+
+    fn insert_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<()> {
+        subscription.insert(&mut self.get_conn()?)
+    }
+
+    fn deactivate_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<bool> {
+        subscription.deactivate(&mut self.get_conn()?)
+    }
+
+    fn select_deliveries(
+        &self,
+        status: Option<DeliveryStatus>,
+        subscriptions_id: Option<WebhookSubscriptionsIdType>,
+        limit: u32,
+    ) -> TheResult<Vec<WebhookDelivery>> {
+        WebhookDelivery::select_filtered(&mut self.get_conn()?, status, subscriptions_id, limit)
+    }
+
+    fn select_due_deliveries(&self, limit: u32) -> TheResult<Vec<WebhookDelivery>> {
+        WebhookDelivery::select_due(&mut self.get_conn()?, limit)
+    }
+
+    fn update_delivery_attempt(&self, delivery: &WebhookDelivery) -> TheResult<bool> {
+        delivery.update_attempt(&mut self.get_conn()?)
+    }
+}
+
+impl WebhookSubscription {
+    fn select_all(conn: &mut PooledConn) -> TheResult<Vec<Self>> {
+        let rows = conn
+            .query::<mysql::Row, _>("SELECT * FROM `webhook_subscriptions` ORDER BY `ID`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_by_id(
+        conn: &mut PooledConn,
+        subscriptions_id: WebhookSubscriptionsIdType,
+    ) -> TheResult<Option<Self>> {
+        let query = "SELECT * FROM `webhook_subscriptions` WHERE `ID` = ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let row = conn
+            .exec_first::<mysql::Row, _, _>(stmt, (subscriptions_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::first(row).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Selects the active subscriptions of the merchant the transaction was made to, none when it was made
+    /// to no merchant
+    fn select_active_by_transactions_id(
+        conn: &mut impl Queryable,
+        transactions_id: TransactionsIdType,
+    ) -> TheResult<Vec<Self>> {
+        let query = "SELECT `webhook_subscriptions`.* FROM `webhook_subscriptions` \
+            JOIN `transactions` ON `transactions`.`merchants_ID` = `webhook_subscriptions`.`merchants_ID` \
+            WHERE `transactions`.`ID` = ? AND `webhook_subscriptions`.`active` = TRUE;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (transactions_id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn insert(&mut self, conn: &mut PooledConn) -> TheResult<()> {
+        let query = "INSERT INTO `webhook_subscriptions`(`merchants_ID`, `url`, `secret`, `active`) VALUES(?, ?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec_drop(
+            stmt,
+            (
+                self.merchants_id,
+                self.url.as_str(),
+                self.secret.as_str(),
+                self.active,
+            ),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+        self.id = conn.last_insert_id();
+
+        Ok(())
+    }
+
+    fn deactivate(&mut self, conn: &mut PooledConn) -> TheResult<bool> {
+        let query = "UPDATE `webhook_subscriptions` SET `active` = FALSE WHERE `ID` = ? AND `active` = TRUE;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec_drop(stmt, (self.id,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        self.active = false;
+
+        Ok(conn.affected_rows() > 0)
+    }
+}
+
+impl WebhookDelivery {
+    /// ## Description
+    /// Creates a pending delivery of the event for every active subscription of the merchant its
+    /// transaction was made to
+    ///
+    /// ### Returns
+    /// The amount of deliveries created
+    pub(super) fn enqueue(conn: &mut impl Executor, event: &TransactionEvent) -> TheResult<usize> {
+        let subscriptions =
+            WebhookSubscription::select_active_by_transactions_id(conn, event.transactions_id)?;
+        if subscriptions.is_empty() {
+            return Ok(0);
+        }
+
+        let payload =
+            serde_json::to_string(event).map_err(|error| create_new_error!(error.to_string()))?;
+        let query = "INSERT INTO `webhook_deliveries`(`webhook_subscriptions_ID`, `event_type`, `payload`) VALUES(?, ?, ?);";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec_batch(
+            stmt,
+            subscriptions
+                .iter()
+                .map(|subscription| (subscription.id, event.event_type.as_str(), payload.as_str())),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(subscriptions.len())
+    }
+
+    fn select_filtered(
+        conn: &mut PooledConn,
+        status: Option<DeliveryStatus>,
+        subscriptions_id: Option<WebhookSubscriptionsIdType>,
+        limit: u32,
+    ) -> TheResult<Vec<Self>> {
+        let mut query = String::from("SELECT * FROM `webhook_deliveries` WHERE TRUE");
+        let mut params = Vec::<String>::new();
+
+        //  Optional filters
+        if let Some(status) = status {
+            query.push_str(" AND `status` = ?");
+            params.push(status.to_string());
+        }
+        if let Some(subscriptions_id) = subscriptions_id {
+            query.push_str(" AND `webhook_subscriptions_ID` = ?");
+            params.push(subscriptions_id.to_string());
+        }
+
+        query.push_str(" ORDER BY `ID` DESC LIMIT ?;");
+        params.push(limit.to_string());
+
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, params)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn select_due(conn: &mut PooledConn, limit: u32) -> TheResult<Vec<Self>> {
+        let query = "SELECT * FROM `webhook_deliveries` WHERE `status` = 'Pending' AND `next_attempt_at` <= NOW() ORDER BY `ID` LIMIT ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let rows = conn
+            .exec::<mysql::Row, _, _>(stmt, (limit,))
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        decode::rows(rows).map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    /// ## Description
+    /// Persists the outcome of the last attempt: status, attempts, response and next attempt datetime
+    fn update_attempt(&self, conn: &mut PooledConn) -> TheResult<bool> {
+        if self.id == 0 {
+            return Err(create_new_error!(
+                "Webhook delivery ID cannot be zero for an update operation"
+            ));
+        }
+
+        let query = "UPDATE `webhook_deliveries` SET `status` = ?, `attempts` = ?, `next_attempt_at` = ?, `last_response_status` = ?, `last_error` = ?, `delivered_at` = ? WHERE `ID` = ?;";
+        let stmt = conn
+            .prep(query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        conn.exec_drop(
+            stmt,
+            (
+                self.status.to_string(),
+                self.attempts,
+                self.next_attempt_at,
+                self.last_response_status,
+                self.last_error.as_deref(),
+                self.delivered_at,
+                self.id,
+            ),
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(conn.affected_rows() > 0)
+    }
+}
+
+impl DecodeRow for WebhookSubscription {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            id: row_to_data!(
+                row,
+                "ID",
+                "webhook_subscriptions",
+                WebhookSubscriptionsIdType
+            ),
+            merchants_id: row_to_data!(
+                row,
+                "merchants_ID",
+                "webhook_subscriptions",
+                Option<MerchantsIdType>
+            ),
+            url: row_to_data!(row, "url", "webhook_subscriptions", String),
+            secret: row_to_data!(row, "secret", "webhook_subscriptions", String),
+            active: row_to_data!(row, "active", "webhook_subscriptions", bool),
+            created_at: row_to_data!(row, "created_at", "webhook_subscriptions", NaiveDateTime),
+        })
+    }
+}
+
+from_row_via_decode!(WebhookSubscription);
+
+impl DecodeRow for WebhookDelivery {
+    fn decode(row: &mysql::Row) -> Result<Self, RowError> {
+        Ok(Self {
+            id: row_to_data!(row, "ID", "webhook_deliveries", WebhookDeliveriesIdType),
+            subscriptions_id: row_to_data!(
+                row,
+                "webhook_subscriptions_ID",
+                "webhook_deliveries",
+                WebhookSubscriptionsIdType
+            ),
+            event_type: row_to_data!(row, "event_type", "webhook_deliveries", String),
+            payload: row_to_data!(row, "payload", "webhook_deliveries", String),
+            attempts: row_to_data!(row, "attempts", "webhook_deliveries", u32),
+            next_attempt_at: row_to_data!(
+                row,
+                "next_attempt_at",
+                "webhook_deliveries",
+                NaiveDateTime
+            ),
+            last_response_status: row_to_data!(
+                row,
+                "last_response_status",
+                "webhook_deliveries",
+                Option<u16>
+            ),
+            last_error: row_to_data!(row, "last_error", "webhook_deliveries", Option<String>),
+            created_at: row_to_data!(row, "created_at", "webhook_deliveries", NaiveDateTime),
+            delivered_at: row_to_data!(
+                row,
+                "delivered_at",
+                "webhook_deliveries",
+                Option<NaiveDateTime>
+            ),
+            status: decode::variant(
+                row,
+                "webhook_deliveries",
+                "status",
+                DeliveryStatus::from_string,
+            )?,
+        })
+    }
+}
+
+from_row_via_decode!(WebhookDelivery);
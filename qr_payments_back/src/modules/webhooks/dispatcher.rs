@@ -0,0 +1,208 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use actix_web::web;
+use chrono::TimeDelta;
+use error_mapper::{create_new_error, TheResult};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use the_logger::TheLogger;
+
+use crate::{
+    config::{Config, WebhooksConfig},
+    database::blocking,
+    datatypes::WebhookSubscriptionsIdType,
+    log_error, log_info, log_warning, tasks,
+};
+
+use super::{repository::WebhookRepository, DeliveryStatus, WebhookDelivery, WebhookSubscription};
+
+/// Max length of the `last_error` column in the `webhook_deliveries` table
+const LAST_ERROR_MAX_LENGTH: usize = 256;
+
+/// ## Description
+/// Background task that posts the pending webhook deliveries whose next attempt is due. Failed attempts
+/// are retried with exponential backoff until `max_attempts`, after which the delivery is dead lettered
+pub async fn run_dispatcher(webhooks: Arc<dyn WebhookRepository>) {
+    let logger = TheLogger::instance();
+    let webhooks = web::Data::from(webhooks);
+    log_info!(logger, "Webhook dispatcher started");
+
+    loop {
+        let config = match Config::get_webhooks_config().await {
+            Ok(config) => config,
+            Err(error) => {
+                log_error!(logger, "Could not get webhooks configurations: {}", error);
+                WebhooksConfig::default()
+            }
+        };
+
+        let interval = Duration::from_secs(config.poll_interval_secs);
+        let outcome = dispatch_due_deliveries(&webhooks, &config).await;
+        if let Err(error) = &outcome {
+            log_error!(logger, "Error dispatching webhook deliveries: {}", error);
+        }
+        tasks::report("webhook_dispatcher", interval, &outcome);
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+async fn dispatch_due_deliveries(
+    webhooks: &web::Data<dyn WebhookRepository>,
+    config: &WebhooksConfig,
+) -> TheResult<()> {
+    let logger = TheLogger::instance();
+    let batch_size = config.batch_size;
+    let deliveries = blocking(webhooks, move |webhooks| {
+        webhooks.select_due_deliveries(batch_size)
+    })
+    .await?;
+    if deliveries.is_empty() {
+        return Ok(());
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    let mut subscriptions =
+        HashMap::<WebhookSubscriptionsIdType, Option<WebhookSubscription>>::new();
+
+    for mut delivery in deliveries {
+        let subscriptions_id = delivery.subscriptions_id;
+        if let Entry::Vacant(entry) = subscriptions.entry(subscriptions_id) {
+            entry.insert(
+                blocking(webhooks, move |webhooks| {
+                    webhooks.select_subscription_by_id(subscriptions_id)
+                })
+                .await?,
+            );
+        }
+
+        let subscription = subscriptions[&subscriptions_id].as_ref();
+        attempt_delivery(&client, config, subscription, &mut delivery).await;
+
+        let deliveries_id = delivery.id;
+        if !blocking(webhooks, move |webhooks| {
+            webhooks.update_delivery_attempt(&delivery)
+        })
+        .await?
+        {
+            log_error!(
+                logger,
+                "Could not update webhook delivery with ID: {}",
+                deliveries_id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// ## Description
+/// Attempts the delivery once, recording the outcome on it. Failed attempts are scheduled again after the
+/// backoff delay, or moved to the dead letter state once `max_attempts` is reached
+pub(super) async fn attempt_delivery(
+    client: &reqwest::Client,
+    config: &WebhooksConfig,
+    subscription: Option<&WebhookSubscription>,
+    delivery: &mut WebhookDelivery,
+) {
+    let result = match subscription {
+        Some(subscription) if subscription.active => {
+            post_delivery(client, subscription, delivery).await
+        }
+        _ => Err((None, String::from("Subscription no longer active"))),
+    };
+
+    let now = chrono::Local::now().naive_local();
+    delivery.attempts += 1;
+    match result {
+        Ok(response_status) => {
+            delivery.status = DeliveryStatus::Delivered;
+            delivery.last_response_status = Some(response_status);
+            delivery.last_error = None;
+            delivery.delivered_at = Some(now);
+        }
+        Err((response_status, error)) => {
+            delivery.last_response_status = response_status;
+            delivery.last_error = Some(error.chars().take(LAST_ERROR_MAX_LENGTH).collect());
+            if delivery.attempts >= config.max_attempts {
+                delivery.status = DeliveryStatus::DeadLetter;
+                log_warning!(
+                    TheLogger::instance(),
+                    "Webhook delivery with ID: {} moved to dead letter after {} attempts",
+                    delivery.id,
+                    delivery.attempts
+                );
+            } else {
+                delivery.next_attempt_at = now + backoff_delay(config, delivery.attempts);
+            }
+        }
+    }
+}
+
+/// ## Description
+/// Posts the delivery payload to the subscription url, signed with the subscription secret
+///
+/// ### Returns
+/// The response status code when it's a success, otherwise the status code if any and the error message
+async fn post_delivery(
+    client: &reqwest::Client,
+    subscription: &WebhookSubscription,
+    delivery: &WebhookDelivery,
+) -> Result<u16, (Option<u16>, String)> {
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign_payload(&subscription.secret, timestamp, &delivery.payload)
+        .map_err(|error| (None, error.to_string()))?;
+
+    let response = client
+        .post(&subscription.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Id", delivery.id.to_string())
+        .header("X-Webhook-Event", delivery.event_type.as_str())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", signature))
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|error| (None, error.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(status.as_u16());
+    }
+
+    Err((
+        Some(status.as_u16()),
+        format!("Receiver responded with status {}", status),
+    ))
+}
+
+/// ## Description
+/// Signs `{timestamp}.{payload}` with HMAC-SHA256, so receivers can verify both origin and freshness
+pub fn sign_payload(secret: &str, timestamp: i64, payload: &str) -> TheResult<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|error| create_new_error!(error.to_string()))?;
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// ## Description
+/// Delay before the next attempt: `base_delay_secs * 2^(attempts - 1)`, capped at `max_delay_secs`
+pub(super) fn backoff_delay(config: &WebhooksConfig, attempts: u32) -> TimeDelta {
+    let factor = 2u64.saturating_pow(attempts.saturating_sub(1));
+    let delay = config
+        .base_delay_secs
+        .saturating_mul(factor)
+        .min(config.max_delay_secs);
+
+    TimeDelta::seconds(delay as i64)
+}
@@ -0,0 +1,97 @@
+use error_mapper::{create_new_error, TheResult};
+
+use crate::{database::memory::InMemoryStore, datatypes::WebhookSubscriptionsIdType};
+
+use super::{repository::WebhookRepository, DeliveryStatus, WebhookDelivery, WebhookSubscription};
+
+impl WebhookRepository for InMemoryStore {
+    fn select_subscriptions(&self) -> TheResult<Vec<WebhookSubscription>> {
+        Ok(self
+            .lock()?
+            .webhook_subscriptions
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    fn select_subscription_by_id(
+        &self,
+        subscriptions_id: WebhookSubscriptionsIdType,
+    ) -> TheResult<Option<WebhookSubscription>> {
+        Ok(self
+            .lock()?
+            .webhook_subscriptions
+            .get(&subscriptions_id)
+            .cloned())
+    }
+
+    fn insert_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<()> {
+        let mut state = self.lock()?;
+        subscription.id = state.webhook_subscriptions.len() as WebhookSubscriptionsIdType + 1;
+        state
+            .webhook_subscriptions
+            .insert(subscription.id, subscription.clone());
+
+        Ok(())
+    }
+
+    fn deactivate_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<bool> {
+        let mut state = self.lock()?;
+        let Some(stored) = state.webhook_subscriptions.get_mut(&subscription.id) else {
+            return Ok(false);
+        };
+        let deactivated = stored.active;
+        stored.active = false;
+        subscription.active = false;
+
+        Ok(deactivated)
+    }
+
+    fn select_deliveries(
+        &self,
+        status: Option<DeliveryStatus>,
+        subscriptions_id: Option<WebhookSubscriptionsIdType>,
+        limit: u32,
+    ) -> TheResult<Vec<WebhookDelivery>> {
+        Ok(self
+            .lock()?
+            .webhook_deliveries
+            .values()
+            .rev()
+            .filter(|delivery| status.is_none_or(|status| delivery.status == status))
+            .filter(|delivery| subscriptions_id.is_none_or(|id| delivery.subscriptions_id == id))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn select_due_deliveries(&self, limit: u32) -> TheResult<Vec<WebhookDelivery>> {
+        let now = chrono::Local::now().naive_local();
+        Ok(self
+            .lock()?
+            .webhook_deliveries
+            .values()
+            .filter(|delivery| {
+                delivery.status == DeliveryStatus::Pending && delivery.next_attempt_at <= now
+            })
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn update_delivery_attempt(&self, delivery: &WebhookDelivery) -> TheResult<bool> {
+        if delivery.id == 0 {
+            return Err(create_new_error!(
+                "Webhook delivery ID cannot be zero for an update operation"
+            ));
+        }
+
+        let mut state = self.lock()?;
+        let Some(stored) = state.webhook_deliveries.get_mut(&delivery.id) else {
+            return Ok(false);
+        };
+        *stored = delivery.clone();
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::NaiveDateTime;
+use rand::Rng;
+use rand_distr::Alphanumeric;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::datatypes::{MerchantsIdType, WebhookDeliveriesIdType, WebhookSubscriptionsIdType};
+
+mod db;
+pub mod dispatcher;
+#[cfg(test)]
+mod memory;
+pub mod repository;
+pub mod services;
+pub mod sink;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+/// Merchant endpoint notified about the status changes of the transactions made to the merchant
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+pub struct WebhookSubscription {
+    pub id: WebhookSubscriptionsIdType,
+    /// None for the subscriptions made to a wallet before they were scoped to merchants, when no merchant
+    /// was settled into it. Those were deactivated
+    pub merchants_id: Option<MerchantsIdType>,
+    pub url: String,
+    /// Key the deliveries are signed with. Only returned when the subscription is created
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+/// Subscription as created, the only response its secret is returned in
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct NewSubscriptionResponse {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: WebhookDeliveriesIdType,
+    pub subscriptions_id: WebhookSubscriptionsIdType,
+    pub event_type: String,
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_response_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Default, strum::Display, PartialEq, ToSchema,
+)]
+pub enum DeliveryStatus {
+    #[default]
+    Pending,
+    Delivered,
+    DeadLetter,
+}
+
+impl WebhookSubscription {
+    fn new(url: String, merchants_id: MerchantsIdType) -> Self {
+        Self {
+            id: WebhookSubscriptionsIdType::default(),
+            merchants_id: Some(merchants_id),
+            url,
+            secret: rand::rng()
+                .sample_iter(&Alphanumeric)
+                .take(32)
+                .map(char::from)
+                .collect(),
+            active: true,
+            created_at: chrono::Local::now().naive_local(),
+        }
+    }
+}
+
+impl DeliveryStatus {
+    fn from_string(input: String) -> Option<Self> {
+        match input.as_str() {
+            "Pending" => Some(Self::Pending),
+            "Delivered" => Some(Self::Delivered),
+            "DeadLetter" => Some(Self::DeadLetter),
+            _ => None,
+        }
+    }
+}
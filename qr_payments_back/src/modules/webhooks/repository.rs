@@ -0,0 +1,29 @@
+use error_mapper::TheResult;
+
+use crate::datatypes::WebhookSubscriptionsIdType;
+
+use super::{DeliveryStatus, WebhookDelivery, WebhookSubscription};
+
+/// Persistence of the webhook subscriptions and their deliveries, implemented by every storage backend.
+/// Deliveries are queued by the webhook outbox sink, in the relay's DB transaction
+pub trait WebhookRepository: Send + Sync {
+    fn select_subscriptions(&self) -> TheResult<Vec<WebhookSubscription>>;
+    fn select_subscription_by_id(
+        &self,
+        subscriptions_id: WebhookSubscriptionsIdType,
+    ) -> TheResult<Option<WebhookSubscription>>;
+    fn insert_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<()>;
+    /// Deactivates an active subscription, keeping its delivery history. False if it was not active
+    fn deactivate_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<bool>;
+    /// Selects the latest deliveries first, optionally filtered by status and subscription
+    fn select_deliveries(
+        &self,
+        status: Option<DeliveryStatus>,
+        subscriptions_id: Option<WebhookSubscriptionsIdType>,
+        limit: u32,
+    ) -> TheResult<Vec<WebhookDelivery>>;
+    /// Selects the pending deliveries whose next attempt is due, oldest first
+    fn select_due_deliveries(&self, limit: u32) -> TheResult<Vec<WebhookDelivery>>;
+    /// Persists the outcome of the last attempt: status, attempts, response and next attempt datetime
+    fn update_delivery_attempt(&self, delivery: &WebhookDelivery) -> TheResult<bool>;
+}
@@ -0,0 +1,270 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::Deserialize;
+use the_logger::TheLogger;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::{
+        error::{ApiError, ErrorBody},
+        validation::ValidJson,
+    },
+    database::blocking,
+    datatypes::{MerchantsIdType, WebhookSubscriptionsIdType},
+    log_error, log_info,
+    modules::{
+        merchants::repository::MerchantRepository,
+        webhooks::{
+            repository::WebhookRepository, DeliveryStatus, NewSubscriptionResponse,
+            WebhookDelivery, WebhookSubscription,
+        },
+    },
+    validation::{self, FieldError, Validate},
+};
+
+/// Length of the `url` column of the `webhook_subscriptions` table
+const URL_MAX_LENGTH: usize = 512;
+/// Default and max amount of deliveries returned by the deliveries endpoint
+const DELIVERIES_DEFAULT_LIMIT: u32 = 100;
+const DELIVERIES_MAX_LIMIT: u32 = 1000;
+
+pub fn webhooks_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_subscriptions)
+        .service(new_subscription)
+        .service(delete_subscription)
+        .service(get_deliveries);
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_subscriptions, new_subscription, delete_subscription, get_deliveries),
+    tags((name = "webhooks"))
+)]
+pub struct WebhooksApi;
+
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(test, derive(serde::Serialize, Default))]
+pub(crate) struct NewSubscriptionRequest {
+    /// HTTP or HTTPS URL the deliveries are posted to
+    #[schema(format = "uri", max_length = 512)]
+    url: String,
+    /// Merchant whose transactions are notified
+    merchants_id: MerchantsIdType,
+}
+
+impl Validate for NewSubscriptionRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        validation::url(&mut errors, "url", &self.url, URL_MAX_LENGTH);
+        validation::identifier(&mut errors, "merchants_id", self.merchants_id);
+        errors
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+struct DeliveriesQuery {
+    status: Option<DeliveryStatus>,
+    subscriptions_id: Option<WebhookSubscriptionsIdType>,
+    /// Defaults to 100, at most 1000
+    limit: Option<u32>,
+}
+
+/// /v1/webhooks/subscriptions
+#[utoipa::path(
+    tag = "webhooks",
+    summary = "Lists every webhook subscription",
+    responses(
+        (status = 200, description = "Subscriptions", body = Vec<WebhookSubscription>),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/subscriptions")]
+async fn get_subscriptions(
+    webhooks: web::Data<dyn WebhookRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+
+    log_info!(logger, "Selecting webhook subscriptions...");
+
+    match blocking(&webhooks, |webhooks| webhooks.select_subscriptions()).await {
+        Ok(subscriptions) => Ok(HttpResponse::Ok().json(subscriptions)),
+        Err(error) => {
+            log_error!(logger, "Could not get webhook subscriptions: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// /v1/webhooks/subscriptions
+#[utoipa::path(
+    tag = "webhooks",
+    summary = "Subscribes a URL to the transaction status changes of a merchant",
+    request_body = NewSubscriptionRequest,
+    responses(
+        (
+            status = 200,
+            description = "Subscription, with the secret used to sign deliveries",
+            body = NewSubscriptionResponse
+        ),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 422, description = "Invalid fields", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[post("/subscriptions")]
+async fn new_subscription(
+    body: ValidJson<NewSubscriptionRequest>,
+    webhooks: web::Data<dyn WebhookRepository>,
+    merchants: web::Data<dyn MerchantRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let body = body.into_inner();
+
+    log_info!(logger, "Received new webhook subscription request");
+
+    let merchants_id = body.merchants_id;
+    match blocking(&merchants, move |merchants| {
+        merchants.select_by_id(merchants_id)
+    })
+    .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            log_info!(logger, "Merchant with ID: {} was not found", merchants_id);
+            return Err(ApiError::merchant_not_found(merchants_id));
+        }
+        Err(error) => {
+            log_error!(logger, "Error selecting merchant: {}", error);
+            return Err(ApiError::internal());
+        }
+    }
+
+    let mut subscription = WebhookSubscription::new(body.url, merchants_id);
+    let subscription = match blocking(&webhooks, move |webhooks| {
+        webhooks
+            .insert_subscription(&mut subscription)
+            .map(|()| subscription)
+    })
+    .await
+    {
+        Ok(subscription) => subscription,
+        Err(error) => {
+            log_error!(logger, "Could not insert webhook subscription: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    log_info!(
+        logger,
+        "Webhook subscription created with ID: {}",
+        subscription.id
+    );
+    let secret = subscription.secret.clone();
+    Ok(HttpResponse::Ok().json(NewSubscriptionResponse {
+        subscription,
+        secret,
+    }))
+}
+
+/// /v1/webhooks/subscriptions/{subscriptions_id}
+#[utoipa::path(
+    tag = "webhooks",
+    summary = "Deactivates a subscription, keeping its deliveries",
+    params(("subscriptions_id" = WebhookSubscriptionsIdType, Path)),
+    responses(
+        (status = 200, description = "Deactivated subscription", body = WebhookSubscription),
+        (status = 404, description = "Webhook subscription not found", body = ErrorBody),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[delete("/subscriptions/{subscriptions_id}")]
+async fn delete_subscription(
+    path: web::Path<WebhookSubscriptionsIdType>,
+    webhooks: web::Data<dyn WebhookRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let subscriptions_id = path.into_inner();
+
+    log_info!(
+        logger,
+        "Deactivating webhook subscription with ID: {}",
+        subscriptions_id
+    );
+
+    let mut subscription = match blocking(&webhooks, move |webhooks| {
+        webhooks.select_subscription_by_id(subscriptions_id)
+    })
+    .await
+    {
+        Ok(Some(subscription)) => subscription,
+        Ok(None) => {
+            log_info!(
+                logger,
+                "Webhook subscription with ID: {} was not found",
+                subscriptions_id
+            );
+            return Err(ApiError::webhook_subscription_not_found(subscriptions_id));
+        }
+        Err(error) => {
+            log_error!(logger, "Could not get webhook subscription: {}", error);
+            return Err(ApiError::internal());
+        }
+    };
+
+    //  Subscriptions are deactivated instead of deleted, to keep their delivery history
+    let subscription = match blocking(&webhooks, move |webhooks| {
+        webhooks
+            .deactivate_subscription(&mut subscription)
+            .map(|_| subscription)
+    })
+    .await
+    {
+        Ok(subscription) => subscription,
+        Err(error) => {
+            log_error!(
+                logger,
+                "Could not deactivate webhook subscription: {}",
+                error
+            );
+            return Err(ApiError::internal());
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(subscription))
+}
+
+/// /v1/webhooks/deliveries
+#[utoipa::path(
+    tag = "webhooks",
+    summary = "Lists the latest webhook deliveries, newest first",
+    params(DeliveriesQuery),
+    responses(
+        (status = 200, description = "Deliveries", body = Vec<WebhookDelivery>),
+        (status = 500, description = "Internal error", body = ErrorBody),
+    )
+)]
+#[get("/deliveries")]
+async fn get_deliveries(
+    query: web::Query<DeliveriesQuery>,
+    webhooks: web::Data<dyn WebhookRepository>,
+) -> Result<HttpResponse, ApiError> {
+    let logger = TheLogger::instance();
+    let query = query.into_inner();
+
+    log_info!(logger, "Selecting webhook deliveries...");
+
+    let limit = query
+        .limit
+        .unwrap_or(DELIVERIES_DEFAULT_LIMIT)
+        .min(DELIVERIES_MAX_LIMIT);
+    match blocking(&webhooks, move |webhooks| {
+        webhooks.select_deliveries(query.status, query.subscriptions_id, limit)
+    })
+    .await
+    {
+        Ok(deliveries) => Ok(HttpResponse::Ok().json(deliveries)),
+        Err(error) => {
+            log_error!(logger, "Could not get webhook deliveries: {}", error);
+            Err(ApiError::internal())
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use error_mapper::{create_new_error, TheResult};
+
+use crate::modules::outbox::{
+    sinks::{OutboxSink, SinkTransaction},
+    AggregateType, OutboxEvent, TransactionEvent,
+};
+
+use super::WebhookDelivery;
+
+/// Outbox sink that queues a webhook delivery of every transaction event for the interested subscriptions
+pub struct WebhookSink;
+
+impl OutboxSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    fn dispatch(&self, conn: &mut SinkTransaction<'_, '_>, event: &OutboxEvent) -> TheResult<()> {
+        if event.aggregate_type != AggregateType::Transaction {
+            return Ok(());
+        }
+
+        let transaction_event = serde_json::from_str::<TransactionEvent>(&event.payload)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        match conn {
+            SinkTransaction::MySql(conn) => WebhookDelivery::enqueue(*conn, &transaction_event)?,
+            #[cfg(feature = "sqlite")]
+            SinkTransaction::Sqlite(conn) => super::sqlite::enqueue(conn, &transaction_event)?,
+        };
+
+        Ok(())
+    }
+}
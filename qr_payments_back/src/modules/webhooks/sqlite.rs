@@ -0,0 +1,199 @@
+use error_mapper::{create_new_error, TheResult};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+use crate::{
+    database::sqlite::{variant_column, SqliteStore},
+    datatypes::WebhookSubscriptionsIdType,
+    modules::outbox::TransactionEvent,
+};
+
+use super::{repository::WebhookRepository, DeliveryStatus, WebhookDelivery, WebhookSubscription};
+
+impl WebhookRepository for SqliteStore {
+    fn select_subscriptions(&self) -> TheResult<Vec<WebhookSubscription>> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT * FROM `webhook_subscriptions` ORDER BY `ID`;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let subscriptions = stmt
+            .query_map([], subscription_from_row)
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+            .collect();
+        subscriptions
+    }
+
+    fn select_subscription_by_id(
+        &self,
+        subscriptions_id: WebhookSubscriptionsIdType,
+    ) -> TheResult<Option<WebhookSubscription>> {
+        self.lock()?
+            .query_row(
+                "SELECT * FROM `webhook_subscriptions` WHERE `ID` = ?;",
+                params![subscriptions_id],
+                subscription_from_row,
+            )
+            .optional()
+            .map_err(|error| create_new_error!(error.to_string()))
+    }
+
+    fn insert_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<()> {
+        let conn = self.lock()?;
+        conn.execute(
+            "INSERT INTO `webhook_subscriptions`(`merchants_ID`, `url`, `secret`, `active`, `created_at`) VALUES(?, ?, ?, ?, ?);",
+            params![
+                subscription.merchants_id,
+                subscription.url,
+                subscription.secret,
+                subscription.active,
+                subscription.created_at,
+            ],
+        )
+        .map_err(|error| create_new_error!(error.to_string()))?;
+        subscription.id = conn.last_insert_rowid() as WebhookSubscriptionsIdType;
+
+        Ok(())
+    }
+
+    fn deactivate_subscription(&self, subscription: &mut WebhookSubscription) -> TheResult<bool> {
+        let affected_rows = self
+            .lock()?
+            .execute(
+                "UPDATE `webhook_subscriptions` SET `active` = FALSE WHERE `ID` = ? AND `active` = TRUE;",
+                params![subscription.id],
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        subscription.active = false;
+
+        Ok(affected_rows > 0)
+    }
+
+    fn select_deliveries(
+        &self,
+        status: Option<DeliveryStatus>,
+        subscriptions_id: Option<WebhookSubscriptionsIdType>,
+        limit: u32,
+    ) -> TheResult<Vec<WebhookDelivery>> {
+        let mut query = String::from("SELECT * FROM `webhook_deliveries` WHERE TRUE");
+        let mut params = Vec::<String>::new();
+
+        //  Optional filters
+        if let Some(status) = status {
+            query.push_str(" AND `status` = ?");
+            params.push(status.to_string());
+        }
+        if let Some(subscriptions_id) = subscriptions_id {
+            query.push_str(" AND `webhook_subscriptions_ID` = ?");
+            params.push(subscriptions_id.to_string());
+        }
+
+        query.push_str(" ORDER BY `ID` DESC LIMIT ?;");
+        params.push(limit.to_string());
+
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare(&query)
+            .map_err(|error| create_new_error!(error.to_string()))?;
+        let deliveries = stmt
+            .query_map(params_from_iter(params), delivery_from_row)
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+            .collect();
+        deliveries
+    }
+
+    fn select_due_deliveries(&self, limit: u32) -> TheResult<Vec<WebhookDelivery>> {
+        let conn = self.lock()?;
+        let mut stmt = conn
+            .prepare("SELECT * FROM `webhook_deliveries` WHERE `status` = 'Pending' AND `next_attempt_at` <= ? ORDER BY `ID` LIMIT ?;")
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        let deliveries = stmt
+            .query_map(
+                params![chrono::Local::now().naive_local(), limit],
+                delivery_from_row,
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?
+            .map(|result| result.map_err(|error| create_new_error!(error.to_string())))
+            .collect();
+        deliveries
+    }
+
+    fn update_delivery_attempt(&self, delivery: &WebhookDelivery) -> TheResult<bool> {
+        if delivery.id == 0 {
+            return Err(create_new_error!(
+                "Webhook delivery ID cannot be zero for an update operation"
+            ));
+        }
+
+        let affected_rows = self
+            .lock()?
+            .execute(
+                "UPDATE `webhook_deliveries` SET `status` = ?, `attempts` = ?, `next_attempt_at` = ?, `last_response_status` = ?, `last_error` = ?, `delivered_at` = ? WHERE `ID` = ?;",
+                params![
+                    delivery.status.to_string(),
+                    delivery.attempts,
+                    delivery.next_attempt_at,
+                    delivery.last_response_status,
+                    delivery.last_error,
+                    delivery.delivered_at,
+                    delivery.id,
+                ],
+            )
+            .map_err(|error| create_new_error!(error.to_string()))?;
+
+        Ok(affected_rows > 0)
+    }
+}
+
+/// ## Description
+/// Creates a pending delivery of the event for every active subscription of the merchant its transaction
+/// was made to
+///
+/// ### Returns
+/// The amount of deliveries created
+pub(super) fn enqueue(conn: &Connection, event: &TransactionEvent) -> TheResult<usize> {
+    let payload =
+        serde_json::to_string(event).map_err(|error| create_new_error!(error.to_string()))?;
+    let now = chrono::Local::now().naive_local();
+
+    conn.execute(
+        "INSERT INTO `webhook_deliveries`(`webhook_subscriptions_ID`, `event_type`, `payload`, `next_attempt_at`, `created_at`) SELECT `webhook_subscriptions`.`ID`, ?, ?, ?, ? FROM `webhook_subscriptions` JOIN `transactions` ON `transactions`.`merchants_ID` = `webhook_subscriptions`.`merchants_ID` WHERE `transactions`.`ID` = ? AND `webhook_subscriptions`.`active` = TRUE;",
+        params![
+            event.event_type,
+            payload,
+            now,
+            now,
+            event.transactions_id,
+        ],
+    )
+    .map_err(|error| create_new_error!(error.to_string()))
+}
+
+fn subscription_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<WebhookSubscription> {
+    Ok(WebhookSubscription {
+        id: row.get("ID")?,
+        merchants_id: row.get("merchants_ID")?,
+        url: row.get("url")?,
+        secret: row.get("secret")?,
+        active: row.get("active")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+fn delivery_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<WebhookDelivery> {
+    Ok(WebhookDelivery {
+        id: row.get("ID")?,
+        subscriptions_id: row.get("webhook_subscriptions_ID")?,
+        event_type: row.get("event_type")?,
+        payload: row.get("payload")?,
+        status: variant_column(row, "status", DeliveryStatus::from_string)?,
+        attempts: row.get("attempts")?,
+        next_attempt_at: row.get("next_attempt_at")?,
+        last_response_status: row.get("last_response_status")?,
+        last_error: row.get("last_error")?,
+        created_at: row.get("created_at")?,
+        delivered_at: row.get("delivered_at")?,
+    })
+}
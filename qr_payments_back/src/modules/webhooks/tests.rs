@@ -0,0 +1,198 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use actix_web::{http::StatusCode, web, App, HttpRequest, HttpResponse, HttpServer};
+use chrono::TimeDelta;
+
+use crate::config::WebhooksConfig;
+
+use super::{
+    dispatcher::{attempt_delivery, backoff_delay, sign_payload},
+    DeliveryStatus, NewSubscriptionResponse, WebhookDelivery, WebhookSubscription,
+};
+
+/// Request received by the stand-in receiver
+struct ReceivedRequest {
+    timestamp: String,
+    signature: String,
+    event_type: String,
+    body: String,
+}
+
+/// Stand-in for a merchant endpoint, answering with the queued statuses and then with 200
+#[derive(Default)]
+struct Receiver {
+    statuses: Mutex<VecDeque<u16>>,
+    requests: Mutex<Vec<ReceivedRequest>>,
+}
+
+async fn receive(
+    request: HttpRequest,
+    body: String,
+    receiver: web::Data<Receiver>,
+) -> HttpResponse {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    receiver.requests.lock().unwrap().push(ReceivedRequest {
+        timestamp: header("X-Webhook-Timestamp"),
+        signature: header("X-Webhook-Signature"),
+        event_type: header("X-Webhook-Event"),
+        body,
+    });
+
+    let status = receiver.statuses.lock().unwrap().pop_front().unwrap_or(200);
+    HttpResponse::build(StatusCode::from_u16(status).unwrap()).finish()
+}
+
+/// ## Description
+/// Starts the stand-in receiver on a free local port
+///
+/// ### Returns
+/// The url to subscribe, and the receiver state
+fn start_receiver(statuses: &[u16]) -> (String, web::Data<Receiver>) {
+    let receiver = web::Data::new(Receiver {
+        statuses: Mutex::new(statuses.iter().copied().collect()),
+        ..Default::default()
+    });
+
+    let data = receiver.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(data.clone())
+            .route("/hook", web::post().to(receive))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .expect("receiver should bind");
+    let address = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+
+    (format!("http://{}/hook", address), receiver)
+}
+
+fn config(max_attempts: u32) -> WebhooksConfig {
+    WebhooksConfig {
+        max_attempts,
+        base_delay_secs: 10,
+        max_delay_secs: 25,
+        ..Default::default()
+    }
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap()
+}
+
+fn delivery() -> WebhookDelivery {
+    WebhookDelivery {
+        id: 1,
+        subscriptions_id: 1,
+        event_type: String::from("transaction.Confirmed"),
+        payload: String::from(r#"{"transactions_id":7,"status":"Confirmed"}"#),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn secret_is_only_serialized_on_creation() {
+    let subscription = WebhookSubscription::new(String::from("https://example.com/hook"), 1);
+    assert_eq!(subscription.secret.len(), 32);
+
+    let listed = serde_json::to_value(&subscription).unwrap();
+    assert!(listed.get("secret").is_none());
+
+    let secret = subscription.secret.clone();
+    let created = serde_json::to_value(NewSubscriptionResponse {
+        subscription,
+        secret: secret.clone(),
+    })
+    .unwrap();
+    assert_eq!(created["secret"], secret);
+    assert_eq!(created["url"], "https://example.com/hook");
+}
+
+#[test]
+fn backoff_doubles_up_to_the_max_delay() {
+    let config = config(8);
+
+    assert_eq!(backoff_delay(&config, 1), TimeDelta::seconds(10));
+    assert_eq!(backoff_delay(&config, 2), TimeDelta::seconds(20));
+    assert_eq!(backoff_delay(&config, 3), TimeDelta::seconds(25));
+    assert_eq!(backoff_delay(&config, 64), TimeDelta::seconds(25));
+}
+
+#[actix_web::test]
+async fn deliveries_are_signed_with_the_subscription_secret() {
+    let (url, receiver) = start_receiver(&[]);
+    let subscription = WebhookSubscription::new(url, 1);
+    let mut delivery = delivery();
+
+    attempt_delivery(&client(), &config(3), Some(&subscription), &mut delivery).await;
+    assert_eq!(delivery.status, DeliveryStatus::Delivered);
+    assert_eq!(delivery.attempts, 1);
+    assert_eq!(delivery.last_response_status, Some(200));
+    assert!(delivery.delivered_at.is_some());
+
+    let requests = receiver.requests.lock().unwrap();
+    let request = &requests[0];
+    assert_eq!(request.body, delivery.payload);
+    assert_eq!(request.event_type, delivery.event_type);
+
+    let timestamp = request.timestamp.parse::<i64>().unwrap();
+    let expected = sign_payload(&subscription.secret, timestamp, &request.body).unwrap();
+    assert_eq!(request.signature, format!("sha256={}", expected));
+
+    let forged = sign_payload("another secret", timestamp, &request.body).unwrap();
+    assert_ne!(request.signature, format!("sha256={}", forged));
+}
+
+#[actix_web::test]
+async fn failed_deliveries_are_retried_with_backoff_until_dead_lettered() {
+    let (url, receiver) = start_receiver(&[500, 503, 500]);
+    let subscription = WebhookSubscription::new(url, 1);
+    let config = config(3);
+    let mut delivery = delivery();
+
+    for (attempts, delay) in [(1, 10), (2, 20)] {
+        let before = chrono::Local::now().naive_local();
+        attempt_delivery(&client(), &config, Some(&subscription), &mut delivery).await;
+        let after = chrono::Local::now().naive_local();
+
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.attempts, attempts);
+        assert!(delivery.next_attempt_at >= before + TimeDelta::seconds(delay));
+        assert!(delivery.next_attempt_at <= after + TimeDelta::seconds(delay));
+        assert!(delivery.last_error.is_some());
+    }
+    assert_eq!(delivery.last_response_status, Some(503));
+
+    attempt_delivery(&client(), &config, Some(&subscription), &mut delivery).await;
+    assert_eq!(delivery.status, DeliveryStatus::DeadLetter);
+    assert_eq!(delivery.attempts, 3);
+    assert_eq!(delivery.last_response_status, Some(500));
+    assert!(delivery.delivered_at.is_none());
+    assert_eq!(receiver.requests.lock().unwrap().len(), 3);
+}
+
+#[actix_web::test]
+async fn deliveries_of_inactive_subscriptions_are_not_posted() {
+    let (url, receiver) = start_receiver(&[]);
+    let mut subscription = WebhookSubscription::new(url, 1);
+    subscription.active = false;
+    let mut delivery = delivery();
+
+    attempt_delivery(&client(), &config(1), Some(&subscription), &mut delivery).await;
+    attempt_delivery(&client(), &config(1), None, &mut delivery).await;
+    assert_eq!(delivery.status, DeliveryStatus::DeadLetter);
+    assert_eq!(delivery.last_response_status, None);
+    assert!(receiver.requests.lock().unwrap().is_empty());
+}
@@ -0,0 +1,77 @@
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+use chrono::NaiveDateTime;
+use error_mapper::TheResult;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::DATETIME_FORMAT;
+
+static TASKS: Mutex<BTreeMap<&'static str, TaskRun>> = Mutex::new(BTreeMap::new());
+
+/// Grace period on top of the task interval before a task that stopped reporting is considered stalled
+const STALL_GRACE: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct TaskRun {
+    interval: Duration,
+    last_run: NaiveDateTime,
+    last_error: Option<String>,
+}
+
+/// State of a background task as of its last iteration
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaskStatus {
+    pub name: &'static str,
+    pub interval_secs: u64,
+    pub last_run: String,
+    /// Failure of the last iteration, retried on the next one. It does not make the task unhealthy
+    pub last_error: Option<String>,
+    /// False when the task has not reported for twice its interval plus a grace period, as it stalled or
+    /// its loop died
+    pub healthy: bool,
+}
+
+/// ## Description
+/// Records the outcome of a background task iteration. Called by the task loops after every run, along
+/// with the interval they sleep before the next one
+pub fn report(name: &'static str, interval: Duration, outcome: &TheResult<()>) {
+    let run = TaskRun {
+        interval,
+        last_run: chrono::Local::now().naive_local(),
+        last_error: outcome
+            .as_ref()
+            .err()
+            .map(|error| error.error.error_content.clone()),
+    };
+
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.insert(name, run);
+    }
+}
+
+/// ## Description
+/// Lists every task that reported at least once, sorted by name
+pub fn statuses() -> Vec<TaskStatus> {
+    let now = chrono::Local::now().naive_local();
+    let Ok(tasks) = TASKS.lock() else {
+        return Vec::new();
+    };
+
+    tasks
+        .iter()
+        .map(|(name, run)| {
+            let deadline = run.interval * 2 + STALL_GRACE;
+            let stalled = (now - run.last_run)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed > deadline);
+            TaskStatus {
+                name,
+                interval_secs: run.interval.as_secs(),
+                last_run: run.last_run.format(DATETIME_FORMAT).to_string(),
+                last_error: run.last_error.clone(),
+                healthy: !stalled,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,205 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::modules::currencies::Currency;
+
+/// Decimal places of the amount columns, `DECIMAL(12,2)`
+pub const AMOUNT_SCALE: u32 = 2;
+/// Largest absolute amount the amount columns can hold, 9999999999.99
+pub const AMOUNT_MAX: Decimal = Decimal::from_parts(0xD4A5_0FFF, 0xE8, 0, false, AMOUNT_SCALE);
+/// Decimal places of the exchange rates, `DECIMAL(20,10)`
+pub const RATE_SCALE: u32 = 10;
+/// Largest exchange rate the rate columns can hold, 9999999999.9999999999
+pub const RATE_MAX: Decimal = Decimal::from_parts(0x630F_FFFF, 0x6BC7_5E2D, 0x5, false, RATE_SCALE);
+/// Length of the tokens generated for transactions, and of the quote identifiers
+pub const TOKEN_LENGTH: usize = 32;
+
+/// What failed on a field. Serialized as the machine readable code of the field error
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FieldErrorCode {
+    InvalidScale,
+    OutOfRange,
+    InvalidSign,
+    InvalidFormat,
+    UnsupportedCurrency,
+    InvalidPair,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub code: FieldErrorCode,
+    pub message: String,
+}
+
+/// Sign an amount field must have. Zero is only accepted by `NonNegative`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sign {
+    Negative,
+    Positive,
+    NonNegative,
+}
+
+/// Request bodies and configurations checked field by field. The API rejects request bodies failing it
+/// through `ValidJson`
+pub trait Validate {
+    /// Every invalid field, empty if the request is valid
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+/// ## Description
+/// Checks an amount fits the amount columns, with at most two decimal places, and has the required sign
+pub fn amount(errors: &mut Vec<FieldError>, field: &'static str, amount: Decimal, sign: Sign) {
+    if amount.normalize().scale() > AMOUNT_SCALE {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidScale,
+            message: format!(
+                "Amount cannot have more than {} decimal places",
+                AMOUNT_SCALE
+            ),
+        });
+    }
+    if amount.abs() > AMOUNT_MAX {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::OutOfRange,
+            message: format!("Amount cannot exceed {} in absolute value", AMOUNT_MAX),
+        });
+    }
+
+    let (valid, expected) = match sign {
+        Sign::Negative => (amount < Decimal::ZERO, "negative"),
+        Sign::Positive => (amount > Decimal::ZERO, "positive"),
+        Sign::NonNegative => (amount >= Decimal::ZERO, "zero or positive"),
+    };
+    if !valid {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidSign,
+            message: format!("Amount must be {}", expected),
+        });
+    }
+}
+
+/// ## Description
+/// Checks a database identifier, which start at 1
+pub fn identifier(errors: &mut Vec<FieldError>, field: &'static str, id: u64) {
+    if id == 0 {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::OutOfRange,
+            message: String::from("Identifier must be greater than zero"),
+        });
+    }
+}
+
+/// ## Description
+/// Checks a transaction token has the format generated by the service: 32 ASCII alphanumerics
+pub fn token(errors: &mut Vec<FieldError>, field: &'static str, token: &str) {
+    if token.len() != TOKEN_LENGTH || !token.chars().all(|char| char.is_ascii_alphanumeric()) {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidFormat,
+            message: format!("Token must be {} alphanumeric characters", TOKEN_LENGTH),
+        });
+    }
+}
+
+/// ## Description
+/// Checks a currency has the format of an ISO 4217 alphabetic code, three uppercase letters, and is one of
+/// the supported currencies
+pub fn currency(errors: &mut Vec<FieldError>, field: &'static str, currency: &str) {
+    if currency.len() != 3 || !currency.chars().all(|char| char.is_ascii_uppercase()) {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidFormat,
+            message: String::from("Currency must be an ISO 4217 code of three uppercase letters"),
+        });
+    } else if Currency::from_code(currency).is_none() {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::UnsupportedCurrency,
+            message: format!("Currency {} is not supported", currency),
+        });
+    }
+}
+
+/// ## Description
+/// Checks an amount is a whole number of minor units of its currency, e.g. no decimals for JPY
+pub fn minor_units(
+    errors: &mut Vec<FieldError>,
+    field: &'static str,
+    amount: Decimal,
+    currency: Currency,
+) {
+    if !currency.is_exact(amount) {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidScale,
+            message: format!(
+                "Amount cannot have more than {} decimal places in {}",
+                currency.minor_units(),
+                currency
+            ),
+        });
+    }
+}
+
+/// ## Description
+/// Checks an exchange rate is positive and fits the rate columns, with at most ten decimal places
+pub fn rate(errors: &mut Vec<FieldError>, field: &'static str, rate: Decimal) {
+    if rate.normalize().scale() > RATE_SCALE {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidScale,
+            message: format!("Rate cannot have more than {} decimal places", RATE_SCALE),
+        });
+    }
+    if rate > RATE_MAX {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::OutOfRange,
+            message: format!("Rate cannot exceed {}", RATE_MAX),
+        });
+    }
+    if rate <= Decimal::ZERO {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidSign,
+            message: String::from("Rate must be positive"),
+        });
+    }
+}
+
+/// ## Description
+/// Checks a conversion is between two different currencies
+pub fn pair(errors: &mut Vec<FieldError>, field: &'static str, from: &str, to: &str) {
+    if from == to {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidPair,
+            message: format!("Cannot convert {} to itself", from),
+        });
+    }
+}
+
+/// ## Description
+/// Checks a URL is http or https and fits its column
+pub fn url(errors: &mut Vec<FieldError>, field: &'static str, url: &str, max_length: usize) {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::InvalidFormat,
+            message: String::from("URL must be an http or https URL"),
+        });
+    }
+    if url.len() > max_length {
+        errors.push(FieldError {
+            field,
+            code: FieldErrorCode::OutOfRange,
+            message: format!("URL cannot exceed {} characters", max_length),
+        });
+    }
+}